@@ -0,0 +1,260 @@
+//! Owned records backed by a bump allocator (requires the `bumpalo` feature).
+//!
+//! Converting many records with [`Record::to_owned_variant`] makes many
+//! small allocations for the strings and vectors of each record.
+//! [`Record::to_arena_variant`] instead allocates them in a user-supplied
+//! [`Bump`] arena and borrows the tag names from the header, so that the
+//! records of a batch are freed at once with [`Bump::reset`].
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::arena::Bump;
+//! let mut f = smart_reader("testdata/test2.bcf");
+//! let header = Header::from_string(&read_header(&mut f).unwrap());
+//! let mut bump = Bump::new();
+//! let mut record = Record::default();
+//! let mut n_variants = 0;
+//! loop {
+//!     // convert the records by batches of 10, reusing the arena memory
+//!     bump.reset();
+//!     let mut batch = vec![];
+//!     while batch.len() < 10 && record.read(&mut f).is_ok() {
+//!         let variant = record.to_arena_variant(&header, &bump).unwrap();
+//!         assert_eq!(variant.to_variant_record(), record.to_owned_variant(&header).unwrap());
+//!         batch.push(variant);
+//!     }
+//!     if batch.is_empty() {
+//!         break;
+//!     }
+//!     assert_eq!(batch[0].chrom, "Pf3D7_01_v3");
+//!     n_variants += batch.len();
+//! }
+//! assert!(n_variants > 0);
+//! ```
+
+use crate::{
+    error::{self, Error},
+    iter_typed_integers, typ_width, FieldValue, GenotypeAllele, Header, Record, VariantRecord,
+};
+use bumpalo::collections::Vec as BumpVec;
+pub use bumpalo::Bump;
+
+/// The value of an INFO field or of a FORMAT field of one sample, allocated
+/// in an arena; see [`FieldValue`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaFieldValue<'a> {
+    /// INFO flag (present)
+    Flag,
+    /// integer values, `None` for missing values
+    Integer(&'a [Option<i32>]),
+    /// float values, `None` for missing values
+    Float(&'a [Option<f32>]),
+    /// string value
+    String(&'a str),
+    /// FORMAT/GT genotype call
+    Genotype(&'a [GenotypeAllele]),
+}
+
+impl ArenaFieldValue<'_> {
+    /// Copy the value into a [`FieldValue`]
+    pub fn to_field_value(&self) -> FieldValue {
+        match *self {
+            ArenaFieldValue::Flag => FieldValue::Flag,
+            ArenaFieldValue::Integer(v) => FieldValue::Integer(v.to_vec()),
+            ArenaFieldValue::Float(v) => FieldValue::Float(v.to_vec()),
+            ArenaFieldValue::String(s) => FieldValue::String(s.to_string()),
+            ArenaFieldValue::Genotype(gt) => FieldValue::Genotype(gt.to_vec()),
+        }
+    }
+}
+
+/// A [`VariantRecord`] whose strings and vectors are allocated in an arena,
+/// or borrowed from the header for the names of contigs, filters and tags
+///
+/// See [`Record::to_arena_variant`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaVariantRecord<'a> {
+    /// chromosome name
+    pub chrom: &'a str,
+    /// 0-based position
+    pub pos: i32,
+    /// reference length
+    pub rlen: i32,
+    /// ID column (`.` if missing)
+    pub id: &'a str,
+    /// alleles, REF first
+    pub alleles: &'a [&'a str],
+    /// quality score, if available
+    pub qual: Option<f32>,
+    /// names of the filters
+    pub filters: &'a [&'a str],
+    /// INFO fields (tag, value) in record order
+    pub info: &'a [(&'a str, ArenaFieldValue<'a>)],
+    /// FORMAT fields (tag, one value per sample) in record order
+    pub format: &'a [(&'a str, &'a [ArenaFieldValue<'a>])],
+}
+
+impl<'a> ArenaVariantRecord<'a> {
+    /// Get the value of an INFO field by tag
+    pub fn info(&self, tag: &str) -> Option<&ArenaFieldValue<'a>> {
+        self.info.iter().find(|(k, _)| *k == tag).map(|(_, v)| v)
+    }
+
+    /// Get the per-sample values of a FORMAT field by tag
+    pub fn format(&self, tag: &str) -> Option<&'a [ArenaFieldValue<'a>]> {
+        self.format.iter().find(|(k, _)| *k == tag).map(|(_, v)| *v)
+    }
+
+    /// Copy the record into a [`VariantRecord`] that does not borrow the
+    /// arena
+    pub fn to_variant_record(&self) -> VariantRecord {
+        VariantRecord {
+            chrom: self.chrom.to_string(),
+            pos: self.pos,
+            rlen: self.rlen,
+            id: self.id.to_string(),
+            alleles: self.alleles.iter().map(|a| a.to_string()).collect(),
+            qual: self.qual,
+            filters: self.filters.iter().map(|f| f.to_string()).collect(),
+            info: self
+                .info
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_field_value()))
+                .collect(),
+            format: self
+                .format
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.to_string(),
+                        v.iter().map(|v| v.to_field_value()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Decode `n` typed values into an [`ArenaFieldValue`]; see
+/// `decode_field_value`
+fn decode_value<'a>(
+    bump: &'a Bump,
+    typ: u8,
+    n: usize,
+    buf: &[u8],
+    is_flag: bool,
+) -> ArenaFieldValue<'a> {
+    let values = || iter_typed_integers(typ, n, buf).take_while(|v| !v.is_end_of_vector());
+    match typ {
+        _ if is_flag => ArenaFieldValue::Flag,
+        0 => ArenaFieldValue::Flag,
+        0x7 => {
+            let end = buf.iter().position(|c| *c == b'\0').unwrap_or(buf.len());
+            ArenaFieldValue::String(bump.alloc_str(&String::from_utf8_lossy(&buf[..end])))
+        }
+        0x5 => ArenaFieldValue::Float(
+            BumpVec::from_iter_in(values().map(|v| v.float_val()), bump).into_bump_slice(),
+        ),
+        _ => ArenaFieldValue::Integer(
+            BumpVec::from_iter_in(values().map(|v| v.signed_int_val()), bump).into_bump_slice(),
+        ),
+    }
+}
+
+/// Decode the GT values of one sample; see `decode_genotype`
+fn decode_genotype<'a>(bump: &'a Bump, typ: u8, n: usize, buf: &[u8]) -> ArenaFieldValue<'a> {
+    let gt = iter_typed_integers(typ, n, buf)
+        .map(|v| v.gt_val())
+        .take_while(|(noploidy, ..)| !noploidy)
+        .enumerate()
+        .map(|(i, (_, dot, phased, allele))| GenotypeAllele {
+            allele: if dot { None } else { Some(allele) },
+            phased: i > 0 && phased,
+        });
+    ArenaFieldValue::Genotype(BumpVec::from_iter_in(gt, bump).into_bump_slice())
+}
+
+impl Record {
+    /// Convert the record into an [`ArenaVariantRecord`] allocated in
+    /// `bump`, with the same content as [`Record::to_owned_variant`].
+    ///
+    /// The result borrows the arena and the header, and can outlive the
+    /// record buffers. An error is returned if the record refers to a
+    /// contig, a filter, or an INFO/FORMAT key not defined in the header.
+    pub fn to_arena_variant<'a>(
+        &self,
+        header: &'a Header,
+        bump: &'a Bump,
+    ) -> error::Result<ArenaVariantRecord<'a>> {
+        let not_in_header = |what: &str, key: usize| {
+            Error::MalformedRecord(format!(
+                "{what} key {key} of the record is not defined in the header"
+            ))
+        };
+        let tag_of = |what: &str, key: usize| match header.dict_entry(what, key) {
+            Some(m) => Ok(m["ID"].as_str()),
+            None => Err(not_in_header(what, key)),
+        };
+        let bytes_to_str =
+            |bytes: &[u8]| -> &'a str { bump.alloc_str(&String::from_utf8_lossy(bytes)) };
+
+        let chrom = header
+            .rid_to_name(self.chrom)
+            .ok_or_else(|| not_in_header("contig", self.chrom as usize))?;
+        let id = match &self.buf_shared[self.id.start..self.id.end] {
+            [] => ".",
+            id => bytes_to_str(id),
+        };
+        let alleles = BumpVec::from_iter_in(
+            self.alleles
+                .iter()
+                .map(|rng| bytes_to_str(&self.buf_shared[rng.start..rng.end])),
+            bump,
+        );
+        let mut filters = BumpVec::new_in(bump);
+        for nv in self.filters() {
+            if let Some(key) = nv.int_val() {
+                filters.push(tag_of("FILTER", key as usize)?);
+            }
+        }
+        let mut info = BumpVec::new_in(bump);
+        for (key, typ, n, rng) in self.info_fields() {
+            let tag = tag_of("INFO", key)?;
+            let is_flag = header
+                .dict_entry("INFO", key)
+                .and_then(|m| m.get("Type"))
+                .is_some_and(|t| t == "Flag");
+            let buf = &self.buf_shared[rng.start..rng.end];
+            info.push((tag, decode_value(bump, typ, n, buf, is_flag)));
+        }
+        let mut format = BumpVec::new_in(bump);
+        for (key, typ, n, rng) in self.fmt_fields() {
+            let tag = tag_of("FORMAT", key)?;
+            let buf = &self.buf_indiv[rng.start..rng.end];
+            let width = typ_width(typ) * n;
+            let is_gt = header.get_fmt_gt_id() == Some(key);
+            let values = (0..self.n_sample as usize).map(|i| {
+                let sample_buf = &buf[i * width..(i + 1) * width];
+                if is_gt {
+                    decode_genotype(bump, typ, n, sample_buf)
+                } else {
+                    decode_value(bump, typ, n, sample_buf, false)
+                }
+            });
+            format.push((tag, BumpVec::from_iter_in(values, bump).into_bump_slice()));
+        }
+
+        Ok(ArenaVariantRecord {
+            chrom,
+            pos: self.pos,
+            rlen: self.rlen,
+            id,
+            alleles: alleles.into_bump_slice(),
+            qual: self.qual(),
+            filters: filters.into_bump_slice(),
+            info: info.into_bump_slice(),
+            format: format.into_bump_slice(),
+        })
+    }
+}
@@ -0,0 +1,367 @@
+//! Export of records as Arrow record batches (requires the `arrow` feature).
+//!
+//! [`ArrowBatchBuilder`] appends records to the columns of an Arrow
+//! [`RecordBatch`], and [`ArrowBatches`] streams the remaining records of a
+//! reader as record batches of [`ArrowOptions::batch_size`] records. The
+//! batches can be handed to Arrow consumers (DataFusion, Polars, pyarrow
+//! through the C data interface) without copying the values again.
+//!
+//! The columns are, in this order, with the optional ones selected by
+//! [`ArrowOptions`]:
+//! - `chrom` (`Utf8`): contig name
+//! - `pos` (`Int32`): 0-based position, as [`Record::pos`]
+//! - `id` (`Utf8`, optional): ID, null if missing
+//! - `ref` (`Utf8`, optional): REF allele
+//! - `alt` (`List<Utf8>`, optional): ALT alleles
+//! - `qual` (`Float32`, optional): quality, null if missing
+//! - `filters` (`List<Utf8>`, optional): names of the filters
+//! - `gt` (`List<Int8>`, optional): genotypes with the rows of a
+//!   [`GenotypeLayout`], as in `matrix::read_genotype_matrix`
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::arrow_export::{ArrowBatches, ArrowOptions};
+//! let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+//! let header = reader.read_header();
+//! let options = ArrowOptions {
+//!     batch_size: 50,
+//!     genotypes: Some(GenotypeLayout::Dosage),
+//!     ..Default::default()
+//! };
+//! let batches = ArrowBatches::new(&mut reader, &header, options)
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! let n_rows: Vec<_> = batches.iter().map(|b| b.num_rows()).collect();
+//! assert_eq!(n_rows, [50, 50, 27]);
+//! let columns: Vec<_> = batches[0].schema().fields().iter().map(|f| f.name().clone()).collect();
+//! assert_eq!(columns, ["chrom", "pos", "id", "ref", "alt", "qual", "filters", "gt"]);
+//! ```
+
+use crate::{error, genotype_column, Error, GenotypeLayout, Header, ReadRecord, Record};
+use arrow_array::builder::{Float32Builder, Int32Builder, Int8Builder, ListBuilder, StringBuilder};
+use arrow_array::ArrayRef;
+use arrow_schema::{DataType, Field};
+use std::sync::Arc;
+
+pub use arrow_array::RecordBatch;
+pub use arrow_schema::{Schema, SchemaRef};
+
+/// Columns and batch size of the record batches, see the
+/// [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrowOptions {
+    /// maximum number of records per batch of [`ArrowBatches`]
+    pub batch_size: usize,
+    /// add the `id` column
+    pub id: bool,
+    /// add the `ref` and `alt` columns
+    pub alleles: bool,
+    /// add the `qual` column
+    pub qual: bool,
+    /// add the `filters` column
+    pub filters: bool,
+    /// add the `gt` column, with the values of each record in this layout
+    pub genotypes: Option<GenotypeLayout>,
+}
+
+impl Default for ArrowOptions {
+    /// Batches of 8192 records with all site columns and no genotypes
+    fn default() -> Self {
+        ArrowOptions {
+            batch_size: 8192,
+            id: true,
+            alleles: true,
+            qual: true,
+            filters: true,
+            genotypes: None,
+        }
+    }
+}
+
+impl ArrowOptions {
+    /// The schema of the record batches with these options
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::GenotypeLayout;
+    /// use bcf_reader::arrow_export::ArrowOptions;
+    /// let options = ArrowOptions {
+    ///     id: false,
+    ///     alleles: false,
+    ///     qual: false,
+    ///     filters: false,
+    ///     genotypes: Some(GenotypeLayout::Alleles { ploidy: 2 }),
+    ///     ..Default::default()
+    /// };
+    /// let schema = options.schema();
+    /// let columns: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    /// assert_eq!(columns, ["chrom", "pos", "gt"]);
+    /// ```
+    pub fn schema(&self) -> Schema {
+        let list = |typ| DataType::List(Arc::new(Field::new_list_field(typ, true)));
+        let mut fields = vec![
+            Field::new("chrom", DataType::Utf8, false),
+            Field::new("pos", DataType::Int32, false),
+        ];
+        if self.id {
+            fields.push(Field::new("id", DataType::Utf8, true));
+        }
+        if self.alleles {
+            fields.push(Field::new("ref", DataType::Utf8, false));
+            fields.push(Field::new("alt", list(DataType::Utf8), false));
+        }
+        if self.qual {
+            fields.push(Field::new("qual", DataType::Float32, true));
+        }
+        if self.filters {
+            fields.push(Field::new("filters", list(DataType::Utf8), false));
+        }
+        if self.genotypes.is_some() {
+            fields.push(Field::new("gt", list(DataType::Int8), false));
+        }
+        Schema::new(fields)
+    }
+}
+
+/// Appends records to the columns of a [`RecordBatch`]
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use bcf_reader::arrow_export::{ArrowBatchBuilder, ArrowOptions};
+/// use arrow_array::{cast::AsArray, types::Int32Type};
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut builder = ArrowBatchBuilder::new(&header, ArrowOptions::default());
+/// let mut record = Record::default();
+/// let mut positions = vec![];
+/// while let Ok(_) = record.read(&mut f) {
+///     builder.push(&record).unwrap();
+///     positions.push(record.pos());
+/// }
+/// assert_eq!(builder.len(), 127);
+/// let batch = builder.finish();
+/// assert!(builder.is_empty());
+/// let pos = batch.column_by_name("pos").unwrap().as_primitive::<Int32Type>();
+/// assert_eq!(pos.values(), &positions[..]);
+/// let chrom = batch.column_by_name("chrom").unwrap().as_string::<i32>();
+/// assert_eq!(chrom.value(0), "Pf3D7_01_v3");
+/// ```
+#[derive(Debug)]
+pub struct ArrowBatchBuilder<'h> {
+    header: &'h Header,
+    options: ArrowOptions,
+    schema: SchemaRef,
+    len: usize,
+    chrom: StringBuilder,
+    pos: Int32Builder,
+    id: StringBuilder,
+    ref_allele: StringBuilder,
+    alt: ListBuilder<StringBuilder>,
+    qual: Float32Builder,
+    filters: ListBuilder<StringBuilder>,
+    gt: ListBuilder<Int8Builder>,
+    /// scratch space of the genotypes
+    gts: Vec<i32>,
+    gt_column: Vec<i8>,
+}
+
+impl<'h> ArrowBatchBuilder<'h> {
+    /// Create a builder for the records of a file with `header`
+    pub fn new(header: &'h Header, options: ArrowOptions) -> Self {
+        ArrowBatchBuilder {
+            header,
+            schema: Arc::new(options.schema()),
+            options,
+            len: 0,
+            chrom: StringBuilder::new(),
+            pos: Int32Builder::new(),
+            id: StringBuilder::new(),
+            ref_allele: StringBuilder::new(),
+            alt: ListBuilder::new(StringBuilder::new()),
+            qual: Float32Builder::new(),
+            filters: ListBuilder::new(StringBuilder::new()),
+            gt: ListBuilder::new(Int8Builder::new()),
+            gts: vec![],
+            gt_column: vec![],
+        }
+    }
+
+    /// The schema of the batches
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Number of records appended since the last batch
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no record was appended since the last batch
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append a record to the columns.
+    ///
+    /// An error is returned if the record refers to a contig or a filter
+    /// not defined in the header, or if its genotypes do not fit the
+    /// layout of the `gt` column; the columns are then left unchanged.
+    pub fn push(&mut self, record: &Record) -> error::Result<()> {
+        let not_in_header = |what: &str, key: i64| {
+            Error::MalformedRecord(format!("{what} {key} is not defined in the header"))
+        };
+        let header = self.header;
+        let chrom = header
+            .interned_contig(record.chrom())
+            .ok_or_else(|| not_in_header("contig", record.chrom() as i64))?;
+        let mut filters = vec![];
+        if self.options.filters {
+            for nv in record.filters() {
+                let Some(key) = nv.int_val() else { continue };
+                let name = usize::try_from(key)
+                    .ok()
+                    .and_then(|key| header.interned_string(key))
+                    .ok_or_else(|| not_in_header("FILTER key", key as i64))?;
+                filters.push(name);
+            }
+        }
+        if let Some(layout) = self.options.genotypes {
+            let n_sample = header.get_samples().len();
+            // samples not kept by a sample selection have missing genotypes
+            if record.n_sample_read as usize != n_sample {
+                return Err(Error::UnexpectedCount {
+                    expected: n_sample,
+                    found: record.n_sample_read as usize,
+                });
+            }
+            self.gt_column.resize(layout.n_rows(n_sample), -1);
+            genotype_column(record, header, layout, &mut self.gts, &mut self.gt_column)?;
+        }
+
+        self.chrom.append_value(&**chrom);
+        self.pos.append_value(record.pos());
+        if self.options.id {
+            match record.id_bytes() {
+                b"" | b"." => self.id.append_null(),
+                id => self.id.append_value(String::from_utf8_lossy(id)),
+            }
+        }
+        if self.options.alleles {
+            self.ref_allele
+                .append_value(String::from_utf8_lossy(record.ref_allele_bytes()));
+            for alt in record.alt_allele_bytes() {
+                self.alt.values().append_value(String::from_utf8_lossy(alt));
+            }
+            self.alt.append(true);
+        }
+        if self.options.qual {
+            self.qual.append_option(record.qual());
+        }
+        if self.options.filters {
+            for name in filters {
+                self.filters.values().append_value(&**name);
+            }
+            self.filters.append(true);
+        }
+        if self.options.genotypes.is_some() {
+            self.gt.values().append_slice(&self.gt_column);
+            self.gt.append(true);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Take the records appended so far as a record batch, and start a new
+    /// batch
+    pub fn finish(&mut self) -> RecordBatch {
+        let mut columns: Vec<ArrayRef> =
+            vec![Arc::new(self.chrom.finish()), Arc::new(self.pos.finish())];
+        if self.options.id {
+            columns.push(Arc::new(self.id.finish()));
+        }
+        if self.options.alleles {
+            columns.push(Arc::new(self.ref_allele.finish()));
+            columns.push(Arc::new(self.alt.finish()));
+        }
+        if self.options.qual {
+            columns.push(Arc::new(self.qual.finish()));
+        }
+        if self.options.filters {
+            columns.push(Arc::new(self.filters.finish()));
+        }
+        if self.options.genotypes.is_some() {
+            columns.push(Arc::new(self.gt.finish()));
+        }
+        self.len = 0;
+        RecordBatch::try_new(self.schema.clone(), columns).expect("columns of the schema")
+    }
+}
+
+/// Iterator over the remaining records of a reader as record batches of
+/// [`ArrowOptions::batch_size`] records (the last batch may be smaller),
+/// see the [module docs](self)
+///
+/// A batch in which a record cannot be read or converted is an error, and
+/// the iteration stops after it.
+#[derive(Debug)]
+pub struct ArrowBatches<'r, 'h, T: ?Sized> {
+    reader: &'r mut T,
+    builder: ArrowBatchBuilder<'h>,
+    record: Record,
+    done: bool,
+}
+
+impl<'r, 'h, T: ReadRecord + ?Sized> ArrowBatches<'r, 'h, T> {
+    /// Stream the remaining records of `reader`, a [`BcfReader`] or an
+    /// [`IndexedBcfReader`] after its header is read
+    ///
+    /// [`BcfReader`]: crate::BcfReader
+    /// [`IndexedBcfReader`]: crate::IndexedBcfReader
+    pub fn new(reader: &'r mut T, header: &'h Header, options: ArrowOptions) -> Self {
+        ArrowBatches {
+            reader,
+            builder: ArrowBatchBuilder::new(header, options),
+            record: Record::default(),
+            done: false,
+        }
+    }
+
+    /// The schema of the batches
+    pub fn schema(&self) -> SchemaRef {
+        self.builder.schema()
+    }
+}
+
+impl<T: ReadRecord + ?Sized> Iterator for ArrowBatches<'_, '_, T> {
+    type Item = error::Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let batch_size = self.builder.options.batch_size.max(1);
+        while self.builder.len() < batch_size {
+            let res = self
+                .reader
+                .try_read_record(&mut self.record)
+                .and_then(|read| read.map(|()| self.builder.push(&self.record)).transpose());
+            match res {
+                Ok(Some(())) => {}
+                Ok(None) => {
+                    self.done = true;
+                    break;
+                }
+                Err(e) => {
+                    self.done = true;
+                    self.builder.finish();
+                    return Some(Err(e));
+                }
+            }
+        }
+        (!self.builder.is_empty()).then(|| Ok(self.builder.finish()))
+    }
+}
+
+impl<T: ReadRecord + ?Sized> std::iter::FusedIterator for ArrowBatches<'_, '_, T> {}
@@ -0,0 +1,438 @@
+//! Options of the readers, and the checks they run on the header and on the
+//! records they read
+
+use crate::{
+    error::{self, Error},
+    Header, RawHeader, Record, MAX_HEADER_TEXT_LENGTH,
+};
+use std::collections::HashSet;
+
+/// How strictly the readers check a file against the specification
+///
+/// In lenient mode (the default), common deviations found in real-world
+/// files, such as header lines without `Description`, empty alleles or
+/// values encoded with a type other than the one declared in the header,
+/// are accepted and reported as warnings (see `header_warnings` and
+/// `record_warnings` of the readers). In strict mode, they are errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// reject any deviation from the specification
+    Strict,
+    /// accept common deviations, reporting them as warnings
+    #[default]
+    Lenient,
+}
+
+/// How the readers handle text that is not valid UTF-8, in the header and
+/// in the strings of records (ID, alleles and string INFO/FORMAT values)
+///
+/// Whatever the policy, the bytes of record strings are available without
+/// validation (e.g. [`Record::id_bytes`], [`Record::info_field_bytes`]).
+/// By default (`Raw`), the strings of records are not scanned, which costs
+/// time on every record; `Error` and `Lossy` opt in to the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// invalid UTF-8 is an error
+    Error,
+    /// invalid UTF-8 is reported as a warning; the header text is decoded
+    /// with invalid sequences replaced by U+FFFD, as are the record strings
+    /// by the lossy accessors (e.g. [`Record::info_field_str_lossy`])
+    Lossy,
+    /// records are not checked, for callers working with the bytes; the
+    /// header text is decoded as with `Lossy`, without warning
+    #[default]
+    Raw,
+}
+
+/// Limits on the size of the data read by [`BcfReader`](crate::BcfReader) and
+/// [`IndexedBcfReader`](crate::IndexedBcfReader), so that a small crafted file
+/// cannot make a reader use a lot of memory or time. A record exceeding a limit
+/// is an [`Error::LimitExceeded`]; it is read to the end, so the next record
+/// can be read (see
+/// [`BcfReader::set_skip_corrupt_records`](crate::BcfReader::set_skip_corrupt_records)).
+///
+/// The default limits only bound the header text length (to
+/// [`MAX_HEADER_TEXT_LENGTH`]); [`ResourceLimits::untrusted`] is a starting
+/// point for files from untrusted sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// maximum length of the header text, in bytes
+    pub max_header_length: u32,
+    /// maximum number of samples in the header and in a record
+    pub max_samples: usize,
+    /// maximum length of a record (`l_shared + l_indv`), in bytes
+    pub max_record_length: u64,
+    /// maximum number of alleles of a record
+    pub max_alleles: usize,
+    /// maximum number of values of an INFO or FORMAT field (over all
+    /// samples)
+    pub max_field_values: usize,
+}
+
+/// Limits of [`ResourceLimits::default`]
+pub(crate) const NO_LIMITS: ResourceLimits = ResourceLimits {
+    max_header_length: MAX_HEADER_TEXT_LENGTH,
+    max_samples: usize::MAX,
+    max_record_length: u64::MAX,
+    max_alleles: usize::MAX,
+    max_field_values: usize::MAX,
+};
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        NO_LIMITS
+    }
+}
+
+impl ResourceLimits {
+    /// Limits for files from untrusted sources: 64 MiB of header text,
+    /// 1 million samples, 256 MiB records, 4096 alleles and 64 million
+    /// values per field
+    pub fn untrusted() -> Self {
+        Self {
+            max_header_length: 64 << 20,
+            max_samples: 1_000_000,
+            max_record_length: 256 << 20,
+            max_alleles: 4096,
+            max_field_values: 64_000_000,
+        }
+    }
+
+    /// Check the length of a record (`l_shared + l_indv`) against
+    /// `max_record_length`
+    pub(crate) fn check_record_length(&self, length: u64) -> error::Result<()> {
+        if length > self.max_record_length {
+            return Err(Error::LimitExceeded {
+                what: "record length",
+                value: length,
+                limit: self.max_record_length,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Kind of a [`ReadWarning`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// a problem in the header (see `header_warnings` of the readers)
+    Header,
+    /// a contig, FILTER, INFO or FORMAT key not defined in the header
+    UnknownIndex,
+    /// a deviation from the specification, e.g. an empty allele or values
+    /// encoded with a type not matching the header
+    Nonconforming,
+    /// a record string that is not valid UTF-8
+    InvalidUtf8,
+    /// bytes after the last field of a record buffer
+    TrailingBytes,
+    /// values after the end-of-vector padding of a vector
+    Padding,
+    /// a record out of order (see [`OrderCheck`])
+    Unsorted,
+    /// a record with the same contig, position and alleles as the previous
+    /// one (see [`DuplicateDetector`])
+    Duplicate,
+}
+
+/// A recoverable oddity found while reading, reported to the warning handler of
+/// the readers (see
+/// [`BcfReader::set_warning_handler`](crate::BcfReader::set_warning_handler))
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadWarning {
+    /// what kind of oddity
+    pub kind: WarningKind,
+    /// offset in the decompressed data of the record, if known
+    pub offset: Option<u64>,
+    /// description of the oddity
+    pub message: String,
+}
+
+impl ReadWarning {
+    pub(crate) fn new(kind: WarningKind, message: String) -> Self {
+        Self {
+            kind,
+            offset: None,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for ReadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "record at byte {offset}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// How the readers check that records are sorted: the records of a contig
+/// must be contiguous and sorted by position. Indexing, merging and
+/// interval queries give wrong results on unsorted files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderCheck {
+    /// do not check the order
+    #[default]
+    Off,
+    /// report records out of order as warnings (see `record_warnings` and
+    /// `set_warning_handler` of the readers)
+    Warn,
+    /// return an [`Error::Unsorted`] for the first record out of order
+    Error,
+}
+
+/// Tracks the contigs and positions of the records as they are read, to
+/// check their order
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OrderTracker {
+    /// (rid, pos) of the previous record
+    previous: Option<(i32, i32)>,
+    /// contigs of the records before the previous one
+    finished_contigs: HashSet<i32>,
+}
+
+impl OrderTracker {
+    /// Check that a record comes after the previous one, returning a
+    /// description of the problem otherwise. The record becomes the
+    /// previous one in both cases, so that a misplaced block of records is
+    /// reported once.
+    pub(crate) fn check(&mut self, header: Option<&Header>, rid: i32, pos: i32) -> Option<String> {
+        let name = |rid: i32| {
+            header
+                .and_then(|h| h.rid_to_name(rid))
+                .map_or_else(|| rid.to_string(), str::to_string)
+        };
+        let previous = self.previous.replace((rid, pos));
+        match previous {
+            Some((prev_rid, prev_pos)) if prev_rid == rid && prev_pos > pos => Some(format!(
+                "{}:{} is after {}:{}",
+                name(rid),
+                pos + 1,
+                name(rid),
+                prev_pos + 1
+            )),
+            Some((prev_rid, _)) if prev_rid != rid => {
+                self.finished_contigs.insert(prev_rid);
+                self.finished_contigs.contains(&rid).then(|| {
+                    format!(
+                        "{}:{} is after records of other contigs following contig {}",
+                        name(rid),
+                        pos + 1,
+                        name(rid)
+                    )
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Check the order of a record read by a reader according to `check`
+pub(crate) fn check_order(
+    record: &Record,
+    header: &CheckHeader,
+    check: OrderCheck,
+    tracker: &mut OrderTracker,
+    warnings: &mut Vec<String>,
+    handler: &mut Option<WarningHandler>,
+    offset: Option<u64>,
+) -> error::Result<()> {
+    if check == OrderCheck::Off {
+        return Ok(());
+    }
+    let Some(message) = tracker.check(header.get(), record.chrom, record.pos) else {
+        return Ok(());
+    };
+    if check == OrderCheck::Error {
+        return Err(Error::Unsorted(message));
+    }
+    warnings.push(message.clone());
+    let warning = ReadWarning::new(WarningKind::Unsorted, message);
+    report_warnings(handler, [warning], offset);
+    Ok(())
+}
+
+/// Detects consecutive records with the same contig, position and alleles,
+/// which usually come from a bug upstream (e.g. a file concatenated with
+/// itself or overlapping shards)
+///
+/// The readers can run a detector on the records they read (see
+/// [`BcfReader::set_duplicate_detection`](crate::BcfReader::set_duplicate_detection)).
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let _header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut record = Record::default();
+/// let mut detector = DuplicateDetector::new();
+/// record.read(&mut f).unwrap();
+/// assert!(!detector.is_duplicate(&record));
+/// // the same record again
+/// assert!(detector.is_duplicate(&record));
+/// record.read(&mut f).unwrap();
+/// assert!(!detector.is_duplicate(&record));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateDetector {
+    /// contig, position and alleles of the previous record
+    previous: Vec<u8>,
+    current: Vec<u8>,
+}
+
+impl DuplicateDetector {
+    /// Create a detector that has not seen any record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a record has the same contig, position and alleles as the
+    /// record previously passed to the detector
+    pub fn is_duplicate(&mut self, record: &Record) -> bool {
+        let key = &mut self.current;
+        key.clear();
+        key.extend(record.chrom.to_le_bytes());
+        key.extend(record.pos.to_le_bytes());
+        for i in 0..record.alleles.len() {
+            let allele = record.allele_bytes(i);
+            key.extend((allele.len() as u32).to_le_bytes());
+            key.extend(allele);
+        }
+        let duplicate = self.current == self.previous;
+        std::mem::swap(&mut self.current, &mut self.previous);
+        duplicate
+    }
+}
+
+/// Report a record with the same contig, position and alleles as the
+/// previous one as a warning, if `detector` is set
+pub(crate) fn check_duplicate(
+    record: &Record,
+    header: &CheckHeader,
+    detector: &mut Option<DuplicateDetector>,
+    warnings: &mut Vec<String>,
+    handler: &mut Option<WarningHandler>,
+    offset: Option<u64>,
+) {
+    if !detector.as_mut().is_some_and(|d| d.is_duplicate(record)) {
+        return;
+    }
+    let chrom = header
+        .get()
+        .and_then(|h| h.rid_to_name(record.chrom))
+        .map_or_else(|| record.chrom.to_string(), str::to_string);
+    let alleles: Vec<_> = (0..record.alleles.len())
+        .map(|i| String::from_utf8_lossy(record.allele_bytes(i)))
+        .collect();
+    let message = format!(
+        "{chrom}:{} {} is a duplicate of the previous record",
+        record.pos + 1,
+        alleles.join(",")
+    );
+    warnings.push(message.clone());
+    let warning = ReadWarning::new(WarningKind::Duplicate, message);
+    report_warnings(handler, [warning], offset);
+}
+
+/// The header of a reader, for the record checks: a copy of the header
+/// parsed by the reader and returned to the caller
+#[derive(Debug, Default)]
+pub(crate) struct CheckHeader {
+    header: Option<Header>,
+}
+
+impl CheckHeader {
+    pub(crate) fn new(header: Header) -> Self {
+        CheckHeader {
+            header: Some(header),
+        }
+    }
+
+    /// The parsed header, if the header has been read
+    fn get(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+}
+
+/// Callback receiving the warnings of a reader
+pub(crate) type WarningHandler = Box<dyn FnMut(&ReadWarning) + Send + Sync>;
+
+/// Report warnings to the handler of a reader, if any, with the offset of
+/// their record
+pub(crate) fn report_warnings(
+    handler: &mut Option<WarningHandler>,
+    warnings: impl IntoIterator<Item = ReadWarning>,
+    offset: Option<u64>,
+) {
+    let Some(handler) = handler else {
+        return;
+    };
+    for mut warning in warnings {
+        warning.offset = offset;
+        handler(&warning);
+    }
+}
+
+/// Check a record read by a reader against its header: deviations are
+/// errors in strict mode and are kept in `warnings` in lenient mode, or if
+/// there is a handler. Strings are checked according to `utf8`. The
+/// warnings, and the [`Record::anomalies`], are reported to `handler` if
+/// there is one.
+pub(crate) fn check_conformance(
+    record: &Record,
+    header: &CheckHeader,
+    mode: ParseMode,
+    utf8: Utf8Policy,
+    warnings: &mut Vec<String>,
+    handler: &mut Option<WarningHandler>,
+    offset: Option<u64>,
+) -> error::Result<()> {
+    warnings.clear();
+    let mut found = vec![];
+    // scanning the strings is skipped unless a policy opts in
+    if utf8 != Utf8Policy::Raw {
+        if let Err(e) = record.check_utf8() {
+            if utf8 == Utf8Policy::Error {
+                return Err(e);
+            }
+            found.push(ReadWarning::new(WarningKind::InvalidUtf8, e.to_string()));
+        }
+    }
+    if let Some(header) = header.get() {
+        let issues = record.conformance_warnings(header);
+        if mode == ParseMode::Strict && !issues.is_empty() {
+            let issues: Vec<_> = issues.into_iter().map(|w| w.message).collect();
+            return Err(Error::Nonconforming(issues.join("; ")));
+        }
+        found.extend(issues);
+    }
+    warnings.extend(found.iter().map(|w| w.message.clone()));
+    if handler.is_some() {
+        found.extend(record.anomalies());
+        report_warnings(handler, found, offset);
+    }
+    Ok(())
+}
+
+/// Apply a UTF-8 policy of a reader to a header whose text is not valid
+/// UTF-8: an error for `Error`, no warning for `Raw`
+pub(crate) fn apply_utf8_policy(raw: &mut RawHeader, policy: Utf8Policy) -> error::Result<()> {
+    let Some(e) = raw.utf8_error else {
+        return Ok(());
+    };
+    match policy {
+        Utf8Policy::Error => Err(Error::InvalidHeader(header_utf8_warning(e))),
+        Utf8Policy::Lossy => Ok(()),
+        Utf8Policy::Raw => {
+            let warning = header_utf8_warning(e);
+            raw.warnings.retain(|w| *w != warning);
+            Ok(())
+        }
+    }
+}
+
+/// The warning about a header text that is not valid UTF-8
+pub(crate) fn header_utf8_warning(e: std::str::Utf8Error) -> String {
+    format!("header text is not valid UTF-8: {e}")
+}
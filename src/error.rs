@@ -0,0 +1,271 @@
+//! Error type of the crate.
+//!
+//! Reading a malformed or truncated BCF file returns an [`Error`] instead of
+//! panicking, so that a process reading untrusted files is not aborted.
+//! Errors in records tell which field is malformed and where (see
+//! [`Error::Context`]).
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! // a record cut short after its first bytes
+//! let data = [8u8, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+//! let mut record = Record::default();
+//! match record.read(&mut data.as_slice()) {
+//!     Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+//!     other => panic!("unexpected result {other:?}"),
+//! }
+//!
+//! // a record with an integer where the second allele should be
+//! let mut shared = vec![];
+//! for x in [0i32, 99, 1] {
+//!     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+//! }
+//! shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+//! shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+//! shared.extend(0u32.to_le_bytes()); // no samples
+//! shared.extend([0x07, 0x17, b'A', 0x11, 3, 0x00]); // ID, alleles, FILTER
+//! let mut data = vec![];
+//! data.extend((shared.len() as u32).to_le_bytes());
+//! data.extend(0u32.to_le_bytes());
+//! data.extend(shared);
+//! let err = record.read(&mut data.as_slice()).unwrap_err();
+//! assert_eq!(
+//!     err.to_string(),
+//!     "allele 1 in the shared buffer at byte 27: \
+//!      expected a string, found BCF2 type code 1 (type descriptor byte 0x11)"
+//! );
+//! assert!(matches!(err.root_cause(), Error::UnexpectedType { found: 1, .. }));
+//! ```
+
+use crate::HeaderParseError;
+use std::io;
+
+/// Errors returned when reading BCF data
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// error of the underlying reader, including unexpected end of data
+    #[error(transparent)]
+    Io(io::Error),
+    /// no data at all where a BCF file was expected, e.g. a zero-byte file
+    /// or a BGZF file with only the end-of-file block. A file with only a
+    /// header is not an error: it has no records.
+    #[error("the input is empty")]
+    EmptyInput,
+    /// malformed header text
+    #[error(transparent)]
+    Header(#[from] HeaderParseError),
+    /// data that is not a BCF header: wrong magic bytes, unsupported
+    /// version or absurd header length
+    #[error("{0}")]
+    InvalidHeader(String),
+    /// invalid BGZF (gzip) data
+    #[error("{0}")]
+    Compression(String),
+    /// a CSI index that cannot be read
+    #[error("invalid CSI index: {0}")]
+    InvalidIndex(String),
+    /// a type code that is not defined by BCF2
+    #[error("invalid BCF2 type code {typ}{}", descriptor_suffix(*.descriptor))]
+    InvalidType {
+        /// the type code
+        typ: u8,
+        /// the type descriptor byte with the type code, if known
+        descriptor: Option<u8>,
+    },
+    /// a typed value of another type than expected
+    #[error("expected {expected}, found BCF2 type code {found}{}", descriptor_suffix(Some(*.descriptor)))]
+    UnexpectedType {
+        /// the expected kind of value
+        expected: &'static str,
+        /// the type code found
+        found: u8,
+        /// the type descriptor byte
+        descriptor: u8,
+    },
+    /// a typed value with an unexpected number of elements
+    #[error("expected {expected} value(s), found {found}")]
+    UnexpectedCount {
+        /// the expected number of elements
+        expected: usize,
+        /// the number of elements found
+        found: usize,
+    },
+    /// a record whose fields do not fit its declared lengths
+    #[error("malformed record: {0}")]
+    MalformedRecord(String),
+    /// a value exceeding a limit of [`ResourceLimits`](crate::ResourceLimits)
+    #[error("{what} {value} exceeds the limit of {limit}")]
+    LimitExceeded {
+        /// the limited value, e.g. `record length`
+        what: &'static str,
+        /// the value
+        value: u64,
+        /// the limit
+        limit: u64,
+    },
+    /// a string that is not valid UTF-8 (see [`Utf8Policy`](crate::Utf8Policy))
+    #[error("invalid UTF-8 in {what}: {source}")]
+    InvalidUtf8 {
+        /// the string, e.g. `the ID` or `allele 1`
+        what: String,
+        /// the UTF-8 error
+        source: std::str::Utf8Error,
+    },
+    /// a record that does not conform to the specification, in strict mode
+    /// (see [`ParseMode`](crate::ParseMode))
+    #[error("record does not conform to the specification: {0}")]
+    Nonconforming(String),
+    /// a record out of order (see [`OrderCheck`](crate::OrderCheck))
+    #[error("records are not sorted: {0}")]
+    Unsorted(String),
+    /// an error while reading a part of a record
+    #[error("{context} at byte {offset}: {source}")]
+    Context {
+        /// the part being read, e.g. `allele 1 in the shared buffer`
+        context: String,
+        /// offset in the record buffer where the part starts
+        offset: usize,
+        /// the error
+        source: Box<Error>,
+    },
+}
+
+fn descriptor_suffix(descriptor: Option<u8>) -> String {
+    match descriptor {
+        Some(d) => format!(" (type descriptor byte {d:#04x})"),
+        None => String::new(),
+    }
+}
+
+impl Error {
+    /// Add the part of a record being read and its offset to an error
+    pub(crate) fn context(self, context: impl Into<String>, offset: usize) -> Self {
+        Error::Context {
+            context: context.into(),
+            offset,
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the error without the context added by [`Error::Context`]
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root_cause(),
+            e => e,
+        }
+    }
+
+    /// Get the category of the error, e.g. to decide whether to retry
+    pub fn category(&self) -> ErrorCategory {
+        match self.root_cause() {
+            Error::Io(_) | Error::EmptyInput => ErrorCategory::Io,
+            Error::Compression(_) => ErrorCategory::Compression,
+            Error::Header(_) | Error::InvalidHeader(_) => ErrorCategory::HeaderFormat,
+            Error::InvalidIndex(_) => ErrorCategory::Index,
+            Error::LimitExceeded { .. } => ErrorCategory::Limit,
+            _ => ErrorCategory::RecordFormat,
+        }
+    }
+
+    /// Whether the data ends too early: a truncated header, record or BGZF
+    /// block, or no data at all. Reading again once the data is complete
+    /// (e.g. after a download finishes) may succeed.
+    pub fn is_truncation(&self) -> bool {
+        match self.root_cause() {
+            Error::Io(e) => e.kind() == io::ErrorKind::UnexpectedEof,
+            Error::EmptyInput => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the data is malformed: reading it again will fail the same
+    /// way
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self.category(),
+            ErrorCategory::Compression
+                | ErrorCategory::HeaderFormat
+                | ErrorCategory::RecordFormat
+                | ErrorCategory::Index
+        )
+    }
+}
+
+/// Category of an [`Error`] (see [`Error::category`])
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let data = std::fs::read("testdata/test.bcf").unwrap();
+/// // a BGZF file cut within a block
+/// let mut reader = BcfReader::from_reader(ParMultiGzipReader::from_reader(
+///     &data[..data.len() / 2],
+///     3,
+///     None,
+///     None,
+/// ));
+/// reader.read_header();
+/// let mut record = Record::default();
+/// let err = loop {
+///     if let Err(e) = reader.read_record(&mut record) {
+///         break e;
+///     }
+/// };
+/// assert_eq!(err.category(), ErrorCategory::Io);
+/// assert!(err.is_truncation() && !err.is_corruption());
+///
+/// // a BGZF file with a damaged block
+/// let mut damaged = data.clone();
+/// damaged[100] ^= 0xff;
+/// let mut reader = ParMultiGzipReader::from_reader(damaged.as_slice(), 3, None, None);
+/// let err: Error = read_header(&mut reader).unwrap_err();
+/// assert_eq!(err.category(), ErrorCategory::Compression);
+/// assert!(err.is_corruption() && !err.is_truncation());
+///
+/// // not a BCF file
+/// let vcf = b"##fileformat=VCFv4.2\n";
+/// let err = read_header(&mut &vcf[..]).unwrap_err();
+/// assert_eq!(err.category(), ErrorCategory::HeaderFormat);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// errors of the underlying reader, including truncated data
+    Io,
+    /// invalid BGZF (gzip) data
+    Compression,
+    /// malformed header
+    HeaderFormat,
+    /// malformed or nonconforming record
+    RecordFormat,
+    /// invalid CSI index
+    Index,
+    /// a limit of [`ResourceLimits`](crate::ResourceLimits) exceeded
+    Limit,
+}
+
+/// Result type of the crate
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::EmptyInput => io::Error::new(io::ErrorKind::UnexpectedEof, e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// An `io::Error` carrying an [`Error`] (e.g. from a reader returning
+/// `io::Result`) converts back to that error
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.get_ref().map(|inner| inner.is::<Error>()) {
+            Some(true) => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
+            _ => Error::Io(e),
+        }
+    }
+}
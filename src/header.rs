@@ -0,0 +1,1480 @@
+//! Typed representations of the structured header lines (`##contig=<..>`,
+//! `##INFO=<..>`, `##FORMAT=<..>` and `##FILTER=<..>`).
+//!
+//! The well-known attributes of each line are exposed as struct fields; the
+//! remaining attributes are kept in `other_fields`.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! let mut f = smart_reader("testdata/test.bcf");
+//! let header = Header::from_string(&read_header(&mut f).unwrap());
+//! let contig = &header.contigs()[0];
+//! assert_eq!(contig.id, "Pf3D7_01_v3");
+//! assert_eq!(contig.length, Some(640851));
+//! let gt = header.format("GT").unwrap();
+//! assert_eq!(gt.number, header::Number::Fixed(1));
+//! assert_eq!(gt.ty, header::Type::String);
+//! assert_eq!(Some(gt.idx), header.get_fmt_gt_id());
+//! assert!(header.info("AC").is_some());
+//! assert_eq!(header.filter("PASS").unwrap().idx, 0);
+//! ```
+
+use std::collections::HashMap;
+
+/// Collect the attributes of a header line that are not in `known`
+fn other_fields(m: &HashMap<String, String>, known: &[&str]) -> HashMap<String, String> {
+    m.iter()
+        .filter(|(k, _)| !known.contains(&k.as_str()) && *k != "Dictionary" && *k != "IDX")
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+/// Get an attribute of a header line, or an empty string if absent
+fn field(m: &HashMap<String, String>, key: &str) -> String {
+    m.get(key).cloned().unwrap_or_default()
+}
+
+/// Get an attribute of a header line, if present
+fn optional_field(m: &HashMap<String, String>, key: &str) -> Option<String> {
+    m.get(key).cloned()
+}
+
+/// The `Number=` attribute of INFO/FORMAT header lines
+///
+/// ```
+/// use bcf_reader::header::Number;
+/// assert_eq!(Number::from("2"), Number::Fixed(2));
+/// assert_eq!(Number::from("R"), Number::R);
+/// assert_eq!(Number::from("."), Number::Dot);
+/// assert_eq!(Number::from("X"), Number::Other("X".into()));
+/// assert_eq!(Number::G.to_string(), "G");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Number {
+    /// a fixed number of values
+    Fixed(usize),
+    /// one value per ALT allele
+    A,
+    /// one value per allele, including REF
+    R,
+    /// one value per genotype
+    G,
+    /// unknown or varying number of values (`.`)
+    Dot,
+    /// one value per allele of the genotype (`P`, VCF 4.4)
+    P,
+    /// one value per local ALT allele (`LA`)
+    LA,
+    /// one value per local allele, including REF (`LR`)
+    LR,
+    /// one value per local genotype (`LG`)
+    LG,
+    /// a value not defined by the specification
+    Other(String),
+}
+
+impl From<&str> for Number {
+    fn from(s: &str) -> Self {
+        match s {
+            "A" => Number::A,
+            "R" => Number::R,
+            "G" => Number::G,
+            "." => Number::Dot,
+            "P" => Number::P,
+            "LA" => Number::LA,
+            "LR" => Number::LR,
+            "LG" => Number::LG,
+            s => match s.parse() {
+                Ok(n) => Number::Fixed(n),
+                Err(_) => Number::Other(s.into()),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Fixed(n) => write!(f, "{n}"),
+            Number::A => write!(f, "A"),
+            Number::R => write!(f, "R"),
+            Number::G => write!(f, "G"),
+            Number::Dot => write!(f, "."),
+            Number::P => write!(f, "P"),
+            Number::LA => write!(f, "LA"),
+            Number::LR => write!(f, "LR"),
+            Number::LG => write!(f, "LG"),
+            Number::Other(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// The `Type=` attribute of INFO/FORMAT header lines
+///
+/// ```
+/// use bcf_reader::header::Type;
+/// assert_eq!(Type::from("Integer"), Type::Integer);
+/// assert_eq!(Type::from("Flag"), Type::Flag);
+/// assert_eq!(Type::Float.to_string(), "Float");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// 32-bit signed integer
+    Integer,
+    /// 32-bit float
+    Float,
+    /// INFO flag (no value)
+    Flag,
+    /// single character
+    Character,
+    /// string
+    String,
+    /// a value not defined by the specification
+    Other(std::string::String),
+}
+
+impl From<&str> for Type {
+    fn from(s: &str) -> Self {
+        match s {
+            "Integer" => Type::Integer,
+            "Float" => Type::Float,
+            "Flag" => Type::Flag,
+            "Character" => Type::Character,
+            "String" => Type::String,
+            s => Type::Other(s.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Integer => write!(f, "Integer"),
+            Type::Float => write!(f, "Float"),
+            Type::Flag => write!(f, "Flag"),
+            Type::Character => write!(f, "Character"),
+            Type::String => write!(f, "String"),
+            Type::Other(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// A `##META=<..>` header line (VCF 4.4), declaring the allowed values of a
+/// `##SAMPLE` attribute
+///
+/// ```
+/// use bcf_reader::Header;
+/// use bcf_reader::header::{Number, Type};
+/// let header = Header::from_string(concat!(
+///     "##fileformat=VCFv4.4\n",
+///     "##META=<ID=Assay,Type=String,Number=.,Values=[WholeGenome, Exome]>\n",
+///     "##FORMAT=<ID=LAA,Number=.,Type=Integer,Description=\"Local alleles\">\n",
+///     "##FORMAT=<ID=LGT,Number=1,Type=String,Description=\"Local genotype\">\n",
+///     "##FORMAT=<ID=LAD,Number=LR,Type=Integer,Description=\"Local allelic depths\">\n",
+///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\n",
+/// ));
+/// let meta = header.meta("Assay").unwrap();
+/// assert_eq!(meta.ty, Type::String);
+/// assert_eq!(meta.number, Number::Dot);
+/// assert_eq!(meta.values, ["WholeGenome", "Exome"]);
+///
+/// assert!(header.has_local_alleles());
+/// assert_eq!(header.format("LAD").unwrap().number, Number::LR);
+/// let local: Vec<_> = header
+///     .local_allele_fields()
+///     .iter()
+///     .map(|(r, global)| (r.id.as_str(), *global))
+///     .collect();
+/// assert_eq!(local, [("LGT", "GT"), ("LAD", "AD")]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetaRecord {
+    /// name of the described `##SAMPLE` attribute
+    pub id: String,
+    /// `Number` attribute
+    pub number: Number,
+    /// `Type` attribute
+    pub ty: Type,
+    /// allowed values, from `Values=[..]`
+    pub values: Vec<String>,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl MetaRecord {
+    pub(crate) fn from_map(m: &HashMap<String, String>) -> Self {
+        let values = field(m, "Values");
+        let values = values.trim_start_matches('[').trim_end_matches(']');
+        Self {
+            id: field(m, "ID"),
+            number: Number::from(field(m, "Number").as_str()),
+            ty: Type::from(field(m, "Type").as_str()),
+            values: values
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect(),
+            other_fields: other_fields(m, &["ID", "Number", "Type", "Values"]),
+        }
+    }
+}
+
+/// An `##ALT=<..>` header line, describing a symbolic ALT allele
+///
+/// ```
+/// use bcf_reader::Header;
+/// let header = Header::from_string(concat!(
+///     "##fileformat=VCFv4.3\n",
+///     "##ALT=<ID=DEL,Description=\"Deletion relative to the reference\">\n",
+///     "##ALT=<ID=DEL:ME:ALU,Description=\"Deletion of ALU element\">\n",
+///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+/// ));
+/// assert_eq!(header.alts().len(), 2);
+/// assert_eq!(header.alt("DEL").unwrap().description, "Deletion relative to the reference");
+/// let alt = header.resolve_symbolic_allele(b"<DEL:ME:ALU>").unwrap();
+/// assert_eq!(alt.id, "DEL:ME:ALU");
+/// // subtypes not declared in the header resolve to their parent type
+/// assert_eq!(header.resolve_symbolic_allele(b"<DEL:ME:L1>").unwrap().id, "DEL");
+/// assert!(header.resolve_symbolic_allele(b"<INV>").is_none());
+/// assert!(header.resolve_symbolic_allele(b"A").is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AltRecord {
+    /// ID of the symbolic allele, without the angle brackets (e.g. `DEL:ME`)
+    pub id: String,
+    /// `Description` attribute, without the quotes
+    pub description: String,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl AltRecord {
+    pub(crate) fn from_map(m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            description: field(m, "Description"),
+            other_fields: other_fields(m, &["ID", "Description"]),
+        }
+    }
+}
+
+/// A `##SAMPLE=<..>` header line, describing a sample (e.g. its assay, or
+/// the genomes and mixture of a tumor sample)
+///
+/// ```
+/// use bcf_reader::Header;
+/// let header = Header::from_string(concat!(
+///     "##fileformat=VCFv4.3\n",
+///     "##SAMPLE=<ID=Blood,Genomes=Germline,Mixture=1.,Description=\"Patient germline genome\">\n",
+///     "##SAMPLE=<ID=TissueSample,Genomes=Germline;Tumor,Mixture=.3;.7,Description=\"Patient tumor\">\n",
+///     "##PEDIGREE=<ID=TissueSample,Original=Blood>\n",
+///     "##PEDIGREE=<ID=Child,Father=Dad,Mother=Mum>\n",
+///     "##PEDIGREE=<Child=Kid,Mother=Mum2,Father=Dad2>\n",
+///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tBlood\tTissueSample\n",
+/// ));
+/// let tumor = header.sample_record("TissueSample").unwrap();
+/// assert_eq!(tumor.description.as_deref(), Some("Patient tumor"));
+/// assert_eq!(tumor.get("Genomes"), Some("Germline;Tumor"));
+/// let ped = header.pedigree("TissueSample").unwrap();
+/// assert_eq!(ped.original.as_deref(), Some("Blood"));
+/// let trio = header.pedigree("Child").unwrap();
+/// assert_eq!(trio.father.as_deref(), Some("Dad"));
+/// assert_eq!(trio.mother.as_deref(), Some("Mum"));
+/// // VCF 4.2 style
+/// assert_eq!(header.pedigree("Kid").unwrap().mother.as_deref(), Some("Mum2"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleRecord {
+    /// sample name
+    pub id: String,
+    /// `Description` attribute, without the quotes
+    pub description: Option<String>,
+    /// other attributes (e.g. `Assay`, `Genomes`, `Mixture`, `Disease`)
+    pub other_fields: HashMap<String, String>,
+}
+
+impl SampleRecord {
+    pub(crate) fn from_map(m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            description: optional_field(m, "Description"),
+            other_fields: other_fields(m, &["ID", "Description"]),
+        }
+    }
+
+    /// Get another attribute of the line by key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.other_fields.get(key).map(|x| x.as_str())
+    }
+}
+
+/// A `##PEDIGREE=<..>` header line, relating a sample to its parents or to
+/// the sample it is derived from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PedigreeRecord {
+    /// the sample described by the line (`ID`, or `Child` in VCF 4.2)
+    pub id: String,
+    /// `Father` attribute
+    pub father: Option<String>,
+    /// `Mother` attribute
+    pub mother: Option<String>,
+    /// `Original` attribute: the sample this one is derived from (e.g. the
+    /// germline sample of a tumor)
+    pub original: Option<String>,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl PedigreeRecord {
+    pub(crate) fn from_map(m: &HashMap<String, String>) -> Self {
+        let id = optional_field(m, "ID")
+            .or_else(|| optional_field(m, "Child"))
+            .unwrap_or_default();
+        Self {
+            id,
+            father: optional_field(m, "Father"),
+            mother: optional_field(m, "Mother"),
+            original: optional_field(m, "Original"),
+            other_fields: other_fields(m, &["ID", "Child", "Father", "Mother", "Original"]),
+        }
+    }
+}
+
+/// A `##contig=<..>` header line
+///
+/// ```
+/// use bcf_reader::Header;
+/// let header = Header::from_string(concat!(
+///     "##fileformat=VCFv4.3\n",
+///     "##contig=<ID=chr1,length=248956422,assembly=GRCh38,md5=6aef897c3d6ff0c78aff06ac189178dd,species=\"Homo sapiens\">\n",
+///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+/// ));
+/// let contig = header.contig("chr1").unwrap();
+/// assert_eq!(contig.length, Some(248956422));
+/// assert_eq!(contig.assembly.as_deref(), Some("GRCh38"));
+/// assert_eq!(contig.md5.as_deref(), Some("6aef897c3d6ff0c78aff06ac189178dd"));
+/// assert_eq!(contig.species.as_deref(), Some("Homo sapiens"));
+/// assert_eq!(contig.url, None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContigRecord {
+    /// contig name
+    pub id: String,
+    /// contig length, if given
+    pub length: Option<u64>,
+    /// `assembly` attribute, e.g. `GRCh38`
+    pub assembly: Option<String>,
+    /// `md5` attribute: MD5 checksum of the contig sequence
+    pub md5: Option<String>,
+    /// `species` attribute
+    pub species: Option<String>,
+    /// `taxonomy` attribute
+    pub taxonomy: Option<String>,
+    /// `URL` attribute
+    pub url: Option<String>,
+    /// offset in the contig dictionary (the `chrom` of records)
+    pub idx: usize,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl ContigRecord {
+    pub(crate) fn from_map(idx: usize, m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            length: m.get("length").and_then(|x| x.parse().ok()),
+            assembly: optional_field(m, "assembly"),
+            md5: optional_field(m, "md5"),
+            species: optional_field(m, "species"),
+            taxonomy: optional_field(m, "taxonomy"),
+            url: optional_field(m, "URL"),
+            idx,
+            other_fields: other_fields(
+                m,
+                &[
+                    "ID", "length", "assembly", "md5", "species", "taxonomy", "URL",
+                ],
+            ),
+        }
+    }
+}
+
+/// An `##INFO=<..>` header line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfoRecord {
+    /// tag of the field
+    pub id: String,
+    /// `Number` attribute
+    pub number: Number,
+    /// `Type` attribute
+    pub ty: Type,
+    /// `Description` attribute, without the quotes
+    pub description: String,
+    /// offset in the dictionary of strings (the key used in records)
+    pub idx: usize,
+    /// other attributes (e.g. `Source`, `Version`)
+    pub other_fields: HashMap<String, String>,
+}
+
+impl InfoRecord {
+    pub(crate) fn from_map(idx: usize, m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            number: Number::from(field(m, "Number").as_str()),
+            ty: Type::from(field(m, "Type").as_str()),
+            description: field(m, "Description"),
+            idx,
+            other_fields: other_fields(m, &["ID", "Number", "Type", "Description"]),
+        }
+    }
+}
+
+/// A `##FORMAT=<..>` header line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatRecord {
+    /// tag of the field
+    pub id: String,
+    /// `Number` attribute
+    pub number: Number,
+    /// `Type` attribute
+    pub ty: Type,
+    /// `Description` attribute, without the quotes
+    pub description: String,
+    /// offset in the dictionary of strings (the key used in records)
+    pub idx: usize,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl FormatRecord {
+    pub(crate) fn from_map(idx: usize, m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            number: Number::from(field(m, "Number").as_str()),
+            ty: Type::from(field(m, "Type").as_str()),
+            description: field(m, "Description"),
+            idx,
+            other_fields: other_fields(m, &["ID", "Number", "Type", "Description"]),
+        }
+    }
+}
+
+/// A `##FILTER=<..>` header line
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterRecord {
+    /// name of the filter
+    pub id: String,
+    /// `Description` attribute, without the quotes
+    pub description: String,
+    /// offset in the dictionary of strings (the key used in records)
+    pub idx: usize,
+    /// other attributes
+    pub other_fields: HashMap<String, String>,
+}
+
+impl FilterRecord {
+    pub(crate) fn from_map(idx: usize, m: &HashMap<String, String>) -> Self {
+        Self {
+            id: field(m, "ID"),
+            description: field(m, "Description"),
+            idx,
+            other_fields: other_fields(m, &["ID", "Description"]),
+        }
+    }
+}
+
+/// A meta-information line of the header (`##key=value`), as written in the
+/// file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderLine {
+    /// the key, e.g. `fileformat`, `INFO` or `bcftools_viewCommand`
+    pub key: String,
+    /// the raw value after the first `=`, e.g. `VCFv4.2` or `<ID=DP,..>`
+    pub value: String,
+}
+
+impl HeaderLine {
+    /// Parse a header line, with or without the leading `##`
+    pub fn parse(line: &str) -> Self {
+        let line = line.strip_prefix("##").unwrap_or(line);
+        match line.split_once('=') {
+            Some((key, value)) => Self {
+                key: key.into(),
+                value: value.into(),
+            },
+            None => Self {
+                key: line.into(),
+                value: String::new(),
+            },
+        }
+    }
+
+    /// Whether the value is a structured list of attributes (`<..>`)
+    pub fn is_structured(&self) -> bool {
+        self.value.starts_with('<') && self.value.ends_with('>')
+    }
+
+    /// Get the ID attribute of a structured line
+    pub fn id(&self) -> Option<String> {
+        self.attributes()?.remove("ID")
+    }
+
+    /// Set an attribute of a structured line, keeping the order of the other
+    /// attributes; the attribute is appended if not present. `value` is
+    /// written as is (quotes should be included for descriptions). Does
+    /// nothing for lines that are not structured.
+    pub fn set_attribute(&mut self, key: &str, value: &str) {
+        if !self.is_structured() {
+            return;
+        }
+        let inner = &self.value[1..self.value.len() - 1];
+        let mut parts: Vec<String> = crate::split_header_attributes(inner)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        let new_part = format!("{key}={value}");
+        match parts
+            .iter()
+            .position(|p| p.trim().split_once('=').map(|(k, _)| k) == Some(key))
+        {
+            Some(i) => parts[i] = new_part,
+            None => parts.push(new_part),
+        }
+        self.value = format!("<{}>", parts.join(","));
+    }
+
+    /// Parse the attributes of a structured line (`<ID=..,..>`), with quotes
+    /// removed from the values. Returns `None` for other lines.
+    ///
+    /// Commas and `=` within quoted values do not split them, and escaped
+    /// quotes are unescaped:
+    /// ```
+    /// use bcf_reader::HeaderLine;
+    /// let line = HeaderLine::parse(concat!(
+    ///     r#"##INFO=<ID=AC,Number=A,Type=Integer,"#,
+    ///     r#"Description="Allele count, A=alt, see \"docs\" \\ notes","#,
+    ///     r#"Source="https://example.org/a?b=1&c=2,3",Version="1">"#,
+    /// ));
+    /// let m = line.attributes().unwrap();
+    /// assert_eq!(m["Description"], r#"Allele count, A=alt, see "docs" \ notes"#);
+    /// assert_eq!(m["Source"], "https://example.org/a?b=1&c=2,3");
+    /// assert_eq!(m["Version"], "1");
+    /// assert_eq!(m.len(), 6);
+    ///
+    /// // the typed records and the rest of the header are parsed the same way
+    /// let text = format!("##fileformat=VCFv4.3\n{line}\n##FILTER=<ID=q10,Description=\"x\">\n");
+    /// let header = bcf_reader::Header::from_string(&text);
+    /// assert_eq!(header.info("AC").unwrap().description, m["Description"]);
+    /// assert_eq!(header.info("AC").unwrap().other_fields["Source"], m["Source"]);
+    /// assert!(header.filter("q10").is_some());
+    /// ```
+    pub fn attributes(&self) -> Option<HashMap<String, String>> {
+        match self.is_structured() {
+            true => Some(crate::parse_header_attributes(
+                &self.value[1..self.value.len() - 1],
+            )),
+            false => None,
+        }
+    }
+}
+
+impl std::fmt::Display for HeaderLine {
+    /// Write the line as in the header (`##key=value`)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "##{}={}", self.key, self.value)
+    }
+}
+
+/// Severity of a [`HeaderViolation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// the header is usable but does not follow a recommendation
+    Warning,
+    /// the header does not conform to the specification
+    Error,
+}
+
+/// A problem found by [`Header::validate`](crate::Header::validate)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderViolation {
+    /// how serious the problem is
+    pub severity: Severity,
+    /// index of the offending line in
+    /// [`Header::lines`](crate::Header::lines), if any
+    pub line: Option<usize>,
+    /// description of the problem
+    pub message: String,
+}
+
+impl std::fmt::Display for HeaderViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            Some(i) => write!(f, "{severity}: header line {}: {}", i + 1, self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// How [`Header::try_from_string_with_policy`](crate::Header::try_from_string_with_policy)
+/// handles a header line defining an ID already defined in the same
+/// dictionary (INFO, FORMAT, FILTER or contig)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateIdPolicy {
+    /// return an error
+    Error,
+    /// keep the first definition and drop the later ones, as htslib does,
+    /// reporting each dropped line as an error of
+    /// [`Header::validate`](crate::Header::validate) (and so as a header
+    /// warning of the readers, or an error in [`ParseMode::Strict`](crate::ParseMode::Strict))
+    #[default]
+    Warn,
+    /// keep the first definition and drop the later ones, as htslib does
+    KeepFirst,
+    /// keep the last definition and drop the earlier ones
+    KeepLast,
+}
+
+/// Error returned by [`Header::try_from_string`](crate::Header::try_from_string)
+/// for a malformed header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderParseError {
+    /// line number (1-based) in the header text
+    pub line: usize,
+    /// text of the offending line
+    pub text: String,
+    /// description of the problem
+    pub message: String,
+}
+
+impl std::fmt::Display for HeaderParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "header line {}: {}: {}",
+            self.line, self.message, self.text
+        )
+    }
+}
+
+impl std::error::Error for HeaderParseError {}
+
+impl std::str::FromStr for crate::Header {
+    type Err = HeaderParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::Header::try_from_string(s)
+    }
+}
+
+impl crate::Header {
+    /// Check the header against the VCF 4.3 specification and return the
+    /// problems found:
+    ///
+    /// - errors: missing or misplaced `##fileformat` line, INFO/FORMAT lines
+    ///   with missing ID/Description or invalid `Number=`/`Type=` values,
+    ///   Flag fields with a `Number=` other than 0, FILTER/ALT lines without
+    ///   Description, duplicate ALT IDs, and the INFO/FORMAT/FILTER/contig
+    ///   lines dropped when parsing because their ID was already defined
+    ///   (with [`DuplicateIdPolicy::Warn`], the default)
+    /// - warnings: contigs without length, FORMAT/GT not declared first among
+    ///   the FORMAT lines
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use bcf_reader::header::Severity;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// assert!(header.validate().iter().all(|v| v.severity < Severity::Error));
+    ///
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.3\n",
+    ///     "##contig=<ID=chr1>\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "##ALT=<ID=DEL,Description=\"Deletion\">\n",
+    ///     "##ALT=<ID=DEL,Description=\"Deletion\">\n",
+    ///     "##INFO=<ID=DB,Number=1,Type=Flag,Description=\"dbSNP\">\n",
+    ///     "##INFO=<ID=AF,Number=B,Type=Float>\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// ));
+    /// let violations = header.validate();
+    /// let n_errors = violations.iter().filter(|v| v.severity == Severity::Error).count();
+    /// // duplicate DEL, Flag with Number=1, AF with bad Number and no Description
+    /// assert_eq!(n_errors, 4);
+    /// // contig without length
+    /// assert_eq!(violations.len(), 5);
+    /// assert!(header.check(true).is_err());
+    /// assert_eq!(header.check(false).unwrap().len(), 5);
+    /// ```
+    pub fn validate(&self) -> Vec<HeaderViolation> {
+        let mut violations = self.duplicate_ids.clone();
+        let mut report = |severity, line, message: String| {
+            violations.push(HeaderViolation {
+                severity,
+                line,
+                message,
+            })
+        };
+        match self.lines.iter().position(|l| l.key == "fileformat") {
+            None => report(Severity::Error, None, "missing ##fileformat line".into()),
+            Some(0) => {}
+            Some(i) => report(
+                Severity::Error,
+                Some(i),
+                "##fileformat should be the first line".into(),
+            ),
+        }
+
+        // duplicate INFO/FORMAT/FILTER/contig IDs are resolved when parsing
+        let mut seen_alts = std::collections::HashSet::new();
+        let mut first_format = true;
+        for (i, line) in self.lines.iter().enumerate() {
+            let dictionary = line.key.as_str();
+            if !line.is_structured()
+                || !["INFO", "FORMAT", "FILTER", "ALT", "contig"].contains(&dictionary)
+            {
+                continue;
+            }
+            let m = crate::parse_header_attributes(&line.value[1..line.value.len() - 1]);
+            let id = match m.get("ID") {
+                Some(id) if !id.is_empty() => id.as_str(),
+                _ => {
+                    report(
+                        Severity::Error,
+                        Some(i),
+                        format!("{dictionary} line without ID"),
+                    );
+                    continue;
+                }
+            };
+            if dictionary == "ALT" && !seen_alts.insert(id.to_string()) {
+                report(
+                    Severity::Error,
+                    Some(i),
+                    format!("duplicate ID {dictionary}/{id}"),
+                );
+            }
+            let missing_description = !m.contains_key("Description");
+            match dictionary {
+                "contig" => {
+                    if m.get("length")
+                        .and_then(|x| x.parse::<u64>().ok())
+                        .is_none()
+                    {
+                        report(
+                            Severity::Warning,
+                            Some(i),
+                            format!("contig {id} has no valid length"),
+                        );
+                    }
+                }
+                "FILTER" | "ALT" => {
+                    if missing_description {
+                        report(
+                            Severity::Error,
+                            Some(i),
+                            format!("{dictionary}/{id} has no Description"),
+                        );
+                    }
+                }
+                _ => {
+                    if missing_description {
+                        report(
+                            Severity::Error,
+                            Some(i),
+                            format!("{dictionary}/{id} has no Description"),
+                        );
+                    }
+                    let number = m.get("Number").map(|x| Number::from(x.as_str()));
+                    let ty = m.get("Type").map(|x| Type::from(x.as_str()));
+                    match &number {
+                        None | Some(Number::Other(_)) => report(
+                            Severity::Error,
+                            Some(i),
+                            format!("{dictionary}/{id} has an invalid Number"),
+                        ),
+                        _ => {}
+                    }
+                    match (&ty, dictionary) {
+                        (None | Some(Type::Other(_)), _) | (Some(Type::Flag), "FORMAT") => report(
+                            Severity::Error,
+                            Some(i),
+                            format!("{dictionary}/{id} has an invalid Type"),
+                        ),
+                        (Some(Type::Flag), _) if number != Some(Number::Fixed(0)) => report(
+                            Severity::Error,
+                            Some(i),
+                            format!("{dictionary}/{id} is a Flag but its Number is not 0"),
+                        ),
+                        _ => {}
+                    }
+                    if dictionary == "FORMAT" {
+                        if id == "GT" && !first_format {
+                            report(
+                                Severity::Warning,
+                                Some(i),
+                                "FORMAT/GT is not the first FORMAT line".into(),
+                            );
+                        }
+                        first_format = false;
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    /// Validate the header (see [`Header::validate`](crate::Header::validate)).
+    ///
+    /// If `strict` is true, any violation (including warnings) is turned
+    /// into an [`Error::InvalidHeader`](crate::Error::InvalidHeader);
+    /// otherwise all violations are returned as warnings for the caller to
+    /// report.
+    pub fn check(&self, strict: bool) -> crate::error::Result<Vec<HeaderViolation>> {
+        let violations = self.validate();
+        if strict && !violations.is_empty() {
+            let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(crate::Error::InvalidHeader(format!(
+                "invalid header:\n{}",
+                messages.join("\n")
+            )));
+        }
+        Ok(violations)
+    }
+}
+
+/// Quote a header attribute value, escaping quotes and backslashes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl crate::Header {
+    /// Rebuild the header from its lines and samples, keeping the text it
+    /// was first parsed from; the header is not changed if the lines are
+    /// malformed
+    fn rebuild(&mut self, lines: Vec<HeaderLine>) -> Result<(), HeaderParseError> {
+        let lines = std::mem::replace(&mut self.lines, lines);
+        match crate::Header::try_from_string(&self.to_text()) {
+            Ok(header) => {
+                let raw_text = std::mem::take(&mut self.raw_text);
+                *self = header;
+                self.raw_text = raw_text;
+                Ok(())
+            }
+            Err(e) => {
+                self.lines = lines;
+                Err(e)
+            }
+        }
+    }
+
+    /// Add an explicit `IDX=` to every INFO/FORMAT/FILTER/contig line with
+    /// its current offset, so that removing or replacing lines does not
+    /// change the keys of the remaining lines
+    fn pin_dictionary_offsets(&self) -> Vec<HeaderLine> {
+        let mut lines = self.lines.clone();
+        for line in lines.iter_mut() {
+            let Some(m) = line.attributes() else { continue };
+            if m.contains_key("IDX") {
+                continue;
+            }
+            let Some(id) = m.get("ID") else { continue };
+            let idx = match line.key.as_str() {
+                "contig" => self.contig(id).map(|r| r.idx),
+                key @ ("INFO" | "FORMAT" | "FILTER") => self.get_idx_from_dictionary_str(key, id),
+                _ => None,
+            };
+            if let Some(idx) = idx {
+                line.set_attribute("IDX", &idx.to_string());
+            }
+        }
+        lines
+    }
+
+    /// Write the header back as VCF header text like
+    /// [`Header::to_text`](crate::Header::to_text), adding an explicit `IDX=`
+    /// to the INFO/FORMAT/FILTER/contig lines without one, so that htslib
+    /// and other readers assign the same keys as this header even if the
+    /// offsets are not in line order (e.g. after lines were removed).
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.3\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\">\n",
+    ///     "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n",
+    /// ));
+    /// let text = header.to_text_with_idx();
+    /// assert!(text.contains("##contig=<ID=chr1,length=1000,IDX=0>\n"));
+    /// assert!(text.contains("##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\",IDX=2>\n"));
+    /// assert!(text.ends_with("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n"));
+    ///
+    /// // the keys are kept when the text is parsed again
+    /// header.remove_line("INFO", "DP");
+    /// header.push_line("##INFO=<ID=AN,Number=1,Type=Integer,Description=\"Number\">").unwrap();
+    /// let header2 = Header::from_string(&header.to_text_with_idx());
+    /// for id in ["AF", "AN"] {
+    ///     assert_eq!(
+    ///         header2.get_idx_from_dictionary_str("INFO", id),
+    ///         header.get_idx_from_dictionary_str("INFO", id)
+    ///     );
+    /// }
+    /// assert_eq!(header2.get_fmt_gt_id(), header.get_fmt_gt_id());
+    /// ```
+    pub fn to_text_with_idx(&self) -> String {
+        self.text_with_lines(&self.pin_dictionary_offsets())
+    }
+
+    /// Append a header line (with or without the leading `##`), e.g. an
+    /// `##INFO` line for a new annotation. New dictionary entries get the
+    /// next unused offset, so existing keys are not changed. An error is
+    /// returned (and the header is not changed) if the line is malformed.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let mut header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let ac_key = header.get_idx_from_dictionary_str("INFO", "AC").unwrap();
+    /// let gt_key = header.get_fmt_gt_id().unwrap();
+    ///
+    /// header
+    ///     .push_line(r#"##INFO=<ID=NEW,Number=1,Type=Integer,Description="New">"#)
+    ///     .unwrap();
+    /// header.push_line("##source=myTool").unwrap();
+    /// assert!(header.push_line("##INFO=<ID=BAD").is_err());
+    /// assert!(header.info("NEW").is_some());
+    /// assert_eq!(header.value_of("source"), Some("myTool"));
+    ///
+    /// header.set_description("INFO", "AC", "Allele count (recomputed)");
+    /// assert_eq!(header.info("AC").unwrap().description, "Allele count (recomputed)");
+    ///
+    /// let n_filter = header.filters().len();
+    /// assert!(header.remove_line("FILTER", "LowQual"));
+    /// assert_eq!(header.filters().len(), n_filter - 1);
+    /// assert!(!header.remove_line("FILTER", "PASS"));
+    ///
+    /// // keys of the other lines are kept
+    /// assert_eq!(header.get_idx_from_dictionary_str("INFO", "AC"), Some(ac_key));
+    /// assert_eq!(header.get_fmt_gt_id(), Some(gt_key));
+    /// ```
+    pub fn push_line(&mut self, line: &str) -> Result<(), HeaderParseError> {
+        let mut lines = self.lines.clone();
+        lines.push(HeaderLine::parse(line.trim_end()));
+        self.rebuild(lines)
+    }
+
+    /// Remove the structured line with the given key (e.g. `FILTER`, `INFO`,
+    /// `contig`, `ALT`) and ID. The offsets of the remaining dictionary
+    /// entries are kept by pinning them with `IDX=`. Returns `false` if no
+    /// line was removed; the implicit FILTER/PASS can not be removed.
+    pub fn remove_line(&mut self, key: &str, id: &str) -> bool {
+        if key == "FILTER" && id == "PASS" {
+            return false;
+        }
+        let mut lines = self.pin_dictionary_offsets();
+        let n = lines.len();
+        lines.retain(|l| !(l.key == key && l.id().as_deref() == Some(id)));
+        if lines.len() == n {
+            return false;
+        }
+        self.rebuild(lines).is_ok()
+    }
+
+    /// Replace the structured line with the given key and ID by `line`,
+    /// keeping its position and, for dictionary lines, its offset (unless
+    /// `line` has its own `IDX=`). Returns `false` if no such line exists,
+    /// or an error (without changing the header) if `line` is malformed.
+    pub fn replace_line(
+        &mut self,
+        key: &str,
+        id: &str,
+        line: &str,
+    ) -> Result<bool, HeaderParseError> {
+        let mut lines = self.pin_dictionary_offsets();
+        let Some(i) = lines
+            .iter()
+            .position(|l| l.key == key && l.id().as_deref() == Some(id))
+        else {
+            return Ok(false);
+        };
+        let mut new_line = HeaderLine::parse(line.trim_end());
+        let has_idx = new_line.attributes().is_some_and(|m| m.contains_key("IDX"));
+        if let (false, Some(idx)) = (
+            has_idx,
+            lines[i].attributes().and_then(|m| m.get("IDX").cloned()),
+        ) {
+            new_line.set_attribute("IDX", &idx);
+        }
+        lines[i] = new_line;
+        self.rebuild(lines)?;
+        Ok(true)
+    }
+
+    /// Replace the Description of the structured line with the given key and
+    /// ID. Returns `false` if no such line exists.
+    pub fn set_description(&mut self, key: &str, id: &str, description: &str) -> bool {
+        let mut lines = self.lines.clone();
+        let Some(line) = lines
+            .iter_mut()
+            .find(|l| l.key == key && l.id().as_deref() == Some(id))
+        else {
+            return false;
+        };
+        line.set_attribute("Description", &quote(description));
+        self.rebuild(lines).is_ok()
+    }
+}
+
+/// Kind of a [`HeaderConflict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConflictKind {
+    /// a contig is defined in both headers with different lengths or MD5
+    /// checksums
+    Contig,
+    /// a contig is defined in only one of the headers
+    ContigMissing,
+    /// an INFO/FORMAT field is defined in both headers with different
+    /// `Number=`
+    Number,
+    /// an INFO/FORMAT field is defined in both headers with different `Type=`
+    Type,
+    /// the headers have different samples, or the same samples in a
+    /// different order
+    Samples,
+}
+
+/// A difference between two headers found by
+/// [`Header::compatibility_report`](crate::Header::compatibility_report)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderConflict {
+    /// kind of the difference
+    pub kind: ConflictKind,
+    /// ID of the contig or field (`INFO/DP`), empty for samples
+    pub id: String,
+    /// description of the difference
+    pub message: String,
+}
+
+impl std::fmt::Display for HeaderConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Format a list of names for a message, eliding long lists
+fn name_list(names: &[&str]) -> String {
+    const MAX: usize = 5;
+    let mut s = names
+        .iter()
+        .take(MAX)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > MAX {
+        s.push_str(&format!(", ... ({} in total)", names.len()));
+    }
+    s
+}
+
+impl crate::Header {
+    /// List the differences between this header and `other` that matter when
+    /// combining their files: contigs with different lengths or MD5
+    /// checksums, contigs defined in only one header, INFO/FORMAT fields with
+    /// conflicting `Number=`/`Type=`, and different samples. Merge tools can
+    /// ignore [`ConflictKind::Samples`] and [`ConflictKind::ContigMissing`];
+    /// concatenation requires an empty report.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use bcf_reader::header::ConflictKind;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// assert!(header.compatibility_report(&header).is_empty());
+    ///
+    /// let mut other = Header::from_string(&header.to_text());
+    /// other
+    ///     .replace_line(
+    ///         "INFO",
+    ///         "AC",
+    ///         r#"##INFO=<ID=AC,Number=1,Type=Float,Description="Allele count">"#,
+    ///     )
+    ///     .unwrap();
+    /// other.remove_line("contig", "Pf3D7_API_v3");
+    /// let report = header.compatibility_report(&other);
+    /// let kinds: Vec<_> = report.iter().map(|c| c.kind).collect();
+    /// assert_eq!(
+    ///     kinds,
+    ///     [ConflictKind::ContigMissing, ConflictKind::Number, ConflictKind::Type]
+    /// );
+    /// assert_eq!(report[1].id, "INFO/AC");
+    /// assert_eq!(
+    ///     report[1].to_string(),
+    ///     "INFO/AC has Number=A in the first header but Number=1 in the second"
+    /// );
+    ///
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header2 = Header::from_string(&read_header(&mut f).unwrap());
+    /// let report = header.compatibility_report(&header2);
+    /// assert!(report.iter().any(|c| c.kind == ConflictKind::Samples));
+    /// ```
+    pub fn compatibility_report(&self, other: &crate::Header) -> Vec<HeaderConflict> {
+        let mut conflicts = vec![];
+        let mut report = |kind, id: String, message: String| {
+            conflicts.push(HeaderConflict { kind, id, message })
+        };
+
+        for (a, b) in [(self, other), (other, self)] {
+            let which = if std::ptr::eq(a, self) {
+                "first"
+            } else {
+                "second"
+            };
+            for contig in a.contigs() {
+                if b.contig(&contig.id).is_none() {
+                    report(
+                        ConflictKind::ContigMissing,
+                        contig.id.clone(),
+                        format!("contig {} is only defined in the {which} header", contig.id),
+                    );
+                }
+            }
+        }
+        for c1 in self.contigs() {
+            let Some(c2) = other.contig(&c1.id) else {
+                continue;
+            };
+            if let (Some(l1), Some(l2)) = (c1.length, c2.length) {
+                if l1 != l2 {
+                    report(
+                        ConflictKind::Contig,
+                        c1.id.clone(),
+                        format!(
+                            "contig {} has length {l1} in the first header but {l2} in the second",
+                            c1.id
+                        ),
+                    );
+                }
+            }
+            if let (Some(m1), Some(m2)) = (&c1.md5, &c2.md5) {
+                if !m1.eq_ignore_ascii_case(m2) {
+                    report(
+                        ConflictKind::Contig,
+                        c1.id.clone(),
+                        format!(
+                            "contig {} has md5 {m1} in the first header but {m2} in the second",
+                            c1.id
+                        ),
+                    );
+                }
+            }
+        }
+
+        let infos = self.infos().iter().filter_map(|r| {
+            let r2 = other.info(&r.id)?;
+            Some(("INFO", &r.id, (&r.number, &r.ty), (&r2.number, &r2.ty)))
+        });
+        let formats = self.formats().iter().filter_map(|r| {
+            let r2 = other.format(&r.id)?;
+            Some(("FORMAT", &r.id, (&r.number, &r.ty), (&r2.number, &r2.ty)))
+        });
+        for (dictionary, id, (n1, t1), (n2, t2)) in infos.chain(formats) {
+            let id = format!("{dictionary}/{id}");
+            if n1 != n2 {
+                report(
+                    ConflictKind::Number,
+                    id.clone(),
+                    format!(
+                        "{id} has Number={n1} in the first header but Number={n2} in the second"
+                    ),
+                );
+            }
+            if t1 != t2 {
+                report(
+                    ConflictKind::Type,
+                    id.clone(),
+                    format!("{id} has Type={t1} in the first header but Type={t2} in the second"),
+                );
+            }
+        }
+
+        if self.samples != other.samples {
+            fn only_in<'a>(a: &'a crate::Header, b: &crate::Header) -> Vec<&'a str> {
+                a.samples
+                    .iter()
+                    .filter(|s| b.sample_index(s).is_none())
+                    .map(|s| s.as_str())
+                    .collect()
+            }
+            let (only1, only2) = (only_in(self, other), only_in(other, self));
+            let message = if only1.is_empty() && only2.is_empty() {
+                "the headers have the same samples in a different order".to_string()
+            } else {
+                let mut parts = vec![];
+                if !only1.is_empty() {
+                    parts.push(format!("only in the first header: {}", name_list(&only1)));
+                }
+                if !only2.is_empty() {
+                    parts.push(format!("only in the second header: {}", name_list(&only2)));
+                }
+                format!("the headers have different samples ({})", parts.join("; "))
+            };
+            report(ConflictKind::Samples, String::new(), message);
+        }
+        conflicts
+    }
+}
+
+/// Human reference genome build, see
+/// [`Header::genome_build`](crate::Header::genome_build)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GenomeBuild {
+    /// GRCh37 with Ensembl/NCBI contig names (`1`, `2`, ..)
+    GRCh37,
+    /// GRCh38 with Ensembl/NCBI contig names
+    GRCh38,
+    /// GRCh37 with UCSC contig names (`chr1`, `chr2`, ..)
+    Hg19,
+    /// GRCh38 with UCSC contig names
+    Hg38,
+    /// not a known human build
+    Unknown,
+}
+
+impl std::fmt::Display for GenomeBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            GenomeBuild::GRCh37 => "GRCh37",
+            GenomeBuild::GRCh38 => "GRCh38",
+            GenomeBuild::Hg19 => "hg19",
+            GenomeBuild::Hg38 => "hg38",
+            GenomeBuild::Unknown => "unknown",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Lengths of some contigs in GRCh37 and GRCh38
+const BUILD_CONTIG_LENGTHS: [(&str, u64, u64); 3] = [
+    ("1", 249250621, 248956422),
+    ("2", 243199373, 242193529),
+    ("X", 155270560, 156040895),
+];
+
+impl crate::Header {
+    /// Guess the human reference genome build of the header (best effort).
+    ///
+    /// The lengths of chromosomes 1, 2 and X are compared to those of
+    /// GRCh37 and GRCh38, and the contig names tell the UCSC (`chr1`) from
+    /// the Ensembl/NCBI (`1`) flavour. Without known contig lengths, the
+    /// `##reference` line and the `assembly` attributes of the contigs are
+    /// searched for the build names.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use bcf_reader::header::GenomeBuild;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1,length=248956422>\n",
+    ///     "##contig=<ID=chr2,length=242193529>\n",
+    /// ));
+    /// assert_eq!(header.genome_build(), GenomeBuild::Hg38);
+    ///
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##reference=file:///ref/human_g1k_v37.fasta\n",
+    ///     "##contig=<ID=1>\n",
+    /// ));
+    /// assert_eq!(header.genome_build(), GenomeBuild::GRCh37);
+    /// assert_eq!(header.genome_build().to_string(), "GRCh37");
+    ///
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// assert_eq!(header.genome_build(), GenomeBuild::Unknown);
+    /// ```
+    pub fn genome_build(&self) -> GenomeBuild {
+        let ucsc = self.contigs.iter().any(|c| c.id.starts_with("chr"));
+        let flavour = |is_38: bool| match (is_38, ucsc) {
+            (false, false) => GenomeBuild::GRCh37,
+            (true, false) => GenomeBuild::GRCh38,
+            (false, true) => GenomeBuild::Hg19,
+            (true, true) => GenomeBuild::Hg38,
+        };
+
+        // contig lengths
+        let (mut n37, mut n38) = (0, 0);
+        for (name, len37, len38) in BUILD_CONTIG_LENGTHS {
+            let contig = self
+                .contig(name)
+                .or_else(|| self.contig(&format!("chr{name}")));
+            match contig.and_then(|c| c.length) {
+                Some(len) if len == len37 => n37 += 1,
+                Some(len) if len == len38 => n38 += 1,
+                _ => {}
+            }
+        }
+        if n37 != n38 {
+            return flavour(n38 > n37);
+        }
+
+        // build names in the ##reference line and contig assemblies
+        let texts = self
+            .values_of("reference")
+            .into_iter()
+            .chain(self.contigs.iter().filter_map(|c| c.assembly.as_deref()));
+        for text in texts {
+            let text = text.to_ascii_lowercase();
+            if text.contains("hg38") {
+                return GenomeBuild::Hg38;
+            }
+            if text.contains("hg19") {
+                return GenomeBuild::Hg19;
+            }
+            if ["grch38", "gca_000001405.15", "hs38"]
+                .iter()
+                .any(|x| text.contains(x))
+            {
+                return flavour(true);
+            }
+            if ["grch37", "hs37d5", "b37", "g1k_v37"]
+                .iter()
+                .any(|x| text.contains(x))
+            {
+                return flavour(false);
+            }
+        }
+        GenomeBuild::Unknown
+    }
+}
+
+/// `serde` support for the typed header representation.
+///
+/// `Number` and `Type` are written as their VCF text (`"R"`, `"Integer"`).
+/// A `Header` is written as its samples, meta-information lines and typed
+/// records; it is read back from the lines and samples only, so the typed
+/// records and dictionary offsets are always consistent with the lines.
+///
+/// Example:
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test2.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let json = serde_json::to_string(&header).unwrap();
+/// let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+/// let infos = value["infos"].as_array().unwrap();
+/// let ac = infos.iter().find(|x| x["id"] == "AC").unwrap();
+/// assert_eq!(ac["number"], "A");
+/// assert_eq!(ac["ty"], "Integer");
+///
+/// let header2: Header = serde_json::from_str(&json).unwrap();
+/// assert_eq!(header2.to_text(), header.to_text());
+/// assert_eq!(header2.infos(), header.infos());
+/// assert_eq!(header2.get_fmt_gt_id(), header.get_fmt_gt_id());
+/// # }
+/// ```
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    impl Serialize for Number {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Number {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let s = String::deserialize(deserializer)?;
+            Ok(Number::from(s.as_str()))
+        }
+    }
+
+    impl Serialize for Type {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Type {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let s = String::deserialize(deserializer)?;
+            Ok(Type::from(s.as_str()))
+        }
+    }
+
+    #[derive(Serialize)]
+    struct HeaderRef<'a> {
+        fileformat: Option<&'a str>,
+        samples: &'a [String],
+        lines: &'a [HeaderLine],
+        contigs: &'a [ContigRecord],
+        infos: &'a [InfoRecord],
+        formats: &'a [FormatRecord],
+        filters: &'a [FilterRecord],
+        metas: &'a [MetaRecord],
+        alts: &'a [AltRecord],
+        sample_records: &'a [SampleRecord],
+        pedigrees: &'a [PedigreeRecord],
+    }
+
+    #[derive(Deserialize)]
+    struct HeaderOwned {
+        samples: Vec<String>,
+        lines: Vec<HeaderLine>,
+    }
+
+    impl Serialize for crate::Header {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            HeaderRef {
+                fileformat: self.fileformat(),
+                samples: &self.samples,
+                lines: &self.lines,
+                contigs: &self.contigs,
+                infos: &self.infos,
+                formats: &self.formats,
+                filters: &self.filters,
+                metas: &self.metas,
+                alts: &self.alts,
+                sample_records: &self.sample_records,
+                pedigrees: &self.pedigrees,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for crate::Header {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let owned = HeaderOwned::deserialize(deserializer)?;
+            let mut header = crate::Header::from_string("");
+            header.samples = owned.samples;
+            header.lines = owned.lines;
+            crate::Header::try_from_string(&header.to_text()).map_err(serde::de::Error::custom)
+        }
+    }
+}
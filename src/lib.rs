@@ -17,10 +17,11 @@
 //! Note: This crate is in its early stages of development.
 //!
 //! ## Usage
-//! ```
+#![cfg_attr(all(feature = "info", feature = "format"), doc = "```")]
+#![cfg_attr(not(all(feature = "info", feature = "format")), doc = "```ignore")]
 //! use bcf_reader::*;
 //! let mut reader = smart_reader("testdata/test2.bcf");
-//! let header = Header::from_string(&read_header(&mut reader));
+//! let header = Header::from_string(&read_header(&mut reader).unwrap());
 //! // find key for a field in INFO or FORMAT or FILTER
 //! let key = header.get_idx_from_dictionary_str("FORMAT", "GT").unwrap();
 //! // access header dictionary
@@ -85,7 +86,11 @@
 //! - For parallelized decompression reader, see [`BcfReader`].
 //! - For parallelized indexed reader, see [ `IndexedBcfReader`].
 //! - For the Lower-level reader underlying `BcfReader` and `IndexedBcfReader`,
-//! see [`ParMultiGzipReader`].
+//!   see [`ParMultiGzipReader`].
+//! - For reading ahead on a background thread, see [`PrefetchReader`].
+//! - For a threaded scan of a whole file with a callback, see [`scan_parallel`].
+//! - For parsing a whole uncompressed BCF file into memory, see the `mmap`
+//!   module (requires the `mmap` feature).
 //!
 //! # `flate2` backends
 //!
@@ -93,21 +98,90 @@
 //! `zlib-ng-compat` has been exported as the corresponding features (`zlib` and
 //! `zlib-ng-compat`). See <https://docs.rs/flate2/latest/flate2/> for more details.
 //!
+//! # Optional features
+//!
+//! Enabled by default, and can be turned off (with `default-features =
+//! false`) by genotype-only pipelines or embedded/WASM builds that do not
+//! need them:
+//! - `info`: accessors of INFO fields (e.g. `Record::info_field_numeric`)
+//!   and the SV helpers reading INFO fields.
+//! - `format`: accessors of FORMAT fields other than GT (e.g.
+//!   `Record::fmt_field`, `BcfReader::read_format_column`).
+//! - `strings`: decoding of records to strings and owned values (e.g.
+//!   `Record::to_owned_variant`, `Record::display`, `JsonlWriter`,
+//!   `Record::info_field_str` with `info`).
+//!
+//! Records are read the same way without them: the INFO and FORMAT fields
+//! are only located when a record is read, and decoded by the accessors.
+//!
+//! Disabled by default:
+//! - `serde`: `Serialize`/`Deserialize` for the header types.
+//! - `ndarray`: genotype matrices as `ndarray` arrays, see the `matrix` module.
+//! - `bumpalo`: conversion of records to owned records allocated in an arena,
+//!   see the `arena` module (enables `strings`).
+//! - `unchecked`: `unsafe` accessors without bounds and sentinel checks for
+//!   trusted (validated) files, e.g. `Record::decode_gt_into_unchecked`.
+//! - `mmap`: parallel parsing of a memory-mapped uncompressed BCF file into
+//!   an in-memory dataset, see the `mmap` module.
+//! - `arrow`: export of records as Arrow record batches, see the
+//!   `arrow_export` module.
+//! - `parquet`: output of records to Parquet files, see the
+//!   `parquet_export` module (enables `arrow`).
+//!
 //!
 use byteorder::{LittleEndian, ReadBytesExt};
 use flate2::bufread::DeflateDecoder;
 use rayon::prelude::*;
+use std::collections::HashSet;
 use std::fmt::Debug;
-use std::fs::File;
 use std::io;
-use std::io::BufReader;
 use std::io::Read;
 use std::ops::Range;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 use std::{collections::HashMap, io::Seek};
 
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+mod checks;
+pub mod error;
+pub mod header;
+#[cfg(feature = "ndarray")]
+pub mod matrix;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
+pub mod pbwt;
+pub mod ploidy;
+mod reader;
+mod records;
+mod scan;
+mod stats;
+pub mod sv;
+pub mod validate;
+
+use checks::{header_utf8_warning, NO_LIMITS};
+pub use checks::{
+    DuplicateDetector, OrderCheck, ParseMode, ReadWarning, ResourceLimits, Utf8Policy, WarningKind,
+};
+pub use error::{Error, ErrorCategory};
+pub use header::{
+    AltRecord, ContigRecord, DuplicateIdPolicy, FilterRecord, FormatRecord, HeaderLine,
+    HeaderParseError, InfoRecord, MetaRecord, PedigreeRecord, SampleRecord,
+};
+use header::{HeaderViolation, Severity};
+pub use reader::{BcfReader, Checkpoint, GenomeInterval, IndexedBcfReader, SkippedRecord};
+pub use records::{LendingIterator, ReadRecord, RecordBatches, RecordStream, Records};
+pub use scan::{scan_parallel, scan_parallel_with_budget, ScanBatch};
+pub use stats::{DecompressionStats, ReaderStats, RecordSizeStats};
+
 /// An iterator used to split a `str` by a separator with separators within pairs
-/// of quotes ignored.
+/// of quotes ignored. Within quotes, a quote escaped with a backslash does not
+/// end the quoted text.
 pub struct QuotedSplitter<'a> {
     data: &'a str,
     in_quotes: bool,
@@ -131,6 +205,10 @@ impl<'a> QuotedSplitter<'a> {
     /// let input_string = "hello,\"world, this is fun\",test";
     /// let result: Vec<_> = QuotedSplitter::new(input_string, ',', '"').collect();
     /// assert_eq!(result, vec!["hello", "\"world, this is fun\"", "test"]);
+    ///
+    /// let input_string = r#"a,"b \", c",d"#;
+    /// let result: Vec<_> = QuotedSplitter::new(input_string, ',', '"').collect();
+    /// assert_eq!(result, vec!["a", r#""b \", c""#, "d"]);
     /// ```
     pub fn new(buffer: &'a str, separator: char, quote: char) -> Self {
         Self {
@@ -152,8 +230,15 @@ impl<'a> Iterator for QuotedSplitter<'a> {
     /// * `Some(&str)` - The next split substring.
     /// * `None` - If there are no more substrings to split.
     fn next(&mut self) -> Option<Self::Item> {
+        let mut escaped = false;
         for (idx, ch) in self.data.char_indices() {
-            if ch == self.quote {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            if self.in_quotes && ch == '\\' {
+                escaped = true;
+            } else if ch == self.quote {
                 self.in_quotes = !self.in_quotes;
             }
             if (!self.in_quotes) && ch == self.sep {
@@ -162,7 +247,7 @@ impl<'a> Iterator for QuotedSplitter<'a> {
                 return Some(out);
             }
         }
-        if self.data.len() > 0 {
+        if !self.data.is_empty() {
             let out = self.data;
             self.data = "";
             Some(out)
@@ -172,6 +257,70 @@ impl<'a> Iterator for QuotedSplitter<'a> {
     }
 }
 
+/// Parse the `key=value` attributes of a structured header line, given the
+/// text between `<` and `>`. Quotes around values are removed and escaped
+/// quotes and backslashes (`\"`, `\\`) within them are unescaped; commas
+/// and `=` in quoted values are kept. Lists in brackets (VCF 4.4 `##META`
+/// lines, e.g. `Values=[WholeGenome, Exome]`) are kept as a single value.
+pub(crate) fn parse_header_attributes(s: &str) -> HashMap<String, String> {
+    let mut m = HashMap::<String, String>::new();
+    for kv_str in split_header_attributes(s) {
+        let kv_str = kv_str.trim();
+        if kv_str.is_empty() {
+            continue;
+        }
+        let (k, v) = kv_str.split_once('=').unwrap_or((kv_str, ""));
+        let v = match v.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(quoted) => unescape_header_value(quoted),
+            None => v.trim_end_matches('"').trim_start_matches('"').into(),
+        };
+        m.insert(k.into(), v);
+    }
+    m
+}
+
+/// Replace `\"` and `\\` in a quoted header value by `"` and `\`
+fn unescape_header_value(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.clone().next()) {
+            ('\\', Some(next @ ('"' | '\\'))) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Split the text of a structured header line between `<` and `>` into
+/// `key=value` parts, on commas outside quotes and brackets
+pub(crate) fn split_header_attributes(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut in_quotes, mut escaped, mut depth, mut start) = (false, false, 0usize, 0);
+    for (i, ch) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '[' if !in_quotes => depth += 1,
+            ']' if !in_quotes => depth = depth.saturating_sub(1),
+            ',' if !in_quotes && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
 /// Represents a header of a BCF file.
 ///
 /// The `Header` struct contains information about the dictionar of strings and
@@ -204,110 +353,463 @@ impl<'a> Iterator for QuotedSplitter<'a> {
 /// assert_eq!(header.dict_strings().len(), 4);
 /// assert_eq!(header.get_samples().len(), 2);
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Header {
     dict_strings: HashMap<usize, HashMap<String, String>>,
     dict_contigs: HashMap<usize, HashMap<String, String>>,
+    /// header lines of the string dictionary by (INFO/FILTER/FORMAT, offset)
+    dict_entries: HashMap<(String, usize), HashMap<String, String>>,
+    contigs: Vec<ContigRecord>,
+    infos: Vec<InfoRecord>,
+    formats: Vec<FormatRecord>,
+    filters: Vec<FilterRecord>,
+    /// positions of the typed lines in the vectors above by ID
+    contig_by_id: HashMap<String, usize>,
+    info_by_id: HashMap<String, usize>,
+    format_by_id: HashMap<String, usize>,
+    filter_by_id: HashMap<String, usize>,
+    /// VCF 4.4 `##META` lines
+    metas: Vec<MetaRecord>,
+    /// `##ALT` lines
+    alts: Vec<AltRecord>,
+    /// `##SAMPLE` lines
+    sample_records: Vec<SampleRecord>,
+    /// `##PEDIGREE` lines
+    pedigrees: Vec<PedigreeRecord>,
+    /// all meta-information lines in file order
+    lines: Vec<HeaderLine>,
     samples: Vec<String>,
+    /// column of each sample by name
+    sample_by_name: HashMap<String, usize>,
+    /// the text the header was parsed from
+    raw_text: String,
+    /// lines dropped by [`DuplicateIdPolicy::Warn`], reported by `validate`
+    duplicate_ids: Vec<HeaderViolation>,
     fmt_gt_idx: Option<usize>,
+    /// contig names by rid and IDs of `dict_strings` by offset, sharing the
+    /// allocations of `interned`
+    interned_contigs: HashMap<usize, Arc<str>>,
+    interned_strings: HashMap<usize, Arc<str>>,
+    interned: HashSet<Arc<str>>,
 }
 impl Header {
     /// parse header lines to structured data `Header`
+    ///
+    /// Explicit `IDX=` values give the offsets of the lines in the
+    /// dictionaries and may be sparse or out of order; lines without `IDX=`
+    /// get the next unused offsets. INFO/FORMAT/FILTER lines with the same ID
+    /// share an offset.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::Header;
+    /// let header_text = concat!(
+    ///     r#"##fileformat=VCFv4.3"#, "\n",
+    ///     r#"##FILTER=<ID=PASS,Description="All filters passed",IDX=0>"#, "\n",
+    ///     r#"##contig=<ID=chr2,length=1000,IDX=3>"#, "\n",
+    ///     r#"##contig=<ID=chr1,length=1000,IDX=1>"#, "\n",
+    ///     r#"##INFO=<ID=DP,Number=1,Type=Integer,Description="Depth",IDX=7>"#, "\n",
+    ///     r#"##FORMAT=<ID=GT,Number=1,Type=String,Description="Genotype",IDX=2>"#, "\n",
+    ///     r#"##FORMAT=<ID=DP,Number=1,Type=Integer,Description="Depth",IDX=7>"#, "\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tsample1","\n",
+    /// );
+    /// let header = Header::from_string(header_text);
+    /// assert_eq!(header.get_chrname(3), "chr2");
+    /// assert_eq!(header.get_chrname(1), "chr1");
+    /// assert_eq!(header.get_fmt_gt_id(), Some(2));
+    /// assert_eq!(header.get_idx_from_dictionary_str("INFO", "DP"), Some(7));
+    /// assert_eq!(header.get_idx_from_dictionary_str("FORMAT", "DP"), Some(7));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the header is malformed; see [`Header::try_from_string`].
     pub fn from_string(text: &str) -> Self {
+        Self::try_from_string(text).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// parse header lines to structured data `Header`, like
+    /// [`Header::from_string`], but return an error for a malformed header:
+    /// a line that does not start with `##`, a structured line without the
+    /// closing `>`, an INFO/FORMAT/FILTER/contig line without ID, or an
+    /// `IDX=` that is not an integer between 0 and 2147483647 (the keys of
+    /// a BCF record are int32). Lines defining an ID already defined in
+    /// the same dictionary are dropped and reported by [`Header::validate`]
+    /// (see [`Header::try_from_string_with_policy`]).
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::Header;
+    /// let header_text = concat!(
+    ///     "##fileformat=VCFv4.3\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##INFO=<Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// );
+    /// let err = Header::try_from_string(header_text).unwrap_err();
+    /// assert_eq!(err.line, 3);
+    /// assert!(err.text.starts_with("##INFO=<Number=1"));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     format!("header line 3: INFO line without ID: {}", err.text)
+    /// );
+    ///
+    /// let err = Header::try_from_string("##contig=<ID=chr1,length=1000\n").unwrap_err();
+    /// assert_eq!(err.message, "missing '>' at the end of a structured line");
+    /// let err = Header::try_from_string("#comment\n").unwrap_err();
+    /// assert_eq!(err.line, 1);
+    /// let err = Header::try_from_string("##contig=<ID=chr1,IDX=x>\n").unwrap_err();
+    /// assert_eq!(err.message, "IDX should be a non-negative integer");
+    /// let err = Header::try_from_string("##contig=<ID=chr1,IDX=18446744073709551615>\n")
+    ///     .unwrap_err();
+    /// assert_eq!(err.message, "IDX should be at most 2147483647");
+    /// // sparse offsets do not allocate dense tables
+    /// let header = Header::try_from_string("##contig=<ID=chr1,IDX=2000000000>\n").unwrap();
+    /// assert_eq!(&**header.interned_contig(2000000000).unwrap(), "chr1");
+    /// ```
+    pub fn try_from_string(text: &str) -> Result<Self, HeaderParseError> {
+        Self::try_from_string_with_policy(text, DuplicateIdPolicy::default())
+    }
+
+    /// parse header lines to structured data `Header`, like
+    /// [`Header::try_from_string`], handling INFO/FORMAT/FILTER/contig lines
+    /// that define an ID already defined in the same dictionary (e.g. after
+    /// a naive concatenation of headers) according to `policy`. The dropped
+    /// lines are removed from [`Header::lines`].
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::{DuplicateIdPolicy, Header};
+    /// let header_text = concat!(
+    ///     "##fileformat=VCFv4.3\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\">\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Float,Description=\"Mean depth\">\n",
+    /// );
+    /// let first = Header::try_from_string_with_policy(header_text, DuplicateIdPolicy::KeepFirst)
+    ///     .unwrap();
+    /// assert_eq!(first.info("DP").unwrap().description, "Depth");
+    /// assert_eq!(first.lines().len(), 4);
+    ///
+    /// let last = Header::try_from_string_with_policy(header_text, DuplicateIdPolicy::KeepLast)
+    ///     .unwrap();
+    /// assert_eq!(last.info("DP").unwrap().description, "Mean depth");
+    /// assert_eq!(last.lines().len(), 4);
+    /// // INFO/DP and FORMAT/DP still share an offset
+    /// assert_eq!(
+    ///     last.get_idx_from_dictionary_str("INFO", "DP"),
+    ///     last.get_idx_from_dictionary_str("FORMAT", "DP")
+    /// );
+    ///
+    /// let err = Header::try_from_string_with_policy(header_text, DuplicateIdPolicy::Error)
+    ///     .unwrap_err();
+    /// assert_eq!(err.line, 5);
+    /// assert_eq!(err.message, "duplicate INFO ID 'DP' (first defined on line 2)");
+    ///
+    /// // by default, the first line is kept and the dropped line is reported
+    /// let header = Header::try_from_string(header_text).unwrap();
+    /// assert_eq!(header.info("DP").unwrap().description, "Depth");
+    /// let violations = header.validate();
+    /// assert_eq!(
+    ///     violations[0].to_string(),
+    ///     "error: header line 2: duplicate INFO ID 'DP' on line 5 dropped"
+    /// );
+    /// // and is not reported with an explicit policy
+    /// assert!(first.validate().iter().all(|v| !v.message.contains("duplicate")));
+    /// ```
+    pub fn try_from_string_with_policy(
+        text: &str,
+        policy: DuplicateIdPolicy,
+    ) -> Result<Self, HeaderParseError> {
         let mut dict_strings = HashMap::<usize, HashMap<String, String>>::new();
         let mut dict_contigs = HashMap::<usize, HashMap<String, String>>::new();
+        let mut dict_entries = HashMap::<(String, usize), HashMap<String, String>>::new();
         let mut samples = Vec::<String>::new();
 
         // implicit FILTER/PASS header line
         let mut m = HashMap::<String, String>::new();
         m.insert("Dictionary".into(), "FILTER".into());
         m.insert("ID".into(), "PASS".into());
-        m.insert("Description".into(), r#""All filters passed""#.into());
-        dict_strings.insert(0, m);
-        //
-        let mut dict_str_idx_counter = 1;
-        let mut dict_contig_idx_counter = 0;
-        for line in QuotedSplitter::new(text.trim_end_matches('\0').trim(), '\n', '"') {
+        m.insert("Description".into(), "All filters passed".into());
+        // dictionary lines are kept with their positions in `lines`
+        let mut string_lines = vec![(usize::MAX, m)];
+        let mut lines = vec![];
+        let mut contig_lines = vec![];
+        // (dictionary, ID) -> (position in `lines`, line number)
+        let mut seen = HashMap::<(String, String), (usize, usize)>::new();
+        let mut dropped = std::collections::HashSet::<usize>::new();
+        let mut duplicate_ids = vec![];
+        for (line_no, line) in
+            QuotedSplitter::new(text.trim_end_matches('\0').trim(), '\n', '"').enumerate()
+        {
+            let error = |message: &str| HeaderParseError {
+                line: line_no + 1,
+                text: line.to_string(),
+                message: message.to_string(),
+            };
             if line.starts_with("#CHROM") {
                 line.split("\t")
                     .skip(9)
                     .for_each(|s| samples.push(s.into()));
                 continue;
             }
-            if line.trim().len() == 0 {
+            if line.trim().is_empty() {
                 continue;
             }
-            let mut it = QuotedSplitter::new(line.strip_prefix("##").unwrap(), '=', '"');
-            let dict_name = it.next().unwrap();
-            let valid_dict = match it.next() {
-                Some(x) if x.starts_with("<") => true,
-                _ => false,
+            let Some(stripped) = line.strip_prefix("##") else {
+                return Err(error("meta-information lines should start with '##'"));
+            };
+            lines.push(HeaderLine::parse(line));
+            let Some((dict_name, value)) = stripped.split_once('=') else {
+                continue;
             };
-            if !valid_dict {
+            let Some(s) = value.strip_prefix('<') else {
                 continue;
+            };
+            let Some(s) = s.trim_end().strip_suffix('>') else {
+                return Err(error("missing '>' at the end of a structured line"));
+            };
+            let mut m = parse_header_attributes(s);
+            if let Some(idx) = m.get("IDX") {
+                let Ok(idx) = idx.parse::<usize>() else {
+                    return Err(error("IDX should be a non-negative integer"));
+                };
+                // keys and contig ids in the records are int32
+                if i32::try_from(idx).is_err() {
+                    return Err(error("IDX should be at most 2147483647"));
+                }
             }
-            let l = line.find('<').unwrap();
-            let s = line.split_at(l + 1).1;
-            let r = s.rfind('>').unwrap();
-            let s = s.split_at(r).0;
-            let mut m = HashMap::<String, String>::new();
-            for kv_str in QuotedSplitter::new(s, ',', '"') {
-                let kv_str = kv_str.trim();
-
-                let mut it = QuotedSplitter::new(kv_str, '=', '"');
-                let k = it.next().unwrap();
-                let v = it
-                    .next()
-                    .unwrap()
-                    .trim_end_matches('"')
-                    .trim_start_matches('"');
-                m.insert(k.into(), v.into());
+            if matches!(dict_name, "contig" | "INFO" | "FILTER" | "FORMAT") && !m.contains_key("ID")
+            {
+                return Err(error(&format!("{dict_name} line without ID")));
             }
-            match dict_name {
-                "contig" => {
-                    if m.contains_key("IDX") {
-                        assert_eq!(dict_contig_idx_counter, 0, "if one dict string has IDX all of them should have IDX in the dictionary");
-                        let idx: usize = m["IDX"].parse().unwrap();
-                        dict_contigs.insert(idx, m);
-                    } else {
-                        dict_contigs.insert(dict_contig_idx_counter, m);
-                        dict_contig_idx_counter += 1;
+            let is_pass = (dict_name == "FILTER") && (&m["ID"] == "PASS");
+            if matches!(dict_name, "contig" | "INFO" | "FILTER" | "FORMAT") && !is_pass {
+                let pos = lines.len() - 1;
+                let key = (dict_name.to_string(), m["ID"].clone());
+                if let Some(&(first_pos, first_no)) = seen.get(&key) {
+                    match policy {
+                        DuplicateIdPolicy::Error => {
+                            return Err(error(&format!(
+                                "duplicate {} ID '{}' (first defined on line {})",
+                                key.0, key.1, first_no
+                            )));
+                        }
+                        DuplicateIdPolicy::Warn => {
+                            duplicate_ids.push(HeaderViolation {
+                                severity: Severity::Error,
+                                line: Some(first_pos),
+                                message: format!(
+                                    "duplicate {} ID '{}' on line {} dropped",
+                                    key.0,
+                                    key.1,
+                                    line_no + 1
+                                ),
+                            });
+                            lines.pop();
+                            continue;
+                        }
+                        DuplicateIdPolicy::KeepFirst => {
+                            lines.pop();
+                            continue;
+                        }
+                        DuplicateIdPolicy::KeepLast => {
+                            dropped.insert(first_pos);
+                        }
                     }
                 }
-                _ => {
-                    if (dict_name == "FILTER") && (&m["ID"] == "PASS") {
-                        // skip FILTER/PASS already added
-                    } else {
-                        if ["INFO", "FILTER", "FORMAT"].iter().any(|x| *x == dict_name) {
-                            m.insert("Dictionary".into(), dict_name.into());
-                            if m.contains_key("IDX") {
-                                assert_eq!(dict_str_idx_counter, 1, "if one dict string has IDX all of them should have IDX in the dictionary");
-                                let idx: usize = m["IDX"].parse().unwrap();
-                                dict_strings.insert(idx, m);
-                            } else {
-                                dict_strings.insert(dict_str_idx_counter, m);
-                                dict_str_idx_counter += 1;
-                            }
+                seen.insert(key, (pos, line_no + 1));
+            }
+            match dict_name {
+                "contig" => contig_lines.push((lines.len() - 1, m)),
+                "INFO" | "FILTER" | "FORMAT" => {
+                    if is_pass {
+                        // FILTER/PASS already added, but keep an explicit IDX
+                        if let Some(idx) = m.get("IDX") {
+                            string_lines[0].1.insert("IDX".into(), idx.clone());
                         }
+                        continue;
+                    }
+                    m.insert("Dictionary".into(), dict_name.into());
+                    string_lines.push((lines.len() - 1, m));
+                }
+                _ => {}
+            };
+        }
+
+        let keep =
+            |(pos, m): (usize, HashMap<String, String>)| (!dropped.contains(&pos)).then_some(m);
+        let string_lines: Vec<_> = string_lines.into_iter().filter_map(keep).collect();
+        let contig_lines: Vec<_> = contig_lines.into_iter().filter_map(keep).collect();
+        let lines: Vec<_> = lines
+            .into_iter()
+            .enumerate()
+            .filter(|(pos, _)| !dropped.contains(pos))
+            .map(|(_, l)| l)
+            .collect();
+
+        // Place the lines in the dictionaries as htslib does: explicit IDX=
+        // values are used as offsets (they can be sparse or out of order);
+        // the other lines get the next unused offsets in file order. In the
+        // string dictionary, INFO/FORMAT/FILTER lines with the same ID share
+        // one offset.
+        // IDX values are checked above
+        let explicit_idx =
+            |m: &HashMap<String, String>| -> Option<usize> { m.get("IDX")?.parse().ok() };
+        let mut id_to_idx = HashMap::<String, usize>::new();
+        for m in string_lines.iter() {
+            if let Some(idx) = explicit_idx(m) {
+                id_to_idx.insert(m["ID"].clone(), idx);
+            }
+        }
+        let mut used: std::collections::HashSet<usize> = id_to_idx.values().copied().collect();
+        let mut next_idx = 0;
+        for m in string_lines {
+            let idx = match (explicit_idx(&m), id_to_idx.get(&m["ID"])) {
+                (Some(idx), _) => idx,
+                (None, Some(idx)) => *idx,
+                (None, None) => {
+                    while used.contains(&next_idx) {
+                        next_idx += 1;
+                    }
+                    used.insert(next_idx);
+                    id_to_idx.insert(m["ID"].clone(), next_idx);
+                    next_idx
+                }
+            };
+            // `dict_strings` keeps the first line for each offset
+            dict_strings.entry(idx).or_insert_with(|| m.clone());
+            dict_entries.insert((m["Dictionary"].clone(), idx), m);
+        }
+
+        let mut used: std::collections::HashSet<usize> =
+            contig_lines.iter().filter_map(explicit_idx).collect();
+        let mut next_idx = 0;
+        for m in contig_lines {
+            let idx = match explicit_idx(&m) {
+                Some(idx) => idx,
+                None => {
+                    while used.contains(&next_idx) {
+                        next_idx += 1;
                     }
+                    used.insert(next_idx);
+                    next_idx
                 }
             };
+            dict_contigs.insert(idx, m);
         }
 
         // find fmt_key for FORMAT/GT for convenience
         let mut fmt_gt_idx = None;
-        for (k, m) in dict_strings.iter() {
-            if (&m["Dictionary"] == "FORMAT") && (&m["ID"] == "GT") {
+        for ((dictionary, k), m) in dict_entries.iter() {
+            if (dictionary == "FORMAT") && (&m["ID"] == "GT") {
                 fmt_gt_idx = Some(*k);
             }
         }
 
-        Self {
+        // typed header lines, ordered by offset
+        let mut contigs: Vec<_> = dict_contigs
+            .iter()
+            .map(|(idx, m)| ContigRecord::from_map(*idx, m))
+            .collect();
+        contigs.sort_by_key(|r| r.idx);
+        let mut infos = vec![];
+        let mut formats = vec![];
+        let mut filters = vec![];
+        for ((dictionary, idx), m) in dict_entries.iter() {
+            match dictionary.as_str() {
+                "INFO" => infos.push(InfoRecord::from_map(*idx, m)),
+                "FORMAT" => formats.push(FormatRecord::from_map(*idx, m)),
+                _ => filters.push(FilterRecord::from_map(*idx, m)),
+            }
+        }
+        infos.sort_by_key(|r| r.idx);
+        formats.sort_by_key(|r| r.idx);
+        filters.sort_by_key(|r| r.idx);
+        // the first of duplicate IDs (e.g. sample names) is found by name
+        fn index_by_id<'a>(ids: impl Iterator<Item = &'a String>) -> HashMap<String, usize> {
+            let mut index = HashMap::new();
+            for (i, id) in ids.enumerate() {
+                index.entry(id.clone()).or_insert(i);
+            }
+            index
+        }
+        let contig_by_id = index_by_id(contigs.iter().map(|r| &r.id));
+        let info_by_id = index_by_id(infos.iter().map(|r| &r.id));
+        let format_by_id = index_by_id(formats.iter().map(|r| &r.id));
+        let filter_by_id = index_by_id(filters.iter().map(|r| &r.id));
+        let sample_by_name = index_by_id(samples.iter());
+        let structured_lines = |key: &'static str| {
+            lines
+                .iter()
+                .filter(move |l| l.key == key)
+                .filter_map(|l| l.attributes())
+        };
+        let metas = structured_lines("META")
+            .map(|m| MetaRecord::from_map(&m))
+            .collect();
+        let alts = structured_lines("ALT")
+            .map(|m| AltRecord::from_map(&m))
+            .collect();
+        let sample_records = structured_lines("SAMPLE")
+            .map(|m| SampleRecord::from_map(&m))
+            .collect();
+        let pedigrees = structured_lines("PEDIGREE")
+            .map(|m| PedigreeRecord::from_map(&m))
+            .collect();
+
+        // one allocation per distinct name, shared by all dictionaries
+        let mut interned = HashSet::<Arc<str>>::new();
+        // keyed by offset, as IDX values can be sparse
+        let mut intern_all = |names: Vec<(usize, &str)>| {
+            let mut table = HashMap::with_capacity(names.len());
+            for (idx, name) in names {
+                let name = match interned.get(name) {
+                    Some(a) => a.clone(),
+                    None => {
+                        let a: Arc<str> = Arc::from(name);
+                        interned.insert(a.clone());
+                        a
+                    }
+                };
+                table.insert(idx, name);
+            }
+            table
+        };
+        let interned_contigs = intern_all(contigs.iter().map(|r| (r.idx, r.id.as_str())).collect());
+        let interned_strings = intern_all(
+            dict_strings
+                .iter()
+                .filter_map(|(idx, m)| Some((*idx, m.get("ID")?.as_str())))
+                .collect(),
+        );
+
+        Ok(Self {
             dict_strings,
             dict_contigs,
+            dict_entries,
+            contigs,
+            infos,
+            formats,
+            filters,
+            contig_by_id,
+            info_by_id,
+            format_by_id,
+            filter_by_id,
+            metas,
+            alts,
+            sample_records,
+            pedigrees,
+            lines,
             samples,
+            sample_by_name,
+            raw_text: text.to_string(),
+            duplicate_ids,
             fmt_gt_idx,
-        }
+            interned_contigs,
+            interned_strings,
+            interned,
+        })
     }
 
     /// Find the key (offset in header line) for a given INFO/xx or FILTER/xx or FORMAT/xx field.
@@ -316,23 +818,55 @@ impl Header {
     /// ```
     ///  use bcf_reader::*;
     ///  let mut f = smart_reader("testdata/test.bcf");
-    ///  let s = read_header(&mut f);
+    ///  let s = read_header(&mut f).unwrap();
     ///  let header = Header::from_string(&s);
     ///  let key_found = header.get_idx_from_dictionary_str("FORMAT", "GT").unwrap();
     ///  assert_eq!(key_found, header.get_fmt_gt_id().unwrap());
     /// ```
     pub fn get_idx_from_dictionary_str(&self, dictionary: &str, field: &str) -> Option<usize> {
-        for (k, m) in self.dict_strings.iter() {
-            if (&m["Dictionary"] == dictionary) && (&m["ID"] == field) {
-                return Some(*k);
-            }
+        match dictionary {
+            "INFO" => self.info(field).map(|r| r.idx),
+            "FORMAT" => self.format(field).map(|r| r.idx),
+            "FILTER" => self.filter(field).map(|r| r.idx),
+            _ => None,
         }
-        None
     }
 
     /// Get chromosome name from the contig index
+    ///
+    /// # Panics
+    /// Panics if the contig index is not defined in the header; see
+    /// [`Header::rid_to_name`] for a non-panicking version.
     pub fn get_chrname(&self, idx: usize) -> &str {
-        &self.dict_contigs[&idx]["ID"]
+        match self.contig_by_rid(idx) {
+            Some(r) => &r.id,
+            None => panic!("contig index {idx} is not defined in the header"),
+        }
+    }
+
+    /// Get the contig index (the `chrom` of records) of a contig by name
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// assert_eq!(header.rid("Pf3D7_01_v3"), Some(record.chrom()));
+    /// assert_eq!(header.rid_to_name(record.chrom()), Some("Pf3D7_01_v3"));
+    /// assert_eq!(header.rid("chr5"), None);
+    /// assert_eq!(header.rid_to_name(-1), None);
+    /// assert_eq!(header.rid_to_name(1000), None);
+    /// ```
+    pub fn rid(&self, name: &str) -> Option<i32> {
+        self.contig(name).map(|r| r.idx as i32)
+    }
+
+    /// Get the name of a contig from its index (the `chrom` of records)
+    pub fn rid_to_name(&self, rid: i32) -> Option<&str> {
+        let rid = usize::try_from(rid).ok()?;
+        self.contig_by_rid(rid).map(|r| r.id.as_str())
     }
 
     /// Get key for FORMAT/GT field.
@@ -340,6 +874,21 @@ impl Header {
         self.fmt_gt_idx
     }
 
+    /// Get the key (dictionary offset) of any FORMAT tag, e.g. `DS` or `DP`.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// assert_eq!(header.format_key("GT"), header.get_fmt_gt_id());
+    /// assert!(header.format_key("DP").is_some());
+    /// assert_eq!(header.format_key("DS"), None);
+    /// ```
+    pub fn format_key(&self, tag: &str) -> Option<usize> {
+        self.format(tag).map(|r| r.idx)
+    }
+
     /// Get hashmap of hashmap of dictionary of contigs
     /// outer key: contig_idx
     /// inner key: is the key of the dictionary of contig, such as 'ID', 'Description'
@@ -350,146 +899,593 @@ impl Header {
     /// Get hashmap of hashmap of dictionary of strings
     /// outer key: item_idx, for FILTER/xx, FORMAT/xx, INFO/xx,
     /// inner key: is the key of the dictionary of string, such as 'ID', 'Description'
+    ///
+    /// An INFO, a FORMAT and a FILTER line with the same ID share an item_idx;
+    /// only the first of these lines is kept here (see `dict_entry`).
     pub fn dict_strings(&self) -> &HashMap<usize, HashMap<String, String>> {
         &self.dict_strings
     }
 
-    /// Get samples names from sample idx
+    /// Get the header line of the dictionary of strings for a dictionary
+    /// (INFO, FORMAT or FILTER) and an item_idx
+    ///
     /// Example:
     /// ```
     /// use bcf_reader::*;
-    /// // read data generated by bcftools
-    /// // bcftools query -l test.bcf | bgzip -c > test_samples.gz
-    /// let mut samples_str = String::new();
-    /// smart_reader("./testdata/test_samples.gz")
-    ///     .read_to_string(&mut samples_str)
-    ///     .unwrap();
-    /// // read data via bcf-reader
-    /// let mut f = smart_reader("testdata/test.bcf");
-    /// let s = read_header(&mut f);
-    /// let header = Header::from_string(&s);
-    /// let samples_str2 = header.get_samples().join("\n");
-    /// // compare bcftools results and bcf-reader results
-    /// assert_eq!(samples_str.trim(), samples_str2.trim());
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// // INFO/DP and FORMAT/DP share a key but have different definitions
+    /// let key = header.get_idx_from_dictionary_str("INFO", "DP").unwrap();
+    /// assert_eq!(header.get_idx_from_dictionary_str("FORMAT", "DP"), Some(key));
+    /// let info_dp = header.dict_entry("INFO", key).unwrap();
+    /// let fmt_dp = header.dict_entry("FORMAT", key).unwrap();
+    /// assert_eq!(info_dp["ID"], "DP");
+    /// assert_eq!(fmt_dp["ID"], "DP");
+    /// assert_ne!(info_dp["Description"], fmt_dp["Description"]);
+    /// assert!(header.dict_entry("FILTER", key).is_none());
     /// ```
-    pub fn get_samples(&self) -> &Vec<String> {
-        &self.samples
+    pub fn dict_entry(&self, dictionary: &str, idx: usize) -> Option<&HashMap<String, String>> {
+        self.dict_entries.get(&(dictionary.to_string(), idx))
     }
-}
 
-/// map bcf2 type to width in bytes
-///
-/// `typ`:
-/// - 0: MISSING
-/// - 1: u8 (1 byte)
-/// - 2: u16 (2 bytes)
-/// - 3: u32 (3 bytes)
-/// - 5: f32 (4 bytes)
-/// - 7: c-char (u8, 1 byte)
-pub fn bcf2_typ_width(typ: u8) -> usize {
-    match typ {
-        0x0 => 0,
-        0x1 => 1,
-        0x2 => 2,
-        0x3 => 4,
-        0x5 => 4,
-        0x7 => 1,
-        _ => panic!(),
+    /// Get the name of a contig by rid as a shared string.
+    ///
+    /// The names of contigs and the IDs of the dictionary of strings are
+    /// interned when the header is parsed: equal names share one allocation,
+    /// so that clones are cheap and names from the same header can be
+    /// compared with [`Arc::ptr_eq`].
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::sync::Arc;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let chrom = header.interned_contig(0).unwrap();
+    /// assert_eq!(&**chrom, header.get_chrname(0));
+    /// assert!(Arc::ptr_eq(chrom, header.interned(header.get_chrname(0)).unwrap()));
+    /// assert!(header.interned_contig(-1).is_none());
+    /// ```
+    pub fn interned_contig(&self, rid: i32) -> Option<&Arc<str>> {
+        let rid = usize::try_from(rid).ok()?;
+        self.interned_contigs.get(&rid)
     }
-}
 
-#[derive(Debug, PartialEq)]
-/// Represents a numeric value in the context of the bcf-reader.
-pub enum NumericValue {
-    /// Represents an unsigned 8-bit integer value.
-    U8(u8),
-    /// Represents an unsigned 16-bit integer value.
-    U16(u16),
-    /// Represents an unsigned 32-bit integer value.
-    U32(u32),
-    /// Represents a 32-bit floating-point value. (Note that a u32 is used to
-    /// hold the bits for the f32 value)
-    F32(u32),
-}
+    /// Get the ID of the dictionary of strings at an item_idx as a shared
+    /// string, see [`Header::interned_contig`]. As with `dict_strings`, the
+    /// ID of the first line is used if several lines share the item_idx.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::sync::Arc;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let key = header.get_idx_from_dictionary_str("INFO", "DP").unwrap();
+    /// let dp = header.interned_string(key).unwrap();
+    /// assert_eq!(&**dp, "DP");
+    /// // INFO/DP and FORMAT/DP share the key and the string
+    /// let fmt_key = header.get_idx_from_dictionary_str("FORMAT", "DP").unwrap();
+    /// assert!(Arc::ptr_eq(dp, header.interned_string(fmt_key).unwrap()));
+    /// ```
+    pub fn interned_string(&self, idx: usize) -> Option<&Arc<str>> {
+        self.interned_strings.get(&idx)
+    }
 
-impl Default for NumericValue {
-    fn default() -> Self {
-        NumericValue::U8(0)
+    /// Get the shared string of a contig name or of an ID of the dictionary
+    /// of strings, see [`Header::interned_contig`]. `None` is returned for
+    /// names not defined in the header.
+    pub fn interned(&self, name: &str) -> Option<&Arc<str>> {
+        self.interned.get(name)
     }
-}
 
-impl From<u8> for NumericValue {
-    fn from(value: u8) -> Self {
-        Self::U8(value)
+    /// Get all meta-information (`##`) lines of the header in file order,
+    /// including lines that are not parsed into dictionaries, such as
+    /// `##fileformat` or `##bcftools_viewCommand`
+    pub fn lines(&self) -> &[HeaderLine] {
+        &self.lines
     }
-}
-impl From<u16> for NumericValue {
-    fn from(value: u16) -> Self {
-        Self::U16(value)
+
+    /// Get the simple `##key=value` lines of the header (lines whose value is
+    /// not a structured `<..>` list), such as `##fileformat`, `##reference`,
+    /// `##source` or `##bcftools_viewCommand`, in file order
+    pub fn generic_lines(&self) -> impl Iterator<Item = &HeaderLine> + '_ {
+        self.lines.iter().filter(|l| !l.is_structured())
     }
-}
-impl From<u32> for NumericValue {
-    fn from(value: u32) -> Self {
-        Self::U32(value)
+
+    /// Get the values of all simple `##key=value` lines with the given key,
+    /// in file order
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// assert_eq!(header.values_of("fileformat"), ["VCFv4.2"]);
+    /// assert!(header.values_of("no_such_key").is_empty());
+    /// // e.g. the provenance of the file
+    /// for line in header.generic_lines() {
+    ///     assert!(!line.value.starts_with('<'));
+    /// }
+    /// ```
+    pub fn values_of(&self, key: &str) -> Vec<&str> {
+        self.generic_lines()
+            .filter(|l| l.key == key)
+            .map(|l| l.value.as_str())
+            .collect()
     }
-}
 
-impl NumericValue {
-    fn is_missing(&self) -> bool {
-        match *self {
-            NumericValue::U8(x) => x == 0x80,
-            NumericValue::U16(x) => x == 0x8000,
-            NumericValue::U32(x) => x == 0x80000000,
-            NumericValue::F32(x) => x == 0x7F800001,
-        }
+    /// Get the value of the first simple `##key=value` line with the given
+    /// key
+    pub fn value_of(&self, key: &str) -> Option<&str> {
+        self.values_of(key).first().copied()
     }
 
-    fn is_end_of_vector(&self) -> bool {
-        match *self {
-            NumericValue::U8(x) => x == 0x81,
-            NumericValue::U16(x) => x == 0x8001,
-            NumericValue::U32(x) => x == 0x80000001,
-            NumericValue::F32(x) => x == 0x7F800002,
-        }
+    /// Get the value of the `##fileformat` line, e.g. `VCFv4.2`
+    pub fn fileformat(&self) -> Option<&str> {
+        self.value_of("fileformat")
     }
 
-    fn as_f32(&self) -> Self {
-        match *self {
-            NumericValue::U32(x) => NumericValue::F32(x),
-            _ => panic!(),
-        }
+    /// Get the VCF (major, minor) version from the `##fileformat` line
+    pub fn vcf_version(&self) -> Option<(u32, u32)> {
+        let (major, minor) = self.fileformat()?.strip_prefix("VCFv")?.split_once('.')?;
+        Some((major.parse().ok()?, minor.parse().ok()?))
     }
 
-    /// Returns the integer value if the NumericValue is an unsigned integer and not missing.
-    ///
-    /// # Examples
+    /// Write the header back as VCF header text: the meta-information lines
+    /// in their original order followed by the `#CHROM` line, each line
+    /// ending with a newline.
     ///
+    /// Example:
     /// ```
-    /// use bcf_reader::NumericValue;
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let text = read_header(&mut f).unwrap();
+    /// let header = Header::from_string(&text);
+    /// assert_eq!(header.lines()[0].key, "fileformat");
+    /// assert_eq!(header.to_text(), text.trim_end_matches('\0'));
+    /// ```
+    pub fn to_text(&self) -> String {
+        self.text_with_lines(&self.lines)
+    }
+
+    /// Get the exact text the header was parsed from, e.g. to write the
+    /// header of a pass-through pipeline unchanged or to hash it for
+    /// provenance. For headers read with [`read_header`] or the readers, this
+    /// is the decoded header text without the NUL terminator and padding.
+    /// The text does not change when the header is modified (e.g. with
+    /// [`Header::push_line`]); [`Header::to_text`] writes the modified
+    /// header.
     ///
-    /// let value = NumericValue::U8(42);
-    /// assert_eq!(value.int_val(), Some(42u32));
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let text = read_header(&mut f).unwrap();
+    /// let mut header = Header::from_string(&text);
+    /// assert_eq!(header.raw_text(), text);
     ///
-    /// let missing_value = NumericValue::U8(0x80u8);
-    /// assert_eq!(missing_value.int_val(), None);
+    /// header.push_line("##source=myTool").unwrap();
+    /// assert_eq!(header.raw_text(), text);
+    /// assert!(header.to_text().contains("##source=myTool\n"));
+    /// assert!(!header.raw_text().contains("##source=myTool"));
     /// ```
-    pub fn int_val(&self) -> Option<u32> {
-        if self.is_end_of_vector() || self.is_missing() {
-            None
-        } else {
-            match *self {
-                Self::U8(x) => Some(x as u32),
-                Self::U16(x) => Some(x as u32),
-                Self::U32(x) => Some(x as u32),
-                _ => None,
+    pub fn raw_text(&self) -> &str {
+        &self.raw_text
+    }
+
+    /// Write `lines` and the `#CHROM` line of the header as VCF header text
+    fn text_with_lines(&self, lines: &[HeaderLine]) -> String {
+        let mut text = String::new();
+        for line in lines.iter() {
+            text.push_str(&line.to_string());
+            text.push('\n');
+        }
+        text.push_str("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO");
+        if !self.samples.is_empty() {
+            text.push_str("\tFORMAT");
+            for s in self.samples.iter() {
+                text.push('\t');
+                text.push_str(s);
             }
         }
+        text.push('\n');
+        text
     }
 
-    /// Returns the floating-point value if the NumericValue is a 32-bit float and not missing.
-    ///
-    /// # Examples
-    ///
+    /// Get the `##contig` header lines, ordered by offset
+    pub fn contigs(&self) -> &[ContigRecord] {
+        &self.contigs
+    }
+
+    /// Get the `##INFO` header lines, ordered by offset
+    pub fn infos(&self) -> &[InfoRecord] {
+        &self.infos
+    }
+
+    /// Get the `##FORMAT` header lines, ordered by offset
+    pub fn formats(&self) -> &[FormatRecord] {
+        &self.formats
+    }
+
+    /// Get the `##FILTER` header lines (including the implicit PASS), ordered
+    /// by offset
+    pub fn filters(&self) -> &[FilterRecord] {
+        &self.filters
+    }
+
+    /// Find a `##contig` header line by offset in the contig dictionary
+    /// (the `chrom` of records)
+    pub fn contig_by_rid(&self, rid: usize) -> Option<&ContigRecord> {
+        self.contigs
+            .binary_search_by_key(&rid, |r| r.idx)
+            .ok()
+            .map(|i| &self.contigs[i])
+    }
+
+    /// Get the length of a contig from its offset in the contig dictionary
+    /// (the `chrom` of records), if given in the header
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let len = header.contig_length(record.chrom() as usize).unwrap();
+    ///     assert!((record.pos() as u64) < len);
+    /// }
+    /// assert_eq!(header.contig_length(1000), None);
+    /// ```
+    pub fn contig_length(&self, rid: usize) -> Option<u64> {
+        self.contig_by_rid(rid).and_then(|r| r.length)
+    }
+
+    /// Find a `##contig` header line by name in constant time
+    pub fn contig(&self, id: &str) -> Option<&ContigRecord> {
+        self.contig_by_id.get(id).map(|i| &self.contigs[*i])
+    }
+
+    /// Find an `##INFO` header line by tag in constant time
+    pub fn info(&self, id: &str) -> Option<&InfoRecord> {
+        self.info_by_id.get(id).map(|i| &self.infos[*i])
+    }
+
+    /// Find a `##FORMAT` header line by tag in constant time
+    pub fn format(&self, id: &str) -> Option<&FormatRecord> {
+        self.format_by_id.get(id).map(|i| &self.formats[*i])
+    }
+
+    /// Find a `##FILTER` header line by name in constant time
+    pub fn filter(&self, id: &str) -> Option<&FilterRecord> {
+        self.filter_by_id.get(id).map(|i| &self.filters[*i])
+    }
+
+    /// Get the VCF 4.4 `##META` header lines in file order
+    pub fn metas(&self) -> &[MetaRecord] {
+        &self.metas
+    }
+
+    /// Find a `##META` header line by ID
+    pub fn meta(&self, id: &str) -> Option<&MetaRecord> {
+        self.metas.iter().find(|r| r.id == id)
+    }
+
+    /// Get the `##ALT` header lines in file order
+    pub fn alts(&self) -> &[AltRecord] {
+        &self.alts
+    }
+
+    /// Find an `##ALT` header line by ID (without angle brackets)
+    pub fn alt(&self, id: &str) -> Option<&AltRecord> {
+        self.alts.iter().find(|r| r.id == id)
+    }
+
+    /// Find the `##ALT` header line describing a symbolic allele such as
+    /// `<DEL:ME>`. If the exact ID is not declared, the parent types are
+    /// tried (`DEL:ME`, then `DEL`). Returns `None` for alleles that are not
+    /// symbolic or not declared.
+    pub fn resolve_symbolic_allele(&self, allele: &[u8]) -> Option<&AltRecord> {
+        let mut id = sv::symbolic_allele_id(allele)?;
+        loop {
+            if let Some(r) = self.alt(id) {
+                return Some(r);
+            }
+            id = id.rsplit_once(':')?.0;
+        }
+    }
+
+    /// Get the `##SAMPLE` header lines in file order
+    pub fn sample_records(&self) -> &[SampleRecord] {
+        &self.sample_records
+    }
+
+    /// Find the `##SAMPLE` header line of a sample by name
+    pub fn sample_record(&self, id: &str) -> Option<&SampleRecord> {
+        self.sample_records.iter().find(|r| r.id == id)
+    }
+
+    /// Get the `##PEDIGREE` header lines in file order
+    pub fn pedigrees(&self) -> &[PedigreeRecord] {
+        &self.pedigrees
+    }
+
+    /// Find the `##PEDIGREE` header line of a sample by name
+    pub fn pedigree(&self, id: &str) -> Option<&PedigreeRecord> {
+        self.pedigrees.iter().find(|r| r.id == id)
+    }
+
+    /// Whether the header declares FORMAT/LAA, the local alleles used by
+    /// local-allele FORMAT fields (LGT, LAD, LPL, ...) of large cohort files
+    pub fn has_local_alleles(&self) -> bool {
+        self.format("LAA").is_some()
+    }
+
+    /// List the local-allele FORMAT fields with the tag of the corresponding
+    /// global field, e.g. (LAD, "AD"): `L`-prefixed FORMAT fields with a
+    /// local-allele `Number=` (LA, LR or LG), plus LGT. FORMAT/LAA itself is
+    /// not included.
+    pub fn local_allele_fields(&self) -> Vec<(&FormatRecord, &str)> {
+        use header::Number;
+        self.formats
+            .iter()
+            .filter(|r| r.id == "LGT" || matches!(r.number, Number::LA | Number::LR | Number::LG))
+            .filter_map(|r| r.id.strip_prefix('L').map(|global| (r, global)))
+            .collect()
+    }
+
+    /// Get samples names from sample idx
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // read data generated by bcftools
+    /// // bcftools query -l test.bcf | bgzip -c > test_samples.gz
+    /// let mut samples_str = String::new();
+    /// smart_reader("./testdata/test_samples.gz")
+    ///     .read_to_string(&mut samples_str)
+    ///     .unwrap();
+    /// // read data via bcf-reader
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let s = read_header(&mut f).unwrap();
+    /// let header = Header::from_string(&s);
+    /// let samples_str2 = header.get_samples().join("\n");
+    /// // compare bcftools results and bcf-reader results
+    /// assert_eq!(samples_str.trim(), samples_str2.trim());
+    /// ```
+    pub fn get_samples(&self) -> &Vec<String> {
+        &self.samples
+    }
+
+    /// Get the column (0-based) of a sample by name. If several samples have
+    /// the same name, the column of the first one is returned.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let samples = header.get_samples();
+    /// assert_eq!(header.sample_index(&samples[2]), Some(2));
+    /// assert_eq!(header.sample_index("NOT_A_SAMPLE"), None);
+    /// assert_eq!(header.samples_index_map().len(), samples.len());
+    ///
+    /// let header = Header::from_string("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tA\tB\tA\n");
+    /// assert_eq!(header.sample_index("A"), Some(0));
+    /// assert_eq!(header.sample_index("B"), Some(1));
+    /// ```
+    pub fn sample_index(&self, name: &str) -> Option<usize> {
+        self.sample_by_name.get(name).copied()
+    }
+
+    /// Get the columns (0-based) of all samples by name
+    pub fn samples_index_map(&self) -> &HashMap<String, usize> {
+        &self.sample_by_name
+    }
+
+    /// Get the ID, `Number=` and `Type=` of an INFO or FORMAT key
+    fn number_and_type_for(
+        &self,
+        dictionary: &str,
+        key: usize,
+    ) -> error::Result<(&str, &header::Number, &header::Type)> {
+        let found = match dictionary {
+            "INFO" => self
+                .infos
+                .binary_search_by_key(&key, |r| r.idx)
+                .ok()
+                .map(|i| (&self.infos[i].id, &self.infos[i].number, &self.infos[i].ty)),
+            _ => self
+                .formats
+                .binary_search_by_key(&key, |r| r.idx)
+                .ok()
+                .map(|i| {
+                    (
+                        &self.formats[i].id,
+                        &self.formats[i].number,
+                        &self.formats[i].ty,
+                    )
+                }),
+        };
+        match found {
+            Some((id, number, ty)) => Ok((id.as_str(), number, ty)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("key {key} is not a {dictionary} field in the header"),
+            )
+            .into()),
+        }
+    }
+}
+
+/// map bcf2 type to width in bytes
+///
+/// `typ`:
+/// - 0: MISSING
+/// - 1: u8 (1 byte)
+/// - 2: u16 (2 bytes)
+/// - 3: u32 (3 bytes)
+/// - 5: f32 (4 bytes)
+/// - 7: c-char (u8, 1 byte)
+///
+/// # Panics
+/// Panics for other type codes; see [`try_bcf2_typ_width`].
+#[deprecated(note = "use `try_bcf2_typ_width`, which returns an error for other type codes")]
+pub fn bcf2_typ_width(typ: u8) -> usize {
+    match try_bcf2_typ_width(typ) {
+        Ok(width) => width,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Width of the type code of a located field: the fields with other type
+/// codes are dropped or rejected when they are located
+pub(crate) fn typ_width(typ: u8) -> usize {
+    try_bcf2_typ_width(typ).expect("type code of a located field")
+}
+
+/// Get the width in bytes of a BCF2 type, or an error for a type code that
+/// is not defined, including the reserved codes
+pub fn try_bcf2_typ_width(typ: u8) -> error::Result<usize> {
+    match typ {
+        0x0 => Ok(0),
+        0x1 => Ok(1),
+        0x2 => Ok(2),
+        0x3 => Ok(4),
+        0x5 => Ok(4),
+        0x7 => Ok(1),
+        _ => Err(Error::InvalidType {
+            typ,
+            descriptor: None,
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Represents a numeric value in the context of the bcf-reader.
+pub enum NumericValue {
+    /// Represents an unsigned 8-bit integer value.
+    U8(u8),
+    /// Represents an unsigned 16-bit integer value.
+    U16(u16),
+    /// Represents an unsigned 32-bit integer value.
+    U32(u32),
+    /// Represents a 32-bit floating-point value. (Note that a u32 is used to
+    /// hold the bits for the f32 value)
+    F32(u32),
+}
+
+impl Default for NumericValue {
+    fn default() -> Self {
+        NumericValue::U8(0)
+    }
+}
+
+impl From<u8> for NumericValue {
+    fn from(value: u8) -> Self {
+        Self::U8(value)
+    }
+}
+impl From<u16> for NumericValue {
+    fn from(value: u16) -> Self {
+        Self::U16(value)
+    }
+}
+impl From<u32> for NumericValue {
+    fn from(value: u32) -> Self {
+        Self::U32(value)
+    }
+}
+
+impl NumericValue {
+    fn is_missing(&self) -> bool {
+        match *self {
+            NumericValue::U8(x) => x == 0x80,
+            NumericValue::U16(x) => x == 0x8000,
+            NumericValue::U32(x) => x == 0x80000000,
+            NumericValue::F32(x) => x == 0x7F800001,
+        }
+    }
+
+    fn is_end_of_vector(&self) -> bool {
+        match *self {
+            NumericValue::U8(x) => x == 0x81,
+            NumericValue::U16(x) => x == 0x8001,
+            NumericValue::U32(x) => x == 0x80000001,
+            NumericValue::F32(x) => x == 0x7F800002,
+        }
+    }
+
+    fn as_f32(&self) -> Self {
+        match *self {
+            NumericValue::U32(x) => NumericValue::F32(x),
+            _ => panic!(),
+        }
+    }
+
+    /// Returns the integer value if the NumericValue is an unsigned integer and not missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bcf_reader::NumericValue;
+    ///
+    /// let value = NumericValue::U8(42);
+    /// assert_eq!(value.int_val(), Some(42u32));
+    ///
+    /// let missing_value = NumericValue::U8(0x80u8);
+    /// assert_eq!(missing_value.int_val(), None);
+    /// ```
+    pub fn int_val(&self) -> Option<u32> {
+        if self.is_end_of_vector() || self.is_missing() {
+            None
+        } else {
+            match *self {
+                Self::U8(x) => Some(x as u32),
+                Self::U16(x) => Some(x as u32),
+                Self::U32(x) => Some(x),
+                _ => None,
+            }
+        }
+    }
+
+    /// Returns the integer value, decoded as a signed integer of the
+    /// corresponding width, if the NumericValue is an integer and not missing.
+    ///
+    /// BCF integers are signed; use this instead of [`NumericValue::int_val`]
+    /// for fields that can hold negative values (e.g. INFO/SVLEN).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bcf_reader::NumericValue;
+    ///
+    /// let value = NumericValue::U8(0xffu8);
+    /// assert_eq!(value.signed_int_val(), Some(-1));
+    ///
+    /// let value = NumericValue::U16((-300i16) as u16);
+    /// assert_eq!(value.signed_int_val(), Some(-300));
+    ///
+    /// let missing_value = NumericValue::U32(0x80000000);
+    /// assert_eq!(missing_value.signed_int_val(), None);
+    /// ```
+    pub fn signed_int_val(&self) -> Option<i32> {
+        if self.is_end_of_vector() || self.is_missing() {
+            None
+        } else {
+            match *self {
+                Self::U8(x) => Some(x as i8 as i32),
+                Self::U16(x) => Some(x as i16 as i32),
+                Self::U32(x) => Some(x as i32),
+                _ => None,
+            }
+        }
+    }
+
+    /// Returns the floating-point value if the NumericValue is a 32-bit float and not missing.
+    ///
+    /// # Examples
+    ///
     /// ```
     /// use bcf_reader::NumericValue;
     ///
@@ -561,75 +1557,313 @@ impl NumericValue {
     }
 }
 
+/// Read `l_shared` and `l_indv` of a record, or `None` if the data ends
+/// before the record (the end of the file); an error is returned if it ends
+/// within them
+fn read_record_lengths<R: Read>(reader: &mut R) -> io::Result<Option<(u32, u32)>> {
+    let mut bytes = [0u8; 8];
+    let mut n = 0;
+    while n < bytes.len() {
+        match reader.read(&mut bytes[n..]) {
+            Ok(0) if n == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("the data ends {n} bytes into a record"),
+                ))
+            }
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let [a, b, c, d, e, f, g, h] = bytes;
+    Ok(Some((
+        u32::from_le_bytes([a, b, c, d]),
+        u32::from_le_bytes([e, f, g, h]),
+    )))
+}
+
+/// The error returned by the reading methods that do not tell the end of
+/// the data apart (e.g. [`Record::read`])
+fn no_more_records() -> Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "no more records").into()
+}
+
+/// Skip exactly `n` bytes of the reader without buffering them
+fn skip_exact<R: Read>(reader: &mut R, n: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.by_ref().take(n), &mut io::sink())?;
+    if skipped != n {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(())
+}
+
+/// Resize `buffer` to `n` bytes and fill it from the reader, growing it while
+/// reading so that a corrupt length does not cause a huge allocation
+fn read_exact_to<R: Read>(reader: &mut R, buffer: &mut Vec<u8>, n: usize) -> io::Result<()> {
+    buffer.clear();
+    let read = reader.take(n as u64).read_to_end(buffer)?;
+    if read < n {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(())
+}
+
+/// Append exactly `n` bytes of the reader to `buffer`, growing it while
+/// reading as [`read_exact_to`]
+fn read_exact_append<R: Read>(reader: &mut R, buffer: &mut Vec<u8>, n: usize) -> io::Result<()> {
+    let read = reader.take(n as u64).read_to_end(buffer)?;
+    if read < n {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(())
+}
+
+/// A reader copying the bytes it reads to a buffer
+struct TeeReader<'a, R> {
+    reader: R,
+    copy: &'a mut Vec<u8>,
+}
+
+impl<R: Read> Read for TeeReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.copy.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// The type descriptor byte of `n` values of type `typ` (with 15 for counts
+/// given by a following integer)
+fn descriptor_byte(typ: u8, n: usize) -> u8 {
+    ((n.min(15) as u8) << 4) | typ
+}
+
+/// Reserved BCF2 type code, used by htslib for 64-bit integers
+const BCF_BT_INT64: u8 = 4;
+
+/// Width in bytes of the values of a field, including the reserved type code
+/// 4 (64-bit integers) so that such fields can be skipped
+fn field_width(typ: u8) -> error::Result<usize> {
+    match typ {
+        BCF_BT_INT64 => Ok(8),
+        _ => try_bcf2_typ_width(typ),
+    }
+}
+
 /// Read typed descriptor from the reader (of decompressed BCF buffer)
 ///
-/// Return `typ` for type and `n` for count of elements of the type.
-pub fn read_typed_descriptor_bytes<R>(reader: &mut R) -> (u8, usize)
+/// Return `typ` for type and `n` for count of elements of the type. An
+/// error is returned for type codes other than those of BCF2 and the
+/// reserved code 4 (64-bit integers).
+pub fn read_typed_descriptor_bytes<R>(reader: &mut R) -> error::Result<(u8, usize)>
 where
     R: std::io::Read + ReadBytesExt,
 {
-    let tdb = reader.read_u8().unwrap();
+    let tdb = reader.read_u8()?;
     let typ = tdb & 0xf;
+    if field_width(typ).is_err() {
+        return Err(Error::InvalidType {
+            typ,
+            descriptor: Some(tdb),
+        });
+    }
     let mut n = (tdb >> 4) as usize;
     if n == 15 {
-        n = read_single_typed_integer(reader) as usize;
+        n = read_count(reader)?;
     }
-    (typ, n)
+    Ok((typ, n))
 }
 
-/// Read a single typed integer from the reader (of decompressed BCF buffer)
-pub fn read_single_typed_integer<R>(reader: &mut R) -> u32
+/// Read the typed integer giving the number of values of a type descriptor,
+/// which is signed and must not be negative
+fn read_count<R>(reader: &mut R) -> error::Result<usize>
 where
     R: std::io::Read + ReadBytesExt,
 {
-    let (typ, n) = read_typed_descriptor_bytes(reader);
-    assert_eq!(n, 1);
-    match typ {
-        1 => reader.read_u8().unwrap() as u32,
-        2 => reader.read_u16::<LittleEndian>().unwrap() as u32,
-        3 => reader.read_u32::<LittleEndian>().unwrap(),
-        _ => panic!(),
+    let (typ, n) = read_typed_descriptor_bytes(reader)?;
+    if n != 1 {
+        return Err(Error::UnexpectedCount {
+            expected: 1,
+            found: n,
+        });
     }
+    let count = match typ {
+        1 => reader.read_i8()? as i32,
+        2 => reader.read_i16::<LittleEndian>()? as i32,
+        3 => reader.read_i32::<LittleEndian>()?,
+        _ => {
+            return Err(Error::UnexpectedType {
+                expected: "an integer count",
+                found: typ,
+                descriptor: 0x10 | typ,
+            })
+        }
+    };
+    usize::try_from(count)
+        .map_err(|_| Error::MalformedRecord(format!("negative number of values {count}")))
 }
 
-/// Iterator for accessing arrays of numeric values (integers or floats)
+/// Read a single typed integer from the reader (of decompressed BCF buffer)
+pub fn read_single_typed_integer<R>(reader: &mut R) -> error::Result<u32>
+where
+    R: std::io::Read + ReadBytesExt,
+{
+    let (typ, n) = read_typed_descriptor_bytes(reader)?;
+    if n != 1 {
+        return Err(Error::UnexpectedCount {
+            expected: 1,
+            found: n,
+        });
+    }
+    match typ {
+        1 => Ok(reader.read_u8()? as u32),
+        2 => Ok(reader.read_u16::<LittleEndian>()? as u32),
+        3 => Ok(reader.read_u32::<LittleEndian>()?),
+        _ => Err(Error::UnexpectedType {
+            expected: "an integer",
+            found: typ,
+            descriptor: 0x10 | typ,
+        }),
+    }
+}
+
+/// Iterator for accessing arrays of numeric values (integers or floats)
 /// directly from the buffer bytes without building Vec<_> or Vec<Vec<_>>
 /// for each site.
 #[derive(Default, Debug)]
 pub struct NumericValueIter<'r> {
-    reader: std::io::Cursor<&'r [u8]>,
+    buf: &'r [u8],
     typ: u8,
     len: usize,
     cur: usize,
 }
 
-impl<'r> Iterator for NumericValueIter<'r> {
+impl<'r> NumericValueIter<'r> {
+    /// Read the next value, returning an error instead of panicking if the
+    /// buffer ends early or the values are not numeric (see
+    /// [`NumericValueIter::fallible`]).
+    pub fn try_next(&mut self) -> error::Result<Option<NumericValue>> {
+        if self.cur >= self.len || self.typ == 0 {
+            return Ok(None);
+        }
+        let width = match self.typ {
+            1 => 1,
+            2 => 2,
+            3 | 5 => 4,
+            typ => {
+                return Err(Error::UnexpectedType {
+                    expected: "a numeric value",
+                    found: typ,
+                    descriptor: descriptor_byte(typ, self.len),
+                })
+            }
+        };
+        let offset = self.cur * width;
+        let Some(bytes) = self.buf.get(offset..offset + width) else {
+            let e = io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+            return Err(Error::from(e).context(format!("value {}", self.cur), offset));
+        };
+        let val = match *bytes {
+            [a] => NumericValue::from(a),
+            [a, b] => NumericValue::from(u16::from_le_bytes([a, b])),
+            [a, b, c, d] if self.typ == 5 => {
+                NumericValue::from(u32::from_le_bytes([a, b, c, d])).as_f32()
+            }
+            [a, b, c, d] => NumericValue::from(u32::from_le_bytes([a, b, c, d])),
+            _ => unreachable!("numeric values have 1, 2 or 4 bytes"),
+        };
+        self.cur += 1;
+        Ok(Some(val))
+    }
+
+    /// Iterate over `Result`s instead of panicking on damaged values. The
+    /// iteration stops after the first error.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // three int16 values declared, but only five bytes
+    /// let buffer = [1, 0, 2, 0, 3];
+    /// let values: Vec<_> = iter_typed_integers(2, 3, &buffer).fallible().collect();
+    /// assert_eq!(values.len(), 3);
+    /// assert_eq!(values[1].as_ref().unwrap().int_val(), Some(2));
+    /// let err = values[2].as_ref().unwrap_err();
+    /// assert_eq!(err.to_string(), "value 2 at byte 4: failed to fill whole buffer");
+    ///
+    /// // strings are not numeric values
+    /// let mut it = iter_typed_integers(7, 1, b"A");
+    /// assert!(matches!(it.try_next(), Err(Error::UnexpectedType { found: 7, .. })));
+    /// ```
+    pub fn fallible(self) -> FallibleNumericValueIter<'r> {
+        FallibleNumericValueIter { inner: self }
+    }
+}
+
+impl Iterator for NumericValueIter<'_> {
     type Item = NumericValue;
+    /// # Panics
+    /// Panics if the buffer ends early or the values are not numeric; see
+    /// [`NumericValueIter::try_next`].
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur >= self.len {
-            None
-        } else {
-            match self.typ {
-                0 => None,
-                1 => {
-                    self.cur += 1;
-                    Some(self.reader.read_u8().unwrap().into())
-                }
-                2 => {
-                    self.cur += 1;
-                    Some(self.reader.read_u16::<LittleEndian>().unwrap().into())
-                }
-                3 => {
-                    self.cur += 1;
-                    Some(self.reader.read_u32::<LittleEndian>().unwrap().into())
-                }
-                5 => {
-                    self.cur += 1;
-                    let val = self.reader.read_u32::<LittleEndian>().unwrap();
-                    Some(NumericValue::from(val).as_f32())
-                }
-                _ => panic!(),
-            }
+        match self.try_next() {
+            Ok(val) => val,
+            Err(e) => panic!("{e}"),
+        }
+    }
+}
+
+/// Iterator over the values of a [`NumericValueIter`] yielding a `Result`
+/// for each value (see [`NumericValueIter::fallible`])
+#[derive(Debug)]
+pub struct FallibleNumericValueIter<'r> {
+    inner: NumericValueIter<'r>,
+}
+
+impl Iterator for FallibleNumericValueIter<'_> {
+    type Item = error::Result<NumericValue>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.inner.try_next();
+        if res.is_err() {
+            // stop after the first error
+            self.inner.cur = self.inner.len;
+        }
+        res.transpose()
+    }
+}
+
+/// Read the `i`-th value of a buffer of BCF2 integers of type `typ` as a
+/// signed integer, without bounds or sentinel checks (requires the
+/// `unchecked` feature)
+///
+/// The missing and end-of-vector values are returned as they are
+/// (`i8::MIN`, `i8::MIN + 1`, etc. sign-extended to `i32`). Meant for files
+/// that are validated once (e.g. with [`validate::validate_file`]) and then
+/// scanned many times.
+///
+/// # Safety
+/// `typ` must be 1, 2 or 3 and `buf` must hold at least `i + 1` values of
+/// that type.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let buf = [0x05, 0x00, 0xff, 0xff, 0x80, 0x00];
+/// // SAFETY: 3 int16 values
+/// let values: Vec<i32> = (0..3).map(|i| unsafe { typed_int_unchecked(2, &buf, i) }).collect();
+/// assert_eq!(values, [5, -1, 128]);
+/// ```
+#[cfg(feature = "unchecked")]
+#[inline]
+pub unsafe fn typed_int_unchecked(typ: u8, buf: &[u8], i: usize) -> i32 {
+    // SAFETY: guaranteed by the caller
+    unsafe {
+        let p = buf.as_ptr();
+        match typ {
+            1 => *p.add(i) as i8 as i32,
+            2 => i16::from_le((p.add(2 * i) as *const i16).read_unaligned()) as i32,
+            _ => i32::from_le((p.add(4 * i) as *const i32).read_unaligned()),
         }
     }
 }
@@ -638,283 +1872,4279 @@ impl<'r> Iterator for NumericValueIter<'r> {
 /// - typ: data type byte
 /// - n: total number of elements to iterate
 /// - buffer: the bytes buffer  
-pub fn iter_typed_integers(typ: u8, n: usize, buffer: &[u8]) -> NumericValueIter {
+pub fn iter_typed_integers(typ: u8, n: usize, buffer: &[u8]) -> NumericValueIter<'_> {
     NumericValueIter {
-        reader: std::io::Cursor::new(buffer),
+        buf: buffer,
         typ,
         len: n,
         cur: 0,
     }
 }
 
-/// Read a typed string from the reader to a Rust String
-pub fn read_typed_string<R>(reader: &mut R, buffer: &mut Vec<u8>) -> usize
-where
-    R: std::io::Read + ReadBytesExt,
-{
-    let (typ, n) = read_typed_descriptor_bytes(reader);
-    assert_eq!(typ, 0x7);
-    let s = buffer.len();
-    buffer.resize(s + n, b'\0');
-    reader.read(&mut buffer.as_mut_slice()[s..s + n]).unwrap();
-    n
+/// Values of an INFO/FORMAT field reshaped according to the `Number=`
+/// attribute declared in the header.
+///
+/// For `Number=A`, `Number=R` and `Number=G` fields, each group holds the value
+/// for one ALT allele, one allele (REF included) or one genotype, respectively.
+/// For other fields (fixed `Number=n` or `Number=.`), all values of a sample
+/// are held in a single group.
+///
+/// For INFO fields, there is only one "sample" (at index 0).
+///
+/// Example:
+#[cfg_attr(feature = "format", doc = "```")]
+#[cfg_attr(not(feature = "format"), doc = "```ignore")]
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let ad_key = header.get_idx_from_dictionary_str("FORMAT", "AD").unwrap();
+/// let pgt_key = header.get_idx_from_dictionary_str("FORMAT", "PGT").unwrap();
+/// let mut record = Record::default();
+/// record.read(&mut f).unwrap();
+/// let ad = record.fmt_field_grouped(&header, ad_key).unwrap();
+/// // out-of-range indices give no values
+/// assert_eq!(ad.group(0, ad.n_group()).count(), 0);
+/// assert_eq!(ad.sample(ad.n_sample()).count(), 0);
+/// assert_eq!(GroupedValues::default().group(0, 0).count(), 0);
+/// // string fields cannot be grouped
+/// while record.fmt_layout().all(|l| l.key != pgt_key) {
+///     record.read(&mut f).unwrap();
+/// }
+/// let err = record.fmt_field_grouped(&header, pgt_key).unwrap_err();
+/// assert!(matches!(err, Error::UnexpectedType { found: 7, .. }));
+/// ```
+#[derive(Debug, Default)]
+pub struct GroupedValues<'r> {
+    typ: u8,
+    buf: &'r [u8],
+    n_sample: usize,
+    n_group: usize,
+    group_size: usize,
 }
 
-/// read the header lines to a String
-/// use Header::from_string(text) to convert the string into structured data
-pub fn read_header<R>(reader: &mut R) -> String
-where
-    R: std::io::Read + ReadBytesExt,
-{
-    // read magic
-    let mut magic = [0u8; 3];
-    reader.read_exact(&mut magic).unwrap();
-    assert_eq!(&magic, b"BCF");
+impl<'r> GroupedValues<'r> {
+    /// Number of samples (1 for INFO fields)
+    pub fn n_sample(&self) -> usize {
+        self.n_sample
+    }
 
-    // read major verion and minor version
-    let major = reader.read_u8().unwrap();
-    let minor = reader.read_u8().unwrap();
-    assert_eq!(major, 2);
-    assert_eq!(minor, 2);
+    /// Number of groups per sample
+    pub fn n_group(&self) -> usize {
+        self.n_group
+    }
 
-    // read text length
-    let l_length = reader.read_u32::<LittleEndian>().unwrap();
-    let mut text = vec![0u8; l_length as usize];
-    reader.read_exact(&mut text).unwrap();
+    /// Number of values in each group
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
 
-    String::from_utf8(text).unwrap()
+    /// Iterate over the values of the `group`-th group of the `sample`-th sample.
+    ///
+    /// The iterator is empty if `sample` or `group` is out of range.
+    pub fn group(&self, sample: usize, group: usize) -> NumericValueIter<'r> {
+        if sample >= self.n_sample || group >= self.n_group {
+            return iter_typed_integers(self.typ, 0, &[]);
+        }
+        self.values(sample * self.n_group + group, self.group_size)
+    }
+
+    /// Iterate over all values of the `sample`-th sample.
+    ///
+    /// The iterator is empty if `sample` is out of range.
+    pub fn sample(&self, sample: usize) -> NumericValueIter<'r> {
+        if sample >= self.n_sample {
+            return iter_typed_integers(self.typ, 0, &[]);
+        }
+        self.values(sample, self.n_group * self.group_size)
+    }
+
+    /// Iterate over the `i`-th chunk of `n` values
+    fn values(&self, i: usize, n: usize) -> NumericValueIter<'r> {
+        let width = typ_width(self.typ);
+        let s = i * n * width;
+        iter_typed_integers(self.typ, n, &self.buf[s..s + n * width])
+    }
 }
 
-/// Represents a record (a line or a site) in BCF file
-#[derive(Default, Debug)]
-pub struct Record {
-    buf_shared: Vec<u8>,
-    buf_indiv: Vec<u8>,
-    chrom: i32,
-    pos: i32,
-    rlen: i32,
-    qual: NumericValue,
-    n_info: u16,
-    n_allele: u16,
-    n_sample: u32,
-    n_fmt: u8,
-    id: Range<usize>,
-    alleles: Vec<Range<usize>>,
-    /// (typ, n, byte_range)
-    filters: (u8, usize, Range<usize>),
-    /// (info_key, typ, n, byte_range)
-    info: Vec<(usize, u8, usize, Range<usize>)>,
-    /// (fmt_key, typ, n, byte_range)
-    gt: Vec<(usize, u8, usize, Range<usize>)>,
+/// Describe how the BCF2 type `typ` of a field with `n` values does not match
+/// the `Type=` of its header line, if it does not
+fn type_mismatch(
+    dictionary: &str,
+    id: &str,
+    ty: &header::Type,
+    typ: u8,
+    n: usize,
+) -> Option<String> {
+    let expected = match ty {
+        // genotypes are encoded as integers
+        _ if dictionary == "FORMAT" && id == "GT" => matches!(typ, 1..=3),
+        header::Type::Integer => matches!(typ, 1..=3),
+        header::Type::Float => typ == 5,
+        header::Type::String | header::Type::Character => typ == 7,
+        header::Type::Flag => matches!(typ, 1..=3) && n <= 1,
+        header::Type::Other(_) => true,
+    };
+    // fields without values may use any type
+    (!expected && typ != 0 && n != 0)
+        .then(|| format!("{dictionary}/{id} is declared as {ty} but encoded as BCF2 type {typ}"))
 }
-impl Record {
-    /// read a record (copy bytes from the reader to the record's interval
-    /// buffers), and separate fields
-    pub fn read<R>(&mut self, reader: &mut R) -> Result<(), Box<dyn std::error::Error>>
-    where
-        R: std::io::Read + ReadBytesExt,
-    {
-        let l_shared;
-        let l_indv;
-        l_shared = match reader.read_u32::<LittleEndian>() {
-            Ok(x) => x,
-            Err(_x) => Err(_x)?,
+
+/// Check that a field with `n` values of type `typ` can be viewed as
+/// [`GroupedValues`]: its values, if any, are integers or floats
+#[cfg(any(feature = "info", feature = "format"))]
+fn check_groupable(typ: u8, n: usize) -> error::Result<()> {
+    match typ {
+        1 | 2 | 3 | 5 => Ok(()),
+        _ if n == 0 => Ok(()),
+        _ => Err(Error::UnexpectedType {
+            expected: "numeric values",
+            found: typ,
+            descriptor: descriptor_byte(typ, n),
+        }),
+    }
+}
+
+/// Work out `(n_group, group_size)` for a field with `n` values per sample
+/// based on its header `Number=` and `Type=` attributes, checking that `n` is
+/// consistent with the number of alleles of the record.
+fn group_values_by_number(
+    dictionary: &str,
+    (id, number, ty): (&str, &header::Number, &header::Type),
+    n_allele: usize,
+    n: usize,
+) -> error::Result<(usize, usize)> {
+    use header::Number;
+    // strings are stored as bytes, which do not reflect `Number=`
+    if matches!(ty, header::Type::String | header::Type::Character) || n == 0 {
+        return Ok((1, n));
+    }
+    let expected = match number {
+        Number::A => Some(n_allele.saturating_sub(1)),
+        Number::R => Some(n_allele),
+        Number::G => {
+            // the ploidy is not known from the header: accept the number of
+            // genotypes, C(n_allele + ploidy - 1, ploidy), of any ploidy
+            let mut n_genotype = 1;
+            let mut ploidy = 0;
+            while n_genotype < n && n_allele > 1 {
+                ploidy += 1;
+                n_genotype = n_genotype * (n_allele + ploidy - 1) / ploidy;
+            }
+            Some(n_genotype)
+        }
+        Number::Fixed(x) => Some(*x),
+        // ploidy and local alleles are not known from the record alone
+        Number::Dot | Number::Other(_) => None,
+        Number::P | Number::LA | Number::LR | Number::LG => None,
+    };
+    match (number, expected) {
+        (_, Some(expected)) if expected != n => Err(Error::MalformedRecord(format!(
+            "{}/{} declared as Number={} expects {} values but the record has {} (n_allele={})",
+            dictionary, id, number, expected, n, n_allele
+        ))),
+        (
+            Number::A | Number::R | Number::G | Number::P | Number::LA | Number::LR | Number::LG,
+            _,
+        ) => Ok((n, 1)),
+        _ => Ok((1, n)),
+    }
+}
+
+/// Get the `i`-th value of a buffer of typed integers (`typ` being 1, 2 or 3)
+/// without going through [`NumericValue`]. Returns `None` for the
+/// missing-value and end-of-vector sentinels.
+#[inline]
+fn raw_int_at(typ: u8, buf: &[u8], i: usize) -> Option<u32> {
+    match typ {
+        1 => match buf[i] {
+            0x80 | 0x81 => None,
+            x => Some(x as u32),
+        },
+        2 => match u16::from_le_bytes([buf[2 * i], buf[2 * i + 1]]) {
+            0x8000 | 0x8001 => None,
+            x => Some(x as u32),
+        },
+        3 => match u32::from_le_bytes([buf[4 * i], buf[4 * i + 1], buf[4 * i + 2], buf[4 * i + 3]])
+        {
+            0x80000000 | 0x80000001 => None,
+            x => Some(x),
+        },
+        _ => None,
+    }
+}
+
+/// Decode int8 GT values into allele indices (see
+/// [`Record::decode_gt_into`]). On x86_64, blocks of 16 values go through
+/// SSE2, which is part of the baseline of that target; the remaining values
+/// and other targets use the scalar loop.
+fn decode_gt_int8(buf: &[u8], out: &mut [i32]) {
+    let n = buf.len().min(out.len());
+    let (buf, out) = (&buf[..n], &mut out[..n]);
+    #[cfg(target_arch = "x86_64")]
+    let done = {
+        let done = n - n % 16;
+        for (b, o) in buf[..done]
+            .chunks_exact(16)
+            .zip(out[..done].chunks_exact_mut(16))
+        {
+            decode_gt_int8_sse2(b.try_into().unwrap(), o.try_into().unwrap());
+        }
+        done
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    let done = 0;
+    for (o, &b) in out[done..].iter_mut().zip(&buf[done..]) {
+        *o = match b {
+            0x80 => -1,
+            0x81 => -2,
+            _ => ((b >> 1) as i32) - 1,
         };
-        l_indv = reader.read_u32::<LittleEndian>()?;
-        // dbg!(l_shared, l_indv);
-        self.buf_shared.resize(l_shared as usize, 0u8);
-        self.buf_indiv.resize(l_indv as usize, 0u8);
-        reader.read_exact(self.buf_shared.as_mut_slice()).unwrap();
-        reader.read_exact(self.buf_indiv.as_mut_slice()).unwrap();
-        self.parse_shared();
-        self.parse_indv();
-        // dbg!(self.pos);
-        Ok(())
     }
-    /// parse shared fields
-    fn parse_shared(&mut self) {
-        let mut reader = std::io::Cursor::new(self.buf_shared.as_slice());
-        self.chrom = reader.read_i32::<LittleEndian>().unwrap();
-        self.pos = reader.read_i32::<LittleEndian>().unwrap();
-        self.rlen = reader.read_i32::<LittleEndian>().unwrap();
-        let qual_u32 = reader.read_u32::<LittleEndian>().unwrap();
-        self.qual = NumericValue::from(qual_u32).as_f32();
-        self.n_info = reader.read_u16::<LittleEndian>().unwrap();
-        self.n_allele = reader.read_u16::<LittleEndian>().unwrap();
-        let combined = reader.read_u32::<LittleEndian>().unwrap();
-        self.n_sample = combined & 0xffffff;
-        self.n_fmt = (combined >> 24) as u8;
-        // id
-        let (typ, n) = read_typed_descriptor_bytes(&mut reader);
-        assert_eq!(typ, 0x7);
-        let cur = reader.position() as usize;
-        self.id = cur..cur + n as usize;
-        reader.seek(std::io::SeekFrom::Current(n as i64)).unwrap();
-        // alleles
-        self.alleles.clear();
-        for _ in 0..self.n_allele {
-            let (typ, n) = read_typed_descriptor_bytes(&mut reader);
-            assert_eq!(typ, 0x7);
-            let cur = reader.position() as usize;
-            self.alleles.push(cur..cur + n as usize);
-            reader.seek(std::io::SeekFrom::Current(n as i64)).unwrap();
+}
+
+/// Branch-free SSE2 version of the scalar loop in [`decode_gt_int8`] for 16
+/// values.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn decode_gt_int8_sse2(buf: &[u8; 16], out: &mut [i32; 16]) {
+    use std::arch::x86_64::*;
+    // SAFETY: SSE2 is always available on x86_64, and the unaligned loads
+    // and stores stay within the two fixed-size arrays.
+    unsafe {
+        let b = _mm_loadu_si128(buf.as_ptr() as *const __m128i);
+        // allele index (b >> 1) - 1, as a signed byte in -1..=126
+        let allele = _mm_sub_epi8(
+            _mm_and_si128(_mm_srli_epi16(b, 1), _mm_set1_epi8(0x7f)),
+            _mm_set1_epi8(1),
+        );
+        // 0x80 (missing) becomes -1 and 0x81 (end of vector) becomes -2
+        let is_sentinel =
+            _mm_cmpeq_epi8(_mm_and_si128(b, _mm_set1_epi8(-2)), _mm_set1_epi8(i8::MIN));
+        let sentinel = _mm_sub_epi8(_mm_set1_epi8(-1), _mm_and_si128(b, _mm_set1_epi8(1)));
+        let v = _mm_or_si128(
+            _mm_and_si128(is_sentinel, sentinel),
+            _mm_andnot_si128(is_sentinel, allele),
+        );
+        // sign-extend the 16 bytes to 16 i32s
+        let sign = _mm_cmpgt_epi8(_mm_setzero_si128(), v);
+        let halves = [_mm_unpacklo_epi8(v, sign), _mm_unpackhi_epi8(v, sign)];
+        let dst = out.as_mut_ptr() as *mut __m128i;
+        for (i, half) in halves.into_iter().enumerate() {
+            let sign = _mm_srai_epi16(half, 15);
+            _mm_storeu_si128(dst.add(2 * i), _mm_unpacklo_epi16(half, sign));
+            _mm_storeu_si128(dst.add(2 * i + 1), _mm_unpackhi_epi16(half, sign));
         }
-        //filters
-        let (typ, n) = read_typed_descriptor_bytes(&mut reader);
-        let width: usize = bcf2_typ_width(typ);
-        let s = reader.position() as usize;
-        let e = s + width * n as usize;
-        reader
-            .seek(std::io::SeekFrom::Current((e - s) as i64))
-            .unwrap();
-        self.filters = (typ, n as usize, s..e);
-        // infos
-        self.info.clear();
-        for _idx in 0..(self.n_info as usize) {
-            let info_key = read_single_typed_integer(&mut reader);
-            let (typ, n) = read_typed_descriptor_bytes(&mut reader);
-            let width = bcf2_typ_width(typ);
-            let s = reader.position() as usize;
-            let e = s + width * n as usize;
-            reader
-                .seek(std::io::SeekFrom::Current((e - s) as i64))
-                .unwrap();
-            self.info.push((info_key as usize, typ, n as usize, s..e));
-        }
-    }
-    /// parse indiv fields, complicated field will need further processing
-    fn parse_indv(&mut self) {
-        let mut reader = std::io::Cursor::new(self.buf_indiv.as_slice());
-        self.gt.clear();
-        for _idx in 0..(self.n_fmt as usize) {
-            let fmt_key = read_single_typed_integer(&mut reader);
-            let (typ, n) = read_typed_descriptor_bytes(&mut reader);
-            let width = bcf2_typ_width(typ);
-            let s = reader.position() as usize;
-            let e = s + width * self.n_sample as usize * n as usize;
-            reader
-                .seek(std::io::SeekFrom::Current((e - s) as i64))
-                .unwrap();
-            self.gt.push((fmt_key as usize, typ, n as usize, s..e));
+    }
+}
+
+/// A genotype reduced to one of four classes, with the 2-bit codes of PLINK 1
+/// `.bed` files (where the first allele, A1, is the ALT allele)
+///
+/// See [`Record::pack_hardcalls`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Hardcall {
+    /// only ALT alleles
+    HomAlt = 0b00,
+    /// a missing allele, or no allele at all
+    Missing = 0b01,
+    /// REF and ALT alleles
+    Het = 0b10,
+    /// only REF alleles
+    HomRef = 0b11,
+}
+
+impl Hardcall {
+    /// The hardcall from its 2-bit code (only the lowest two bits are used)
+    pub fn from_code(code: u8) -> Self {
+        match code & 0b11 {
+            0b00 => Hardcall::HomAlt,
+            0b01 => Hardcall::Missing,
+            0b10 => Hardcall::Het,
+            _ => Hardcall::HomRef,
         }
     }
 
-    /// get chromosome offset
-    /// Example:
-    /// ```
-    /// use bcf_reader::*;
-    /// use std::io::Write;
-    /// // read data generated by bcftools
-    /// // bcftools query -f '%CHROM\n' test.bcf | bgzip -c > test_chrom.gz
-    /// let mut chrom_str = String::new();
-    /// smart_reader("testdata/test_chrom.gz")
-    ///     .read_to_string(&mut chrom_str)
-    ///     .unwrap();
-    /// // read data via bcf-reader
-    /// let mut f = smart_reader("testdata/test.bcf");
-    /// let s = read_header(&mut f);
-    /// let header = Header::from_string(&s);
-    /// let mut record = Record::default();
-    /// let mut chrom_str2 = Vec::<u8>::new();
-    /// while let Ok(_) = record.read(&mut f) {
-    ///     write!(
-    ///         chrom_str2,
-    ///         "{}\n",
-    ///         header.get_chrname(record.chrom() as usize)
-    ///     )
-    ///     .unwrap();
-    /// }
-    /// let chrom_str2 = String::from_utf8(chrom_str2).unwrap();
-    /// // compare bcftools results and bcf-reader results
-    /// assert_eq!(chrom_str, chrom_str2);
-    /// ```
-    pub fn chrom(&self) -> i32 {
-        self.chrom
+    /// The hardcall of sample `i` in packed data written by
+    /// [`Record::pack_hardcalls`]
+    pub fn unpack(packed: &[u8], i: usize) -> Self {
+        Self::from_code(packed[i / 4] >> (2 * (i % 4)))
     }
+}
 
-    /// Returns the reference length of the record.
-    pub fn rlen(&self) -> i32 {
-        self.rlen
+/// A compact bit vector with one bit per sample.
+///
+/// Bits are stored in `u64` words, with sample `i` at bit `i % 64` of word
+/// `i / 64`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SampleMask {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl SampleMask {
+    /// Create a mask of `len` bits all set to `value`
+    pub fn new(len: usize, value: bool) -> Self {
+        let fill = if value { u64::MAX } else { 0 };
+        let mut mask = Self {
+            words: vec![fill; len.div_ceil(64)],
+            len,
+        };
+        mask.clear_unused_bits();
+        mask
     }
 
-    /// Returns the quality score of the record, if available
-    pub fn qual(&self) -> Option<f32> {
-        self.qual.float_val()
+    fn clear_unused_bits(&mut self) {
+        if !self.len.is_multiple_of(64) {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1u64 << (self.len % 64)) - 1;
+            }
+        }
     }
 
-    pub fn n_allele(&self) -> u16 {
-        self.n_allele
+    /// Number of bits (samples) in the mask
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the mask has no bits
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the bit for the `i`-th sample. Panics if `i` is out of range.
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "sample index out of range");
+        (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    /// Set the bit for the `i`-th sample. Panics if `i` is out of range.
+    pub fn set(&mut self, i: usize, value: bool) {
+        assert!(i < self.len, "sample index out of range");
+        if value {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    /// Number of bits set
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Iterate over the bits, one `bool` per sample
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// The underlying `u64` words
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+/// Allele counts of a record computed from its genotypes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AlleleCounts {
+    counts: Vec<u32>,
+    an: u32,
+}
+
+impl AlleleCounts {
+    /// Count of each allele, REF included (indexed by allele index)
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Count of each ALT allele, as in INFO/AC
+    pub fn ac(&self) -> &[u32] {
+        self.counts.get(1..).unwrap_or(&[])
+    }
+
+    /// Total number of called alleles, as in INFO/AN
+    pub fn an(&self) -> u32 {
+        self.an
+    }
+
+    /// Frequency of each allele, REF included. Returns `None` if no allele
+    /// is called (AN is 0).
+    pub fn freqs(&self) -> Option<Vec<f32>> {
+        if self.an == 0 {
+            return None;
+        }
+        Some(
+            self.counts
+                .iter()
+                .map(|c| *c as f32 / self.an as f32)
+                .collect(),
+        )
+    }
+
+    /// Frequency of each ALT allele, as in INFO/AF. Returns `None` if no
+    /// allele is called (AN is 0).
+    pub fn af(&self) -> Option<Vec<f32>> {
+        if self.an == 0 {
+            return None;
+        }
+        Some(
+            self.ac()
+                .iter()
+                .map(|c| *c as f32 / self.an as f32)
+                .collect(),
+        )
+    }
+
+    /// Minor allele frequency, computed as given by `mode` (see
+    /// [`MafMode`]). For biallelic sites both modes give `min(p, 1 - p)`.
+    /// Returns `None` if no allele is called (AN is 0), or if the allele of
+    /// [`MafMode::Allele`] is not an allele of the site.
+    pub fn maf(&self, mode: MafMode) -> Option<f32> {
+        if self.an == 0 {
+            return None;
+        }
+        let count = match mode {
+            MafMode::Minor => {
+                let mut observed = self.counts.iter().filter(|&&c| c > 0);
+                let first = *observed.next()?;
+                // a monomorphic site has no minor allele
+                observed.min().map_or(0, |&c| c.min(first))
+            }
+            MafMode::Allele(i) => {
+                let c = *self.counts.get(i)?;
+                c.min(self.an - c)
+            }
+        };
+        Some(count as f32 / self.an as f32)
+    }
+}
+
+/// How [`AlleleCounts::maf`] and [`Record::maf`] fold the allele
+/// frequencies of a site into a minor allele frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MafMode {
+    /// the frequency of the least frequent allele among the observed ones
+    /// (REF included), or 0 if only one allele is observed; ALT alleles
+    /// that no sample carries are ignored
+    #[default]
+    Minor,
+    /// `min(p, 1 - p)` for the frequency `p` of the allele at this index
+    /// (0 for REF)
+    Allele(usize),
+}
+
+/// Number of samples in each genotype class of a record
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GenotypeClassCounts {
+    /// samples with all alleles being REF
+    pub hom_ref: u32,
+    /// samples carrying different alleles
+    pub het: u32,
+    /// samples with all alleles being the same ALT allele
+    pub hom_alt: u32,
+    /// samples with at least one missing allele (or no allele at all)
+    pub missing: u32,
+}
+
+impl GenotypeClassCounts {
+    /// Number of samples with a non-missing genotype
+    pub fn n_called(&self) -> u32 {
+        self.hom_ref + self.het + self.hom_alt
+    }
+
+    /// Exact test of Hardy-Weinberg equilibrium for a biallelic site with
+    /// diploid genotypes (Wigginton, Cutler and Abecasis, 2005).
+    ///
+    /// Returns `None` if no genotype is called.
+    ///
+    /// # Examples
+    /// ```
+    /// use bcf_reader::GenotypeClassCounts;
+    /// let counts = GenotypeClassCounts { hom_ref: 25, het: 50, hom_alt: 25, missing: 0 };
+    /// assert!(counts.hwe_pvalue().unwrap() > 0.9);
+    /// let counts = GenotypeClassCounts { hom_ref: 50, het: 0, hom_alt: 50, missing: 0 };
+    /// assert!(counts.hwe_pvalue().unwrap() < 1e-20);
+    /// let counts = GenotypeClassCounts { hom_ref: 100, het: 0, hom_alt: 0, missing: 3 };
+    /// assert_eq!(counts.hwe_pvalue(), Some(1.0));
+    /// ```
+    pub fn hwe_pvalue(&self) -> Option<f64> {
+        let obs_hets = self.het as usize;
+        let obs_homc = self.hom_ref.max(self.hom_alt) as usize;
+        let obs_homr = self.hom_ref.min(self.hom_alt) as usize;
+        let rare_copies = 2 * obs_homr + obs_hets;
+        let genotypes = obs_hets + obs_homc + obs_homr;
+        if genotypes == 0 {
+            return None;
+        }
+
+        let mut het_probs = vec![0f64; rare_copies + 1];
+        // start at the mid point, with the same parity as rare_copies
+        let mut mid = rare_copies * (2 * genotypes - rare_copies) / (2 * genotypes);
+        if (rare_copies & 1) ^ (mid & 1) != 0 {
+            mid += 1;
+        }
+        het_probs[mid] = 1.0;
+        let mut sum = het_probs[mid];
+
+        // fewer heterozygotes than the mid point
+        let mut curr_homr = ((rare_copies - mid) / 2) as f64;
+        let mut curr_homc = (genotypes - mid) as f64 - curr_homr;
+        let mut curr_hets = mid;
+        while curr_hets > 1 {
+            let h = curr_hets as f64;
+            het_probs[curr_hets - 2] = het_probs[curr_hets] * h * (h - 1.0)
+                / (4.0 * (curr_homr + 1.0) * (curr_homc + 1.0));
+            sum += het_probs[curr_hets - 2];
+            curr_homr += 1.0;
+            curr_homc += 1.0;
+            curr_hets -= 2;
+        }
+
+        // more heterozygotes than the mid point
+        let mut curr_homr = ((rare_copies - mid) / 2) as f64;
+        let mut curr_homc = (genotypes - mid) as f64 - curr_homr;
+        let mut curr_hets = mid;
+        while curr_hets + 2 <= rare_copies {
+            let h = curr_hets as f64;
+            het_probs[curr_hets + 2] =
+                het_probs[curr_hets] * 4.0 * curr_homr * curr_homc / ((h + 2.0) * (h + 1.0));
+            sum += het_probs[curr_hets + 2];
+            curr_homr -= 1.0;
+            curr_homc -= 1.0;
+            curr_hets += 2;
+        }
+
+        // sum the probabilities of configurations at most as likely as observed
+        let p_obs = het_probs[obs_hets];
+        let p_hwe: f64 = het_probs.iter().filter(|p| **p <= p_obs).sum::<f64>() / sum;
+        Some(p_hwe.min(1.0))
+    }
+}
+
+/// Content of the rows of a genotype matrix, see [`SampleMajorGenotypes`]
+/// and `matrix::read_genotype_matrix` (with the `ndarray` feature)
+///
+/// The samples are those of the header, in order: for records read with a
+/// sample selection (see [`FieldSelection::samples`]), the rows of the
+/// samples that were not kept are `-1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenotypeLayout {
+    /// one row per sample, with the number of non-reference alleles of its
+    /// genotype, or `-1` if any allele is missing. Absent alleles (e.g. of a
+    /// haploid call in a diploid field) are ignored.
+    Dosage,
+    /// `ploidy` rows per sample, with the allele indices of its genotype:
+    /// row `i * ploidy + j` holds allele `j` of sample `i`. Missing alleles
+    /// are `-1` and absent alleles `-2`, as in
+    /// [`Record::decode_gt_into`]. Sites with a smaller ploidy are padded
+    /// with `-2`; a larger ploidy is an error.
+    Alleles {
+        /// number of alleles per sample
+        ploidy: usize,
+    },
+}
+
+impl GenotypeLayout {
+    /// Number of rows for `n_sample` samples
+    pub fn n_rows(&self, n_sample: usize) -> usize {
+        match *self {
+            GenotypeLayout::Dosage => n_sample,
+            GenotypeLayout::Alleles { ploidy } => n_sample * ploidy,
+        }
+    }
+}
+
+/// Write the genotypes of a record as one value per row of `layout` to
+/// `column`, using `gts` as scratch space. Without a FORMAT/GT field, all
+/// values are `-1`. The rows are those of the samples of the header, so the
+/// rows of samples not kept when reading the record are `-1`.
+pub(crate) fn genotype_column(
+    record: &Record,
+    header: &Header,
+    layout: GenotypeLayout,
+    gts: &mut Vec<i32>,
+    column: &mut [i8],
+) -> error::Result<()> {
+    let width = record.gt_field(header).map_or(0, |(_, ploidy, _)| ploidy);
+    gts.resize(record.n_sample as usize * width, 0);
+    let ploidy = record.decode_gt_into(header, gts)?;
+    column.fill(-1);
+    if ploidy == 0 {
+        return Ok(());
+    }
+    // header column of sample `i` of the record
+    let row = |i: usize| record.sample_columns().map_or(i, |c| c[i]);
+    match layout {
+        GenotypeLayout::Dosage => {
+            for (i, gt) in gts.chunks_exact(ploidy).enumerate() {
+                let Some(v) = column.get_mut(row(i)) else {
+                    break;
+                };
+                *v = if gt.contains(&-1) || gt.iter().all(|&a| a == -2) {
+                    -1
+                } else {
+                    gt.iter().filter(|&&a| a > 0).count().min(i8::MAX as usize) as i8
+                };
+            }
+        }
+        GenotypeLayout::Alleles { ploidy: width } => {
+            if ploidy > width {
+                return Err(Error::UnexpectedCount {
+                    expected: width,
+                    found: ploidy,
+                });
+            }
+            for (i, gt) in gts.chunks_exact(ploidy).enumerate() {
+                let Some(v) = column.get_mut(row(i) * width..(row(i) + 1) * width) else {
+                    break;
+                };
+                v.fill(-2);
+                for (v, &allele) in v.iter_mut().zip(gt) {
+                    *v = i8::try_from(allele).map_err(|_| {
+                        Error::MalformedRecord(format!(
+                            "allele index {allele} does not fit in an i8"
+                        ))
+                    })?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Genotypes of a window of sites in sample-major order, i.e. with the
+/// values of each sample (row of the [`GenotypeLayout`]) contiguous across
+/// sites, as needed by PCA or kinship computations.
+///
+/// Sites are added one at a time with [`push`](Self::push) or from a reader
+/// with [`fill_from`](Self::fill_from), up to the window size given to
+/// [`new`](Self::new); [`clear`](Self::clear) starts the next window
+/// without reallocating.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+/// let header = reader.read_header();
+/// let n_sample = header.get_samples().len();
+/// let mut window = SampleMajorGenotypes::new(n_sample, GenotypeLayout::Dosage, 50);
+/// let mut n_windows = 0;
+/// let mut n_sites = 0;
+/// loop {
+///     window.clear();
+///     let n = window.fill_from(&mut reader, &header).unwrap();
+///     if n == 0 {
+///         break;
+///     }
+///     // dosages of the first sample at the sites of the window
+///     assert_eq!(window.row(0).len(), n);
+///     n_windows += 1;
+///     n_sites += n;
+/// }
+/// assert_eq!((n_windows, n_sites), (3, 127));
+///
+/// // the same values as the genotypes of the records
+/// let mut f = smart_reader("testdata/test.bcf");
+/// read_header(&mut f).unwrap();
+/// let mut record = Record::default();
+/// window.clear();
+/// let mut gts = vec![0; n_sample * 2];
+/// for _ in 0..10 {
+///     record.read(&mut f).unwrap();
+///     assert!(window.push(&record, &header).unwrap());
+///     record.decode_gt_into(&header, &mut gts).unwrap();
+///     let site = window.n_sites() - 1;
+///     for (sample, gt) in gts.chunks(2).enumerate() {
+///         let expected = match gt {
+///             [-1, _] | [_, -1] => -1,
+///             _ => gt.iter().filter(|&&a| a > 0).count() as i8,
+///         };
+///         assert_eq!(window.row(sample)[site], expected);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SampleMajorGenotypes {
+    layout: GenotypeLayout,
+    n_rows: usize,
+    window: usize,
+    n_sites: usize,
+    /// row `r` is stored at `values[r * window..(r + 1) * window]`
+    values: Vec<i8>,
+    column: Vec<i8>,
+    gts: Vec<i32>,
+}
+
+impl SampleMajorGenotypes {
+    /// Create an empty window of up to `window` sites for `n_sample` samples
+    pub fn new(n_sample: usize, layout: GenotypeLayout, window: usize) -> Self {
+        let n_rows = layout.n_rows(n_sample);
+        SampleMajorGenotypes {
+            layout,
+            n_rows,
+            window,
+            n_sites: 0,
+            values: vec![-1; n_rows * window],
+            column: vec![-1; n_rows],
+            gts: vec![],
+        }
+    }
+
+    /// The layout of the rows
+    pub fn layout(&self) -> GenotypeLayout {
+        self.layout
+    }
+
+    /// Number of rows (samples, or alleles of samples)
+    pub fn n_rows(&self) -> usize {
+        self.n_rows
+    }
+
+    /// Number of sites in the window
+    pub fn n_sites(&self) -> usize {
+        self.n_sites
+    }
+
+    /// Whether the window has no room for another site
+    pub fn is_full(&self) -> bool {
+        self.n_sites == self.window
+    }
+
+    /// Remove all sites from the window
+    pub fn clear(&mut self) {
+        self.n_sites = 0;
+    }
+
+    /// The values of row `r` at the sites of the window
+    ///
+    /// Panics if `r` is not less than [`n_rows`](Self::n_rows).
+    pub fn row(&self, r: usize) -> &[i8] {
+        assert!(r < self.n_rows, "row {r} out of range");
+        &self.values[r * self.window..r * self.window + self.n_sites]
+    }
+
+    /// Iterate over the rows, see [`row`](Self::row)
+    pub fn rows(&self) -> impl Iterator<Item = &[i8]> + '_ {
+        (0..self.n_rows).map(|r| self.row(r))
+    }
+
+    /// Add the genotypes of `record` as the next site. Returns `Ok(false)`
+    /// without adding the site if the window is full.
+    ///
+    /// See [`GenotypeLayout`] for the errors of the `Alleles` layout.
+    pub fn push(&mut self, record: &Record, header: &Header) -> error::Result<bool> {
+        if self.is_full() {
+            return Ok(false);
+        }
+        genotype_column(record, header, self.layout, &mut self.gts, &mut self.column)?;
+        let site = self.n_sites;
+        for (row, &v) in self.values.chunks_exact_mut(self.window).zip(&self.column) {
+            row[site] = v;
+        }
+        self.n_sites += 1;
+        Ok(true)
+    }
+
+    /// Read records from `reader` until the window is full or there are no
+    /// more records, and return the number of sites added
+    pub fn fill_from<T: ReadRecord>(
+        &mut self,
+        reader: &mut T,
+        header: &Header,
+    ) -> error::Result<usize> {
+        let mut record = Record::default();
+        let start = self.n_sites;
+        while !self.is_full() && reader.try_read_record(&mut record)?.is_some() {
+            self.push(&record, header)?;
+        }
+        Ok(self.n_sites - start)
+    }
+}
+
+/// Signed integer value of a BCF2 integer of type `typ` (1, 2 or 3) from its
+/// little-endian bytes, or `None` for the missing and end-of-vector values
+#[cfg(feature = "format")]
+fn raw_signed_int(typ: u8, bytes: &[u8]) -> Option<i32> {
+    match typ {
+        1 => match bytes[0] {
+            0x80 | 0x81 => None,
+            x => Some(x as i8 as i32),
+        },
+        2 => match u16::from_le_bytes([bytes[0], bytes[1]]) {
+            0x8000 | 0x8001 => None,
+            x => Some(x as i16 as i32),
+        },
+        _ => match u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+            0x80000000 | 0x80000001 => None,
+            x => Some(x as i32),
+        },
+    }
+}
+
+/// Type of the values of a [`FormatColumn`], see [`Record::fmt_field_into`]
+#[cfg(feature = "format")]
+pub trait ColumnValue: Copy {
+    /// value of missing values, also used to pad vectors shorter than the
+    /// column width
+    const MISSING: Self;
+    /// the kind of BCF2 values that can be converted, for error messages
+    const EXPECTED: &'static str;
+    /// Whether values of BCF2 type `typ` can be converted
+    fn accepts(typ: u8) -> bool;
+    /// Convert a value of an accepted BCF2 type `typ` from its little-endian
+    /// bytes, or return `None` for the missing and end-of-vector values
+    fn from_bytes(typ: u8, bytes: &[u8]) -> Option<Self>;
+}
+
+/// Integer fields, with `i32::MIN` for missing values
+#[cfg(feature = "format")]
+impl ColumnValue for i32 {
+    const MISSING: Self = i32::MIN;
+    const EXPECTED: &'static str = "an integer";
+
+    fn accepts(typ: u8) -> bool {
+        matches!(typ, 1..=3)
+    }
+
+    fn from_bytes(typ: u8, bytes: &[u8]) -> Option<Self> {
+        raw_signed_int(typ, bytes)
+    }
+}
+
+/// Float and integer fields, with NaN for missing values
+#[cfg(feature = "format")]
+impl ColumnValue for f32 {
+    const MISSING: Self = f32::NAN;
+    const EXPECTED: &'static str = "a numeric value";
+
+    fn accepts(typ: u8) -> bool {
+        matches!(typ, 1..=3 | 5)
+    }
+
+    fn from_bytes(typ: u8, bytes: &[u8]) -> Option<Self> {
+        match typ {
+            5 => match u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+                0x7F800001 | 0x7F800002 => None,
+                x => Some(f32::from_bits(x)),
+            },
+            _ => raw_signed_int(typ, bytes).map(|x| x as f32),
+        }
+    }
+}
+
+/// Values of one FORMAT field for all samples across records, in a single
+/// buffer: the values of sample `s` of record `r` are at
+/// `values()[(r * n_samples() + s) * width()..][..width()]`.
+///
+/// Returned by [`BcfReader::read_format_column`] and
+/// [`IndexedBcfReader::read_format_column`].
+#[cfg(feature = "format")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatColumn<T> {
+    values: Vec<T>,
+    n_records: usize,
+    n_samples: usize,
+    width: usize,
+}
+
+#[cfg(feature = "format")]
+impl<T> FormatColumn<T> {
+    /// All values, record after record
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Take the values, see [`values`](Self::values)
+    pub fn into_values(self) -> Vec<T> {
+        self.values
+    }
+
+    /// Number of records
+    pub fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// Number of samples per record
+    pub fn n_samples(&self) -> usize {
+        self.n_samples
+    }
+
+    /// Number of values per sample
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The values of the `r`-th record
+    pub fn record(&self, r: usize) -> &[T] {
+        let n = self.n_samples * self.width;
+        &self.values[r * n..(r + 1) * n]
+    }
+}
+
+/// Compute the allele counts of the remaining records of `reader` and pass
+/// them to `f` with the record
+fn scan_allele_counts<R, F>(reader: &mut R, header: &Header, mut f: F) -> error::Result<usize>
+where
+    R: ReadRecord + ?Sized,
+    F: FnMut(&Record, &AlleleCounts),
+{
+    let mut record = Record::default();
+    let mut counts = AlleleCounts::default();
+    let mut n = 0;
+    while reader.try_read_record(&mut record)?.is_some() {
+        record.allele_counts_into(header, &mut counts);
+        f(&record, &counts);
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Read the FORMAT field `tag` of up to `max_records` records into a
+/// [`FormatColumn`]
+#[cfg(feature = "format")]
+fn read_format_column<T, R>(
+    reader: &mut R,
+    header: &Header,
+    tag: &str,
+    width: usize,
+    max_records: Option<usize>,
+) -> error::Result<FormatColumn<T>>
+where
+    T: ColumnValue,
+    R: ReadRecord + ?Sized,
+{
+    let key = header
+        .get_idx_from_dictionary_str("FORMAT", tag)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("FORMAT/{tag} is not defined in the header"),
+            )
+        })?;
+    let mut column = FormatColumn {
+        values: vec![],
+        n_records: 0,
+        n_samples: header.get_samples().len(),
+        width,
+    };
+    let mut record = Record::default();
+    while max_records.is_none_or(|n| column.n_records < n)
+        && reader.try_read_record(&mut record)?.is_some()
+    {
+        record.fmt_field_into(key, width, &mut column.values)?;
+        column.n_samples = record.n_sample as usize;
+        column.n_records += 1;
+    }
+    Ok(column)
+}
+
+/// One allele of a genotype call
+#[cfg(feature = "strings")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenotypeAllele {
+    /// allele index, `None` for a missing allele (`.`)
+    pub allele: Option<u32>,
+    /// whether the allele is phased with the previous one (always `false` for
+    /// the first allele)
+    pub phased: bool,
+}
+
+/// An owned, decoded value of an INFO field or of a FORMAT field for one sample
+#[cfg(feature = "strings")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// INFO flag (present)
+    Flag,
+    /// integer values, `None` for missing values
+    Integer(Vec<Option<i32>>),
+    /// float values, `None` for missing values
+    Float(Vec<Option<f32>>),
+    /// string value
+    String(String),
+    /// FORMAT/GT genotype call
+    Genotype(Vec<GenotypeAllele>),
+}
+
+/// Decode `n` typed values from `buf` into an owned [`FieldValue`]; trailing
+/// end-of-vector values are dropped.
+#[cfg(feature = "strings")]
+fn decode_field_value(typ: u8, n: usize, buf: &[u8], is_flag: bool) -> FieldValue {
+    let values = || iter_typed_integers(typ, n, buf).take_while(|v| !v.is_end_of_vector());
+    match typ {
+        _ if is_flag => FieldValue::Flag,
+        0 => FieldValue::Flag,
+        0x7 => {
+            let end = buf.iter().position(|c| *c == b'\0').unwrap_or(buf.len());
+            FieldValue::String(String::from_utf8_lossy(&buf[..end]).into_owned())
+        }
+        0x5 => FieldValue::Float(values().map(|v| v.float_val()).collect()),
+        _ => FieldValue::Integer(values().map(|v| v.signed_int_val()).collect()),
+    }
+}
+
+/// Decode the GT values of one sample into an owned [`FieldValue::Genotype`]
+#[cfg(feature = "strings")]
+fn decode_genotype(typ: u8, n: usize, buf: &[u8]) -> FieldValue {
+    let gt = iter_typed_integers(typ, n, buf)
+        .map(|v| v.gt_val())
+        .take_while(|(noploidy, ..)| !noploidy)
+        .enumerate()
+        .map(|(i, (_, dot, phased, allele))| GenotypeAllele {
+            allele: if dot { None } else { Some(allele) },
+            phased: i > 0 && phased,
+        })
+        .collect();
+    FieldValue::Genotype(gt)
+}
+
+/// A fully-owned, self-describing representation of a [`Record`], detached
+/// from the internal buffers of the record and from the header.
+///
+/// See [`Record::to_owned_variant`].
+#[cfg(feature = "strings")]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VariantRecord {
+    /// chromosome name
+    pub chrom: String,
+    /// 0-based position
+    pub pos: i32,
+    /// reference length
+    pub rlen: i32,
+    /// ID column (`.` if missing)
+    pub id: String,
+    /// alleles, REF first
+    pub alleles: Vec<String>,
+    /// quality score, if available
+    pub qual: Option<f32>,
+    /// names of the filters
+    pub filters: Vec<String>,
+    /// INFO fields (tag, value) in record order
+    pub info: Vec<(String, FieldValue)>,
+    /// FORMAT fields (tag, one value per sample) in record order
+    pub format: Vec<(String, Vec<FieldValue>)>,
+}
+
+#[cfg(feature = "strings")]
+impl VariantRecord {
+    /// Get the value of an INFO field by tag
+    pub fn info(&self, tag: &str) -> Option<&FieldValue> {
+        self.info.iter().find(|(k, _)| k == tag).map(|(_, v)| v)
+    }
+
+    /// Get the per-sample values of a FORMAT field by tag
+    pub fn format(&self, tag: &str) -> Option<&[FieldValue]> {
+        self.format
+            .iter()
+            .find(|(k, _)| k == tag)
+            .map(|(_, v)| v.as_slice())
+    }
+}
+
+/// A difference between two records reported by [`VariantRecord::diff`]
+#[cfg(feature = "strings")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordDiff {
+    /// CHROM differs
+    Chrom,
+    /// POS differs
+    Pos,
+    /// reference length differs
+    Rlen,
+    /// ID differs
+    Id,
+    /// REF/ALT alleles differ
+    Alleles,
+    /// QUAL differs
+    Qual,
+    /// the set of filters differs
+    Filters,
+    /// the INFO field with this tag differs or is only in one of the records
+    Info(String),
+    /// the FORMAT field with this tag differs or is only in one of the records
+    Format(String),
+}
+
+#[cfg(feature = "strings")]
+impl VariantRecord {
+    /// List the columns and tags that differ between two records.
+    ///
+    /// Fields are compared by name, not by dictionary index, so records from
+    /// files with different headers can be compared. The order of filters,
+    /// INFO fields and FORMAT fields does not matter.
+    pub fn diff(&self, other: &VariantRecord) -> Vec<RecordDiff> {
+        let mut diffs = vec![];
+        if self.chrom != other.chrom {
+            diffs.push(RecordDiff::Chrom);
+        }
+        if self.pos != other.pos {
+            diffs.push(RecordDiff::Pos);
+        }
+        if self.rlen != other.rlen {
+            diffs.push(RecordDiff::Rlen);
+        }
+        if self.id != other.id {
+            diffs.push(RecordDiff::Id);
+        }
+        if self.alleles != other.alleles {
+            diffs.push(RecordDiff::Alleles);
+        }
+        if self.qual != other.qual {
+            diffs.push(RecordDiff::Qual);
+        }
+        let mut f1: Vec<_> = self.filters.iter().collect();
+        let mut f2: Vec<_> = other.filters.iter().collect();
+        f1.sort();
+        f2.sort();
+        if f1 != f2 {
+            diffs.push(RecordDiff::Filters);
+        }
+        for (tag, value) in self.info.iter() {
+            if other.info(tag) != Some(value) {
+                diffs.push(RecordDiff::Info(tag.clone()));
+            }
+        }
+        for (tag, _) in other.info.iter() {
+            if self.info(tag).is_none() {
+                diffs.push(RecordDiff::Info(tag.clone()));
+            }
+        }
+        for (tag, values) in self.format.iter() {
+            if other.format(tag) != Some(values.as_slice()) {
+                diffs.push(RecordDiff::Format(tag.clone()));
+            }
+        }
+        for (tag, _) in other.format.iter() {
+            if self.format(tag).is_none() {
+                diffs.push(RecordDiff::Format(tag.clone()));
+            }
+        }
+        diffs
+    }
+}
+
+/// Format a float the way htslib does (like `%g`, with 6 significant digits)
+#[cfg(feature = "strings")]
+fn format_float(f: f32) -> String {
+    if f == 0.0 {
+        return "0".into();
+    }
+    if !f.is_finite() {
+        return match f {
+            f if f.is_nan() => "nan".into(),
+            f if f > 0.0 => "inf".into(),
+            _ => "-inf".into(),
+        };
+    }
+    let trim_zeros = |s: &str| -> String {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    // rounding to 6 significant digits can change the exponent, so get the
+    // exponent from the rounded scientific representation
+    let sci = format!("{:.5e}", f as f64);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if !(-4..6).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exp.abs())
+    } else {
+        trim_zeros(&format!("{:.*}", (5 - exp) as usize, f as f64))
+    }
+}
+
+#[cfg(feature = "strings")]
+impl std::fmt::Display for FieldValue {
+    /// Write the value as in a VCF file
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_list<T>(
+            f: &mut std::fmt::Formatter<'_>,
+            values: &[Option<T>],
+            fmt_one: impl Fn(&T) -> String,
+        ) -> std::fmt::Result {
+            if values.is_empty() {
+                return write!(f, ".");
+            }
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                match v {
+                    None => write!(f, ".")?,
+                    Some(v) => write!(f, "{}", fmt_one(v))?,
+                }
+            }
+            Ok(())
+        }
+        match self {
+            FieldValue::Flag => Ok(()),
+            FieldValue::Integer(v) => write_list(f, v, |x| x.to_string()),
+            FieldValue::Float(v) => write_list(f, v, |x| format_float(*x)),
+            FieldValue::String(s) if s.is_empty() => write!(f, "."),
+            FieldValue::String(s) => write!(f, "{s}"),
+            FieldValue::Genotype(alleles) if alleles.is_empty() => write!(f, "."),
+            FieldValue::Genotype(alleles) => {
+                for (i, a) in alleles.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", if a.phased { '|' } else { '/' })?;
+                    }
+                    match a.allele {
+                        None => write!(f, ".")?,
+                        Some(x) => write!(f, "{x}")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "strings")]
+impl std::fmt::Display for VariantRecord {
+    /// Write the record as a VCF line (without the trailing newline)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\t{}\t{}\t", self.chrom, self.pos + 1, self.id)?;
+        match self.alleles.first() {
+            None => write!(f, ".\t.")?,
+            Some(r) if self.alleles.len() == 1 => write!(f, "{r}\t.")?,
+            Some(r) => write!(f, "{}\t{}", r, self.alleles[1..].join(","))?,
+        }
+        match self.qual {
+            None => write!(f, "\t.")?,
+            Some(q) => write!(f, "\t{}", format_float(q))?,
+        }
+        if self.filters.is_empty() {
+            write!(f, "\t.")?;
+        } else {
+            write!(f, "\t{}", self.filters.join(";"))?;
+        }
+        if self.info.is_empty() {
+            write!(f, "\t.")?;
+        }
+        for (i, (tag, value)) in self.info.iter().enumerate() {
+            let sep = if i == 0 { '\t' } else { ';' };
+            match value {
+                FieldValue::Flag => write!(f, "{sep}{tag}")?,
+                value => write!(f, "{sep}{tag}={value}")?,
+            }
+        }
+        if self.format.is_empty() {
+            return Ok(());
+        }
+        let tags: Vec<&str> = self.format.iter().map(|(k, _)| k.as_str()).collect();
+        write!(f, "\t{}", tags.join(":"))?;
+        let n_sample = self.format[0].1.len();
+        for i in 0..n_sample {
+            for (j, (_, values)) in self.format.iter().enumerate() {
+                let sep = if j == 0 { '\t' } else { ':' };
+                write!(f, "{sep}{}", values[i])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `s` as a JSON string literal
+#[cfg(feature = "strings")]
+fn write_json_str(out: &mut String, s: &str) {
+    use std::fmt::Write;
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(feature = "strings")]
+impl FieldValue {
+    /// Write the value as JSON: `true` for flags, arrays of numbers (`null`
+    /// for missing values) for numeric fields, and strings for string fields
+    /// and genotypes (as in VCF, e.g. `"0|1"`).
+    fn write_json(&self, out: &mut String) {
+        fn write_list<T>(out: &mut String, values: &[Option<T>], fmt_one: impl Fn(&T) -> String) {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match v {
+                    None => out.push_str("null"),
+                    Some(v) => out.push_str(&fmt_one(v)),
+                }
+            }
+            out.push(']');
+        }
+        match self {
+            FieldValue::Flag => out.push_str("true"),
+            FieldValue::Integer(v) => write_list(out, v, |x| x.to_string()),
+            FieldValue::Float(v) => write_list(out, v, |x| match x.is_finite() {
+                true => format_float(*x),
+                false => "null".into(),
+            }),
+            FieldValue::String(s) => write_json_str(out, s),
+            gt @ FieldValue::Genotype(_) => write_json_str(out, &gt.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "strings")]
+impl VariantRecord {
+    /// Serialize the record as a single-line JSON object:
+    ///
+    /// `{"chrom":..,"pos":..,"id":..,"ref":..,"alt":[..],"qual":..,
+    /// "filters":[..],"info":{tag:value,..},"samples":{name:{tag:value,..},..}}`
+    ///
+    /// `pos` is 1-based as in VCF, `id` and `qual` are `null` when missing.
+    /// `samples` gives the sample names, in the order of the FORMAT values.
+    /// See [`FieldValue`] for how values are written.
+    ///
+    /// Strings are escaped as required by JSON: quotes, backslashes and
+    /// control characters are escaped, and non-ASCII text is kept as UTF-8.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let record = VariantRecord {
+    ///     chrom: "chr1".into(),
+    ///     id: "rs\"1\"\\\t\u{1}\u{1f}".into(),
+    ///     alleles: vec!["A".into(), "C".into()],
+    ///     info: vec![("NOTE".into(), FieldValue::String("café\n5′→3′ 😀".into()))],
+    ///     format: vec![("GT".into(), vec![FieldValue::Genotype(vec![
+    ///         GenotypeAllele { allele: Some(0), phased: false },
+    ///         GenotypeAllele { allele: Some(1), phased: true },
+    ///     ])])],
+    ///     ..Default::default()
+    /// };
+    /// let json = record.to_json(&["échantillon_α".into()]);
+    /// assert!(json.contains(r#""id":"rs\"1\"\\\t\u0001\u001f""#));
+    /// assert!(json.contains(r#""NOTE":"café\n5′→3′ 😀""#));
+    /// assert!(!json.contains('\n'));
+    ///
+    /// // the output is valid JSON with the same strings
+    /// let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(value["id"], "rs\"1\"\\\t\u{1}\u{1f}");
+    /// assert_eq!(value["info"]["NOTE"], "café\n5′→3′ 😀");
+    /// assert_eq!(value["samples"]["échantillon_α"]["GT"], "0|1");
+    /// ```
+    pub fn to_json(&self, samples: &[String]) -> String {
+        let mut out = String::new();
+        out.push_str("{\"chrom\":");
+        write_json_str(&mut out, &self.chrom);
+        out.push_str(&format!(",\"pos\":{},\"id\":", self.pos + 1));
+        match self.id.as_str() {
+            "." => out.push_str("null"),
+            id => write_json_str(&mut out, id),
+        }
+        out.push_str(",\"ref\":");
+        match self.alleles.first() {
+            None => out.push_str("null"),
+            Some(r) => write_json_str(&mut out, r),
+        }
+        out.push_str(",\"alt\":[");
+        for (i, a) in self.alleles.iter().skip(1).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_str(&mut out, a);
+        }
+        out.push_str("],\"qual\":");
+        match self.qual {
+            Some(q) if q.is_finite() => out.push_str(&format_float(q)),
+            _ => out.push_str("null"),
+        }
+        out.push_str(",\"filters\":[");
+        for (i, f) in self.filters.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_str(&mut out, f);
+        }
+        out.push_str("],\"info\":{");
+        for (i, (tag, value)) in self.info.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_str(&mut out, tag);
+            out.push(':');
+            value.write_json(&mut out);
+        }
+        out.push_str("},\"samples\":{");
+        for (i, name) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_str(&mut out, name);
+            out.push_str(":{");
+            for (j, (tag, values)) in self.format.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                write_json_str(&mut out, tag);
+                out.push(':');
+                match values.get(i) {
+                    None => out.push_str("null"),
+                    Some(v) => v.write_json(&mut out),
+                }
+            }
+            out.push('}');
+        }
+        out.push_str("}}");
+        out
+    }
+}
+
+/// Writes records as newline-delimited JSON (one [`Record::to_json`] object
+/// per line).
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut writer = JsonlWriter::new(Vec::<u8>::new());
+/// let mut record = Record::default();
+/// while let Ok(_) = record.read(&mut f) {
+///     writer.write_record(&record, &header).unwrap();
+/// }
+/// let out = String::from_utf8(writer.into_inner()).unwrap();
+/// assert_eq!(out.lines().count(), 127);
+/// assert!(out.lines().all(|l| l.starts_with("{\"chrom\":\"Pf3D7_") && l.ends_with("}}")));
+/// ```
+#[cfg(feature = "strings")]
+pub struct JsonlWriter<W> {
+    inner: W,
+}
+
+#[cfg(feature = "strings")]
+impl<W> JsonlWriter<W>
+where
+    W: std::io::Write,
+{
+    /// Create a writer writing JSON lines to `inner`
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Write a record as a JSON line
+    pub fn write_record(&mut self, record: &Record, header: &Header) -> error::Result<()> {
+        let json = record.to_json(header)?;
+        self.inner.write_all(json.as_bytes())?;
+        self.inner.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Get back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Displays a [`Record`] as a VCF line; see [`Record::display`].
+#[cfg(feature = "strings")]
+pub struct RecordDisplay<'a> {
+    record: &'a Record,
+    header: &'a Header,
+}
+
+#[cfg(feature = "strings")]
+impl std::fmt::Display for RecordDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // an error would make `to_string` panic; see `Record::to_vcf_line`
+        match self.record.to_owned_variant(self.header) {
+            Ok(v) => write!(f, "{v}"),
+            Err(e) => write!(f, "<invalid record: {e}>"),
+        }
+    }
+}
+
+/// Read a typed string from the reader, appending its bytes to `buffer`, and
+/// return its length
+pub fn read_typed_string<R>(reader: &mut R, buffer: &mut Vec<u8>) -> error::Result<usize>
+where
+    R: std::io::Read + ReadBytesExt,
+{
+    let (typ, n) = read_typed_descriptor_bytes(reader)?;
+    if typ != 0x7 {
+        return Err(Error::UnexpectedType {
+            expected: "a string",
+            found: typ,
+            descriptor: descriptor_byte(typ, n),
+        });
+    }
+    let s = buffer.len();
+    let read = reader.take(n as u64).read_to_end(buffer)?;
+    if read < n {
+        buffer.truncate(s);
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(n)
+}
+
+/// BCF format version from the magic bytes of a file (e.g. 2.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BcfVersion {
+    /// major version
+    pub major: u8,
+    /// minor version
+    pub minor: u8,
+}
+
+impl std::fmt::Display for BcfVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Which BCF versions are accepted when reading a header
+///
+/// By default, BCF 2.1 and 2.2 are supported, and other minor versions of
+/// BCF 2 are accepted with a warning. The records of BCF 2.1 files are
+/// converted to the BCF 2.2 encoding by the readers; use
+/// [`VersionPolicy::strict`] to only accept BCF 2.2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionPolicy {
+    /// versions accepted without warning
+    pub supported: Vec<BcfVersion>,
+    /// whether other minor versions of a supported major version are
+    /// accepted (with a warning) rather than rejected
+    pub accept_unknown_minor: bool,
+}
+
+impl Default for VersionPolicy {
+    fn default() -> Self {
+        Self {
+            supported: vec![
+                BcfVersion { major: 2, minor: 1 },
+                BcfVersion { major: 2, minor: 2 },
+            ],
+            accept_unknown_minor: true,
+        }
+    }
+}
+
+impl VersionPolicy {
+    /// Only accept BCF 2.2
+    pub fn strict() -> Self {
+        Self {
+            supported: vec![BcfVersion { major: 2, minor: 2 }],
+            accept_unknown_minor: false,
+        }
+    }
+
+    /// Check a version against the policy. Returns a warning message if the
+    /// version is accepted but not supported, and an
+    /// [`Error::InvalidHeader`] if it is rejected.
+    pub fn check(&self, version: BcfVersion) -> error::Result<Option<String>> {
+        if self.supported.contains(&version) {
+            return Ok(None);
+        }
+        let known_major = self.supported.iter().any(|v| v.major == version.major);
+        if known_major && self.accept_unknown_minor {
+            return Ok(Some(format!(
+                "BCF version {version} is not supported, reading it as BCF {}",
+                version.major
+            )));
+        }
+        Err(Error::InvalidHeader(format!(
+            "unsupported BCF version {version}"
+        )))
+    }
+}
+
+/// Whether the values of a vector (of an INFO field, or of a sample for a
+/// FORMAT field) have values other than end-of-vector padding after the
+/// end of the vector
+fn has_bad_padding(typ: u8, values: &[u8]) -> bool {
+    let width = match typ {
+        // strings are padded with NUL bytes
+        0x7 => return values.iter().skip_while(|&&b| b != 0).any(|&b| b != 0),
+        0x1 => 1,
+        0x2 => 2,
+        0x3 | 0x5 => 4,
+        _ => return false,
+    };
+    let mut values = values.chunks_exact(width).map(|x| match *x {
+        [x] => NumericValue::U8(x),
+        [a, b] => NumericValue::U16(u16::from_le_bytes([a, b])),
+        [a, b, c, d] if typ == 0x5 => NumericValue::F32(u32::from_le_bytes([a, b, c, d])),
+        [a, b, c, d] => NumericValue::U32(u32::from_le_bytes([a, b, c, d])),
+        _ => unreachable!(),
+    });
+    values.any(|v| v.is_end_of_vector()) && values.any(|v| !v.is_end_of_vector())
+}
+
+/// Whether records use the BCF 2.1 encoding (see
+/// [`Record::upgrade_bcf21_vectors`])
+fn is_bcf21(version: Option<BcfVersion>) -> bool {
+    version == Some(BcfVersion { major: 2, minor: 1 })
+}
+
+/// The header of a BCF file as read by [`read_header_with_policy`], before
+/// parsing into a [`Header`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawHeader {
+    /// BCF version from the magic bytes
+    pub version: BcfVersion,
+    /// header text, without the NUL terminator and padding
+    pub text: String,
+    /// warnings from the version check and about the header text
+    pub warnings: Vec<String>,
+    /// why the header text is not valid UTF-8, if it is not; invalid
+    /// sequences are then replaced by U+FFFD in `text`
+    pub utf8_error: Option<std::str::Utf8Error>,
+}
+
+/// Guess what the data is from its first bytes, for the error message when
+/// they are not the BCF magic bytes
+fn describe_magic(magic: &[u8]) -> String {
+    match magic {
+        [0x1f, 0x8b, ..] => "the data looks gzip (BGZF) compressed; decompress it first, \
+                             e.g. with `smart_reader` or `ParMultiGzipReader`"
+            .into(),
+        [b'#', b'#', ..] | [b'#', b'C', ..] => {
+            "the data looks like a text VCF file; convert it with `bcftools view -Ob`".into()
+        }
+        b"CRA" => "the data looks like a CRAM file".into(),
+        b"BAM" => "the data looks like a decompressed BAM file".into(),
+        b"CSI" => "the data looks like a decompressed CSI index".into(),
+        _ => format!("unexpected magic bytes {magic:02x?}"),
+    }
+}
+
+/// Largest header text length (`l_text`) accepted by
+/// [`read_header_with_policy`]; larger values are taken as a sign of a
+/// corrupted file rather than allocated
+pub const MAX_HEADER_TEXT_LENGTH: u32 = 1 << 30;
+
+/// read the header lines to a String
+/// use Header::from_string(text) to convert the string into structured data
+///
+/// BCF versions are checked with the default [`VersionPolicy`]; use
+/// [`read_header_with_policy`] to get the version or to change the policy.
+///
+/// Empty data is an [`Error::EmptyInput`]. Data with only a header is not an
+/// error: reading a record then returns an `UnexpectedEof` error without
+/// consuming any data, as at the end of any file.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// // a zero-byte file
+/// let path = std::env::temp_dir().join("bcf_reader_read_header_doctest.bcf");
+/// std::fs::write(&path, b"").unwrap();
+/// let err = read_header(&mut smart_reader(&path)).unwrap_err();
+/// std::fs::remove_file(&path).unwrap();
+/// assert!(matches!(err, Error::EmptyInput));
+///
+/// // a BGZF file with only the end-of-file block
+/// let bgzf = std::fs::read("testdata/test.bcf").unwrap();
+/// let eof_block = &bgzf[bgzf.len() - 28..];
+/// let mut reader = ParMultiGzipReader::from_reader(eof_block, 3, None, None);
+/// assert!(matches!(read_header(&mut reader), Err(Error::EmptyInput)));
+///
+/// // a file with only a header
+/// let text = "##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n";
+/// let mut data = b"BCF\x02\x02".to_vec();
+/// data.extend((text.len() as u32 + 1).to_le_bytes());
+/// data.extend(text.as_bytes());
+/// data.push(0);
+/// let mut reader = BcfReader::from_reader(data.as_slice());
+/// let header = reader.try_read_header().unwrap();
+/// assert!(header.get_samples().is_empty());
+/// let mut record = Record::default();
+/// match reader.read_record(&mut record) {
+///     Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+///     other => panic!("unexpected result {other:?}"),
+/// }
+/// assert_eq!(reader.offset(), data.len() as u64);
+/// ```
+pub fn read_header<R>(reader: &mut R) -> error::Result<String>
+where
+    R: std::io::Read + ReadBytesExt,
+{
+    Ok(read_header_with_policy(reader, &VersionPolicy::default())?.text)
+}
+
+/// Read the magic bytes, the BCF version and the header text, checking the
+/// version against `policy`.
+///
+/// The header text is cut at the first NUL byte (the terminator, possibly
+/// followed by padding). An error is returned if `l_text` is larger than
+/// [`MAX_HEADER_TEXT_LENGTH`] or than the remaining data; invalid UTF-8 is
+/// replaced with U+FFFD and reported in the warnings.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let raw = read_header_with_policy(&mut f, &VersionPolicy::strict()).unwrap();
+/// assert_eq!(raw.version, BcfVersion { major: 2, minor: 2 });
+/// assert!(raw.warnings.is_empty());
+/// let header = Header::from_string(&raw.text);
+/// assert_eq!(header.fileformat(), Some("VCFv4.2"));
+/// assert_eq!(header.vcf_version(), Some((4, 2)));
+///
+/// // BCF 2.1 is accepted by default, but not by the strict policy
+/// let policy = VersionPolicy::default();
+/// assert_eq!(policy.check(BcfVersion { major: 2, minor: 1 }).unwrap(), None);
+/// assert!(policy.check(BcfVersion { major: 2, minor: 3 }).unwrap().is_some());
+/// assert!(policy.check(BcfVersion { major: 3, minor: 0 }).is_err());
+/// assert!(VersionPolicy::strict().check(BcfVersion { major: 2, minor: 1 }).is_err());
+///
+/// // padding after the NUL terminator is dropped
+/// let mut data = b"BCF\x02\x02".to_vec();
+/// let text = b"##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0\0\0";
+/// data.extend((text.len() as u32).to_le_bytes());
+/// data.extend(text);
+/// let raw = read_header_with_policy(&mut data.as_slice(), &VersionPolicy::default()).unwrap();
+/// assert!(raw.text.ends_with("INFO\n"));
+/// assert!(raw.warnings.is_empty());
+/// // other bytes are reported
+/// let mut padded = b"BCF\x02\x02".to_vec();
+/// padded.extend(5u32.to_le_bytes());
+/// padded.extend(b"#\n\0xy");
+/// let raw = read_header_with_policy(&mut padded.as_slice(), &VersionPolicy::default()).unwrap();
+/// assert_eq!(raw.text, "#\n");
+/// assert_eq!(raw.warnings, ["header text has bytes other than NUL after its terminator"]);
+///
+/// // truncated or absurdly long header text
+/// let truncated = &data[..data.len() - 10];
+/// let err = read_header_with_policy(&mut &truncated[..], &VersionPolicy::default()).unwrap_err();
+/// assert!(err.is_truncation());
+/// let mut data = b"BCF\x02\x02".to_vec();
+/// data.extend(u32::MAX.to_le_bytes());
+/// let err = read_header_with_policy(&mut data.as_slice(), &VersionPolicy::default()).unwrap_err();
+/// assert!(matches!(err, Error::InvalidHeader(_)));
+///
+/// // other formats are recognized in the error message
+/// let compressed = std::fs::read("testdata/test.bcf").unwrap();
+/// let err = read_header_with_policy(&mut compressed.as_slice(), &VersionPolicy::default())
+///     .unwrap_err();
+/// assert!(err.to_string().contains("gzip (BGZF) compressed"));
+/// let vcf = b"##fileformat=VCFv4.2\n";
+/// let err = read_header_with_policy(&mut &vcf[..], &VersionPolicy::default()).unwrap_err();
+/// assert!(err.to_string().contains("text VCF file"));
+/// ```
+pub fn read_header_with_policy<R>(
+    reader: &mut R,
+    policy: &VersionPolicy,
+) -> error::Result<RawHeader>
+where
+    R: std::io::Read + ReadBytesExt,
+{
+    read_header_limited(reader, policy, MAX_HEADER_TEXT_LENGTH)
+}
+
+/// [`read_header_with_policy`] with a maximum header text length
+fn read_header_limited<R>(
+    reader: &mut R,
+    policy: &VersionPolicy,
+    max_length: u32,
+) -> error::Result<RawHeader>
+where
+    R: std::io::Read + ReadBytesExt,
+{
+    // read magic
+    let mut magic = vec![];
+    reader.take(3).read_to_end(&mut magic)?;
+    if magic.is_empty() {
+        return Err(Error::EmptyInput);
+    }
+    if magic.len() < 3 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("the data ends after {} bytes", magic.len()),
+        )
+        .into());
+    }
+    if magic != b"BCF" {
+        let message = format!("not a BCF file: {}", describe_magic(&magic));
+        return Err(Error::InvalidHeader(message));
+    }
+
+    // read major verion and minor version
+    let version = BcfVersion {
+        major: reader.read_u8()?,
+        minor: reader.read_u8()?,
+    };
+    let mut warnings: Vec<String> = policy.check(version)?.into_iter().collect();
+
+    // read text length
+    let l_length = reader.read_u32::<LittleEndian>()?;
+    if l_length > max_length {
+        return Err(Error::InvalidHeader(format!(
+            "header text length {l_length} is larger than {max_length} bytes; \
+             the file is probably corrupted"
+        )));
+    }
+    // grow the buffer while reading instead of trusting l_length
+    let mut text = vec![];
+    reader.take(l_length as u64).read_to_end(&mut text)?;
+    if text.len() < l_length as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "header text is truncated: expected {l_length} bytes, found {}",
+                text.len()
+            ),
+        )
+        .into());
+    }
+
+    // drop the NUL terminator and any padding
+    match text.iter().position(|&b| b == 0) {
+        Some(end) => {
+            if text[end..].iter().any(|&b| b != 0) {
+                warnings.push("header text has bytes other than NUL after its terminator".into());
+            }
+            text.truncate(end);
+        }
+        None => warnings.push("header text is not NUL-terminated".into()),
+    }
+    let mut utf8_error = None;
+    let text = match String::from_utf8(text) {
+        Ok(text) => text,
+        Err(e) => {
+            warnings.push(header_utf8_warning(e.utf8_error()));
+            utf8_error = Some(e.utf8_error());
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    };
+    Ok(RawHeader {
+        version,
+        text,
+        warnings,
+        utf8_error,
+    })
+}
+
+/// A field skipped by the accessors of a record (see [`Record::dropped_fields`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedField {
+    /// `INFO` or `FORMAT`
+    pub dictionary: &'static str,
+    /// dictionary key (IDX) of the field
+    pub key: usize,
+    /// BCF2 type code of the values
+    pub typ: u8,
+}
+
+/// (key, typ, n, byte_range) of a field in a buffer
+type RawFieldLayout = (usize, u8, usize, Range<usize>);
+
+/// Iterator over the layout of consecutive INFO or FORMAT fields in a buffer,
+/// yielding (key, typ, n, byte_range) for each field.
+struct FieldLayoutIter<'r> {
+    buf: &'r [u8],
+    /// offset of the next field in `buf`
+    pos: usize,
+    remaining: usize,
+    n_sample: usize,
+}
+
+impl FieldLayoutIter<'_> {
+    /// Locate the next field, checking that it fits in the buffer
+    fn try_next(&mut self) -> error::Result<Option<RawFieldLayout>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let len = self.buf.len();
+        let mut rest = self.buf.get(self.pos..).unwrap_or_default();
+        let key = read_single_typed_integer(&mut rest)?;
+        let (typ, n) = read_typed_descriptor_bytes(&mut rest)?;
+        let width = field_width(typ)?;
+        let s = len - rest.len();
+        let e = width
+            .checked_mul(self.n_sample)
+            .and_then(|x| x.checked_mul(n))
+            .and_then(|x| x.checked_add(s))
+            .filter(|&e| e <= len)
+            .ok_or_else(|| {
+                Error::MalformedRecord(format!(
+                    "field with key {key} of {n} values of type {typ} for {} samples \
+                     does not fit in the {len} byte buffer",
+                    self.n_sample,
+                ))
+            })?;
+        self.pos = e;
+        Ok(Some((key as usize, typ, n, s..e)))
+    }
+}
+
+impl Iterator for FieldLayoutIter<'_> {
+    type Item = RawFieldLayout;
+    /// Locate the next field, skipping the fields with 64-bit values (see
+    /// [`Record::dropped_fields`]). The fields of a record are checked when
+    /// it is read (see [`Record::check_fields`]), so `try_next` does not fail
+    /// here.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.try_next().ok().flatten()? {
+                (_, BCF_BT_INT64, _, _) => continue,
+                field => return Some(field),
+            }
+        }
+    }
+}
+
+/// Location of a field in a record buffer (see [`Record::info_layout`] and
+/// [`Record::fmt_layout`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// dictionary key (IDX) of the field
+    pub key: usize,
+    /// BCF2 type of the values
+    pub typ: u8,
+    /// number of values (per sample for FORMAT fields)
+    pub n: usize,
+    /// byte range of the values in the buffer
+    pub range: Range<usize>,
+}
+
+impl FieldLayout {
+    /// Width in bytes of a single value
+    ///
+    /// Panics if `typ` was changed to a code that is not a BCF2 type; see
+    /// [`try_bcf2_typ_width`].
+    pub fn width(&self) -> usize {
+        typ_width(self.typ)
+    }
+}
+
+impl From<(usize, u8, usize, Range<usize>)> for FieldLayout {
+    fn from((key, typ, n, range): (usize, u8, usize, Range<usize>)) -> Self {
+        Self { key, typ, n, range }
+    }
+}
+
+/// Selection of the fields to keep when reading records, so that only the
+/// requested INFO/FORMAT tags (and samples, see [`FieldSelection::samples`])
+/// are located and decoded.
+///
+/// By default, all fields and samples are kept. See [`Record::read_selected`] and
+/// [`BcfReader::set_field_selection`].
+///
+/// # Example
+#[cfg_attr(all(feature = "info", feature = "format"), doc = "```")]
+#[cfg_attr(not(all(feature = "info", feature = "format")), doc = "```ignore")]
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let selection = FieldSelection::default()
+///     .parse_info(false)
+///     .parse_formats(&header, &["GT", "DP"]);
+/// let ad_key = header.get_idx_from_dictionary_str("FORMAT", "AD").unwrap();
+/// let dp_key = header.get_idx_from_dictionary_str("FORMAT", "DP").unwrap();
+/// let an_key = header.get_idx_from_dictionary_str("INFO", "AN").unwrap();
+/// let mut record = Record::default();
+/// while let Ok(_) = record.read_selected(&mut f, &selection) {
+///     assert_eq!(record.fmt_gt(&header).count(), 2 * header.get_samples().len());
+///     assert_eq!(record.fmt_field(dp_key).count(), header.get_samples().len());
+///     assert_eq!(record.fmt_field(ad_key).count(), 0);
+///     assert_eq!(record.info_field_numeric(an_key).count(), 0);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FieldSelection {
+    info: bool,
+    /// keys of FORMAT fields to keep (`None` to keep all)
+    fmt_keys: Option<Vec<usize>>,
+    /// sorted columns of the samples to keep (`None` to keep all), shared
+    /// with the records read with the selection
+    samples: Option<Arc<[usize]>>,
+}
+
+impl Default for FieldSelection {
+    fn default() -> Self {
+        Self {
+            info: true,
+            fmt_keys: None,
+            samples: None,
+        }
+    }
+}
+
+impl FieldSelection {
+    /// Whether to keep INFO fields
+    pub fn parse_info(mut self, yes: bool) -> Self {
+        self.info = yes;
+        self
+    }
+
+    /// Keep only the FORMAT fields with the given tags. Tags not defined in
+    /// the header are ignored; an empty list of tags skips all sample-level
+    /// data.
+    pub fn parse_formats(mut self, header: &Header, tags: &[&str]) -> Self {
+        let keys = tags
+            .iter()
+            .filter_map(|tag| header.get_idx_from_dictionary_str("FORMAT", tag))
+            .collect();
+        self.fmt_keys = Some(keys);
+        self
+    }
+
+    /// This selection narrowed to the FORMAT field `key`, without INFO
+    /// fields
+    fn only_format(selection: Option<&FieldSelection>, key: usize) -> Self {
+        FieldSelection {
+            info: false,
+            fmt_keys: Some(vec![key]),
+            samples: selection.and_then(|s| s.samples.clone()),
+        }
+    }
+
+    /// Keep all FORMAT fields
+    pub fn parse_all_formats(mut self) -> Self {
+        self.fmt_keys = None;
+        self
+    }
+
+    /// Keep only the FORMAT data of the samples at the given columns
+    /// (0-based). Columns out of range are ignored.
+    ///
+    /// The kept samples stay in file order, so the `i`-th sample of a
+    /// record is the sample at column `sample_columns().unwrap()[i]` of the
+    /// header. The record keeps this mapping (see [`Record::sample_columns`]
+    /// and [`Record::sample_names`]): the genotype and FORMAT accessors of
+    /// the record, which index samples from 0 (e.g.
+    /// [`Record::decode_gt_into`]), only decode data for the kept samples,
+    /// while the APIs giving values by header sample (e.g.
+    /// [`Record::to_json`] and the rows of a [`GenotypeLayout`]) map them
+    /// back to their header columns.
+    ///
+    /// # Example
+    #[cfg_attr(feature = "format", doc = "```")]
+    #[cfg_attr(not(feature = "format"), doc = "```ignore")]
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut full = Record::default();
+    /// full.read(&mut f).unwrap();
+    ///
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// read_header(&mut f).unwrap();
+    /// let names = [header.get_samples()[3].as_str(), header.get_samples()[1].as_str()];
+    /// let selection = FieldSelection::default().sample_names(&header, &names);
+    /// assert_eq!(selection.sample_columns(), Some(&[1, 3][..]));
+    /// let mut subset = Record::default();
+    /// subset.read_selected(&mut f, &selection).unwrap();
+    ///
+    /// let gt_full: Vec<_> = full.fmt_gt(&header).collect();
+    /// let gt_subset: Vec<_> = subset.fmt_gt(&header).collect();
+    /// assert_eq!(gt_subset.len(), 4);
+    /// assert_eq!(&gt_subset[0..2], &gt_full[2..4]);
+    /// assert_eq!(&gt_subset[2..4], &gt_full[6..8]);
+    ///
+    /// let ad = header.get_idx_from_dictionary_str("FORMAT", "AD").unwrap();
+    /// let ad_full: Vec<_> = full.fmt_field(ad).collect();
+    /// let ad_subset: Vec<_> = subset.fmt_field(ad).collect();
+    /// let n = ad_full.len() / header.get_samples().len();
+    /// assert_eq!(&ad_subset[..n], &ad_full[n..2 * n]);
+    /// assert_eq!(&ad_subset[n..], &ad_full[3 * n..4 * n]);
+    ///
+    /// // the record maps its samples back to the header
+    /// assert_eq!(subset.sample_columns(), Some(&[1, 3][..]));
+    /// assert_eq!(subset.sample_names(&header), [names[1], names[0]]);
+    /// let mut sites = SampleMajorGenotypes::new(header.get_samples().len(), GenotypeLayout::Dosage, 1);
+    /// sites.push(&subset, &header).unwrap();
+    /// let mut full_sites = sites.clone();
+    /// full_sites.clear();
+    /// full_sites.push(&full, &header).unwrap();
+    /// for row in 0..header.get_samples().len() {
+    ///     let expected = if [1, 3].contains(&row) { full_sites.row(row)[0] } else { -1 };
+    ///     assert_eq!(sites.row(row)[0], expected);
+    /// }
+    /// ```
+    pub fn samples(mut self, header: &Header, columns: &[usize]) -> Self {
+        let n_sample = header.get_samples().len();
+        let mut columns: Vec<usize> = columns.iter().copied().filter(|&c| c < n_sample).collect();
+        columns.sort_unstable();
+        columns.dedup();
+        self.samples = Some(columns.into());
+        self
+    }
+
+    /// Keep only the FORMAT data of the samples with the given names; see
+    /// [`FieldSelection::samples`]. Names not in the header are ignored.
+    pub fn sample_names(self, header: &Header, names: &[&str]) -> Self {
+        let columns: Vec<usize> = names
+            .iter()
+            .filter_map(|name| header.sample_index(name))
+            .collect();
+        self.samples(header, &columns)
+    }
+
+    /// Keep the FORMAT data of all samples
+    pub fn all_samples(mut self) -> Self {
+        self.samples = None;
+        self
+    }
+
+    /// Columns (0-based, in file order) of the selected samples, or `None`
+    /// if all samples are kept
+    pub fn sample_columns(&self) -> Option<&[usize]> {
+        self.samples.as_deref()
+    }
+}
+
+/// 64-bit FNV-1a hash of `data`.
+///
+/// This is a fixed, documented algorithm, so hashes are stable across
+/// platforms and versions of the crate (unlike `std::hash::DefaultHasher`).
+///
+/// ```
+/// assert_eq!(bcf_reader::fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+/// ```
+pub fn fnv1a_64(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in data {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// 128-bit FNV-1a hash of `data`; stable like [`fnv1a_64`].
+///
+/// ```
+/// assert_eq!(
+///     bcf_reader::fnv1a_128(b"a"),
+///     0xd228cb696f1a8caf78912b704e4a8964
+/// );
+/// ```
+pub fn fnv1a_128(data: &[u8]) -> u128 {
+    let mut h: u128 = 0x6c62272e07bb014262b821756295c58d;
+    for b in data {
+        h ^= *b as u128;
+        h = h.wrapping_mul(0x0000000001000000000000000000013b);
+    }
+    h
+}
+
+/// Trim the bases shared by a REF/ALT pair, first from the end and then from
+/// the start, keeping at least one base in each allele. Returns the adjusted
+/// 0-based position and the trimmed alleles.
+#[cfg(feature = "strings")]
+fn trim_allele_pair<'a>(
+    mut pos: i32,
+    mut r: &'a [u8],
+    mut a: &'a [u8],
+) -> (i32, &'a [u8], &'a [u8]) {
+    while r.len() > 1 && a.len() > 1 && r.last() == a.last() {
+        r = &r[..r.len() - 1];
+        a = &a[..a.len() - 1];
+    }
+    while r.len() > 1 && a.len() > 1 && r[0] == a[0] {
+        r = &r[1..];
+        a = &a[1..];
+        pos += 1;
+    }
+    (pos, r, a)
+}
+
+/// A view of the FORMAT/GT values of a record as stored, for decoding single
+/// alleles in tight loops (see [`Record::gt_raw`])
+///
+/// The accessors are inlined and read the value directly from the bytes,
+/// without going through [`NumericValue`]s. They panic if the sample or the
+/// allele is out of range.
+#[derive(Debug, Clone, Copy)]
+pub struct GtRaw<'r> {
+    typ: u8,
+    ploidy: usize,
+    buf: &'r [u8],
+}
+
+impl<'r> GtRaw<'r> {
+    /// BCF2 type of the values: 1 (int8), 2 (int16) or 3 (int32)
+    pub fn typ(&self) -> u8 {
+        self.typ
+    }
+
+    /// Number of values per sample
+    pub fn ploidy(&self) -> usize {
+        self.ploidy
+    }
+
+    /// The bytes of the values of all samples
+    pub fn bytes(&self) -> &'r [u8] {
+        self.buf
+    }
+
+    /// The stored value of allele `i` of `sample`, sign-extended:
+    /// `(allele + 1) << 1 | phased`, or the missing and end-of-vector values
+    /// of the type
+    #[inline]
+    pub fn raw_at(&self, sample: usize, i: usize) -> i32 {
+        debug_assert!(i < self.ploidy);
+        let k = sample * self.ploidy + i;
+        match self.typ {
+            1 => self.buf[k] as i8 as i32,
+            2 => i16::from_le_bytes([self.buf[2 * k], self.buf[2 * k + 1]]) as i32,
+            _ => i32::from_le_bytes(self.buf[4 * k..4 * k + 4].try_into().unwrap()),
+        }
+    }
+
+    /// The allele index of allele `i` of `sample`, decoded as by
+    /// [`Record::decode_gt_into`]: `-1` for a missing allele and `-2` for an
+    /// absent one
+    #[inline]
+    pub fn allele_at(&self, sample: usize, i: usize) -> i32 {
+        let (missing, end) = match self.typ {
+            1 => (i8::MIN as i32, i8::MIN as i32 + 1),
+            2 => (i16::MIN as i32, i16::MIN as i32 + 1),
+            _ => (i32::MIN, i32::MIN + 1),
+        };
+        match self.raw_at(sample, i) {
+            x if x == missing => -1,
+            x if x == end => -2,
+            x => ((x as u32 & type_mask(self.typ)) >> 1) as i32 - 1,
+        }
+    }
+
+    /// Whether allele `i` of `sample` is phased with the previous allele
+    #[inline]
+    pub fn is_phased(&self, sample: usize, i: usize) -> bool {
+        self.raw_at(sample, i) & 1 == 1
+    }
+}
+
+/// Mask of the bits of a value of an integer type
+#[inline]
+fn type_mask(typ: u8) -> u32 {
+    match typ {
+        1 => 0xff,
+        2 => 0xffff,
+        _ => u32::MAX,
+    }
+}
+
+/// The FORMAT/GT values of a record, decoded on first access and cached in
+/// the record (see [`Record::genotypes`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedGenotypes {
+    /// GT key of the header the values were decoded with
+    gt_key: Option<usize>,
+    ploidy: usize,
+    values: Vec<i32>,
+}
+
+impl DecodedGenotypes {
+    /// Number of alleles per sample (0 for a record without FORMAT/GT)
+    pub fn ploidy(&self) -> usize {
+        self.ploidy
+    }
+
+    /// The alleles of all samples, `ploidy()` values per sample, decoded as
+    /// by [`Record::decode_gt_into`]
+    pub fn values(&self) -> &[i32] {
+        &self.values
+    }
+
+    /// The alleles of the `i`-th sample
+    pub fn sample(&self, i: usize) -> &[i32] {
+        &self.values[i * self.ploidy..(i + 1) * self.ploidy]
+    }
+}
+
+/// Represents a record (a line or a site) in BCF file
+///
+/// A `Record` owns its buffers: cloning it makes a deep copy that stays
+/// valid after the original is reused for reading the next record, and
+/// records can be moved to other threads (`Record` is `Send` and `Sync`).
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut record = Record::default();
+/// let mut records = vec![];
+/// while let Ok(_) = record.read(&mut f) {
+///     records.push(record.clone());
+/// }
+/// assert_eq!(records.len(), 127);
+/// // dispatch the collected records to a worker thread
+/// let n_called = std::thread::spawn(move || {
+///     records
+///         .iter()
+///         .map(|r| r.genotype_class_counts(&header).n_called())
+///         .sum::<u32>()
+/// })
+/// .join()
+/// .unwrap();
+/// assert!(n_called > 0);
+/// ```
+#[derive(Default, Debug, Clone)]
+pub struct Record {
+    buf_shared: Vec<u8>,
+    buf_indiv: Vec<u8>,
+    chrom: i32,
+    pos: i32,
+    rlen: i32,
+    qual: NumericValue,
+    n_info: u16,
+    /// whether the INFO fields were not selected when reading the record
+    info_skipped: bool,
+    n_allele: u16,
+    n_sample: u32,
+    /// n_sample as read, before a sample selection
+    n_sample_read: u32,
+    /// header columns of the samples kept by a sample selection
+    sample_columns: Option<Arc<[usize]>>,
+    n_fmt: u8,
+    id: Range<usize>,
+    alleles: Vec<Range<usize>>,
+    /// (typ, n, byte_range)
+    filters: (u8, usize, Range<usize>),
+    /// offset of the first INFO field in buf_shared
+    info_start: usize,
+    /// fields with 64-bit values, found by the first call to
+    /// `dropped_fields` and reset when the record is read
+    dropped_fields: std::sync::OnceLock<Vec<DroppedField>>,
+    /// number of bytes after the last FORMAT field skipped by a field
+    /// selection
+    skipped_trailing_bytes: usize,
+    /// GT values decoded by the first call to `genotypes`, reset when the
+    /// record is read
+    genotypes: std::sync::OnceLock<DecodedGenotypes>,
+    /// layout of the FORMAT fields, located by the first lookup of a FORMAT
+    /// key and reset when the record is read
+    fmt_slots: std::sync::OnceLock<Vec<RawFieldLayout>>,
+    /// l_shared and l_indv of the record as read
+    lengths: (u32, u32),
+}
+impl Record {
+    /// read a record (copy bytes from the reader to the record's interval
+    /// buffers), and separate fields
+    ///
+    /// An error is returned if the data ends before the end of the record,
+    /// if its fixed fields, ID, alleles and FILTER do not fit in its
+    /// declared lengths, or if one of its INFO and FORMAT fields does not fit
+    /// or has an undefined type code (see [`Record::check_fields`]). The
+    /// fields are only located when read; their values are decoded lazily by
+    /// the accessors.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let frame = |shared: &[u8]| {
+    ///     let mut data = vec![];
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    ///     data
+    /// };
+    /// let mut record = Record::default();
+    /// // a shared buffer too short for the fixed fields
+    /// let err = record.read(&mut frame(&[0; 10]).as_slice()).unwrap_err();
+    /// assert!(matches!(err, Error::MalformedRecord(_)));
+    ///
+    /// // an ID with a negative length (-1 as an int8 count)
+    /// let mut shared = vec![0u8; 16];
+    /// shared.extend(0u32.to_le_bytes()); // no INFO, no allele
+    /// shared.extend(0u32.to_le_bytes()); // no samples
+    /// shared.extend([0xF7, 0x11, 0xFF]);
+    /// let err = record.read(&mut frame(&shared).as_slice()).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "ID in the shared buffer at byte 24: malformed record: negative number of values -1"
+    /// );
+    /// ```
+    pub fn read<R>(&mut self, reader: &mut R) -> error::Result<()>
+    where
+        R: std::io::Read + ReadBytesExt,
+    {
+        self.try_read(reader)?.ok_or_else(no_more_records)
+    }
+
+    /// Read a record as [`Record::read`], returning `Ok(None)` at the end of
+    /// the data, so that the end of a file can be told apart from a
+    /// truncated or corrupt record, which are still errors.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// read_header(&mut f).unwrap();
+    /// let mut record = Record::default();
+    /// let mut n_records = 0;
+    /// while let Some(()) = record.try_read(&mut f).unwrap() {
+    ///     n_records += 1;
+    /// }
+    /// assert_eq!(n_records, 127);
+    ///
+    /// // a record cut short is an error
+    /// let mut data = vec![];
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// read_header(&mut f).unwrap();
+    /// f.read_to_end(&mut data).unwrap();
+    /// let mut truncated = &data[..data.len() - 3];
+    /// let mut result = Ok(Some(()));
+    /// while let Ok(Some(())) = result {
+    ///     result = record.try_read(&mut truncated);
+    /// }
+    /// match result {
+    ///     Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+    ///     other => panic!("unexpected result {other:?}"),
+    /// }
+    /// ```
+    pub fn try_read<R>(&mut self, reader: &mut R) -> error::Result<Option<()>>
+    where
+        R: std::io::Read + ReadBytesExt,
+    {
+        self.read_limited(reader, None, false, &NO_LIMITS)
+    }
+
+    /// Read a record, keeping the fields in `selection` if any, and
+    /// skipping the indiv bytes if `site_only`. An error is returned if the
+    /// record exceeds `limits`; a record longer than allowed is skipped
+    /// without being buffered. `Ok(None)` is returned at the end of the data.
+    fn read_limited<R>(
+        &mut self,
+        reader: &mut R,
+        selection: Option<&FieldSelection>,
+        site_only: bool,
+        limits: &ResourceLimits,
+    ) -> error::Result<Option<()>>
+    where
+        R: std::io::Read + ReadBytesExt,
+    {
+        self.reset_caches();
+        let fmt_keys = selection.and_then(|s| s.fmt_keys.as_deref());
+        let site_only = site_only || fmt_keys.is_some_and(|keys| keys.is_empty());
+        let Some((l_shared, l_indv)) = read_record_lengths(reader)? else {
+            return Ok(None);
+        };
+        self.lengths = (l_shared, l_indv);
+        let length = l_shared as u64 + l_indv as u64;
+        if let Err(e) = limits.check_record_length(length) {
+            skip_exact(reader, length)?;
+            return Err(e);
+        }
+        read_exact_to(reader, &mut self.buf_shared, l_shared as usize)?;
+        let columns = selection.and_then(|s| s.samples.as_ref());
+        // the FORMAT fields of the shared buffer are needed to select fields
+        // and samples while reading the indiv bytes
+        let parsed = self
+            .parse_shared()
+            .and_then(|()| self.check_sample_columns(columns));
+        self.skipped_trailing_bytes = 0;
+        if site_only {
+            skip_exact(reader, l_indv as u64)?;
+            self.buf_indiv.clear();
+        } else if parsed.is_ok() && (fmt_keys.is_some() || columns.is_some()) {
+            let columns = columns.map(|c| &c[..]);
+            self.skipped_trailing_bytes =
+                self.read_fmt_fields_selected(reader, l_indv, fmt_keys, columns)?;
+        } else {
+            read_exact_to(reader, &mut self.buf_indiv, l_indv as usize)?;
+        }
+        parsed?;
+        self.info_skipped = selection.is_some_and(|s| !s.info);
+        self.sample_columns = columns.cloned();
+        if let Some(columns) = columns {
+            self.n_sample = columns.len() as u32;
+        }
+        for (what, value, limit) in [
+            (
+                "number of alleles",
+                self.n_allele as usize,
+                limits.max_alleles,
+            ),
+            (
+                "number of samples",
+                self.n_sample as usize,
+                limits.max_samples,
+            ),
+        ] {
+            if value > limit {
+                return Err(Error::LimitExceeded {
+                    what,
+                    value: value as u64,
+                    limit: limit as u64,
+                });
+            }
+        }
+        self.check_field_values(limits.max_field_values)?;
+        Ok(Some(()))
+    }
+
+    /// read a record, only keeping the INFO/FORMAT fields in `selection`.
+    ///
+    /// If INFO fields are not selected, they are not located and the INFO
+    /// accessors of the record return no data, but [`Record::n_info`] is
+    /// still the number of INFO fields in the data. The FORMAT fields that
+    /// are not selected are skipped while reading the indiv bytes, without
+    /// being copied to the record's indiv buffer; if no FORMAT field is
+    /// selected, the indiv bytes are skipped as in [`Record::read_site_only`].
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut full = Record::default();
+    /// full.read(&mut f).unwrap();
+    ///
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// read_header(&mut f).unwrap();
+    /// let selection = FieldSelection::default()
+    ///     .parse_info(false)
+    ///     .parse_formats(&header, &["GT"]);
+    /// let mut record = Record::default();
+    /// record.read_selected(&mut f, &selection).unwrap();
+    /// assert_eq!(record.n_info(), full.n_info());
+    /// // only the bytes of the GT field are kept
+    /// let gt = full.fmt_layout().find(|l| l.key == header.format_key("GT").unwrap()).unwrap();
+    /// let kept = record.fmt_layout().next().unwrap();
+    /// assert_eq!(record.fmt_layout().count(), 1);
+    /// assert_eq!(record.buf_indiv().len(), kept.range.end);
+    /// assert_eq!(&record.buf_indiv()[kept.range], &full.buf_indiv()[gt.range]);
+    /// assert_eq!(record.record_lengths(), full.record_lengths());
+    /// ```
+    pub fn read_selected<R>(
+        &mut self,
+        reader: &mut R,
+        selection: &FieldSelection,
+    ) -> error::Result<()>
+    where
+        R: std::io::Read + ReadBytesExt,
+    {
+        self.read_limited(reader, Some(selection), false, &NO_LIMITS)?
+            .ok_or_else(no_more_records)
+    }
+
+    /// Check that the record has samples at the selected sample `columns`
+    fn check_sample_columns(&self, columns: Option<&Arc<[usize]>>) -> error::Result<()> {
+        // the columns are sorted
+        match columns.and_then(|c| c.last()) {
+            Some(&last) if last >= self.n_sample as usize => Err(Error::MalformedRecord(format!(
+                "the record has {} samples, too few for the selected sample column {last}",
+                self.n_sample
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the `l_indv` indiv bytes of a record field by field, only
+    /// copying the FORMAT fields whose keys are in `keys` (all if `None`) and
+    /// the values of the samples at `columns` (all if `None`) to the indiv
+    /// buffer, and skipping the other bytes. Return the number of bytes after
+    /// the last FORMAT field.
+    ///
+    /// The rest of the indiv bytes is skipped after an error, so that the
+    /// next record can still be read.
+    fn read_fmt_fields_selected<R: Read>(
+        &mut self,
+        reader: &mut R,
+        l_indv: u32,
+        keys: Option<&[usize]>,
+        columns: Option<&[usize]>,
+    ) -> error::Result<usize> {
+        let mut indiv = reader.take(l_indv as u64);
+        let result = self.copy_fmt_fields_selected(&mut indiv, l_indv, keys, columns);
+        let trailing_bytes = indiv.limit();
+        let skipped = skip_exact(&mut indiv, trailing_bytes);
+        result?;
+        skipped?;
+        Ok(trailing_bytes as usize)
+    }
+
+    /// Copy the selected FORMAT values from the indiv bytes to the indiv
+    /// buffer; see [`Record::read_fmt_fields_selected`]
+    fn copy_fmt_fields_selected<R: Read>(
+        &mut self,
+        indiv: &mut io::Take<R>,
+        l_indv: u32,
+        keys: Option<&[usize]>,
+        columns: Option<&[usize]>,
+    ) -> error::Result<()> {
+        self.buf_indiv.clear();
+        let n_sample = self.n_sample as usize;
+        let n_fmt = self.n_fmt;
+        self.n_fmt = 0;
+        for i in 0..n_fmt {
+            let offset = (l_indv as u64 - indiv.limit()) as usize;
+            let context =
+                |e: Error| e.context(format!("FORMAT field {i} in the individual buffer"), offset);
+            let start = self.buf_indiv.len();
+            let mut tee = TeeReader {
+                reader: &mut *indiv,
+                copy: &mut self.buf_indiv,
+            };
+            let key = read_single_typed_integer(&mut tee).map_err(context)? as usize;
+            let (typ, n) = read_typed_descriptor_bytes(&mut tee).map_err(context)?;
+            // bytes per sample
+            let (w, len) = field_width(typ)
+                .ok()
+                .and_then(|w| w.checked_mul(n))
+                .and_then(|w| Some((w, w.checked_mul(n_sample)?)))
+                .filter(|&(_, len)| len as u64 <= indiv.limit())
+                .ok_or_else(|| {
+                    context(Error::MalformedRecord(format!(
+                        "field with key {key} of {n} values of type {typ} for {n_sample} samples \
+                         does not fit in the {l_indv} byte buffer"
+                    )))
+                })?;
+            if !keys.is_none_or(|keys| keys.contains(&key)) {
+                self.buf_indiv.truncate(start);
+                skip_exact(indiv, len as u64)?;
+                continue;
+            }
+            match columns {
+                None => read_exact_append(indiv, &mut self.buf_indiv, len)?,
+                Some(columns) => {
+                    // the columns are sorted and in range
+                    let mut next = 0;
+                    for &c in columns {
+                        skip_exact(indiv, ((c - next) * w) as u64)?;
+                        read_exact_append(indiv, &mut self.buf_indiv, w)?;
+                        next = c + 1;
+                    }
+                    skip_exact(indiv, ((n_sample - next) * w) as u64)?;
+                }
+            }
+            self.n_fmt += 1;
+        }
+        Ok(())
+    }
+
+    /// Convert the FORMAT vectors of a record read from a BCF 2.1 file to the
+    /// BCF 2.2 encoding.
+    ///
+    /// BCF 2.1 has no end-of-vector value: vectors shorter than the field
+    /// width (e.g. a haploid GT in a diploid field) are padded with the
+    /// missing value. This replaces the trailing missing values of the vector
+    /// of each sample (after its first value) by the end-of-vector value, so
+    /// that the accessors report them as absent rather than missing. Missing
+    /// values followed by a value are kept. [`BcfReader`] and
+    /// [`IndexedBcfReader`] do this automatically for BCF 2.1 files.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1>\n",
+    ///     "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\n",
+    /// ));
+    /// let gt_key = header.get_fmt_gt_id().unwrap() as u8;
+    /// let mut shared = vec![];
+    /// for x in [0i32, 99, 1] {
+    ///     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    /// }
+    /// shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    /// shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+    /// shared.extend(((1u32 << 24) | 2).to_le_bytes()); // 1 FORMAT field, 2 samples
+    /// shared.extend([0x07, 0x17, b'A', 0x17, b'C', 0x00]); // ID, alleles, FILTER
+    /// // GT: sample 1 is 0/1, sample 2 is haploid 1 padded with a missing value
+    /// let indiv = vec![0x11, gt_key, 0x21, 2, 4, 4, 0x80];
+    /// let mut data = vec![];
+    /// data.extend((shared.len() as u32).to_le_bytes());
+    /// data.extend((indiv.len() as u32).to_le_bytes());
+    /// data.extend(shared);
+    /// data.extend(indiv);
+    ///
+    /// let mut record = Record::default();
+    /// record.read(&mut data.as_slice()).unwrap();
+    /// let mut gt = [0; 4];
+    /// record.decode_gt_into(&header, &mut gt).unwrap();
+    /// assert_eq!(gt, [0, 1, 1, -1]);
+    /// record.upgrade_bcf21_vectors();
+    /// record.decode_gt_into(&header, &mut gt).unwrap();
+    /// assert_eq!(gt, [0, 1, 1, -2]);
+    ///
+    /// // a missing value in the middle of a vector is not padding
+    /// let mut data = data;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1>\n",
+    ///     "##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Depths\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\n",
+    /// ));
+    /// let ad_key = header.format_key("AD").unwrap() as u8;
+    /// // AD: sample 1 is 3,.,5 and sample 2 is 7 padded with missing values
+    /// let indiv = [0x11, ad_key, 0x31, 3, 0x80, 5, 7, 0x80, 0x80];
+    /// let l_shared = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+    /// data.truncate(8 + l_shared);
+    /// data[4..8].copy_from_slice(&(indiv.len() as u32).to_le_bytes());
+    /// data.extend(indiv);
+    /// record.read(&mut data.as_slice()).unwrap();
+    /// record.upgrade_bcf21_vectors();
+    /// // only the padding of sample 2 becomes end-of-vector (0x81)
+    /// assert_eq!(record.buf_indiv()[3..], [3, 0x80, 5, 7, 0x81, 0x81]);
+    /// ```
+    pub fn upgrade_bcf21_vectors(&mut self) {
+        self.reset_caches();
+        let fields: Vec<_> = self.fmt_fields().collect();
+        let n_sample = self.n_sample as usize;
+        for (_, typ, n, rng) in fields {
+            let (missing, end): (&[u8], &[u8]) = match typ {
+                1 => (&[0x80], &[0x81]),
+                2 => (&[0x00, 0x80], &[0x01, 0x80]),
+                3 => (&[0, 0, 0, 0x80], &[1, 0, 0, 0x80]),
+                5 => (&[0x01, 0, 0x80, 0x7F], &[0x02, 0, 0x80, 0x7F]),
+                _ => continue,
+            };
+            let width = missing.len();
+            for i in 0..n_sample {
+                let start = rng.start + i * n * width;
+                // only the trailing run of missing values is padding; missing
+                // values before a value are real missing values
+                for j in (1..n).rev() {
+                    let at = start + j * width;
+                    match self.buf_indiv.get_mut(at..at + width) {
+                        Some(v) if v == missing => v.copy_from_slice(end),
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
+
+    /// read a record but only keep the shared (site-level) fields: the indiv
+    /// (sample-level) bytes are discarded without being copied into the
+    /// record, so FORMAT accessors of the record return no data.
+    ///
+    /// This is much cheaper than [`Record::read`] for site-level scans over
+    /// files with many samples.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut positions = vec![];
+    /// while let Ok(_) = record.read_site_only(&mut f) {
+    ///     assert!(record.buf_indiv().is_empty());
+    ///     assert_eq!(record.fmt_gt(&header).count(), 0);
+    ///     positions.push(record.pos());
+    /// }
+    /// // same positions as a full read
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let _header = read_header(&mut f).unwrap();
+    /// let mut positions2 = vec![];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     positions2.push(record.pos());
+    /// }
+    /// assert_eq!(positions, positions2);
+    /// ```
+    pub fn read_site_only<R>(&mut self, reader: &mut R) -> error::Result<()>
+    where
+        R: std::io::Read + ReadBytesExt,
+    {
+        self.read_limited(reader, None, true, &NO_LIMITS)?
+            .ok_or_else(no_more_records)
+    }
+
+    /// parse shared fields
+    fn parse_shared(&mut self) -> error::Result<()> {
+        let len = self.buf_shared.len();
+        // chrom, pos, rlen, qual, n_info/n_allele and n_sample/n_fmt
+        if len < 24 {
+            return Err(Error::MalformedRecord(format!(
+                "the shared buffer has {len} bytes, fewer than the 24 bytes of the fixed fields"
+            )));
+        }
+        let buf = self.buf_shared.as_slice();
+        let word = |i: usize| u32::from_le_bytes(buf[4 * i..4 * i + 4].try_into().unwrap());
+        self.chrom = word(0) as i32;
+        self.pos = word(1) as i32;
+        self.rlen = word(2) as i32;
+        self.qual = NumericValue::from(word(3)).as_f32();
+        self.n_info = word(4) as u16;
+        self.n_allele = (word(4) >> 16) as u16;
+        let combined = word(5);
+        self.n_sample = combined & 0xffffff;
+        self.n_sample_read = self.n_sample;
+        self.n_fmt = (combined >> 24) as u8;
+        // offset of the next value
+        let mut pos = 24;
+        // skip `n` values of width `width`, checking they are in the buffer
+        let skip = |pos: &mut usize, width: usize, n: usize| {
+            let s = *pos;
+            let e = width
+                .checked_mul(n)
+                .and_then(|x| x.checked_add(s))
+                .filter(|&e| e <= len)
+                .ok_or_else(|| {
+                    Error::MalformedRecord(format!(
+                        "{n} values of {width} bytes do not fit in the {len} byte buffer"
+                    ))
+                })?;
+            *pos = e;
+            Ok::<_, Error>(s..e)
+        };
+        // read a type descriptor, moving `pos` after it
+        let descriptor = |pos: &mut usize| {
+            let mut rest = &buf[*pos..];
+            let (typ, n) = read_typed_descriptor_bytes(&mut rest)?;
+            *pos = len - rest.len();
+            Ok::<_, Error>((typ, n))
+        };
+        let read_string = |pos: &mut usize, what: &str| {
+            let offset = *pos;
+            let in_shared = |e: Error| e.context(format!("{what} in the shared buffer"), offset);
+            let (typ, n) = descriptor(pos).map_err(in_shared)?;
+            if typ != 0x7 {
+                return Err(in_shared(Error::UnexpectedType {
+                    expected: "a string",
+                    found: typ,
+                    descriptor: descriptor_byte(typ, n),
+                }));
+            }
+            skip(pos, 1, n).map_err(in_shared)
+        };
+        // id
+        self.id = read_string(&mut pos, "ID")?;
+        // alleles
+        self.alleles.clear();
+        for i in 0..self.n_allele {
+            self.alleles
+                .push(read_string(&mut pos, &format!("allele {i}"))?);
+        }
+        //filters
+        let offset = pos;
+        let in_shared = |e: Error| e.context("FILTER in the shared buffer", offset);
+        let (typ, n) = descriptor(&mut pos).map_err(in_shared)?;
+        let width: usize = try_bcf2_typ_width(typ).map_err(in_shared)?;
+        let rng = skip(&mut pos, width, n).map_err(in_shared)?;
+        // infos: only keep the start of the info block; the fields are located
+        // lazily by the INFO accessors
+        self.info_start = rng.end;
+        self.filters = (typ, n, rng);
+        Ok(())
+    }
+
+    /// Check that the INFO and FORMAT fields fit in the record buffers and
+    /// have defined type codes.
+    ///
+    /// The fields are checked when the record is read, so that a record
+    /// with a malformed field is an error rather than a record whose later
+    /// fields are missing; the check only walks the field descriptors, the
+    /// values are decoded lazily by the accessors.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut shared = vec![];
+    /// for x in [0i32, 99, 1] {
+    ///     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    /// }
+    /// shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    /// shared.extend((2u32 | 1 << 16).to_le_bytes()); // 2 INFO, 1 allele
+    /// shared.extend(0u32.to_le_bytes()); // no samples
+    /// shared.extend([0x07, 0x17, b'A', 0x00]); // ID, REF, FILTER
+    /// shared.extend([0x11, 1, 0x11, 30]); // INFO key 1 = 30
+    /// shared.extend([0x11, 2, 0x31, 7]); // 3 values of INFO key 2, only 1 in the buffer
+    /// let mut data = vec![];
+    /// data.extend((shared.len() as u32).to_le_bytes());
+    /// data.extend(0u32.to_le_bytes());
+    /// data.extend(shared);
+    ///
+    /// let mut record = Record::default();
+    /// let err = record.read(&mut data.as_slice()).unwrap_err();
+    /// assert!(err.is_corruption());
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "INFO field 1 in the shared buffer at byte 32: malformed record: field with key 2 \
+    ///      of 3 values of type 1 for 1 samples does not fit in the 36 byte buffer"
+    /// );
+    /// ```
+    pub fn check_fields(&self) -> error::Result<()> {
+        self.check_field_values(usize::MAX)
+    }
+
+    /// [`Record::check_fields`], also checking that each field has at most
+    /// `max_values` values (over all samples)
+    fn check_field_values(&self, max_values: usize) -> error::Result<()> {
+        for (what, buffer, mut it) in [
+            ("INFO", "shared", self.info_fields()),
+            ("FORMAT", "individual", self.fmt_fields()),
+        ] {
+            for i in 0.. {
+                let offset = it.pos;
+                let n_sample = it.n_sample;
+                let context = |e: Error| {
+                    e.context(format!("{what} field {i} in the {buffer} buffer"), offset)
+                };
+                match it.try_next().map_err(context)? {
+                    None => break,
+                    Some((_, _, n, _)) if n.saturating_mul(n_sample) > max_values => {
+                        return Err(context(Error::LimitExceeded {
+                            what: "number of values",
+                            value: n.saturating_mul(n_sample) as u64,
+                            limit: max_values as u64,
+                        }));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes after the last INFO field of the shared buffer and
+    /// after the last FORMAT field of the indiv bytes
+    fn trailing_bytes(&self) -> [usize; 2] {
+        let mut trailing_bytes = [0, self.skipped_trailing_bytes];
+        for (n, mut it) in trailing_bytes
+            .iter_mut()
+            .zip([self.info_fields(), self.fmt_fields()])
+        {
+            while let Ok(Some(_)) = it.try_next() {}
+            if it.remaining == 0 {
+                *n += it.buf.len().saturating_sub(it.pos);
+            }
+        }
+        trailing_bytes
+    }
+
+    /// Get the INFO and FORMAT fields skipped by the accessors because their
+    /// values use the reserved BCF2 type code 4 (64-bit integers, written by
+    /// some encoders), which the accessors do not support. Fields with other
+    /// undefined type codes cannot be located, and reading the record
+    /// returns an error (see [`Record::check_fields`]).
+    ///
+    /// Example:
+    #[cfg_attr(feature = "info", doc = "```")]
+    #[cfg_attr(not(feature = "info"), doc = "```ignore")]
+    /// use bcf_reader::*;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##INFO=<ID=END,Number=1,Type=Integer,Description=\"End\">\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// ));
+    /// let end = header.get_idx_from_dictionary_str("INFO", "END").unwrap();
+    /// let dp = header.get_idx_from_dictionary_str("INFO", "DP").unwrap();
+    /// let record_bytes = |end_typ: u8| {
+    ///     let mut shared = vec![];
+    ///     for x in [0i32, 99, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((2u32 | 1 << 16).to_le_bytes()); // 2 INFO, 1 allele
+    ///     shared.extend(0u32.to_le_bytes()); // no samples
+    ///     shared.extend([0x07, 0x17, b'A', 0x00]); // ID, REF, FILTER
+    ///     shared.extend([0x11, end as u8, 0x10 | end_typ]);
+    ///     shared.extend(200i64.to_le_bytes());
+    ///     shared.extend([0x11, dp as u8, 0x11, 30]);
+    ///     let mut data = vec![];
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    ///     data
+    /// };
+    ///
+    /// // a 64-bit INFO/END is dropped, the other fields are kept
+    /// let mut record = Record::default();
+    /// record.read(&mut record_bytes(4).as_slice()).unwrap();
+    /// record.check_fields().unwrap();
+    /// assert_eq!(record.dropped_fields(), [DroppedField { dictionary: "INFO", key: end, typ: 4 }]);
+    /// assert_eq!(record.info_field_numeric(end).count(), 0);
+    /// let depth: Vec<_> = record.info_field_numeric(dp).map(|v| v.int_val().unwrap()).collect();
+    /// assert_eq!(depth, [30]);
+    /// assert_eq!(
+    ///     record.conformance_issues(&header),
+    ///     ["INFO/END uses the reserved BCF2 type 4 and was dropped"]
+    /// );
+    ///
+    /// // other undefined type codes are errors
+    /// let err = record.read(&mut record_bytes(6).as_slice()).unwrap_err();
+    /// assert!(matches!(err.root_cause(), Error::InvalidType { typ: 6, .. }));
+    /// ```
+    pub fn dropped_fields(&self) -> &[DroppedField] {
+        self.dropped_fields.get_or_init(|| {
+            let mut dropped = vec![];
+            for (dictionary, mut it) in
+                [("INFO", self.info_fields()), ("FORMAT", self.fmt_fields())]
+            {
+                while let Ok(Some((key, typ, _, _))) = it.try_next() {
+                    if typ == BCF_BT_INT64 {
+                        dropped.push(DroppedField {
+                            dictionary,
+                            key,
+                            typ,
+                        });
+                    }
+                }
+            }
+            dropped
+        })
+    }
+
+    /// Check the record against the header and the specification, returning
+    /// a description of each deviation: empty alleles, a number of samples
+    /// other than in the header (for records with FORMAT fields), contigs
+    /// and FILTER/INFO/FORMAT keys not defined in the header, and values
+    /// encoded with a type that does not match the `Type=` of their header
+    /// line.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// assert!(record.conformance_issues(&header).is_empty());
+    ///
+    /// // the same record checked against a header declaring DP as a float
+    /// let text = header.to_text().replace(
+    ///     "##FORMAT=<ID=DP,Number=1,Type=Integer",
+    ///     "##FORMAT=<ID=DP,Number=1,Type=Float",
+    /// );
+    /// let header = Header::from_string(&text);
+    /// let issues = record.conformance_issues(&header);
+    /// assert_eq!(issues, ["FORMAT/DP is declared as Float but encoded as BCF2 type 1"]);
+    ///
+    /// // and against a header also without the last sample
+    /// let text = header.to_text();
+    /// let (head, _) = text.trim_end().rsplit_once('\t').unwrap();
+    /// let header = Header::from_string(&format!("{head}\n"));
+    /// let n = header.get_samples().len();
+    /// let issues = record.conformance_issues(&header);
+    /// assert_eq!(issues[0], format!("the record has {} samples but the header has {n}", n + 1));
+    /// ```
+    pub fn conformance_issues(&self, header: &Header) -> Vec<String> {
+        self.conformance_warnings(header)
+            .into_iter()
+            .map(|w| w.message)
+            .collect()
+    }
+
+    /// [`Record::conformance_issues`] with the kind of each deviation
+    fn conformance_warnings(&self, header: &Header) -> Vec<ReadWarning> {
+        let mut issues = vec![];
+        let mut push = |kind, message| issues.push(ReadWarning::new(kind, message));
+        for field in self.dropped_fields() {
+            let id = match header.number_and_type_for(field.dictionary, field.key) {
+                Ok((id, _, _)) => id.to_string(),
+                Err(_) => format!("key {}", field.key),
+            };
+            push(
+                WarningKind::Nonconforming,
+                format!(
+                    "{}/{id} uses the reserved BCF2 type {} and was dropped",
+                    field.dictionary, field.typ
+                ),
+            );
+        }
+        if header.rid_to_name(self.chrom).is_none() {
+            push(
+                WarningKind::UnknownIndex,
+                format!("contig {} is not defined in the header", self.chrom),
+            );
+        }
+        for key in self.filters() {
+            let key = key.int_val().unwrap_or(0) as usize;
+            if header.dict_entry("FILTER", key).is_none() {
+                push(
+                    WarningKind::UnknownIndex,
+                    format!("FILTER key {key} is not defined in the header"),
+                );
+            }
+        }
+        for (i, allele) in self.alleles.iter().enumerate() {
+            if allele.is_empty() {
+                push(WarningKind::Nonconforming, format!("allele {i} is empty"));
+            }
+        }
+        // the sample count only matters to locate the FORMAT values
+        let n_sample = header.get_samples().len();
+        if self.n_fmt > 0 && self.n_sample_read as usize != n_sample {
+            push(
+                WarningKind::Nonconforming,
+                format!(
+                    "the record has {} samples but the header has {n_sample}",
+                    self.n_sample_read
+                ),
+            );
+        }
+        for (dictionary, fields) in [("INFO", self.info_fields()), ("FORMAT", self.fmt_fields())] {
+            for (key, typ, n, _) in fields {
+                let Ok((id, _, ty)) = header.number_and_type_for(dictionary, key) else {
+                    push(
+                        WarningKind::UnknownIndex,
+                        format!("{dictionary} key {key} is not defined in the header"),
+                    );
+                    continue;
+                };
+                if let Some(message) = type_mismatch(dictionary, id, ty, typ, n) {
+                    push(WarningKind::Nonconforming, message);
+                }
+            }
+        }
+        issues
+    }
+
+    /// Check the encoding of the record for oddities that do not prevent
+    /// reading it: bytes after the last INFO or FORMAT field (counted when
+    /// the record was read), and values other than end-of-vector padding
+    /// after the end of a vector (NUL bytes for strings). The readers report
+    /// them to their warning handler (see
+    /// [`BcfReader::set_warning_handler`]).
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut shared = vec![];
+    /// for x in [0i32, 99, 1] {
+    ///     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    /// }
+    /// shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    /// shared.extend((1u32 | 1 << 16).to_le_bytes()); // 1 INFO, 1 allele
+    /// shared.extend(0u32.to_le_bytes()); // no samples
+    /// shared.extend([0x07, 0x17, b'A', 0x00]); // ID, REF, FILTER
+    /// shared.extend([0x11, 1, 0x31, 7, 0x81, 8]); // a value after the end of the vector
+    /// shared.extend([0, 0]); // trailing bytes
+    /// let mut data = vec![];
+    /// data.extend((shared.len() as u32).to_le_bytes());
+    /// data.extend(0u32.to_le_bytes());
+    /// data.extend(shared);
+    ///
+    /// let mut record = Record::default();
+    /// record.read(&mut data.as_slice()).unwrap();
+    /// let anomalies: Vec<_> = record.anomalies().into_iter().map(|w| (w.kind, w.message)).collect();
+    /// assert_eq!(
+    ///     anomalies,
+    ///     [
+    ///         (
+    ///             WarningKind::TrailingBytes,
+    ///             "2 bytes after the last field of the shared buffer".to_string()
+    ///         ),
+    ///         (
+    ///             WarningKind::Padding,
+    ///             "INFO key 1 has values after the end of the vector".to_string()
+    ///         ),
+    ///     ]
+    /// );
+    /// ```
+    pub fn anomalies(&self) -> Vec<ReadWarning> {
+        let mut anomalies = vec![];
+        for (buffer, n) in ["shared", "individual"]
+            .into_iter()
+            .zip(self.trailing_bytes())
+        {
+            if n > 0 {
+                anomalies.push(ReadWarning::new(
+                    WarningKind::TrailingBytes,
+                    format!("{n} bytes after the last field of the {buffer} buffer"),
+                ));
+            }
+        }
+        for (dictionary, buf, fields) in [
+            ("INFO", &self.buf_shared, self.info_fields()),
+            ("FORMAT", &self.buf_indiv, self.fmt_fields()),
+        ] {
+            for (key, typ, n, range) in fields {
+                let Ok(width) = field_width(typ) else {
+                    continue;
+                };
+                if n < 2 {
+                    continue;
+                }
+                let Some(i) = buf[range]
+                    .chunks_exact(width * n)
+                    .position(|values| has_bad_padding(typ, values))
+                else {
+                    continue;
+                };
+                let message = match dictionary {
+                    "INFO" => format!("INFO key {key} has values after the end of the vector"),
+                    _ => format!(
+                        "FORMAT key {key} of sample {i} has values after the end of the vector"
+                    ),
+                };
+                anomalies.push(ReadWarning::new(WarningKind::Padding, message));
+            }
+        }
+        anomalies
+    }
+
+    /// Iterate over the layout of the INFO fields: (info_key, typ, n, byte_range)
+    fn info_fields(&self) -> FieldLayoutIter<'_> {
+        FieldLayoutIter {
+            buf: &self.buf_shared,
+            pos: self.info_start,
+            // the INFO fields are kept but not located if not selected
+            remaining: if self.info_skipped {
+                0
+            } else {
+                self.n_info as usize
+            },
+            n_sample: 1,
+        }
+    }
+
+    /// Clear the values cached by the accessors, before the buffers change
+    fn reset_caches(&mut self) {
+        self.genotypes.take();
+        self.fmt_slots.take();
+        self.dropped_fields.take();
+    }
+
+    /// Find the FORMAT field `fmt_key`: (fmt_key, typ, n, byte_range)
+    ///
+    /// The fields are located by the first lookup and cached, so that
+    /// accessing several tags of a record walks the FORMAT fields once.
+    fn fmt_slot(&self, fmt_key: usize) -> Option<RawFieldLayout> {
+        let slots = self.fmt_slots.get_or_init(|| self.fmt_fields().collect());
+        slots.iter().find(|e| e.0 == fmt_key).cloned()
+    }
+
+    /// Iterate over the layout of the FORMAT fields: (fmt_key, typ, n, byte_range)
+    ///
+    /// The FORMAT fields are not parsed by `read` but located lazily by the
+    /// FORMAT accessors.
+    fn fmt_fields(&self) -> FieldLayoutIter<'_> {
+        FieldLayoutIter {
+            buf: &self.buf_indiv,
+            pos: 0,
+            // no indiv bytes are kept by `read_site_only`
+            remaining: if self.buf_indiv.is_empty() {
+                0
+            } else {
+                self.n_fmt as usize
+            },
+            n_sample: self.n_sample as usize,
+        }
+    }
+
+    /// The lengths of the shared and indiv buffers of the record in the
+    /// data (`l_shared` and `l_indv`), before any field or sample selection
+    pub fn record_lengths(&self) -> (usize, usize) {
+        (self.lengths.0 as usize, self.lengths.1 as usize)
+    }
+
+    /// Reserve capacity for shared and indiv buffers of at least `l_shared`
+    /// and `l_indv` bytes, so that reading records up to these sizes does
+    /// not reallocate the buffers (see [`RecordSizeStats::presize`])
+    pub fn reserve_buffers(&mut self, l_shared: usize, l_indv: usize) {
+        for (buf, n) in [
+            (&mut self.buf_shared, l_shared),
+            (&mut self.buf_indiv, l_indv),
+        ] {
+            if buf.capacity() < n {
+                buf.reserve_exact(n - buf.len());
+            }
+        }
+    }
+
+    /// Byte range of the ID in `buf_shared()`
+    pub fn id_range(&self) -> Range<usize> {
+        self.id.clone()
+    }
+
+    /// Byte ranges of the alleles (REF first) in `buf_shared()`
+    pub fn allele_ranges(&self) -> &[Range<usize>] {
+        &self.alleles
+    }
+
+    /// Layout of the FILTER field in `buf_shared()`
+    pub fn filter_layout(&self) -> FieldLayout {
+        FieldLayout {
+            key: 0,
+            typ: self.filters.0,
+            n: self.filters.1,
+            range: self.filters.2.clone(),
+        }
+    }
+
+    /// Layout of the INFO fields in `buf_shared()`, in file order
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let an_key = header.get_idx_from_dictionary_str("INFO", "AN").unwrap();
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let an = record.info_layout().find(|l| l.key == an_key).unwrap();
+    /// assert_eq!(an.n, 1);
+    /// let width = an.width();
+    /// let bytes = &record.buf_shared()[an.range];
+    /// let expected = record.info_field_numeric(an_key).next().unwrap();
+    /// assert_eq!(bytes, &expected.int_val().unwrap().to_le_bytes()[..width]);
+    /// ```
+    #[cfg(feature = "info")]
+    pub fn info_layout(&self) -> impl Iterator<Item = FieldLayout> + '_ {
+        self.info_fields().map(FieldLayout::from)
+    }
+
+    /// Layout of the FORMAT fields in `buf_indiv()`, in file order. The
+    /// `range` of each field covers the values of all samples.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let gt_key = header.get_fmt_gt_id().unwrap();
+    /// let gt = record.fmt_layout().find(|l| l.key == gt_key).unwrap();
+    /// assert_eq!(gt.range.len(), gt.width() * gt.n * header.get_samples().len());
+    /// ```
+    pub fn fmt_layout(&self) -> impl Iterator<Item = FieldLayout> + '_ {
+        self.fmt_fields().map(FieldLayout::from)
+    }
+
+    /// get chromosome offset
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::io::Write;
+    /// // read data generated by bcftools
+    /// // bcftools query -f '%CHROM\n' test.bcf | bgzip -c > test_chrom.gz
+    /// let mut chrom_str = String::new();
+    /// smart_reader("testdata/test_chrom.gz")
+    ///     .read_to_string(&mut chrom_str)
+    ///     .unwrap();
+    /// // read data via bcf-reader
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let s = read_header(&mut f).unwrap();
+    /// let header = Header::from_string(&s);
+    /// let mut record = Record::default();
+    /// let mut chrom_str2 = Vec::<u8>::new();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     write!(
+    ///         chrom_str2,
+    ///         "{}\n",
+    ///         header.get_chrname(record.chrom() as usize)
+    ///     )
+    ///     .unwrap();
+    /// }
+    /// let chrom_str2 = String::from_utf8(chrom_str2).unwrap();
+    /// // compare bcftools results and bcf-reader results
+    /// assert_eq!(chrom_str, chrom_str2);
+    /// ```
+    pub fn chrom(&self) -> i32 {
+        self.chrom
+    }
+
+    /// Get the chromosome name from the header as a shared string (see
+    /// [`Header::interned_contig`]), or `None` if the contig is not defined
+    /// in the header
+    pub fn chrom_name(&self, header: &Header) -> Option<Arc<str>> {
+        header.interned_contig(self.chrom).cloned()
+    }
+
+    /// Iterate over the names of the filters of the record as shared strings
+    /// (see [`Header::interned_string`]), `None` for keys not defined in the
+    /// header
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::sync::Arc;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let pass = header.interned("PASS").unwrap().clone();
+    /// let mut record = Record::default();
+    /// let mut n_pass = 0;
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     assert_eq!(&*record.chrom_name(&header).unwrap(), "Pf3D7_01_v3");
+    ///     for name in record.filter_names(&header) {
+    ///         let name = name.unwrap();
+    ///         // the same string as the header, compared by pointer
+    ///         if Arc::ptr_eq(&name, &pass) {
+    ///             n_pass += 1;
+    ///         }
+    ///     }
+    /// }
+    /// assert!(n_pass > 0);
+    /// ```
+    pub fn filter_names<'a>(
+        &'a self,
+        header: &'a Header,
+    ) -> impl Iterator<Item = Option<Arc<str>>> + 'a {
+        self.filters().map(|nv| {
+            let key = usize::try_from(nv.int_val()?).ok()?;
+            header.interned_string(key).cloned()
+        })
+    }
+
+    /// Iterate over the tags of the INFO fields of the record as shared
+    /// strings, in file order, `None` for keys not defined in the header
+    #[cfg(feature = "info")]
+    pub fn info_tags<'a>(
+        &'a self,
+        header: &'a Header,
+    ) -> impl Iterator<Item = Option<Arc<str>>> + 'a {
+        self.info_fields()
+            .map(|(key, ..)| header.interned_string(key).cloned())
+    }
+
+    /// Iterate over the tags of the FORMAT fields of the record as shared
+    /// strings, in file order, `None` for keys not defined in the header
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let tags: Vec<_> = record.fmt_tags(&header).map(Option::unwrap).collect();
+    /// assert_eq!(&*tags[0], "GT");
+    /// ```
+    pub fn fmt_tags<'a>(
+        &'a self,
+        header: &'a Header,
+    ) -> impl Iterator<Item = Option<Arc<str>>> + 'a {
+        self.fmt_fields()
+            .map(|(key, ..)| header.interned_string(key).cloned())
+    }
+
+    /// Returns the reference length of the record.
+    pub fn rlen(&self) -> i32 {
+        self.rlen
+    }
+
+    /// Returns the quality score of the record, if available
+    pub fn qual(&self) -> Option<f32> {
+        self.qual.float_val()
+    }
+
+    pub fn n_allele(&self) -> u16 {
+        self.n_allele
+    }
+
+    /// Header columns (0-based, in file order) of the samples of the record,
+    /// if only some samples were kept when reading it (see
+    /// [`FieldSelection::samples`]); sample `i` of the record is the sample at
+    /// column `sample_columns().unwrap()[i]` of the header
+    pub fn sample_columns(&self) -> Option<&[usize]> {
+        self.sample_columns.as_deref()
+    }
+
+    /// Names of the samples of the record in the header, in the order of
+    /// their FORMAT values: all samples of the header, or those kept when
+    /// reading the record (see [`Record::sample_columns`])
+    pub fn sample_names<'h>(&self, header: &'h Header) -> Vec<&'h str> {
+        let samples = header.get_samples();
+        match self.sample_columns() {
+            None => samples.iter().map(|s| s.as_str()).collect(),
+            Some(columns) => columns
+                .iter()
+                .filter_map(|&c| samples.get(c).map(|s| s.as_str()))
+                .collect(),
+        }
+    }
+
+    /// Number of INFO fields of the record in the data, including those not
+    /// selected by a [`FieldSelection`] (see [`Record::read_selected`]) and
+    /// those with 64-bit integer values (see [`Record::dropped_fields`])
+    pub fn n_info(&self) -> u16 {
+        self.n_info
+    }
+
+    /// Approximate memory used by the record, in bytes: the size of the
+    /// struct and the capacity of its buffers. This is used by the memory
+    /// budgets of the batch APIs (see [`RecordBatches::max_bytes`]).
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.buf_shared.capacity()
+            + self.buf_indiv.capacity()
+            + self.alleles.capacity() * std::mem::size_of::<Range<usize>>()
+            + self
+                .dropped_fields
+                .get()
+                .map_or(0, |d| d.capacity() * std::mem::size_of::<DroppedField>())
+            + self
+                .genotypes
+                .get()
+                .map_or(0, |gt| gt.values.capacity() * std::mem::size_of::<i32>())
+            + self.fmt_slots.get().map_or(0, |slots| {
+                slots.capacity() * std::mem::size_of::<RawFieldLayout>()
+            })
+    }
+
+    /// Returns an iterator over the genotype values in the record's FORMAT field.
+    /// If no FORMAT/GT field available, the returned iterator will have items.
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::io::Write;
+    /// // read data generated by bcftools
+    /// // bcftools query -f '[\t%GT]\n' test.bcf | bgzip -c > test_gt.gz
+    /// let mut gt_str = String::new();
+    /// smart_reader("testdata/test_gt.gz")
+    ///     .read_to_string(&mut gt_str)
+    ///     .unwrap();
+    /// // read data via bcf-reader
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let s = read_header(&mut f).unwrap();
+    /// let header = Header::from_string(&s);
+    /// let mut record = Record::default();
+    /// let mut gt_str2 = Vec::<u8>::new();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     for (i, bn) in record.fmt_gt(&header).enumerate() {
+    ///         let (noploidy, dot, phased, allele) = bn.gt_val();
+    ///         assert_eq!(noploidy, false); // missing ploidy
+    ///         let mut sep = '\t';
+    ///         if i % 2 == 1 {
+    ///             if phased {
+    ///                 sep = '|';
+    ///             } else {
+    ///                 sep = '/';
+    ///             }
+    ///         }
+    ///         if dot {
+    ///             write!(gt_str2, "{sep}.").unwrap();
+    ///         } else {
+    ///             write!(gt_str2, "{sep}{allele}").unwrap();
+    ///         }
+    ///     }
+    ///     write!(gt_str2, "\n").unwrap();
+    /// }
+    /// let gt_str2 = String::from_utf8(gt_str2).unwrap();
+    /// // compare bcftools results and bcf-reader results
+    /// for (a, b) in gt_str
+    ///     .split(|c| (c == '\n') || (c == '\t'))
+    ///     .zip(gt_str2.split(|c| (c == '\n') || (c == '\t')))
+    /// {
+    ///     assert_eq!(a, b);
+    /// }
+    /// ```
+    pub fn fmt_gt(&self, header: &Header) -> NumericValueIter<'_> {
+        match header.get_fmt_gt_id() {
+            None => NumericValueIter::default(),
+            Some(fmt_gt_id) => self.fmt_values(fmt_gt_id),
+        }
+    }
+
+    /// get an iterator over the values of the FORMAT field with the given
+    /// tag, like [`Record::fmt_gt`] for FORMAT/GT. The iterator is empty if
+    /// the tag is not defined in the header or not present in the record.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let dp_key = header.format_key("DP").unwrap();
+    /// assert!(record.fmt_tag(&header, "DP").eq(record.fmt_field(dp_key)));
+    /// assert_eq!(record.fmt_tag(&header, "DS").count(), 0);
+    /// ```
+    #[cfg(feature = "format")]
+    pub fn fmt_tag(&self, header: &Header, tag: &str) -> NumericValueIter<'_> {
+        match header.format_key(tag) {
+            None => NumericValueIter::default(),
+            Some(key) => self.fmt_values(key),
+        }
+    }
+
+    /// Find the FORMAT/GT field and return its type, ploidy (values per
+    /// sample) and bytes.
+    fn gt_field(&self, header: &Header) -> Option<(u8, usize, &[u8])> {
+        let fmt_gt_id = header.get_fmt_gt_id()?;
+        self.fmt_slot(fmt_gt_id)
+            .map(|e| (e.1, e.2, &self.buf_indiv[e.3.start..e.3.end]))
+    }
+
+    /// A view of the FORMAT/GT values as stored (see [`GtRaw`]), or `None`
+    /// if the record has no FORMAT/GT field or its values are not integers
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut gts = vec![0; header.get_samples().len() * 2];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let gt = record.gt_raw(&header).unwrap();
+    ///     assert_eq!((gt.typ(), gt.ploidy()), (1, 2));
+    ///     record.decode_gt_into(&header, &mut gts).unwrap();
+    ///     let phased = record.fmt_gt(&header).map(|nv| nv.gt_val().2);
+    ///     for (k, phased) in (0..gts.len()).zip(phased) {
+    ///         assert_eq!(gt.allele_at(k / 2, k % 2), gts[k]);
+    ///         assert_eq!(gt.is_phased(k / 2, k % 2), phased);
+    ///     }
+    /// }
+    /// ```
+    pub fn gt_raw(&self, header: &Header) -> Option<GtRaw<'_>> {
+        match self.gt_field(header)? {
+            (typ @ 1..=3, ploidy, buf) => Some(GtRaw { typ, ploidy, buf }),
+            _ => None,
+        }
+    }
+
+    /// Decode the GT field of all samples into `out`, without going through
+    /// `NumericValue`s, and return the ploidy (number of values per sample).
+    ///
+    /// Values of sample `i` of the record (the sample at header column
+    /// `sample_columns().unwrap()[i]` if only some samples were kept, see
+    /// [`Record::sample_columns`]) are written to
+    /// `out[i * ploidy..(i + 1) * ploidy]` as 0-based allele indices, with `-1` for a missing allele (`.`) and
+    /// `-2` for an absent allele (vector end, e.g. a haploid call in a
+    /// diploid field). Phasing is not reported. If the record has no
+    /// FORMAT/GT field, nothing is written and `Ok(0)` is returned.
+    ///
+    /// An `InvalidInput` error is returned if `out` is shorter than
+    /// `n_sample * ploidy`.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut gts = vec![0i32; header.get_samples().len() * 2];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let ploidy = record.decode_gt_into(&header, &mut gts).unwrap();
+    ///     assert_eq!(ploidy, 2);
+    ///     // compare with the generic iterator
+    ///     for (nv, fast) in record.fmt_gt(&header).zip(gts.iter()) {
+    ///         let expected = match nv.gt_val() {
+    ///             (true, _, _, _) => -2,
+    ///             (_, true, _, _) => -1,
+    ///             (_, _, _, allele) => allele as i32,
+    ///         };
+    ///         assert_eq!(expected, *fast);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Int8 GT values, by far the most common, are decoded 16 at a time with
+    /// SIMD instructions where available. Every byte value decodes the same
+    /// way as on the scalar path:
+    /// ```
+    /// use bcf_reader::*;
+    /// let samples: Vec<_> = (0..128).map(|i| format!("s{i}")).collect();
+    /// let header = Header::from_string(&format!(
+    ///     "##fileformat=VCFv4.2\n##contig=<ID=chr1>\n{}{}\n",
+    ///     "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t".to_string()
+    ///         + &samples.join("\t"),
+    /// ));
+    /// let gt_key = header.get_fmt_gt_id().unwrap() as u8;
+    /// let mut shared = vec![];
+    /// for x in [0i32, 99, 1] {
+    ///     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    /// }
+    /// shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    /// shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+    /// shared.extend(((1u32 << 24) | 128).to_le_bytes()); // 1 FORMAT field, 128 samples
+    /// shared.extend([0x07, 0x17, b'A', 0x17, b'C', 0x00]); // ID, alleles, FILTER
+    /// // diploid int8 GT going through all 256 byte values
+    /// let mut indiv = vec![0x11, gt_key, 0x21];
+    /// indiv.extend(0..=255u8);
+    /// let mut data = vec![];
+    /// data.extend((shared.len() as u32).to_le_bytes());
+    /// data.extend((indiv.len() as u32).to_le_bytes());
+    /// data.extend(shared);
+    /// data.extend(indiv);
+    ///
+    /// let mut record = Record::default();
+    /// record.read(&mut data.as_slice()).unwrap();
+    /// let mut gt = [0; 256];
+    /// assert_eq!(record.decode_gt_into(&header, &mut gt).unwrap(), 2);
+    /// for (b, v) in (0..=255u8).zip(gt) {
+    ///     let expected = match b {
+    ///         0x80 => -1,
+    ///         0x81 => -2,
+    ///         _ => (b >> 1) as i32 - 1,
+    ///     };
+    ///     assert_eq!(v, expected, "byte {b:#04x}");
+    /// }
+    /// ```
+    pub fn decode_gt_into(&self, header: &Header, out: &mut [i32]) -> error::Result<usize> {
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => return Ok(0),
+            Some(x) => x,
+        };
+        let n = self.n_sample as usize * ploidy;
+        if out.len() < n {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "output buffer too short for GT values: {} < {}",
+                    out.len(),
+                    n
+                ),
+            )
+            .into());
+        }
+        let out = &mut out[..n];
+        match typ {
+            1 => decode_gt_int8(buf, out),
+            2 => {
+                for (o, c) in out.iter_mut().zip(buf.chunks_exact(2)) {
+                    *o = match u16::from_le_bytes([c[0], c[1]]) {
+                        0x8000 => -1,
+                        0x8001 => -2,
+                        x => ((x >> 1) as i32) - 1,
+                    };
+                }
+            }
+            3 => {
+                for (o, c) in out.iter_mut().zip(buf.chunks_exact(4)) {
+                    *o = match u32::from_le_bytes([c[0], c[1], c[2], c[3]]) {
+                        0x80000000 => -1,
+                        0x80000001 => -2,
+                        x => ((x >> 1) as i32) - 1,
+                    };
+                }
+            }
+            _ => {
+                return Err(Error::MalformedRecord(format!(
+                    "unexpected type {typ} for FORMAT/GT"
+                )))
+            }
+        }
+        Ok(ploidy)
+    }
+
+    /// The FORMAT/GT values of the record, decoded by the first call and
+    /// cached until the record is read again.
+    ///
+    /// Reading a record only copies and locates its FORMAT fields, so scans
+    /// that never look at the genotypes do not pay for decoding them; code
+    /// that looks at them several times per record (e.g. in separate
+    /// functions) decodes them once. The values are decoded as by
+    /// [`decode_gt_into`](Self::decode_gt_into). An [`Error::Io`] of kind
+    /// `InvalidInput` is returned if the cached values were decoded with a
+    /// header with another GT key.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut gts = vec![0; header.get_samples().len() * 2];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let genotypes = record.genotypes(&header).unwrap();
+    ///     assert_eq!(genotypes.ploidy(), 2);
+    ///     record.decode_gt_into(&header, &mut gts).unwrap();
+    ///     assert_eq!(genotypes.values(), gts);
+    ///     assert_eq!(genotypes.sample(1), &gts[2..4]);
+    ///     // decoded once
+    ///     assert!(std::ptr::eq(genotypes, record.genotypes(&header).unwrap()));
+    /// }
+    /// ```
+    pub fn genotypes(&self, header: &Header) -> error::Result<&DecodedGenotypes> {
+        let gt_key = header.get_fmt_gt_id();
+        if self.genotypes.get().is_none() {
+            let ploidy = self.gt_field(header).map_or(0, |(_, ploidy, _)| ploidy);
+            let mut values = vec![0; self.n_sample as usize * ploidy];
+            self.decode_gt_into(header, &mut values)?;
+            // a value set concurrently by another thread is the same
+            let _ = self.genotypes.set(DecodedGenotypes {
+                gt_key,
+                ploidy,
+                values,
+            });
+        }
+        let genotypes = self.genotypes.get().expect("genotypes are set");
+        if genotypes.gt_key != gt_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the genotypes were decoded with a header with another GT key",
+            )
+            .into());
+        }
+        Ok(genotypes)
+    }
+
+    /// [`decode_gt_into`](Self::decode_gt_into) without bounds and sentinel
+    /// checks (requires the `unchecked` feature)
+    ///
+    /// Every GT value `x` is decoded as `(x >> 1) - 1`, so missing alleles
+    /// (`.`) are `-1` but end-of-vector values (e.g. of a haploid call in a
+    /// diploid field) are not mapped to `-2` and decode as large allele
+    /// indices. Meant for files that are validated once (e.g. with
+    /// [`validate::validate_file`]) and then scanned many times.
+    ///
+    /// # Safety
+    /// `out` must hold at least `n_sample * ploidy` values, and the FORMAT/GT
+    /// field must have an integer type (1, 2 or 3), which are not checked.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut checked = vec![0; header.get_samples().len() * 2];
+    /// let mut unchecked = checked.clone();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     record.decode_gt_into(&header, &mut checked).unwrap();
+    ///     // SAFETY: test.bcf has a diploid int8 GT field in every record
+    ///     let ploidy = unsafe { record.decode_gt_into_unchecked(&header, &mut unchecked) };
+    ///     assert_eq!(ploidy, 2);
+    ///     assert_eq!(checked, unchecked);
+    /// }
+    /// ```
+    #[cfg(feature = "unchecked")]
+    pub unsafe fn decode_gt_into_unchecked(&self, header: &Header, out: &mut [i32]) -> usize {
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => return 0,
+            Some(x) => x,
+        };
+        let n = self.n_sample as usize * ploidy;
+        // SAFETY: `out` holds `n` values and the GT type is an integer type,
+        // as guaranteed by the caller, and `buf` holds `n` values of that
+        // type, as checked when the record was read
+        unsafe {
+            let out = out.get_unchecked_mut(..n);
+            match typ {
+                1 => {
+                    for (i, o) in out.iter_mut().enumerate() {
+                        *o = ((*buf.get_unchecked(i) >> 1) as i32) - 1;
+                    }
+                }
+                _ => {
+                    for (i, o) in out.iter_mut().enumerate() {
+                        let x = typed_int_unchecked(typ, buf, i) as u32;
+                        let x = if typ == 2 { x & 0xffff } else { x };
+                        *o = (x >> 1) as i32 - 1;
+                    }
+                }
+            }
+        }
+        ploidy
+    }
+
+    /// Returns a mask with one bit per sample, set if the genotype of the
+    /// sample is fully missing (all alleles are `.` or absent).
+    ///
+    /// The mask is computed in a single pass over the GT bytes. If the record
+    /// has no FORMAT/GT field, all samples are reported as missing.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let mask = record.gt_missing_mask(&header);
+    ///     assert_eq!(mask.len(), header.get_samples().len());
+    ///     // compare with the generic iterator
+    ///     let gts: Vec<_> = record.fmt_gt(&header).map(|nv| nv.gt_val()).collect();
+    ///     for (i, sample_gts) in gts.chunks(2).enumerate() {
+    ///         let missing = sample_gts.iter().all(|(noploidy, dot, _, _)| *noploidy || *dot);
+    ///         assert_eq!(mask.get(i), missing);
+    ///     }
+    /// }
+    /// ```
+    pub fn gt_missing_mask(&self, header: &Header) -> SampleMask {
+        let n_sample = self.n_sample as usize;
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => return SampleMask::new(n_sample, true),
+            Some(x) => x,
+        };
+        let mut mask = SampleMask::new(n_sample, false);
+        for i in 0..n_sample {
+            let missing = (i * ploidy..(i + 1) * ploidy)
+                .all(|j| raw_int_at(typ, buf, j).is_none_or(|x| (x >> 1) == 0));
+            if missing {
+                mask.set(i, true);
+            }
+        }
+        mask
+    }
+
+    /// Append the genotypes of all samples to `out` as 2-bit [`Hardcall`]s,
+    /// four samples per byte with the first sample in the lowest bits, and
+    /// return the number of bytes appended (`ceil(n_sample / 4)`).
+    ///
+    /// This is the layout of a variant in a SNP-major PLINK 1 `.bed` file,
+    /// including the zero bits after the last sample. All ALT alleles are
+    /// treated as one; a genotype with any missing allele is missing, while
+    /// absent alleles (e.g. of a haploid call) are ignored. The codes are
+    /// computed directly from the GT bytes; if the record has no FORMAT/GT
+    /// field, all samples are missing.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let n_sample = header.get_samples().len();
+    /// // a .bed file starts with magic bytes
+    /// let mut bed = vec![0x6c, 0x1b, 0x01];
+    /// let mut record = Record::default();
+    /// let mut gts = vec![0; n_sample * 2];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let start = bed.len();
+    ///     assert_eq!(record.pack_hardcalls(&header, &mut bed), n_sample.div_ceil(4));
+    ///     // compare with the decoded genotypes
+    ///     record.decode_gt_into(&header, &mut gts).unwrap();
+    ///     for (i, gt) in gts.chunks(2).enumerate() {
+    ///         let expected = match gt {
+    ///             [-1, _] | [_, -1] => Hardcall::Missing,
+    ///             [0, 0] | [0, -2] => Hardcall::HomRef,
+    ///             [0, _] | [_, 0] => Hardcall::Het,
+    ///             _ => Hardcall::HomAlt,
+    ///         };
+    ///         assert_eq!(Hardcall::unpack(&bed[start..], i), expected);
+    ///     }
+    /// }
+    /// ```
+    pub fn pack_hardcalls(&self, header: &Header, out: &mut Vec<u8>) -> usize {
+        let n_sample = self.n_sample as usize;
+        let n_bytes = n_sample.div_ceil(4);
+        let start = out.len();
+        // all samples missing, with zero padding bits
+        out.resize(start + n_bytes, 0x55);
+        if !n_sample.is_multiple_of(4) {
+            out[start + n_bytes - 1] &= (1 << (2 * (n_sample % 4))) - 1;
+        }
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => return n_bytes,
+            Some(x) => x,
+        };
+        let packed = &mut out[start..];
+        for i in 0..n_sample {
+            let (mut n_ref, mut n_alt) = (0, 0);
+            let mut missing = false;
+            for j in i * ploidy..(i + 1) * ploidy {
+                match raw_int_at(typ, buf, j).map(|x| x >> 1) {
+                    None => {}
+                    Some(0) => missing = true,
+                    Some(1) => n_ref += 1,
+                    Some(_) => n_alt += 1,
+                }
+            }
+            let call = match (n_ref, n_alt) {
+                _ if missing => Hardcall::Missing,
+                (0, 0) => Hardcall::Missing,
+                (_, 0) => Hardcall::HomRef,
+                (0, _) => Hardcall::HomAlt,
+                _ => Hardcall::Het,
+            };
+            let shift = 2 * (i % 4);
+            packed[i / 4] = (packed[i / 4] & !(0b11 << shift)) | ((call as u8) << shift);
+        }
+        n_bytes
+    }
+
+    /// Tally allele occurrences (AC) and the total number of called alleles
+    /// (AN) directly from the GT bytes, skipping missing alleles.
+    ///
+    /// Allele indices beyond `n_allele` are ignored. If the record has no
+    /// FORMAT/GT field, all counts are zero.
+    ///
+    /// Example:
+    #[cfg_attr(feature = "info", doc = "```")]
+    #[cfg_attr(not(feature = "info"), doc = "```ignore")]
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let ac_key = header.get_idx_from_dictionary_str("INFO", "AC").unwrap();
+    /// let an_key = header.get_idx_from_dictionary_str("INFO", "AN").unwrap();
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let counts = record.allele_counts(&header);
+    ///     // compare with INFO/AC and INFO/AN filled by bcftools
+    ///     let ac: Vec<u32> = record
+    ///         .info_field_numeric(ac_key)
+    ///         .map(|nv| nv.int_val().unwrap())
+    ///         .collect();
+    ///     let an = record.info_field_numeric(an_key).next().unwrap().int_val().unwrap();
+    ///     assert_eq!(counts.ac(), &ac[..]);
+    ///     assert_eq!(counts.an(), an);
+    /// }
+    /// ```
+    pub fn allele_counts(&self, header: &Header) -> AlleleCounts {
+        let mut counts = AlleleCounts::default();
+        self.allele_counts_into(header, &mut counts);
+        counts
+    }
+
+    /// [`allele_counts`](Self::allele_counts) reusing the allocation of
+    /// `counts`.
+    ///
+    /// Int8 GT values, the common case, are tallied with a histogram of the
+    /// byte values that is then folded into allele counts, without a branch
+    /// per value.
+    pub fn allele_counts_into(&self, header: &Header, counts: &mut AlleleCounts) {
+        counts.counts.clear();
+        counts.counts.resize(self.n_allele as usize, 0);
+        counts.an = 0;
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => return,
+            Some(x) => x,
+        };
+        let n = ploidy * self.n_sample as usize;
+        if typ == 1 {
+            let mut hist = [0u32; 256];
+            for &b in &buf[..n] {
+                hist[b as usize] += 1;
+            }
+            // skip 0x00-0x01 (missing allele) and 0x80-0x81 (sentinels)
+            for (b, &h) in hist.iter().enumerate().skip(2) {
+                if h == 0 || b == 0x80 || b == 0x81 {
+                    continue;
+                }
+                if let Some(c) = counts.counts.get_mut((b >> 1) - 1) {
+                    *c += h;
+                    counts.an += h;
+                }
+            }
+            return;
+        }
+        for j in 0..n {
+            match raw_int_at(typ, buf, j) {
+                Some(x) if (x >> 1) > 0 => {
+                    if let Some(c) = counts.counts.get_mut((x >> 1) as usize - 1) {
+                        *c += 1;
+                        counts.an += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Minor allele frequency across samples, computed from the genotypes
+    /// (see [`AlleleCounts::maf`]).
+    ///
+    /// With [`MafMode::Minor`], multiallelic sites are folded to the least
+    /// frequent observed allele; [`MafMode::Allele`] gives the minor allele
+    /// frequency of one allele. Returns `None` if no allele is called.
+    ///
+    /// Example:
+    #[cfg_attr(feature = "info", doc = "```")]
+    #[cfg_attr(not(feature = "info"), doc = "```ignore")]
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test3.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let ac_key = header.get_idx_from_dictionary_str("INFO", "AC").unwrap();
+    /// let an_key = header.get_idx_from_dictionary_str("INFO", "AN").unwrap();
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let maf = record.maf(&header, MafMode::Minor).unwrap();
+    ///     assert!(maf <= 0.5);
+    ///     if record.n_allele() == 2 {
+    ///         let ac = record.info_field_numeric(ac_key).next().unwrap().int_val().unwrap();
+    ///         let an = record.info_field_numeric(an_key).next().unwrap().int_val().unwrap();
+    ///         let af = ac as f32 / an as f32;
+    ///         assert!((maf - af.min(1.0 - af)).abs() < 1e-6);
+    ///         assert_eq!(record.maf(&header, MafMode::Allele(1)), Some(maf));
+    ///     }
+    /// }
+    ///
+    /// // a multiallelic site where the last ALT allele is not observed
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// while record.read(&mut f).is_ok() && record.pos() != 14039 {}
+    /// assert_eq!(record.allele_counts(&header).counts(), [118, 8, 8, 2, 0]);
+    /// assert_eq!(record.maf(&header, MafMode::Minor), Some(2.0 / 136.0));
+    /// assert_eq!(record.maf(&header, MafMode::Allele(1)), Some(8.0 / 136.0));
+    /// assert_eq!(record.maf(&header, MafMode::Allele(0)), Some(18.0 / 136.0));
+    /// assert_eq!(record.maf(&header, MafMode::Allele(4)), Some(0.0));
+    /// assert_eq!(record.maf(&header, MafMode::Allele(5)), None);
+    /// ```
+    pub fn maf(&self, header: &Header, mode: MafMode) -> Option<f32> {
+        self.allele_counts(header).maf(mode)
+    }
+
+    /// Count the number of samples in each genotype class (hom-ref, het,
+    /// hom-alt, missing) in a single pass over the GT bytes.
+    ///
+    /// Haploid calls are counted as homozygous. A genotype with any missing
+    /// allele is counted as missing. If the record has no FORMAT/GT field, all
+    /// samples are counted as missing.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let counts = record.genotype_class_counts(&header);
+    ///     assert_eq!(
+    ///         counts.n_called() + counts.missing,
+    ///         header.get_samples().len() as u32
+    ///     );
+    ///     // compare with the generic iterator
+    ///     let gts: Vec<_> = record.fmt_gt(&header).map(|nv| nv.gt_val()).collect();
+    ///     let het = gts
+    ///         .chunks(2)
+    ///         .filter(|x| !x[0].1 && !x[1].1 && x[0].3 != x[1].3)
+    ///         .count();
+    ///     assert_eq!(counts.het, het as u32);
+    /// }
+    /// ```
+    pub fn genotype_class_counts(&self, header: &Header) -> GenotypeClassCounts {
+        self.genotype_class_counts_with_ploidy(header).0
+    }
+
+    /// The genotype class counts, and whether all called genotypes are
+    /// diploid
+    fn genotype_class_counts_with_ploidy(&self, header: &Header) -> (GenotypeClassCounts, bool) {
+        let mut counts = GenotypeClassCounts::default();
+        let (typ, ploidy, buf) = match self.gt_field(header) {
+            None => {
+                counts.missing = self.n_sample;
+                return (counts, true);
+            }
+            Some(x) => x,
+        };
+        let mut diploid = true;
+        for i in 0..self.n_sample as usize {
+            let mut first = None;
+            let mut missing = false;
+            let mut het = false;
+            let mut n_alleles = 0;
+            for j in i * ploidy..(i + 1) * ploidy {
+                match raw_int_at(typ, buf, j) {
+                    // end of vector for samples with a lower ploidy
+                    None if j > i * ploidy => break,
+                    Some(x) if (x >> 1) > 0 => {
+                        let allele = x >> 1;
+                        n_alleles += 1;
+                        match first {
+                            None => first = Some(allele),
+                            Some(a) if a != allele => het = true,
+                            _ => {}
+                        }
+                    }
+                    _ => {
+                        missing = true;
+                        break;
+                    }
+                }
+            }
+            if !missing && first.is_some() && n_alleles != 2 {
+                diploid = false;
+            }
+            match first {
+                _ if missing => counts.missing += 1,
+                None => counts.missing += 1,
+                _ if het => counts.het += 1,
+                Some(1) => counts.hom_ref += 1,
+                Some(_) => counts.hom_alt += 1,
+            }
+        }
+        (counts, diploid)
     }
 
-    /// Returns an iterator over the genotype values in the record's FORMAT field.
-    /// If no FORMAT/GT field available, the returned iterator will have items.
+    /// Exact Hardy-Weinberg equilibrium p-value computed from the genotype
+    /// class counts (see [`GenotypeClassCounts::hwe_pvalue`]).
+    ///
+    /// Returns `None` for sites that are not biallelic, without called
+    /// genotypes, or with a called genotype that is not diploid (haploid,
+    /// polyploid or mixed-ploidy sites), for which the diploid test is not
+    /// meaningful.
+    ///
     /// Example:
     /// ```
     /// use bcf_reader::*;
-    /// use std::io::Write;
-    /// // read data generated by bcftools
-    /// // bcftools query -f '[\t%GT]\n' test.bcf | bgzip -c > test_gt.gz
-    /// let mut gt_str = String::new();
-    /// smart_reader("testdata/test_gt.gz")
-    ///     .read_to_string(&mut gt_str)
-    ///     .unwrap();
-    /// // read data via bcf-reader
-    /// let mut f = smart_reader("testdata/test.bcf");
-    /// let s = read_header(&mut f);
-    /// let header = Header::from_string(&s);
+    /// let mut f = smart_reader("testdata/test3.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
     /// let mut record = Record::default();
-    /// let mut gt_str2 = Vec::<u8>::new();
+    /// let mut n_out_of_hwe = 0;
     /// while let Ok(_) = record.read(&mut f) {
-    ///     for (i, bn) in record.fmt_gt(&header).enumerate() {
-    ///         let (noploidy, dot, phased, allele) = bn.gt_val();
-    ///         assert_eq!(noploidy, false); // missing ploidy
-    ///         let mut sep = '\t';
-    ///         if i % 2 == 1 {
-    ///             if phased {
-    ///                 sep = '|';
-    ///             } else {
-    ///                 sep = '/';
+    ///     match record.hwe_pvalue(&header) {
+    ///         Some(p) => {
+    ///             assert!(p > 0.0 && p <= 1.0);
+    ///             if p < 1e-6 {
+    ///                 n_out_of_hwe += 1;
     ///             }
     ///         }
-    ///         if dot {
-    ///             write!(gt_str2, "{sep}.").unwrap();
-    ///         } else {
-    ///             write!(gt_str2, "{sep}{allele}").unwrap();
-    ///         }
+    ///         None => assert_ne!(record.n_allele(), 2),
     ///     }
-    ///     write!(gt_str2, "\n").unwrap();
-    /// }
-    /// let gt_str2 = String::from_utf8(gt_str2).unwrap();
-    /// // compare bcftools results and bcf-reader results
-    /// for (a, b) in gt_str
-    ///     .split(|c| (c == '\n') || (c == '\t'))
-    ///     .zip(gt_str2.split(|c| (c == '\n') || (c == '\t')))
-    /// {
-    ///     assert_eq!(a, b);
     /// }
+    /// // simulated data are expected to be in equilibrium
+    /// assert_eq!(n_out_of_hwe, 0);
+    ///
+    /// // no p-value with a haploid call
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1>\n",
+    ///     "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\n",
+    /// ));
+    /// let gt_key = header.get_fmt_gt_id().unwrap() as u8;
+    /// let record_with_gt = |gt: [u8; 4]| {
+    ///     let mut shared = vec![];
+    ///     for x in [0i32, 99, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+    ///     shared.extend(((1u32 << 24) | 2).to_le_bytes()); // 1 FORMAT field, 2 samples
+    ///     shared.extend([0x07, 0x17, b'A', 0x17, b'C', 0x00]); // ID, alleles, FILTER
+    ///     let mut indiv = vec![0x11, gt_key, 0x21];
+    ///     indiv.extend(gt);
+    ///     let mut data = vec![];
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend((indiv.len() as u32).to_le_bytes());
+    ///     data.extend(shared);
+    ///     data.extend(indiv);
+    ///     let mut record = Record::default();
+    ///     record.read(&mut data.as_slice()).unwrap();
+    ///     record
+    /// };
+    /// // 0/1 and 1/1
+    /// assert!(record_with_gt([2, 4, 4, 4]).hwe_pvalue(&header).is_some());
+    /// // 0/1 and haploid 1
+    /// assert_eq!(record_with_gt([2, 4, 4, 0x81]).hwe_pvalue(&header), None);
     /// ```
-    pub fn fmt_gt(&self, header: &Header) -> NumericValueIter<'_> {
-        let mut it = NumericValueIter::default();
-        match header.get_fmt_gt_id() {
-            None => it,
-            Some(fmt_gt_id) => {
-                // find the right field for gt
-                self.gt.iter().for_each(|e| {
-                    if e.0 == fmt_gt_id {
-                        it = iter_typed_integers(
-                            e.1,
-                            e.2 as usize * self.n_sample as usize,
-                            &self.buf_indiv[e.3.start..e.3.end],
-                        );
-                    }
-                });
-                it
-            }
+    pub fn hwe_pvalue(&self, header: &Header) -> Option<f64> {
+        if self.n_allele != 2 {
+            return None;
+        }
+        match self.genotype_class_counts_with_ploidy(header) {
+            (counts, true) => counts.hwe_pvalue(),
+            (_, false) => None,
         }
     }
 
     /// Returns an iterator over all values for a field in the record's FORMATs (indiv).
     ///
+    /// The FORMAT fields are located by the first lookup of a FORMAT key
+    /// (including GT) and cached until the record is read again, so that
+    /// accessing several tags of a record does not walk the fields each
+    /// time:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     // numeric fields
+    ///     for layout in record.fmt_layout().filter(|l| l.typ != 7) {
+    ///         let values: Vec<_> = record.fmt_field(layout.key).collect();
+    ///         assert_eq!(values.len(), layout.n * header.get_samples().len());
+    ///         let expected = iter_typed_integers(layout.typ, values.len(), &record.buf_indiv()[layout.range]);
+    ///         assert!(values.into_iter().eq(expected));
+    ///     }
+    /// }
+    /// ```
+    ///
     /// Example:
     /// ```
     /// use bcf_reader::*;
@@ -927,7 +6157,7 @@ impl Record {
     ///     .unwrap();
     /// // read data via bcf-reader
     /// let mut f = smart_reader("testdata/test.bcf");
-    /// let s = read_header(&mut f);
+    /// let s = read_header(&mut f).unwrap();
     /// let header = Header::from_string(&s);
     /// let mut record = Record::default();
     /// let mut ad_str2 = Vec::<u8>::new();
@@ -959,21 +6189,99 @@ impl Record {
     ///     assert_eq!(a, b);
     /// }
     /// ```
+    #[cfg(feature = "format")]
     pub fn fmt_field(&self, fmt_key: usize) -> NumericValueIter<'_> {
-        // default iterator
-        let mut it = NumericValueIter::default();
-
-        // find the right field for gt
-        self.gt.iter().for_each(|e| {
-            if e.0 == fmt_key {
-                it = iter_typed_integers(
-                    e.1,
-                    e.2 as usize * self.n_sample as usize,
-                    &self.buf_indiv[e.3.start..e.3.end],
-                );
+        self.fmt_values(fmt_key)
+    }
+
+    /// The values of the FORMAT field `fmt_key` of all samples, see
+    /// [`Record::fmt_field`]
+    fn fmt_values(&self, fmt_key: usize) -> NumericValueIter<'_> {
+        match self.fmt_slot(fmt_key) {
+            // default iterator
+            None => NumericValueIter::default(),
+            Some((_, typ, n, rng)) => iter_typed_integers(
+                typ,
+                n * self.n_sample as usize,
+                &self.buf_indiv[rng.start..rng.end],
+            ),
+        }
+    }
+
+    /// Append the values of the FORMAT field `fmt_key` to `out`, `width`
+    /// values per sample, converted to `T` (`i32` or `f32`).
+    ///
+    /// Missing values, and the values after the end of shorter vectors, are
+    /// [`ColumnValue::MISSING`]; if the record does not have the field, all
+    /// values are missing. An error is returned, and nothing is appended, if
+    /// the field has more than `width` values per sample or values that
+    /// cannot be converted to `T`.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let dp_key = header.get_idx_from_dictionary_str("FORMAT", "DP").unwrap();
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let mut dp = Vec::<i32>::new();
+    /// record.fmt_field_into(dp_key, 1, &mut dp).unwrap();
+    /// let expected: Vec<_> = record
+    ///     .fmt_field(dp_key)
+    ///     .map(|nv| nv.int_val().map_or(i32::MIN, |x| x as i32))
+    ///     .collect();
+    /// assert_eq!(dp, expected);
+    /// // as floats, with NaN for missing values
+    /// let mut dp = Vec::<f32>::new();
+    /// record.fmt_field_into(dp_key, 1, &mut dp).unwrap();
+    /// for (x, y) in dp.iter().zip(&expected) {
+    ///     assert!(*y == i32::MIN && x.is_nan() || *x == *y as f32);
+    /// }
+    /// ```
+    #[cfg(feature = "format")]
+    pub fn fmt_field_into<T: ColumnValue>(
+        &self,
+        fmt_key: usize,
+        width: usize,
+        out: &mut Vec<T>,
+    ) -> error::Result<()> {
+        let n_sample = self.n_sample as usize;
+        let field = self.fmt_slot(fmt_key);
+        if let Some((_, typ, n, _)) = field {
+            if !T::accepts(typ) {
+                return Err(Error::UnexpectedType {
+                    expected: T::EXPECTED,
+                    found: typ,
+                    descriptor: descriptor_byte(typ, n),
+                });
             }
-        });
-        it
+            if n > width {
+                return Err(Error::UnexpectedCount {
+                    expected: width,
+                    found: n,
+                });
+            }
+        }
+        let start = out.len();
+        out.resize(start + n_sample * width, T::MISSING);
+        let (typ, n, rng) = match field {
+            Some((_, typ, n, rng)) if n > 0 => (typ, n, rng),
+            _ => return Ok(()),
+        };
+        let size = typ_width(typ);
+        let buf = &self.buf_indiv[rng.start..rng.end];
+        for (o, sample) in out[start..]
+            .chunks_exact_mut(width)
+            .zip(buf.chunks_exact(n * size))
+        {
+            for (o, v) in o.iter_mut().zip(sample.chunks_exact(size)) {
+                if let Some(x) = T::from_bytes(typ, v) {
+                    *o = x;
+                }
+            }
+        }
+        Ok(())
     }
 
     /// get 0-based position (bp) value
@@ -988,7 +6296,7 @@ impl Record {
     ///     .unwrap();
     /// // read data via bcf-reader
     /// let mut f = smart_reader("testdata/test.bcf");
-    /// let _s = read_header(&mut f);
+    /// let _s = read_header(&mut f).unwrap();
     /// let mut record = Record::default();
     /// let mut pos_str2 = Vec::<u8>::new();
     /// use std::io::Write;
@@ -1015,7 +6323,7 @@ impl Record {
     ///     .unwrap();
     /// // read data via bcf-reader
     /// let mut f = smart_reader("testdata/test.bcf");
-    /// let _s = read_header(&mut f);
+    /// let _s = read_header(&mut f).unwrap();
     /// let mut record = Record::default();
     /// let mut allele_str2 = Vec::<u8>::new();
     /// while let Ok(_) = record.read(&mut f) {
@@ -1034,6 +6342,197 @@ impl Record {
         &self.alleles[..]
     }
 
+    /// Returns the bytes of the ID column, without UTF-8 validation.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test3.bcf");
+    /// let _s = read_header(&mut f).unwrap();
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// assert_eq!(record.id_bytes(), b"0");
+    /// ```
+    pub fn id_bytes(&self) -> &[u8] {
+        &self.buf_shared[self.id.start..self.id.end]
+    }
+
+    /// Returns the bytes of the `i`-th allele (0 for REF), without UTF-8
+    /// validation. Panics if `i` is not less than `n_allele`.
+    pub fn allele_bytes(&self, i: usize) -> &[u8] {
+        let rng = &self.alleles[i];
+        &self.buf_shared[rng.start..rng.end]
+    }
+
+    /// Returns the bytes of the REF allele, without UTF-8 validation.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // read data generated by bcftools
+    /// // bcftools query -f '%REF,%ALT\n' test.bcf | bgzip -c > test_allele.gz
+    /// let mut allele_str = String::new();
+    /// smart_reader("testdata/test_allele.gz")
+    ///     .read_to_string(&mut allele_str)
+    ///     .unwrap();
+    /// // read data via bcf-reader
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let _s = read_header(&mut f).unwrap();
+    /// let mut record = Record::default();
+    /// let mut allele_str2 = Vec::<u8>::new();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     allele_str2.extend(record.ref_allele_bytes());
+    ///     for alt in record.alt_allele_bytes() {
+    ///         allele_str2.push(b',');
+    ///         allele_str2.extend(alt);
+    ///     }
+    ///     allele_str2.push(b'\n');
+    /// }
+    /// assert_eq!(allele_str.as_bytes(), allele_str2);
+    /// ```
+    pub fn ref_allele_bytes(&self) -> &[u8] {
+        match self.alleles.first() {
+            Some(rng) => &self.buf_shared[rng.start..rng.end],
+            None => &[],
+        }
+    }
+
+    /// Returns an iterator over the bytes of the ALT alleles, without UTF-8
+    /// validation.
+    pub fn alt_allele_bytes(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.alleles
+            .iter()
+            .skip(1)
+            .map(|rng| &self.buf_shared[rng.start..rng.end])
+    }
+
+    /// Canonical `chrom-pos-ref-alt` keys, one per ALT allele, with a 1-based
+    /// position as in VCF.
+    ///
+    /// If `trim` is true, the bases shared by REF and ALT are trimmed (first
+    /// from the end, then from the start, adjusting the position), keeping at
+    /// least one base in each allele, so that the same variant gets the same
+    /// key when represented differently in multi-allelic records.
+    ///
+    /// An error is returned if the contig is not defined in the header.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let keys = record.variant_key(&header, false).unwrap();
+    ///     assert_eq!(keys.len(), record.alleles().len() - 1);
+    ///     if record.pos() == 9186 {
+    ///         assert_eq!(keys, ["Pf3D7_01_v3-9187-CGT-GGT", "Pf3D7_01_v3-9187-CGT-C"]);
+    ///         let keys = record.variant_key(&header, true).unwrap();
+    ///         assert_eq!(keys, ["Pf3D7_01_v3-9187-C-G", "Pf3D7_01_v3-9187-CGT-C"]);
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn variant_key(&self, header: &Header, trim: bool) -> error::Result<Vec<String>> {
+        let chrom = match header.rid_to_name(self.chrom) {
+            Some(name) => name,
+            None => Err(Error::MalformedRecord(format!(
+                "contig key {} of the record is not defined in the header",
+                self.chrom
+            )))?,
+        };
+        let ref_allele = self.ref_allele_bytes();
+        let keys = self
+            .alt_allele_bytes()
+            .map(|alt| {
+                let (pos, r, a) = match trim {
+                    true => trim_allele_pair(self.pos, ref_allele, alt),
+                    false => (self.pos, ref_allele, alt),
+                };
+                format!(
+                    "{}-{}-{}-{}",
+                    chrom,
+                    pos + 1,
+                    String::from_utf8_lossy(r),
+                    String::from_utf8_lossy(a)
+                )
+            })
+            .collect();
+        Ok(keys)
+    }
+
+    /// Stable 64-bit hashes of the variant keys (one per ALT allele, see
+    /// [`Record::variant_key`]), for building dedup/join maps.
+    ///
+    /// The hash is [`fnv1a_64`] of the UTF-8 bytes of the key. If
+    /// `with_genotypes` is true, the key is followed by a tab and the VCF text
+    /// of the genotypes of all samples separated by tabs (e.g.
+    /// `chr1-100-A-T\t0|1\t./.`), so that the hash does not depend on how the
+    /// genotypes are encoded in the file. This definition will not change
+    /// between versions of the crate.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let hashes = record.variant_hash(&header, true, false).unwrap();
+    /// assert_eq!(hashes, [fnv1a_64(b"Pf3D7_01_v3-1119-T-A")]);
+    /// assert_eq!(hashes, [0xf2b07e2c08a9607f]);
+    /// let with_gt = record.variant_hash(&header, true, true).unwrap();
+    /// assert_ne!(hashes, with_gt);
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn variant_hash(
+        &self,
+        header: &Header,
+        trim: bool,
+        with_genotypes: bool,
+    ) -> error::Result<Vec<u64>> {
+        let inputs = self.variant_hash_inputs(header, trim, with_genotypes)?;
+        Ok(inputs.iter().map(|s| fnv1a_64(s.as_bytes())).collect())
+    }
+
+    /// Stable 128-bit version of [`Record::variant_hash`], using
+    /// [`fnv1a_128`] on the same input, for keys sets large enough that
+    /// 64-bit collisions are a concern.
+    #[cfg(feature = "strings")]
+    pub fn variant_hash128(
+        &self,
+        header: &Header,
+        trim: bool,
+        with_genotypes: bool,
+    ) -> error::Result<Vec<u128>> {
+        let inputs = self.variant_hash_inputs(header, trim, with_genotypes)?;
+        Ok(inputs.iter().map(|s| fnv1a_128(s.as_bytes())).collect())
+    }
+
+    /// The strings hashed by `variant_hash`/`variant_hash128`
+    #[cfg(feature = "strings")]
+    fn variant_hash_inputs(
+        &self,
+        header: &Header,
+        trim: bool,
+        with_genotypes: bool,
+    ) -> error::Result<Vec<String>> {
+        let mut keys = self.variant_key(header, trim)?;
+        if with_genotypes {
+            let mut gts = String::new();
+            if let Some((typ, ploidy, buf)) = self.gt_field(header) {
+                // a GT without values (type 0 or no allele) has no data
+                let sample_width = typ_width(typ) * ploidy;
+                for sample_buf in buf.chunks_exact(sample_width.max(1)) {
+                    gts.push('\t');
+                    gts.push_str(&decode_genotype(typ, ploidy, sample_buf).to_string());
+                }
+            }
+            keys.iter_mut().for_each(|k| k.push_str(&gts));
+        }
+        Ok(keys)
+    }
+
     /// Return an iterator of numeric values for an INFO/xxx field.
     /// If the key is not found, the returned iterator will have a zero length.
     ///
@@ -1049,7 +6548,7 @@ impl Record {
     ///     .unwrap();
     /// // read data via bcf-reader
     /// let mut f = smart_reader("testdata/test2.bcf");
-    /// let s = read_header(&mut f);
+    /// let s = read_header(&mut f).unwrap();
     /// let header = Header::from_string(&s);
     /// let mut record = Record::default();
     /// let mut info_af_str2 = Vec::<u8>::new();
@@ -1064,20 +6563,21 @@ impl Record {
     /// let filter_str2 = String::from_utf8(info_af_str2).unwrap();
     /// assert_eq!(info_af_str, filter_str2);
     /// ```
-    pub fn info_field_numeric(&self, info_key: usize) -> NumericValueIter {
+    #[cfg(feature = "info")]
+    pub fn info_field_numeric(&self, info_key: usize) -> NumericValueIter<'_> {
         // default
         let mut it = NumericValueIter {
-            reader: std::io::Cursor::new(&[0u8; 0]),
+            buf: &[],
             typ: 0,
             len: 0,
             cur: 0,
         };
-        for (key, typ, n, rng) in self.info.iter() {
-            if *key == info_key {
+        for (key, typ, n, rng) in self.info_fields() {
+            if key == info_key {
                 it = NumericValueIter {
-                    reader: std::io::Cursor::new(&self.buf_shared[rng.start..rng.end]),
-                    typ: *typ,
-                    len: *n,
+                    buf: &self.buf_shared[rng.start..rng.end],
+                    typ,
+                    len: n,
                     cur: 0,
                 };
                 break;
@@ -1087,20 +6587,64 @@ impl Record {
     }
 
     /// Return str value for an INFO/xxx field.
-    /// If the key is not found or data type is not string, then return None.
+    /// If the key is not found, data type is not string or the value is not
+    /// valid UTF-8, then return None (see [`Record::info_field_bytes`] and
+    /// [`Record::info_field_str_lossy`]).
+    #[cfg(all(feature = "info", feature = "strings"))]
     pub fn info_field_str(&self, info_key: usize) -> Option<&str> {
-        let mut res = None;
-        for (key, typ, _n, rng) in self.info.iter() {
-            if *key == info_key {
-                if *typ != 0x7 {
-                    return None;
-                }
-                let s = std::str::from_utf8(&self.buf_shared[rng.start..rng.end]).unwrap();
-                res = Some(s);
-                break;
+        std::str::from_utf8(self.info_field_bytes(info_key)?).ok()
+    }
+
+    /// Return the bytes of a string INFO/xxx field, without UTF-8
+    /// validation. If the key is not found or data type is not string, then
+    /// return None.
+    #[cfg(feature = "info")]
+    pub fn info_field_bytes(&self, info_key: usize) -> Option<&[u8]> {
+        let (_, typ, _, rng) = self.info_fields().find(|f| f.0 == info_key)?;
+        (typ == 0x7).then(|| &self.buf_shared[rng.start..rng.end])
+    }
+
+    /// Return the value of a string INFO/xxx field, with invalid UTF-8
+    /// sequences replaced by U+FFFD. If the key is not found or data type is
+    /// not string, then return None.
+    #[cfg(all(feature = "info", feature = "strings"))]
+    pub fn info_field_str_lossy(&self, info_key: usize) -> Option<std::borrow::Cow<'_, str>> {
+        Some(String::from_utf8_lossy(self.info_field_bytes(info_key)?))
+    }
+
+    /// Check that the strings of the record (ID, alleles and string
+    /// INFO/FORMAT values) are valid UTF-8, returning an
+    /// [`Error::InvalidUtf8`] for the first one that is not.
+    pub fn check_utf8(&self) -> error::Result<()> {
+        let check = |bytes: &[u8], what: &dyn Fn() -> String| {
+            std::str::from_utf8(bytes)
+                .map(|_| ())
+                .map_err(|source| Error::InvalidUtf8 {
+                    what: what(),
+                    source,
+                })
+        };
+        check(self.id_bytes(), &|| "the ID".into())?;
+        for i in 0..self.alleles.len() {
+            check(self.allele_bytes(i), &|| format!("allele {i}"))?;
+        }
+        for (key, typ, _, rng) in self.info_fields() {
+            if typ == 0x7 {
+                let what = || format!("INFO field with key {key}");
+                check(&self.buf_shared[rng.start..rng.end], &what)?;
             }
         }
-        res
+        for (key, typ, n, rng) in self.fmt_fields() {
+            if typ != 0x7 || n == 0 {
+                continue;
+            }
+            let values = self.buf_indiv[rng.start..rng.end].chunks(n);
+            for (sample, value) in values.enumerate() {
+                let what = || format!("FORMAT field with key {key} of sample {sample}");
+                check(value, &what)?;
+            }
+        }
+        Ok(())
     }
 
     /// iterate an integer for each filter key.
@@ -1117,7 +6661,7 @@ impl Record {
     ///     .unwrap();
     /// // read data via bcf-reader
     /// let mut f = smart_reader("testdata/test2.bcf");
-    /// let s = read_header(&mut f);
+    /// let s = read_header(&mut f).unwrap();
     /// let header = Header::from_string(&s);
     /// let mut record = Record::default();
     /// let mut filter_str2 = Vec::<u8>::new();
@@ -1135,14 +6679,373 @@ impl Record {
     /// // compare bcftools results and bcf-reader results
     /// assert_eq!(filter_str, filter_str2);
     /// ```
-    pub fn filters(&self) -> NumericValueIter {
-        let (typ, n, rng) = &self.filters;
-        NumericValueIter {
-            reader: std::io::Cursor::new(&self.buf_shared[rng.start..rng.end]),
-            typ: *typ,
-            len: *n,
-            cur: 0,
-        }
+    pub fn filters(&self) -> NumericValueIter<'_> {
+        let (typ, n, rng) = &self.filters;
+        NumericValueIter {
+            buf: &self.buf_shared[rng.start..rng.end],
+            typ: *typ,
+            len: *n,
+            cur: 0,
+        }
+    }
+
+    /// Returns the values of an INFO/xxx field grouped according to the
+    /// `Number=` attribute of the field in the header (see [`GroupedValues`]).
+    ///
+    /// An error is returned if the key is not an INFO field in the header,
+    /// if the values are not numeric, or if the number of values is
+    /// inconsistent with the number of alleles of the record. If the key is not found in the record, the returned view is
+    /// empty.
+    #[cfg(feature = "info")]
+    pub fn info_field_grouped(
+        &self,
+        header: &Header,
+        info_key: usize,
+    ) -> error::Result<GroupedValues<'_>> {
+        let header_def = header.number_and_type_for("INFO", info_key)?;
+        match self.info_fields().find(|e| e.0 == info_key) {
+            None => Ok(GroupedValues::default()),
+            Some((_, typ, n, rng)) => {
+                check_groupable(typ, n)?;
+                let (n_group, group_size) =
+                    group_values_by_number("INFO", header_def, self.n_allele as usize, n)?;
+                Ok(GroupedValues {
+                    typ,
+                    buf: &self.buf_shared[rng.start..rng.end],
+                    n_sample: 1,
+                    n_group,
+                    group_size,
+                })
+            }
+        }
+    }
+
+    /// Returns the values of a FORMAT/xxx field grouped according to the
+    /// `Number=` attribute of the field in the header (see [`GroupedValues`]).
+    ///
+    /// An error is returned if the key is not a FORMAT field in the header,
+    /// if the values are not numeric, or if the number of values per sample
+    /// is inconsistent with the number of alleles of the record. If the key is not found in the record, the
+    /// returned view is empty.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let s = read_header(&mut f).unwrap();
+    /// let header = Header::from_string(&s);
+    /// let ad_key = header.get_idx_from_dictionary_str("FORMAT", "AD").unwrap();
+    /// let pl_key = header.get_idx_from_dictionary_str("FORMAT", "PL").unwrap();
+    /// let mut record = Record::default();
+    /// let mut n_checked = 0;
+    /// let values = |v: &GroupedValues, group: usize| -> Vec<i32> {
+    ///     v.group(0, group).map(|x| x.int_val().unwrap() as i32).collect()
+    /// };
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let n_allele = record.n_allele() as usize;
+    ///     // Number=R: one group per allele
+    ///     let ad = record.fmt_field_grouped(&header, ad_key).unwrap();
+    ///     assert_eq!(ad.n_group(), n_allele);
+    ///     assert_eq!(ad.group_size(), 1);
+    ///     assert_eq!(ad.n_sample(), header.get_samples().len());
+    ///     // Number=G: one group per diploid genotype
+    ///     let pl = record.fmt_field_grouped(&header, pl_key).unwrap();
+    ///     assert_eq!(pl.n_group(), n_allele * (n_allele + 1) / 2);
+    ///     // the first sample at a biallelic and at a triallelic site
+    ///     let (ad0, pl0): (Vec<_>, Vec<_>) = (
+    ///         (0..ad.n_group()).flat_map(|g| values(&ad, g)).collect(),
+    ///         (0..pl.n_group()).flat_map(|g| values(&pl, g)).collect(),
+    ///     );
+    ///     match record.pos() {
+    ///         1118 => {
+    ///             assert_eq!(ad0, [4, 0]);
+    ///             assert_eq!(pl0, [0, 12, 109]);
+    ///         }
+    ///         9186 => {
+    ///             assert_eq!(ad0, [4, 0, 0]);
+    ///             assert_eq!(pl0, [0, 12, 94, 12, 94, 94]);
+    ///         }
+    ///         _ => continue,
+    ///     }
+    ///     n_checked += 1;
+    /// }
+    /// assert_eq!(n_checked, 2);
+    /// ```
+    #[cfg(feature = "format")]
+    pub fn fmt_field_grouped(
+        &self,
+        header: &Header,
+        fmt_key: usize,
+    ) -> error::Result<GroupedValues<'_>> {
+        let header_def = header.number_and_type_for("FORMAT", fmt_key)?;
+        match self.fmt_slot(fmt_key) {
+            None => Ok(GroupedValues::default()),
+            Some((_, typ, n, rng)) => {
+                check_groupable(typ, n)?;
+                let (n_group, group_size) =
+                    group_values_by_number("FORMAT", header_def, self.n_allele as usize, n)?;
+                Ok(GroupedValues {
+                    typ,
+                    buf: &self.buf_indiv[rng.start..rng.end],
+                    n_sample: self.n_sample as usize,
+                    n_group,
+                    group_size,
+                })
+            }
+        }
+    }
+
+    /// Convert the record into a fully-owned [`VariantRecord`] with all the
+    /// strings and values decoded, so that it can outlive the record buffers,
+    /// be sent across threads or be stored in collections.
+    ///
+    /// An error is returned if the record refers to a contig, a filter, or an
+    /// INFO/FORMAT key not defined in the header.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut variants = vec![];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     variants.push(record.to_owned_variant(&header).unwrap());
+    /// }
+    /// let v = &variants[0];
+    /// assert_eq!(v.chrom, "Pf3D7_01_v3");
+    /// assert_eq!(v.alleles.len(), 2);
+    /// assert!(v.info("AN").is_some());
+    /// let gts = v.format("GT").unwrap();
+    /// assert_eq!(gts.len(), header.get_samples().len());
+    /// match &gts[0] {
+    ///     FieldValue::Genotype(alleles) => assert_eq!(alleles.len(), 2),
+    ///     _ => panic!("GT should be decoded as a genotype"),
+    /// }
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn to_owned_variant(&self, header: &Header) -> error::Result<VariantRecord> {
+        let not_in_header = |what: &str, key: usize| {
+            Error::MalformedRecord(format!(
+                "{what} key {key} of the record is not defined in the header"
+            ))
+        };
+        let tag_of = |what: &str, key: usize| match header.dict_entry(what, key) {
+            Some(m) => Ok(m),
+            None => Err(not_in_header(what, key)),
+        };
+        let bytes_to_string = |rng: &Range<usize>| {
+            String::from_utf8_lossy(&self.buf_shared[rng.start..rng.end]).into_owned()
+        };
+
+        let chrom = match header.rid_to_name(self.chrom) {
+            Some(name) => name.to_string(),
+            None => Err(not_in_header("contig", self.chrom as usize))?,
+        };
+        let id = match bytes_to_string(&self.id) {
+            id if id.is_empty() => ".".to_string(),
+            id => id,
+        };
+        let alleles = self.alleles.iter().map(bytes_to_string).collect();
+        let mut filters = vec![];
+        for nv in self.filters() {
+            if let Some(key) = nv.int_val() {
+                filters.push(tag_of("FILTER", key as usize)?["ID"].clone());
+            }
+        }
+        let mut info = vec![];
+        for (key, typ, n, rng) in self.info_fields() {
+            let m = tag_of("INFO", key)?;
+            let is_flag = m.get("Type").map(|t| t == "Flag").unwrap_or(false);
+            let value = decode_field_value(typ, n, &self.buf_shared[rng.start..rng.end], is_flag);
+            info.push((m["ID"].clone(), value));
+        }
+        let mut format = vec![];
+        for (key, typ, n, rng) in self.fmt_fields() {
+            let m = tag_of("FORMAT", key)?;
+            let buf = &self.buf_indiv[rng.start..rng.end];
+            let width = typ_width(typ) * n;
+            let is_gt = header.get_fmt_gt_id() == Some(key);
+            let values = (0..self.n_sample as usize)
+                .map(|i| {
+                    let sample_buf = &buf[i * width..(i + 1) * width];
+                    if is_gt {
+                        decode_genotype(typ, n, sample_buf)
+                    } else {
+                        decode_field_value(typ, n, sample_buf, false)
+                    }
+                })
+                .collect();
+            format.push((m["ID"].clone(), values));
+        }
+
+        Ok(VariantRecord {
+            chrom,
+            pos: self.pos,
+            rlen: self.rlen,
+            id,
+            alleles,
+            qual: self.qual(),
+            filters,
+            info,
+            format,
+        })
+    }
+
+    /// Returns an adapter implementing [`std::fmt::Display`] that writes the
+    /// record as a VCF line (without the trailing newline).
+    ///
+    /// A record that refers to keys not defined in the header (see
+    /// [`Record::to_owned_variant`]) is written as `<invalid record: ...>`
+    /// with the error message; use [`Record::to_vcf_line`] to get the error.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // read data generated by bcftools
+    /// let mut pos_str = String::new();
+    /// smart_reader("testdata/test_pos.gz").read_to_string(&mut pos_str).unwrap();
+    /// let mut allele_str = String::new();
+    /// smart_reader("testdata/test_allele.gz").read_to_string(&mut allele_str).unwrap();
+    /// let mut gt_str = String::new();
+    /// smart_reader("testdata/test_gt.gz").read_to_string(&mut gt_str).unwrap();
+    /// // read data via bcf-reader
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// let mut expected = pos_str.lines().zip(allele_str.lines()).zip(gt_str.lines());
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     let line = record.display(&header).to_string();
+    ///     let fields: Vec<&str> = line.split('\t').collect();
+    ///     let ((pos, alleles), gts) = expected.next().unwrap();
+    ///     assert_eq!(fields[0], header.get_chrname(record.chrom() as usize));
+    ///     assert_eq!(fields[1], pos);
+    ///     assert_eq!(format!("{},{}", fields[3], fields[4]), alleles);
+    ///     assert!(fields[8].starts_with("GT:"));
+    ///     let gts2: Vec<&str> = fields[9..].iter().map(|x| x.split(':').next().unwrap()).collect();
+    ///     assert_eq!(gts.trim_start_matches('\t'), gts2.join("\t"));
+    /// }
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn display<'a>(&'a self, header: &'a Header) -> RecordDisplay<'a> {
+        RecordDisplay {
+            record: self,
+            header,
+        }
+    }
+
+    /// Format the record as a VCF line (without the trailing newline), as
+    /// [`Record::display`], returning an error if the record refers to keys
+    /// not defined in the header.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1>\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// ));
+    /// let record_on_contig = |chrom: i32| {
+    ///     let mut shared = vec![];
+    ///     for x in [chrom, 99, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+    ///     shared.extend(0u32.to_le_bytes()); // no FORMAT field, no sample
+    ///     shared.extend([0x07, 0x17, b'A', 0x17, b'C', 0x00]); // ID, alleles, FILTER
+    ///     let mut data = vec![];
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    ///     let mut record = Record::default();
+    ///     record.read(&mut data.as_slice()).unwrap();
+    ///     record
+    /// };
+    /// let record = record_on_contig(0);
+    /// assert_eq!(record.to_vcf_line(&header).unwrap(), "chr1\t100\t.\tA\tC\t.\t.\t.");
+    /// // contig 1 is not in the header
+    /// let record = record_on_contig(1);
+    /// assert!(record.to_vcf_line(&header).is_err());
+    /// assert!(record.display(&header).to_string().starts_with("<invalid record: "));
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn to_vcf_line(&self, header: &Header) -> error::Result<String> {
+        Ok(self.to_owned_variant(header)?.to_string())
+    }
+
+    /// Whether two records, each read with its own header, hold the same
+    /// data. Fields are matched by name so that differences in dictionary
+    /// indices (IDX) between the two files are tolerated; see
+    /// [`Record::diff`].
+    ///
+    /// Records that can not be decoded with their header are not equal.
+    #[cfg(feature = "strings")]
+    pub fn semantic_eq(&self, header: &Header, other: &Record, other_header: &Header) -> bool {
+        matches!(self.diff(header, other, other_header), Ok(d) if d.is_empty())
+    }
+
+    /// List the columns and tags that differ between two records, each read
+    /// with its own header; see [`VariantRecord::diff`].
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut r1 = Record::default();
+    /// let mut r2 = Record::default();
+    /// r1.read(&mut f).unwrap();
+    /// r2.read(&mut f).unwrap();
+    /// assert!(r1.semantic_eq(&header, &r1.clone(), &header));
+    /// assert!(!r1.semantic_eq(&header, &r2, &header));
+    /// let diffs = r1.diff(&header, &r2, &header).unwrap();
+    /// assert!(diffs.contains(&RecordDiff::Pos));
+    /// assert!(!diffs.contains(&RecordDiff::Chrom));
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn diff(
+        &self,
+        header: &Header,
+        other: &Record,
+        other_header: &Header,
+    ) -> error::Result<Vec<RecordDiff>> {
+        let v1 = self.to_owned_variant(header)?;
+        let v2 = other.to_owned_variant(other_header)?;
+        Ok(v1.diff(&v2))
+    }
+
+    /// Serialize the record as a single-line JSON object with the sample
+    /// names from the header (see [`Record::sample_names`]); see
+    /// [`VariantRecord::to_json`] for the schema.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut record = Record::default();
+    /// record.read(&mut f).unwrap();
+    /// let json = record.to_json(&header).unwrap();
+    /// assert!(json.starts_with(r#"{"chrom":"Pf3D7_01_v3","pos":"#));
+    /// let first_sample = &header.get_samples()[0];
+    /// assert!(json.contains(&format!(r#""{first_sample}":{{"GT":"#)));
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn to_json(&self, header: &Header) -> error::Result<String> {
+        let variant = self.to_owned_variant(header)?;
+        Ok(match self.sample_columns() {
+            None => variant.to_json(header.get_samples()),
+            Some(_) => {
+                let names: Vec<String> = self
+                    .sample_names(header)
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                variant.to_json(&names)
+            }
+        })
     }
 
     /// Returns the buffer containing indv (sample-level) information
@@ -1156,20 +7059,133 @@ impl Record {
     }
 }
 
+/// A pool of reusable records shared between threads.
+///
+/// Records checked out with [`RecordPool::get`] keep the capacity of their
+/// buffers from earlier use and go back to the pool when the returned
+/// [`RecordBuf`] is dropped, so steady-state reading does not allocate even
+/// with many records in flight. Cloning the pool gives another handle to the
+/// same set of records.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use std::sync::mpsc;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let pool = RecordPool::new(8);
+/// let (tx, rx) = mpsc::sync_channel::<RecordBuf>(8);
+/// let worker = std::thread::spawn(move || {
+///     // records are returned to the pool when dropped here
+///     rx.iter().map(|r| r.pos()).count()
+/// });
+/// loop {
+///     let mut record = pool.get();
+///     if record.read(&mut f).is_err() {
+///         break;
+///     }
+///     tx.send(record).unwrap();
+/// }
+/// drop(tx);
+/// assert_eq!(worker.join().unwrap(), 127);
+/// // records were reused instead of allocating one per site
+/// assert!(pool.len() <= 10);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordPool {
+    records: std::sync::Arc<std::sync::Mutex<Vec<Record>>>,
+}
+
+impl RecordPool {
+    /// Create a pool with `n` records ready to be checked out
+    pub fn new(n: usize) -> Self {
+        let records = (0..n).map(|_| Record::default()).collect();
+        Self {
+            records: std::sync::Arc::new(std::sync::Mutex::new(records)),
+        }
+    }
+
+    /// Check out a record from the pool, or create a new one if the pool is
+    /// empty
+    pub fn get(&self) -> RecordBuf {
+        let record = self.records.lock().unwrap().pop().unwrap_or_default();
+        RecordBuf {
+            record: Some(record),
+            pool: self.clone(),
+        }
+    }
+
+    /// Return a record to the pool
+    pub fn put(&self, record: Record) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    /// Number of records currently available in the pool
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    /// Whether no record is currently available in the pool
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A record checked out from a [`RecordPool`]; dereferences to [`Record`]
+/// and is returned to the pool when dropped.
+#[derive(Debug)]
+pub struct RecordBuf {
+    record: Option<Record>,
+    pool: RecordPool,
+}
+
+impl RecordBuf {
+    /// Detach the record from the pool so that it is not returned on drop
+    pub fn into_inner(mut self) -> Record {
+        self.record.take().unwrap()
+    }
+}
+
+impl std::ops::Deref for RecordBuf {
+    type Target = Record;
+    fn deref(&self) -> &Record {
+        self.record.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for RecordBuf {
+    fn deref_mut(&mut self) -> &mut Record {
+        self.record.as_mut().unwrap()
+    }
+}
+
+impl Drop for RecordBuf {
+    fn drop(&mut self) {
+        if let Some(record) = self.record.take() {
+            self.pool.put(record);
+        }
+    }
+}
+
 /// Open a file from a path as a MultiGzDecoder or a BufReader depending on
 /// whether the file has the magic number for gzip (0x1f and 0x8b)
 pub fn smart_reader(p: impl AsRef<std::path::Path>) -> Box<dyn std::io::Read> {
-    let mut f = std::fs::File::open(p.as_ref()).expect("can not open file");
-    if (f.read_u8().expect("can not read first byte") == 0x1fu8)
-        && (f.read_u8().expect("can not read second byte") == 0x8bu8)
-    {
+    open_smart(p.as_ref()).expect("can not open file")
+}
+
+/// [`smart_reader`] returning an error if the file cannot be read
+pub(crate) fn open_smart(p: &Path) -> io::Result<Box<dyn std::io::Read>> {
+    let mut f = std::fs::File::open(p)?;
+    let mut magic = vec![];
+    f.by_ref().take(2).read_to_end(&mut magic)?;
+    f.rewind()?;
+    // empty files are reported by `read_header`
+    if magic == [0x1f, 0x8b] {
         // gzip format
-        f.rewind().unwrap();
-        Box::new(flate2::read::MultiGzDecoder::new(f))
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(f)))
     } else {
         // not gzip format
-        f.rewind().unwrap();
-        Box::new(std::io::BufReader::new(f))
+        Ok(Box::new(std::io::BufReader::new(f)))
     }
 }
 
@@ -1196,7 +7212,7 @@ pub fn smart_reader(p: impl AsRef<std::path::Path>) -> Box<dyn std::io::Read> {
 /// let max_gzip_block_in_buffer = 10;
 /// let reader = File::open("testdata/test.bcf").map(BufReader::new).unwrap();
 /// let mut f = ParMultiGzipReader::from_reader(reader, max_gzip_block_in_buffer, None, None);
-/// let s = read_header(&mut f);
+/// let s = read_header(&mut f).unwrap();
 /// let header = Header::from_string(&s);
 /// let mut record = Record::default();
 /// let mut gt_str2 = Vec::<u8>::new();
@@ -1230,6 +7246,32 @@ pub fn smart_reader(p: impl AsRef<std::path::Path>) -> Box<dyn std::io::Read> {
 /// }
 /// ```
 ///
+/// Truncated or corrupted BGZF data is reported as an error by `read`:
+/// ```
+/// use bcf_reader::*;
+/// use std::io::Read;
+/// let data = std::fs::read("testdata/test.bcf").unwrap();
+/// let mut full = vec![];
+/// ParMultiGzipReader::from_reader(data.as_slice(), 3, None, None)
+///     .read_to_end(&mut full)
+///     .unwrap();
+///
+/// // without the empty block marking the end of the file
+/// let mut out = vec![];
+/// let no_eof_marker = &data[..data.len() - 28];
+/// ParMultiGzipReader::from_reader(no_eof_marker, 3, None, None)
+///     .read_to_end(&mut out)
+///     .unwrap();
+/// assert_eq!(out, full);
+///
+/// // cut in the middle of a block
+/// let truncated = &data[..data.len() - 100];
+/// let err = ParMultiGzipReader::from_reader(truncated, 3, None, None)
+///     .read_to_end(&mut out)
+///     .unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+/// ```
+///
 /// See [`ParMultiGzipReader::from_reader`] for an example to jump to a target
 /// genome interval.
 ///
@@ -1245,6 +7287,10 @@ where
     ibyte: usize, // the current byte to be consumed
     coffset: u64,
     inner_eof: bool,
+    /// error reading or decompressing the next blocks, returned once the
+    /// data read before it has been consumed
+    pending_error: Option<io::Error>,
+    stats: DecompressionStats,
 }
 
 #[derive(Default, Clone)]
@@ -1345,12 +7391,34 @@ where
             ibyte: 0,
             coffset: coffset.unwrap_or(0),
             inner_eof: false,
+            pending_error: None,
+            stats: DecompressionStats::default(),
         };
-        this.clear_and_fill_buffers();
-        this.decomp_all();
+        this.refill();
         this.ibyte = uoffset.unwrap_or(0) as usize;
         this
     }
+
+    /// Get the counters of the blocks read and decompressed so far
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::{fs::File, io::{BufReader, Read}};
+    /// let f = File::open("testdata/test.bcf").map(BufReader::new).unwrap();
+    /// let mut gz = ParMultiGzipReader::from_reader(f, 3, None, None);
+    /// let mut data = vec![];
+    /// gz.read_to_end(&mut data).unwrap();
+    /// let stats = gz.stats();
+    /// assert_eq!(stats.decompressed_bytes, data.len() as u64);
+    /// let file_size = std::fs::metadata("testdata/test.bcf").unwrap().len();
+    /// assert_eq!(stats.compressed_bytes, file_size);
+    /// assert!(stats.n_blocks > 1);
+    /// ```
+    pub fn stats(&self) -> DecompressionStats {
+        self.stats
+    }
+
     pub fn get_coffset_uoffset(&self) -> (u64, u64) {
         let coffset = self.buffer[self.igzip].coffset;
         let uoffset = self.ibyte as u64;
@@ -1365,10 +7433,11 @@ where
         };
         let this_buffer = &mut self.buffer[self.ngzip];
 
-        // dbg!(this_buffer_offset);
-
-        // let coffset_beg = self.inner.stream_position().unwrap();
-        // dbg!(coffset_beg);
+        let invalid = |what: &str| {
+            io::Error::from(Error::Compression(format!(
+                "invalid BGZF block at offset {this_buffer_offset}: {what}"
+            )))
+        };
         let id1 = match self.inner.read_u8() {
             Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
                 self.inner_eof = true;
@@ -1379,46 +7448,42 @@ where
             }
             Ok(id1) => id1,
         };
-        assert_eq!(id1, 31);
-        let id2 = self.inner.read_u8().unwrap();
-        assert_eq!(id2, 139);
-        let cm = self.inner.read_u8().unwrap();
-        assert_eq!(cm, 8);
-        let flg = self.inner.read_u8().unwrap();
-        assert_eq!(flg, 4);
-        let _mtime = self.inner.read_u32::<LittleEndian>().unwrap();
-        let _xfl = self.inner.read_u8().unwrap();
-        let _os = self.inner.read_u8().unwrap();
-        let xlen = self.inner.read_u16::<LittleEndian>().unwrap();
-        let si1 = self.inner.read_u8().unwrap();
-        assert_eq!(si1, 66);
-        let si2 = self.inner.read_u8().unwrap();
-        assert_eq!(si2, 67);
-        let slen = self.inner.read_u16::<LittleEndian>().unwrap();
-        assert_eq!(slen, 2);
-        let bsize = self.inner.read_u16::<LittleEndian>().unwrap();
+        // the rest of the block must be there: a short read is an error
+        let id2 = self.inner.read_u8()?;
+        if (id1, id2) != (31, 139) {
+            return Err(invalid("not gzip data"));
+        }
+        let cm = self.inner.read_u8()?;
+        let flg = self.inner.read_u8()?;
+        if cm != 8 || flg != 4 {
+            return Err(invalid("unexpected compression method or flags"));
+        }
+        let _mtime = self.inner.read_u32::<LittleEndian>()?;
+        let _xfl = self.inner.read_u8()?;
+        let _os = self.inner.read_u8()?;
+        let xlen = self.inner.read_u16::<LittleEndian>()?;
+        let si1 = self.inner.read_u8()?;
+        let si2 = self.inner.read_u8()?;
+        let slen = self.inner.read_u16::<LittleEndian>()?;
+        if (si1, si2, slen) != (66, 67, 2) {
+            return Err(invalid("missing BC extra field"));
+        }
+        let bsize = self.inner.read_u16::<LittleEndian>()?;
 
         let buffer_compressed = &mut this_buffer.compressed;
-        let cdata_sz = bsize - xlen as u16 - 19;
+        let cdata_sz = bsize
+            .checked_sub(xlen)
+            .and_then(|x| x.checked_sub(19))
+            .ok_or_else(|| invalid("block size smaller than its header"))?;
 
-        buffer_compressed.clear();
-        buffer_compressed.reserve(cdata_sz as usize);
-        unsafe {
-            buffer_compressed.set_len(cdata_sz as usize);
-        }
-        self.inner
-            .read_exact(buffer_compressed.as_mut_slice())
-            .unwrap();
+        buffer_compressed.resize(cdata_sz as usize, 0u8);
+        self.inner.read_exact(buffer_compressed.as_mut_slice())?;
 
-        let _crc32 = self.inner.read_u32::<LittleEndian>().unwrap();
-        let isize = self.inner.read_u32::<LittleEndian>().unwrap();
+        let _crc32 = self.inner.read_u32::<LittleEndian>()?;
+        let isize = self.inner.read_u32::<LittleEndian>()?;
 
         let buffer_uncompressed = &mut this_buffer.uncompressed;
-        buffer_uncompressed.clear();
-        buffer_uncompressed.reserve(isize as usize);
-        unsafe {
-            buffer_uncompressed.set_len(isize as usize);
-        }
+        buffer_uncompressed.resize(isize as usize, 0u8);
         this_buffer.coffset = this_buffer_offset;
         this_buffer.gzip_size = bsize + 1;
         this_buffer.uncompressed_data_size = isize;
@@ -1429,7 +7494,7 @@ where
         Ok(())
     }
     // clear buffer and refill (sequential)
-    fn clear_and_fill_buffers(&mut self) {
+    fn clear_and_fill_buffers(&mut self) -> io::Result<()> {
         let Self {
             inner: _,
             buffer,
@@ -1438,6 +7503,8 @@ where
             igzip,
             ibyte,
             inner_eof: _,
+            pending_error: _,
+            stats: _,
         } = self;
 
         // update coffset for the buffer vector based on last used buffer
@@ -1457,21 +7524,59 @@ where
         *igzip = 0;
         *ibyte = 0;
         for _i in 0..self.buffer.len() {
-            self.read_single_gzip().unwrap();
+            self.read_single_gzip()?;
             if self.inner_eof {
                 break;
             }
         }
+        Ok(())
     }
 
     /// decompress all read gzip file in memory (parallel)
-    fn decomp_all(&mut self) {
-        self.buffer.par_iter_mut().for_each(|buffer| {
-            let compressed = buffer.compressed.as_slice();
-            let uncompressed = &mut buffer.uncompressed.as_mut_slice();
-            let mut deflater = DeflateDecoder::new(compressed);
-            deflater.read_exact(uncompressed).unwrap();
+    fn decomp_all(&mut self) -> io::Result<()> {
+        self.buffer[..self.ngzip]
+            .par_iter_mut()
+            .try_for_each(|buffer| {
+                let compressed = buffer.compressed.as_slice();
+                let uncompressed = &mut buffer.uncompressed.as_mut_slice();
+                let mut deflater = DeflateDecoder::new(compressed);
+                deflater.read_exact(uncompressed).map_err(|e| {
+                    Error::Compression(format!(
+                        "cannot decompress the BGZF block at offset {}: {e}",
+                        buffer.coffset
+                    ))
+                    .into()
+                })
+            })
+    }
+
+    /// Read and decompress the next blocks. On error, no data is left to
+    /// read and the error is returned by the next `read`.
+    fn refill(&mut self) {
+        let start = Instant::now();
+        let filled = self.clear_and_fill_buffers();
+        let read = Instant::now();
+        self.stats.read_time += read - start;
+        let res = filled.and_then(|()| {
+            let res = self.decomp_all();
+            self.stats.decompression_time += read.elapsed();
+            res
         });
+        if res.is_ok() {
+            let blocks = &self.buffer[..self.ngzip];
+            self.stats.n_blocks += blocks.len() as u64;
+            for block in blocks {
+                self.stats.compressed_bytes += block.gzip_size as u64;
+                self.stats.decompressed_bytes += block.uncompressed_data_size as u64;
+            }
+        }
+        if let Err(e) = res {
+            self.ngzip = 0;
+            self.igzip = 0;
+            self.ibyte = 0;
+            self.inner_eof = true;
+            self.pending_error = Some(e);
+        }
     }
 }
 
@@ -1480,40 +7585,195 @@ where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        // no more data in the buffer
-        if self.ngzip == self.igzip {
-            // no more data to read from file
-            if self.inner_eof {
-                return Ok(0);
-            }
-            self.clear_and_fill_buffers();
-            self.decomp_all();
+        if buf.is_empty() {
+            return Ok(0);
         }
-        // read from the buffer
-        //  check current
-        let uncompressed = self.buffer[self.igzip].uncompressed.as_slice();
-        let mut n = uncompressed.len() - self.ibyte;
-        if n > buf.len() {
-            n = buf.len();
-        }
-        // copy data
-        buf[0..n]
-            .iter_mut()
-            .zip(uncompressed[self.ibyte..].iter())
-            .for_each(|(d, s)| *d = *s);
-        self.ibyte += n;
-        if self.ibyte == uncompressed.len() {
-            self.igzip += 1;
-            self.ibyte = 0;
-            if self.ngzip == self.igzip {
+        loop {
+            // no more data in the buffer
+            if self.igzip >= self.ngzip {
+                if let Some(e) = self.pending_error.take() {
+                    return Err(e);
+                }
                 // no more data to read from file
                 if self.inner_eof {
                     return Ok(0);
                 }
-                self.clear_and_fill_buffers();
-                self.decomp_all();
+                self.refill();
+                continue;
+            }
+            // read from the buffer
+            let uncompressed = self.buffer[self.igzip].uncompressed.as_slice();
+            let n = (uncompressed.len() - self.ibyte).min(buf.len());
+            buf[..n].copy_from_slice(&uncompressed[self.ibyte..self.ibyte + n]);
+            self.ibyte += n;
+            if self.ibyte == uncompressed.len() {
+                self.igzip += 1;
+                self.ibyte = 0;
+                if self.igzip == self.ngzip && !self.inner_eof {
+                    self.refill();
+                }
+            }
+            // empty blocks (e.g. the EOF marker of concatenated files) are
+            // skipped rather than reported as the end of the data
+            if n > 0 {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+/// A reader that reads ahead from the inner reader on a background thread,
+/// with two buffers: while the data of one buffer is consumed, the next
+/// bytes are read into the other. This hides the latency of slow storage
+/// (spinning disks, network filesystems) behind the parsing of the records.
+///
+/// It can wrap the file, below a [`ParMultiGzipReader`], so that the next
+/// BGZF blocks are read while the current ones are decompressed, or the
+/// decompressed data, below a [`BcfReader`] (see
+/// [`BcfReader::with_prefetch`]), so that the next records are decompressed
+/// while the current ones are parsed. As it does not seek, it
+/// cannot be used by [`IndexedBcfReader`].
+///
+/// Errors of the inner reader are returned by `read` after the data read
+/// before them, as they are (e.g. an [`Error`] of a [`ParMultiGzipReader`]
+/// keeps its [`category`](Error::category)); every later call returns an
+/// error of the same kind and message.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use std::fs::File;
+/// let file = File::open("testdata/test.bcf").unwrap();
+/// let gz = ParMultiGzipReader::from_reader(PrefetchReader::new(file, 1 << 16), 3, None, None);
+/// let mut reader = BcfReader::from_reader(PrefetchReader::new(gz, 1 << 12));
+/// let header = reader.read_header();
+/// let positions: Vec<_> = reader.records().map(|r| r.unwrap().pos()).collect();
+///
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let expected = Header::from_string(&read_header(&mut f).unwrap());
+/// assert_eq!(header.get_samples(), expected.get_samples());
+/// let mut record = Record::default();
+/// for pos in positions {
+///     record.read(&mut f).unwrap();
+///     assert_eq!(pos, record.pos());
+/// }
+/// assert!(record.read(&mut f).is_err());
+///
+/// // errors are passed on
+/// use std::io::Read;
+/// let mut data = std::fs::read("testdata/test.bcf").unwrap();
+/// data.truncate(data.len() - 100);
+/// let gz = ParMultiGzipReader::from_reader(std::io::Cursor::new(data), 3, None, None);
+/// let mut reader = PrefetchReader::new(gz, 1 << 12);
+/// let mut out = vec![];
+/// let err = reader.read_to_end(&mut out).unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+/// assert!(!out.is_empty());
+/// assert_eq!(reader.read(&mut [0; 10]).unwrap_err().kind(), err.kind());
+///
+/// let mut damaged = std::fs::read("testdata/test.bcf").unwrap();
+/// damaged[100] ^= 0xff;
+/// let gz = ParMultiGzipReader::from_reader(std::io::Cursor::new(damaged), 3, None, None);
+/// let err = read_header(&mut PrefetchReader::new(gz, 1 << 12)).unwrap_err();
+/// assert_eq!(err.category(), ErrorCategory::Compression);
+/// ```
+pub struct PrefetchReader {
+    current: Vec<u8>,
+    pos: usize,
+    /// buffers filled by the background thread, in order
+    filled: std::sync::mpsc::Receiver<io::Result<Vec<u8>>>,
+    /// consumed buffers, given back to the background thread
+    empty: std::sync::mpsc::SyncSender<Vec<u8>>,
+    eof: bool,
+    /// an error of the inner reader, returned as is by the next `read`
+    error: Option<io::Error>,
+    /// kind and message of the error returned, for the later calls
+    failed: Option<(io::ErrorKind, String)>,
+}
+
+impl PrefetchReader {
+    /// Start reading ahead from `inner`, `buffer_size` bytes at a time
+    pub fn new<R>(mut inner: R, buffer_size: usize) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        assert!(buffer_size > 0, "the buffer size should be positive");
+        let (filled_tx, filled) = std::sync::mpsc::sync_channel(2);
+        let (empty, empty_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(2);
+        // the second buffer is `current`, given to the thread by the first read
+        empty.send(Vec::with_capacity(buffer_size)).unwrap();
+        std::thread::spawn(move || {
+            // stops when the reader is dropped or at the end of the data
+            while let Ok(mut buf) = empty_rx.recv() {
+                buf.resize(buffer_size, 0);
+                let mut n = 0;
+                let result = loop {
+                    match inner.read(&mut buf[n..]) {
+                        Ok(0) => break Ok(()),
+                        Ok(m) => {
+                            n += m;
+                            if n == buffer_size {
+                                break Ok(());
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => break Err(e),
+                    }
+                };
+                buf.truncate(n);
+                let last = n == 0 || result.is_err();
+                if n > 0 && filled_tx.send(Ok(buf)).is_err() {
+                    return;
+                }
+                if let Err(e) = result {
+                    let _ = filled_tx.send(Err(e));
+                }
+                if last {
+                    return;
+                }
+            }
+        });
+        PrefetchReader {
+            current: Vec::with_capacity(buffer_size),
+            pos: 0,
+            filled,
+            empty,
+            eof: false,
+            error: None,
+            failed: None,
+        }
+    }
+}
+
+impl Read for PrefetchReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.current.len() {
+            if let Some(e) = self.error.take() {
+                self.failed = Some((e.kind(), e.to_string()));
+                return Err(e);
+            }
+            if let Some((kind, msg)) = &self.failed {
+                return Err(io::Error::new(*kind, msg.clone()));
+            }
+            if self.eof {
+                return Ok(0);
+            }
+            let consumed = std::mem::take(&mut self.current);
+            self.pos = 0;
+            // the background thread is gone once it reached the end
+            let _ = self.empty.send(consumed);
+            match self.filled.recv() {
+                Ok(Ok(next)) => self.current = next,
+                Ok(Err(e)) => self.error = Some(e),
+                Err(_) => self.eof = true,
             }
         }
+        let n = (self.current.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
         Ok(n)
     }
 }
@@ -1570,6 +7830,11 @@ impl CsiBin {
     pub fn chunks(&self) -> &[CsiChunk] {
         &self.chunks[..]
     }
+
+    /// return the virtual file offset of the first record overlapping the bin
+    pub fn loffset(&self) -> &VirtualFileOffsets {
+        &self.loffset
+    }
 }
 
 /// A struct representing CSI index file content
@@ -1587,71 +7852,100 @@ pub struct Csi {
 
 impl Csi {
     /// Create Csi from a path to a `*.csi` file
+    ///
+    /// # Panics
+    /// Panics if the file cannot be read or is not a CSI index; see
+    /// [`Csi::try_from_path`].
     pub fn from_path(p: impl AsRef<Path>) -> Self {
+        Self::try_from_path(p).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Create Csi from a path to a `*.csi` file, or return an error if the
+    /// file cannot be opened ([`Error::Io`]) or is not a valid CSI index
+    /// ([`Error::InvalidIndex`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let csi = Csi::try_from_path("testdata/test3.bcf.csi").unwrap();
+    /// assert!(csi.get_bin_limit() > 0);
+    /// let err = Csi::try_from_path("testdata/test3.bcf").unwrap_err();
+    /// assert_eq!(err.to_string(), "invalid CSI index: this is a BCF file");
+    /// assert_eq!(err.category(), ErrorCategory::Index);
+    /// ```
+    pub fn try_from_path(p: impl AsRef<Path>) -> error::Result<Self> {
         let mut csi = Csi::default();
-        let mut file = smart_reader(p.as_ref());
+        let mut file = open_smart(p.as_ref())?;
+        let invalid = |what: &'static str| {
+            move |e: io::Error| Error::InvalidIndex(format!("cannot read {what}: {e}"))
+        };
         // magic
         file.read_exact(csi.magic.as_mut())
-            .expect("error in reading csi magic bytes");
-        assert_eq!(csi.magic, [b'C', b'S', b'I', 1]);
+            .map_err(invalid("magic bytes"))?;
+        match &csi.magic {
+            b"CSI\x01" => {}
+            [b'B', b'C', b'F', _] => return Err(Error::InvalidIndex("this is a BCF file".into())),
+            b"TBI\x01" => return Err(Error::InvalidIndex("this is a tabix index".into())),
+            magic => return Err(Error::InvalidIndex(describe_magic(magic))),
+        }
         // min_shift
         csi.min_shift = file
             .read_i32::<LittleEndian>()
-            .expect("error in reading csi min_shift field");
+            .map_err(invalid("min_shift"))?;
         // dbg!(csi.min_shift);
         // depth
-        csi.depth = file
-            .read_i32::<LittleEndian>()
-            .expect("error in reading csi depth field");
+        csi.depth = file.read_i32::<LittleEndian>().map_err(invalid("depth"))?;
         // dbg!(csi.depth);
         // l_aux
-        csi.l_aux = file
-            .read_i32::<LittleEndian>()
-            .expect("error in reading csi l_aux field");
+        csi.l_aux = file.read_i32::<LittleEndian>().map_err(invalid("l_aux"))?;
         // dbg!(csi.l_aux);
         // aux
+        if !(0..=MAX_HEADER_TEXT_LENGTH as i32).contains(&csi.l_aux) {
+            return Err(Error::InvalidIndex(format!(
+                "l_aux {} is out of range",
+                csi.l_aux
+            )));
+        }
         csi.aux.resize(csi.l_aux as usize, 0u8);
-        file.read_exact(&mut csi.aux.as_mut())
-            .expect("error in reading csi aux field");
+        file.read_exact(csi.aux.as_mut()).map_err(invalid("aux"))?;
         // n_ref
-        csi.n_ref = file
-            .read_i32::<LittleEndian>()
-            .expect("error in reading csi n_ref field");
+        csi.n_ref = file.read_i32::<LittleEndian>().map_err(invalid("n_ref"))?;
 
         // iterate over chromosomes
         for _ in 0..csi.n_ref {
-            let mut idx = CsiIndex::default();
-            idx.n_bin = file
-                .read_i32::<LittleEndian>()
-                .expect("error in reading csi index n_bin field");
+            let mut idx = CsiIndex {
+                n_bin: file.read_i32::<LittleEndian>().map_err(invalid("n_bin"))?,
+                ..Default::default()
+            };
             for _ in 0..idx.n_bin {
-                let mut bin = CsiBin::default();
-                // bin
-                bin.bin = file
-                    .read_u32::<LittleEndian>()
-                    .expect("error in reading csi bin bin field");
-                // loffset
-                bin.loffset = file
-                    .read_u64::<LittleEndian>()
-                    .expect("error in reading csi bin loffset field")
-                    .into();
-                // n_chunk
-                bin.n_chunk = file
-                    .read_i32::<LittleEndian>()
-                    .expect("error in reading csi bin n_chunk field");
+                let mut bin = CsiBin {
+                    // bin
+                    bin: file.read_u32::<LittleEndian>().map_err(invalid("bin"))?,
+                    // loffset
+                    loffset: file
+                        .read_u64::<LittleEndian>()
+                        .map_err(invalid("loffset"))?
+                        .into(),
+                    // n_chunk
+                    n_chunk: file
+                        .read_i32::<LittleEndian>()
+                        .map_err(invalid("n_chunk"))?,
+                    ..Default::default()
+                };
 
                 for _ in 0..bin.n_chunk {
-                    let mut chunk = CsiChunk::default();
-                    // chunk_beg
-                    chunk.chunk_beg = file
-                        .read_u64::<LittleEndian>()
-                        .expect("error in reading csi chunk chunk_beg")
-                        .into();
-                    // chunk_end
-                    chunk.chunk_end = file
-                        .read_u64::<LittleEndian>()
-                        .expect("error in reading csi chunk chunk_end")
-                        .into();
+                    let chunk = CsiChunk {
+                        // chunk_beg
+                        chunk_beg: file
+                            .read_u64::<LittleEndian>()
+                            .map_err(invalid("chunk_beg"))?
+                            .into(),
+                        // chunk_end
+                        chunk_end: file
+                            .read_u64::<LittleEndian>()
+                            .map_err(invalid("chunk_end"))?
+                            .into(),
+                    };
                     bin.chunks.push(chunk);
                 }
                 idx.bins.push(bin);
@@ -1662,7 +7956,7 @@ impl Csi {
         // n_no_coor
         csi.n_no_coor = file.read_u64::<LittleEndian>().ok();
 
-        csi
+        Ok(csi)
     }
 
     /// Convert positional coordinate range to a bin number
@@ -1688,7 +7982,7 @@ impl Csi {
             l += 1;
         }
 
-        return 0;
+        0
     }
 
     /// Get CsiBin based the chromosome id and bin number.
@@ -1708,245 +8002,15 @@ impl Csi {
 
     /// Get the max possible bin number in theory. Note, the maximum bin may not
     /// be present in the Csi index file.
-    pub fn get_bin_limit(&self) -> u32 {
-        (1 << ((self.depth + 1) * 3) - 1) / 7
-    }
-}
-
-/// BcfReader suitable for read through the BCF file.
-///
-/// This assumes that the source reader is in BCF format and stored as BGZF blocks.
-///
-/// # Example
-/// ```
-/// use bcf_reader::*;
-/// use std::{ fs::File, io::{BufReader, Seek} };
-/// // underlying reader can be stdin or a file
-/// let reader = std::fs::File::open("testdata/test3.bcf")
-/// .map(BufReader::new)
-/// .unwrap();
-/// // 1. for sequential decompression (works for data from stdin or a file)
-/// let reader = flate2::bufread::MultiGzDecoder::new(reader);
-/// // 2. for parallel decompression (works for data from stdin or a file)
-/// // however, when data is from stdin, jump is not supported for now.
-/// // let reader = ParMultiGzipReader::from_reader(reader, 3, None, None);
-///
-/// // create bcf reader
-/// let mut reader = BcfReader::from_reader(reader);
-/// // read though header
-/// let _header = reader.read_header();
-/// // create a reusable record
-/// let mut record = Record::default();
-///
-/// let mut pos_found = vec![];
-/// // iterate records from the targeted genome interval
-/// while let Ok(_) = reader.read_record(&mut record) {
-/// pos_found.push(record.pos() + 1);
-/// }
-///
-/// assert_eq!(pos_found[0], 72);
-/// assert_eq!(*pos_found.last().unwrap(), 1498841);
-/// ```
-pub struct BcfReader<R>
-where
-    R: Read,
-{
-    inner: R,
-    header_parsed: bool,
-}
-
-impl<R> BcfReader<R>
-where
-    R: Read,
-{
-    /// `max_gzip`, the number of gzip blocks to read before batch
-    /// decompression.  See `ParMultiGzipReader::from_reader` (by default or
-    /// None, 3 gzip buffers will be used.)
-    pub fn from_reader(reader: R) -> Self {
-        Self {
-            inner: reader,
-            header_parsed: false,
-        }
-    }
-
-    /// Read the header
-    pub fn read_header(&mut self) -> Header {
-        let header = Header::from_string(&read_header(&mut self.inner));
-        self.header_parsed = true;
-        header
-    }
-
-    /// Read one record. This should be called after the header is read and parsed.
-    /// Otherwise, it will panic.
-    pub fn read_record(
-        &mut self,
-        record: &mut Record,
-    ) -> Result<(), Box<(dyn std::error::Error + 'static)>> {
-        assert!(
-            self.header_parsed,
-            "header should be parsed before reading records"
-        );
-        record.read(&mut self.inner)
-    }
-}
-
-/// A genome interval defined by chromosome id, start, and end positions
-pub struct GenomeInterval {
-    pub chrom_id: usize,
-    pub start: i64,
-    pub end: Option<i64>,
-}
-
-/// IndexedBcfReader allows random access to a specific genome interval of the
-/// BCF file using a CSI index file. It is an wrapper around
-/// [`ParMultiGzipReader<BufReader<File>>`] to allow parallelizable bgzip
-/// decompression.
-///
-/// The source should be BCF file consisting of small BGZF blocks.
-///
-/// # Example
-///
-/// ```
-/// use bcf_reader::*;
-/// // create indexed bcf reader
-/// let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
-/// // read though header
-/// let _header = reader.read_header();
-/// // define targeted genome interval
-/// let interval = GenomeInterval {
-///     chrom_id: 0,
-///     start: 1489230 - 1,
-///     end: Some(1498509 - 1),
-/// };
-/// // set interval
-/// reader.set_interval(interval);
-/// // create a reusable record
-/// let mut record = Record::default();
-///
-/// let mut pos_found = vec![];
-/// // iterate records from the targeted genome interval
-/// while let Ok(_) = reader.read_record(&mut record) {
-///     pos_found.push(record.pos() + 1);
-/// }
-///
-/// assert_eq!(
-///     pos_found,
-///     vec![
-///         1489230, 1489979, 1490069, 1490311, 1492233, 1492337, 1493505, 1494178, 1495207,
-///         1495403, 1495746, 1496047, 1497964, 1498188,
-///     ]
-/// )
-/// ```
-pub struct IndexedBcfReader {
-    inner: ParMultiGzipReader<BufReader<File>>,
-    csi: Csi,
-    header_parsed: bool,
-    genome_interval: Option<GenomeInterval>,
-}
-
-impl IndexedBcfReader {
-    /// Create an IndexedBcfReader from paths to a bcf file and a corresponding
-    /// csi index file.
-    ///
-    ///  - `max_gzip`, the number of gzip blocks to read before each batch
-    /// parallelized decompression. See [`ParMultiGzipReader::from_reader`]
-    /// (by default (None) use 3); this construct will automaticall read and
-    /// parse the header
-    pub fn from_path(
-        path_bcf: impl AsRef<Path>,
-        path_csi: impl AsRef<Path>,
-        max_gzip: Option<usize>,
-    ) -> Self {
-        let reader = File::open(path_bcf.as_ref()).map(BufReader::new).unwrap();
-        let csi = Csi::from_path(path_csi.as_ref());
-        let reader = ParMultiGzipReader::from_reader(reader, max_gzip.unwrap_or(3), None, None);
-        Self {
-            inner: reader,
-            csi: csi,
-            header_parsed: false,
-            genome_interval: None,
-        }
-    }
-    /// Read the header bytes, parse them and return a `Header`
-    pub fn read_header(&mut self) -> Header {
-        let header = Header::from_string(&read_header(&mut self.inner));
-        self.header_parsed = true;
-        header
-    }
-
-    /// Jump the file pointer to the begining to the targeted genome interval
-    ///
-    /// If no site within the genome interval, read_record will return Err(_)
-    pub fn set_interval(&mut self, genome_interval: GenomeInterval) {
-        // find the target based on csi
-        let bin_id = self
-            .csi
-            .get_bin_id(genome_interval.start, genome_interval.start + 1);
-
-        let (coffset, uoffset) = self
-            .csi
-            .get_bin_details(genome_interval.chrom_id, bin_id)
-            .chunks
-            .first()
-            .unwrap()
-            .chunk_beg
-            .get_coffset_uoffset();
-
-        let par_reader = &mut self.inner;
-        par_reader.inner.seek(io::SeekFrom::Start(coffset)).unwrap();
-
-        // clear buffer, especially things related to coffset
-        par_reader.buffer.iter_mut().for_each(|bgzf_buffer| {
-            bgzf_buffer.compressed.clear();
-            bgzf_buffer.uncompressed.clear();
-            bgzf_buffer.coffset = 0;
-            bgzf_buffer.gzip_size = 0;
-            bgzf_buffer.uncompressed_data_size = 0;
-        });
-        par_reader.ngzip = 0;
-        par_reader.igzip = 0;
-        par_reader.ibyte = 0;
-
-        // fill buffer
-        par_reader.clear_and_fill_buffers();
-        par_reader.decomp_all();
-
-        // jump for uoffset
-        par_reader.ibyte = uoffset as usize;
-
-        self.genome_interval = Some(genome_interval);
-    }
-
-    /// Read one record. Should be called after header is parsed.
     ///
-    /// If `set_interval` has been called, only records within the given interval
-    /// will be read.
-    pub fn read_record(
-        &mut self,
-        record: &mut Record,
-    ) -> Result<(), Box<(dyn std::error::Error + 'static)>> {
-        assert!(
-            self.header_parsed,
-            "header should be parsed before reading records"
-        );
-        let start = self.genome_interval.as_ref().unwrap().start;
-        let end = self.genome_interval.as_ref().unwrap().end;
-        loop {
-            match record.read(&mut self.inner) {
-                Ok(_) => {
-                    if let Some(end) = end {
-                        if record.pos as i64 >= end {
-                            let e =
-                                std::io::Error::new(std::io::ErrorKind::NotFound, "out of range");
-                            return Err(Box::new(e));
-                        }
-                    }
-                    if record.pos as i64 >= start {
-                        return Ok(());
-                    }
-                }
-                Err(e) => return Err(e),
-            }
-        }
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let csi = Csi::try_from_path("testdata/test3.bcf.csi").unwrap();
+    /// // 1 + 8 + 64 + 512 bins for depth 3
+    /// assert_eq!(csi.get_bin_limit(), 585);
+    /// ```
+    pub fn get_bin_limit(&self) -> u32 {
+        ((1 << ((self.depth + 1) * 3)) - 1) / 7
     }
 }
@@ -0,0 +1,84 @@
+//! Genotype matrices as [`ndarray`] arrays (requires the `ndarray` feature).
+//!
+//! [`read_genotype_matrix`] reads the remaining records of a reader, i.e. all
+//! records of a [`BcfReader`](crate::BcfReader) or the records of the
+//! interval set on an [`IndexedBcfReader`](crate::IndexedBcfReader), and
+//! returns their genotypes in a single `Array2<i8>` with one row per sample
+//! (or per allele of each sample) and one column per site. Missing
+//! genotypes are `-1`.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::matrix::{read_genotype_matrix, GenotypeLayout};
+//! let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
+//! let header = reader.read_header();
+//! reader.set_interval(GenomeInterval {
+//!     chrom_id: 0,
+//!     start: 1489230 - 1,
+//!     end: Some(1498509 - 1),
+//! });
+//! let dosages = read_genotype_matrix(&mut reader, &header, GenotypeLayout::Dosage).unwrap();
+//! assert_eq!(dosages.dim(), (header.get_samples().len(), 14));
+//! assert!(dosages.iter().all(|&d| (-1..=2).contains(&d)));
+//! ```
+
+use crate::{error, genotype_column, Header, ReadRecord, Record};
+use ndarray::Array2;
+
+pub use crate::GenotypeLayout;
+
+/// Read the genotypes of the remaining records of `reader` into a matrix
+/// with one column per site and the rows described by `layout`.
+///
+/// The matrix is in column-major (Fortran) order, so that the values of each
+/// site are contiguous; see [`SampleMajorGenotypes`](crate::SampleMajorGenotypes)
+/// for windows of sites with the values of each sample contiguous. Sites
+/// without a FORMAT/GT field have a column of `-1`.
+///
+/// An error is returned if a record cannot be read, if a site has more
+/// alleles per sample than `ploidy` in [`GenotypeLayout::Alleles`], or if an
+/// allele index does not fit in an `i8`.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use bcf_reader::matrix::{read_genotype_matrix, GenotypeLayout};
+/// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+/// let header = reader.read_header();
+/// let layout = GenotypeLayout::Alleles { ploidy: 2 };
+/// let alleles = read_genotype_matrix(&mut reader, &header, layout).unwrap();
+/// let n_sample = header.get_samples().len();
+/// assert_eq!(alleles.dim(), (n_sample * 2, 127));
+///
+/// // the same values as the genotypes of the records
+/// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+/// reader.read_header();
+/// let mut gts = vec![0; n_sample * 2];
+/// for (site, record) in reader.records().enumerate() {
+///     record.unwrap().decode_gt_into(&header, &mut gts).unwrap();
+///     for (row, &gt) in gts.iter().enumerate() {
+///         assert_eq!(alleles[[row, site]] as i32, gt);
+///     }
+/// }
+/// ```
+pub fn read_genotype_matrix<T: ReadRecord>(
+    reader: &mut T,
+    header: &Header,
+    layout: GenotypeLayout,
+) -> error::Result<Array2<i8>> {
+    let n_row = layout.n_rows(header.get_samples().len());
+    let mut record = Record::default();
+    let mut gts = vec![];
+    let mut values = vec![];
+    let mut n_site = 0;
+    while reader.try_read_record(&mut record)?.is_some() {
+        let start = values.len();
+        values.resize(start + n_row, -1);
+        genotype_column(&record, header, layout, &mut gts, &mut values[start..])?;
+        n_site += 1;
+    }
+    let matrix =
+        Array2::from_shape_vec((n_site, n_row), values).expect("one column of values per site");
+    Ok(matrix.reversed_axes())
+}
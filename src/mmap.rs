@@ -0,0 +1,207 @@
+//! Whole-file parallel parsing of uncompressed BCF (requires the `mmap`
+//! feature).
+//!
+//! [`BcfDataset::open`] maps an uncompressed BCF file (e.g. written by
+//! `bcftools view -Ou`) into memory, finds the record boundaries from the
+//! record lengths, and parses all records in parallel with rayon. The
+//! records are then kept in memory, for repeated random access by index or
+//! by genome interval without decompressing or parsing the file again.
+//!
+//! BGZF-compressed files are not supported: their records can only be found
+//! after decompression; see [`BcfReader`](crate::BcfReader) and
+//! [`scan_parallel`](crate::scan_parallel) for them.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::mmap::BcfDataset;
+//! // decompress test.bcf into an uncompressed BCF file
+//! let path = std::env::temp_dir().join("bcf_reader_mmap_doc.bcf");
+//! let mut data = vec![];
+//! smart_reader("testdata/test.bcf").read_to_end(&mut data).unwrap();
+//! std::fs::write(&path, &data).unwrap();
+//!
+//! let dataset = BcfDataset::open(&path).unwrap();
+//! assert_eq!(dataset.len(), 127);
+//! // the same records as a sequential read
+//! let mut f = smart_reader("testdata/test.bcf");
+//! read_header(&mut f).unwrap();
+//! let mut record = Record::default();
+//! for r in dataset.records() {
+//!     record.read(&mut f).unwrap();
+//!     assert_eq!((r.chrom(), r.pos()), (record.chrom(), record.pos()));
+//!     assert_eq!(r.buf_indiv(), record.buf_indiv());
+//! }
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    error::{self, Error},
+    is_bcf21, read_header_with_policy, GenomeInterval, Header, Record, VersionPolicy,
+};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+/// The header and all records of an uncompressed BCF file, parsed in
+/// parallel from a memory map; see the [module docs](self)
+#[derive(Debug)]
+pub struct BcfDataset {
+    header: Header,
+    records: Vec<Record>,
+    /// indices of the records of each contig, sorted by position
+    by_chrom: HashMap<i32, Vec<usize>>,
+}
+
+impl BcfDataset {
+    /// Map the uncompressed BCF file at `path` and parse its header and
+    /// records, on the rayon thread pool.
+    ///
+    /// An error is returned if the file cannot be mapped, if it is not an
+    /// uncompressed BCF file (the message tells BGZF-compressed files
+    /// apart), or if a record is truncated or malformed. BCF 2.1 records are
+    /// upgraded as by [`BcfReader`](crate::BcfReader).
+    pub fn open(path: impl AsRef<Path>) -> error::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only read while parsing, and the records copy
+        // their bytes; as for any memory map, the file must not be truncated
+        // by another process in the meantime.
+        let map = unsafe { Mmap::map(&file)? };
+        Self::from_bytes(&map)
+    }
+
+    /// Parse the header and records of uncompressed BCF data in memory, as
+    /// [`BcfDataset::open`]
+    pub fn from_bytes(data: &[u8]) -> error::Result<Self> {
+        let mut rest = data;
+        let raw = read_header_with_policy(&mut rest, &VersionPolicy::default())?;
+        let header = Header::try_from_string(&raw.text)?;
+        let bcf21 = is_bcf21(Some(raw.version));
+        let ranges = record_ranges(rest, data.len() - rest.len())?;
+        let records = ranges
+            .into_par_iter()
+            .map(|(offset, end)| {
+                let mut record = Record::default();
+                record
+                    .read(&mut &data[offset..end])
+                    .map_err(|e| Error::MalformedRecord(format!("record at byte {offset}: {e}")))?;
+                if bcf21 {
+                    record.upgrade_bcf21_vectors();
+                }
+                Ok(record)
+            })
+            .collect::<error::Result<Vec<_>>>()?;
+        let mut by_chrom = HashMap::<i32, Vec<usize>>::new();
+        for (i, record) in records.iter().enumerate() {
+            by_chrom.entry(record.chrom()).or_default().push(i);
+        }
+        for indices in by_chrom.values_mut() {
+            // stable, so that records at the same position stay in file order
+            indices.sort_by_key(|&i| records[i].pos());
+        }
+        Ok(BcfDataset {
+            header,
+            records,
+            by_chrom,
+        })
+    }
+
+    /// The header of the file
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the file has no records
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The record at index `i` in file order
+    pub fn get(&self, i: usize) -> Option<&Record> {
+        self.records.get(i)
+    }
+
+    /// All records in file order
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Iterate over the records of a genome interval, ordered by position
+    /// (records at the same position in file order). As with
+    /// [`IndexedBcfReader`](crate::IndexedBcfReader), a record is in the
+    /// interval if its 0-based position is in `start..end`.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use bcf_reader::mmap::BcfDataset;
+    /// let mut data = vec![];
+    /// smart_reader("testdata/test3.bcf").read_to_end(&mut data).unwrap();
+    /// let dataset = BcfDataset::from_bytes(&data).unwrap();
+    /// let interval = || GenomeInterval {
+    ///     chrom_id: 0,
+    ///     start: 1489230 - 1,
+    ///     end: Some(1498509 - 1),
+    /// };
+    /// // the same records as the indexed reader
+    /// let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
+    /// reader.read_header();
+    /// reader.set_interval(interval());
+    /// let mut record = Record::default();
+    /// let mut n = 0;
+    /// for r in dataset.interval(&interval()) {
+    ///     reader.read_record(&mut record).unwrap();
+    ///     assert_eq!(r.pos(), record.pos());
+    ///     n += 1;
+    /// }
+    /// assert_eq!(n, 14);
+    /// ```
+    pub fn interval<'a>(
+        &'a self,
+        interval: &GenomeInterval,
+    ) -> impl Iterator<Item = &'a Record> + 'a {
+        let indices = i32::try_from(interval.chrom_id)
+            .ok()
+            .and_then(|chrom| self.by_chrom.get(&chrom))
+            .map_or(&[][..], |v| v.as_slice());
+        let (start, end) = (interval.start, interval.end.unwrap_or(i64::MAX));
+        let first = indices.partition_point(|&i| (self.records[i].pos() as i64) < start);
+        indices[first..]
+            .iter()
+            .map(|&i| &self.records[i])
+            .take_while(move |r| (r.pos() as i64) < end)
+    }
+}
+
+/// Find the byte ranges of the records in `data`, the bytes after the
+/// header, which start at byte `offset` of the file
+fn record_ranges(data: &[u8], offset: usize) -> error::Result<Vec<(usize, usize)>> {
+    let mut ranges = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let length = |at: usize| {
+            data.get(at..at + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        };
+        let end = match (length(pos), length(pos + 4)) {
+            (Some(l_shared), Some(l_indv)) => pos + 8 + l_shared + l_indv,
+            _ => data.len() + 1,
+        };
+        if end > data.len() {
+            return Err(Error::MalformedRecord(format!(
+                "record at byte {} is truncated",
+                offset + pos
+            )));
+        }
+        ranges.push((offset + pos, offset + end));
+        pos = end;
+    }
+    Ok(ranges)
+}
@@ -0,0 +1,253 @@
+//! Parquet output of site and genotype data (requires the `parquet`
+//! feature).
+//!
+//! [`ParquetWriter`] writes records to a Parquet file with the columns of
+//! the [`arrow_export`](crate::arrow_export) module, starting a new row
+//! group at each genomic window of [`ParquetOptions::window`] bp, so that
+//! readers of the file can skip the row groups outside a region from the
+//! statistics of the `chrom` and `pos` columns. A window with more data
+//! than [`ParquetOptions::max_row_group_bytes`] spans several row groups.
+//! [`BcfReader::to_parquet`]
+//! and [`IndexedBcfReader::to_parquet`] write the remaining records of a
+//! reader to a file.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::parquet_export::ParquetOptions;
+//! use parquet::file::reader::{FileReader, SerializedFileReader};
+//! let path = std::env::temp_dir().join("bcf_reader_parquet_doc.parquet");
+//! let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+//! let header = reader.read_header();
+//! let mut options = ParquetOptions::default();
+//! options.window = 100_000;
+//! options.schema.genotypes = Some(GenotypeLayout::Alleles { ploidy: 2 });
+//! let n_records = reader.to_parquet(&header, &path, &options).unwrap();
+//! assert_eq!(n_records, 127);
+//!
+//! // one row group per window with records
+//! let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+//! reader.read_header();
+//! let mut windows: Vec<_> = reader
+//!     .records()
+//!     .map(|r| r.map(|r| (r.chrom(), r.pos() / 100_000)))
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! windows.dedup();
+//! let file = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+//! let metadata = file.metadata();
+//! assert_eq!(metadata.num_row_groups(), windows.len());
+//! assert_eq!(metadata.file_metadata().num_rows(), 127);
+//!
+//! // more row groups with a smaller memory budget, checked after each batch
+//! options.max_row_group_bytes = 1 << 10;
+//! options.schema.batch_size = 8;
+//! let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+//! let header = reader.read_header();
+//! reader.to_parquet(&header, &path, &options).unwrap();
+//! let file = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+//! assert!(file.metadata().num_row_groups() > windows.len());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::arrow_export::{ArrowBatchBuilder, ArrowOptions};
+use crate::{error, BcfReader, Error, Header, IndexedBcfReader, ReadRecord, Record};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub use parquet::basic::Compression;
+
+/// Columns, windows and compression of a Parquet file, see the
+/// [module docs](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParquetOptions {
+    /// the columns of the file; at most `schema.batch_size` records are
+    /// buffered before they are encoded
+    pub schema: ArrowOptions,
+    /// length in bp of the genomic windows of the row groups
+    pub window: u32,
+    /// memory budget of a row group: a row group is ended early once the
+    /// data buffered for it reaches this many bytes (checked after each
+    /// batch of `schema.batch_size` records), so that a dense window spans
+    /// several row groups rather than being held in memory whole
+    pub max_row_group_bytes: usize,
+    /// compression of the column chunks
+    pub compression: Compression,
+}
+
+impl Default for ParquetOptions {
+    /// The default columns of [`ArrowOptions`], windows of 1 Mbp, row groups
+    /// of at most 128 MiB and Snappy compression
+    fn default() -> Self {
+        ParquetOptions {
+            schema: ArrowOptions::default(),
+            window: 1_000_000,
+            max_row_group_bytes: 128 << 20,
+            compression: Compression::SNAPPY,
+        }
+    }
+}
+
+/// The error of the Parquet writer, as an I/O error
+fn parquet_error(e: parquet::errors::ParquetError) -> Error {
+    Error::Io(io::Error::other(e))
+}
+
+/// Writes records to a Parquet file with one row group per genomic window
+///
+/// A row group holds the records of one window of
+/// [`ParquetOptions::window`] bp of a contig, and a new row group is started
+/// whenever the window of a record differs from the window of the previous
+/// record. The records of a sorted file give one row group per window with
+/// records; unsorted records give more, smaller row groups. A row group is
+/// also ended once the data buffered for it reaches
+/// [`ParquetOptions::max_row_group_bytes`], so a window may span several row
+/// groups.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use bcf_reader::parquet_export::{ParquetOptions, ParquetWriter};
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut writer = ParquetWriter::new(vec![], &header, &ParquetOptions::default()).unwrap();
+/// let mut record = Record::default();
+/// while let Ok(_) = record.read(&mut f) {
+///     writer.write(&record).unwrap();
+/// }
+/// assert_eq!(writer.n_records(), 127);
+/// let data = writer.into_inner().unwrap();
+/// assert_eq!(&data[..4], b"PAR1");
+/// ```
+#[derive(Debug)]
+pub struct ParquetWriter<'h, W: Write + Send> {
+    writer: ArrowWriter<W>,
+    builder: ArrowBatchBuilder<'h>,
+    batch_size: usize,
+    window: i64,
+    max_row_group_bytes: usize,
+    /// (chrom, window index) of the current row group
+    current: Option<(i32, i64)>,
+    n_records: usize,
+}
+
+impl<'h, W: Write + Send> ParquetWriter<'h, W> {
+    /// Create a writer of the records of a file with `header` to `inner`
+    pub fn new(inner: W, header: &'h Header, options: &ParquetOptions) -> error::Result<Self> {
+        let builder = ArrowBatchBuilder::new(header, options.schema.clone());
+        let properties = WriterProperties::builder()
+            .set_compression(options.compression)
+            // the row groups are delimited by the windows and the memory
+            // budget only
+            .set_max_row_group_size(usize::MAX)
+            .build();
+        let writer = ArrowWriter::try_new(inner, builder.schema(), Some(properties))
+            .map_err(parquet_error)?;
+        Ok(ParquetWriter {
+            writer,
+            builder,
+            batch_size: options.schema.batch_size.max(1),
+            window: options.window.max(1) as i64,
+            max_row_group_bytes: options.max_row_group_bytes,
+            current: None,
+            n_records: 0,
+        })
+    }
+
+    /// Number of records written so far
+    pub fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// Write a record, starting a new row group if it is not in the window
+    /// of the previous record.
+    ///
+    /// An error is returned if the record cannot be converted (see
+    /// [`ArrowBatchBuilder::push`]) or written.
+    pub fn write(&mut self, record: &Record) -> error::Result<()> {
+        let key = (
+            record.chrom(),
+            (record.pos() as i64).div_euclid(self.window),
+        );
+        if self.current.is_some_and(|current| current != key) {
+            self.write_batch()?;
+            self.writer.flush().map_err(parquet_error)?;
+        }
+        self.current = Some(key);
+        self.builder.push(record)?;
+        self.n_records += 1;
+        if self.builder.len() >= self.batch_size {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Encode the buffered records into the current row group, ending it if
+    /// it reaches the memory budget
+    fn write_batch(&mut self) -> error::Result<()> {
+        if self.builder.is_empty() {
+            return Ok(());
+        }
+        let batch = self.builder.finish();
+        self.writer.write(&batch).map_err(parquet_error)?;
+        if self.writer.memory_size() >= self.max_row_group_bytes {
+            self.writer.flush().map_err(parquet_error)?;
+        }
+        Ok(())
+    }
+
+    /// Write the last row group and the footer of the file, and return the
+    /// inner writer
+    pub fn into_inner(mut self) -> error::Result<W> {
+        self.write_batch()?;
+        self.writer.into_inner().map_err(parquet_error)
+    }
+}
+
+/// Write the remaining records of `reader` to a Parquet file at `path`
+fn to_parquet<T: ReadRecord + ?Sized>(
+    reader: &mut T,
+    header: &Header,
+    path: &Path,
+    options: &ParquetOptions,
+) -> error::Result<usize> {
+    let mut writer = ParquetWriter::new(File::create(path)?, header, options)?;
+    let mut record = Record::default();
+    while reader.try_read_record(&mut record)?.is_some() {
+        writer.write(&record)?;
+    }
+    let n_records = writer.n_records();
+    writer.into_inner()?.sync_all()?;
+    Ok(n_records)
+}
+
+impl<R: Read> BcfReader<R> {
+    /// Write the remaining records to a Parquet file at `path`, with one row
+    /// group per genomic window (see [`ParquetWriter`]), and return the
+    /// number of records written. `header` is the header read by this
+    /// reader.
+    pub fn to_parquet(
+        &mut self,
+        header: &Header,
+        path: impl AsRef<Path>,
+        options: &ParquetOptions,
+    ) -> error::Result<usize> {
+        to_parquet(self, header, path.as_ref(), options)
+    }
+}
+
+impl IndexedBcfReader {
+    /// Write the remaining records of the interval to a Parquet file at
+    /// `path`, as [`BcfReader::to_parquet`]
+    pub fn to_parquet(
+        &mut self,
+        header: &Header,
+        path: impl AsRef<Path>,
+        options: &ParquetOptions,
+    ) -> error::Result<usize> {
+        to_parquet(self, header, path.as_ref(), options)
+    }
+}
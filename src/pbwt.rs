@@ -0,0 +1,307 @@
+//! Positional Burrows–Wheeler transform (PBWT) of phased haplotypes.
+//!
+//! The PBWT (Durbin 2014, <https://doi.org/10.1093/bioinformatics/btu014>)
+//! keeps the haplotypes sorted by their reversed prefixes while the sites are
+//! added one at a time. After `k` sites, the prefix array lists the
+//! haplotypes in that order, and the divergence array gives, for each
+//! haplotype of the prefix array, the first site of its match with the
+//! previous haplotype. Haplotypes with long matches ending at site `k` are
+//! therefore next to each other, which allows fast haplotype matching.
+//!
+//! [`Pbwt`] is built from the record stream with [`Pbwt::push_record`] (or
+//! from allele vectors with [`Pbwt::update`]); set-maximal matches are
+//! reported with [`Pbwt::set_maximal_matches`] before each site and at the
+//! end, or with [`Pbwt::push_record_with_matches`] while adding records.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::pbwt::Pbwt;
+//! let mut f = smart_reader("testdata/test.bcf");
+//! let header = Header::from_string(&read_header(&mut f).unwrap());
+//! let mut pbwt = Pbwt::new(header.get_samples().len() * 2);
+//! let mut record = Record::default();
+//! let mut haplotypes = vec![vec![]; pbwt.n_haplotypes()];
+//! let mut gts = vec![0; pbwt.n_haplotypes()];
+//! while let Ok(_) = record.read(&mut f) {
+//!     pbwt.push_record(&record, &header).unwrap();
+//!     record.decode_gt_into(&header, &mut gts).unwrap();
+//!     for (h, &a) in haplotypes.iter_mut().zip(&gts) {
+//!         h.push((a > 0) as u8);
+//!     }
+//! }
+//! let k = pbwt.n_sites();
+//! assert_eq!(k, 127);
+//! // neighbours in the prefix array match from the divergence site to the
+//! // last site, and differ just before
+//! let (a, d) = (pbwt.prefix(), pbwt.divergence());
+//! for i in 1..a.len() {
+//!     let (x, y) = (&haplotypes[a[i - 1]], &haplotypes[a[i]]);
+//!     assert_eq!(x[d[i]..k], y[d[i]..k]);
+//!     assert!(d[i] == 0 || x[d[i] - 1] != y[d[i] - 1]);
+//!     // sorted by reversed prefix
+//!     assert!(x.iter().rev().le(y.iter().rev()));
+//! }
+//! ```
+
+use crate::{error, Header, Record};
+
+/// A match between two haplotypes over the sites `start..end` that cannot
+/// be extended, reported by [`Pbwt::set_maximal_matches`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetMaximalMatch {
+    /// the haplotype the match is set-maximal for
+    pub haplotype: usize,
+    /// the matching haplotype
+    pub other: usize,
+    /// first site of the match
+    pub start: usize,
+    /// site after the last site of the match
+    pub end: usize,
+}
+
+/// PBWT of a set of haplotypes, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Pbwt {
+    n_sites: usize,
+    prefix: Vec<usize>,
+    divergence: Vec<usize>,
+    // buffers for the updates
+    prefix_alt: Vec<usize>,
+    divergence_alt: Vec<usize>,
+    alleles: Vec<u8>,
+    gts: Vec<i32>,
+}
+
+impl Pbwt {
+    /// Create the PBWT of `n_haplotypes` haplotypes, before the first site
+    pub fn new(n_haplotypes: usize) -> Self {
+        Pbwt {
+            n_sites: 0,
+            prefix: (0..n_haplotypes).collect(),
+            divergence: vec![0; n_haplotypes],
+            prefix_alt: vec![],
+            divergence_alt: vec![],
+            alleles: vec![],
+            gts: vec![],
+        }
+    }
+
+    /// Number of haplotypes
+    pub fn n_haplotypes(&self) -> usize {
+        self.prefix.len()
+    }
+
+    /// Number of sites added so far
+    pub fn n_sites(&self) -> usize {
+        self.n_sites
+    }
+
+    /// The prefix array: haplotype indices sorted by their reversed prefixes
+    /// up to the last site
+    pub fn prefix(&self) -> &[usize] {
+        &self.prefix
+    }
+
+    /// The divergence array: element `i` is the first site from which the
+    /// haplotype `prefix()[i]` matches `prefix()[i - 1]` up to the last site
+    /// (`n_sites()` if they differ at the last site, and `n_sites()` for the
+    /// first haplotype)
+    pub fn divergence(&self) -> &[usize] {
+        &self.divergence
+    }
+
+    /// Add a site with the allele of each haplotype (`0` for REF, anything
+    /// else for ALT).
+    ///
+    /// Panics if `alleles` does not have one value per haplotype.
+    pub fn update(&mut self, alleles: &[u8]) {
+        assert_eq!(
+            alleles.len(),
+            self.n_haplotypes(),
+            "expected one allele per haplotype"
+        );
+        self.prefix_alt.clear();
+        self.divergence_alt.clear();
+        let mut n_ref = 0;
+        let (mut p, mut q) = (self.n_sites + 1, self.n_sites + 1);
+        for i in 0..self.prefix.len() {
+            let (h, d) = (self.prefix[i], self.divergence[i]);
+            p = p.max(d);
+            q = q.max(d);
+            if alleles[h] == 0 {
+                // REF haplotypes are compacted at the start of the arrays
+                self.prefix[n_ref] = h;
+                self.divergence[n_ref] = p;
+                n_ref += 1;
+                p = 0;
+            } else {
+                self.prefix_alt.push(h);
+                self.divergence_alt.push(q);
+                q = 0;
+            }
+        }
+        self.prefix[n_ref..].copy_from_slice(&self.prefix_alt);
+        self.divergence[n_ref..].copy_from_slice(&self.divergence_alt);
+        self.n_sites += 1;
+    }
+
+    /// Add the GT values of `record` as the next site, with the alleles of
+    /// sample `i` as haplotypes `i * ploidy..(i + 1) * ploidy`.
+    ///
+    /// The genotypes are expected to be phased; this is not checked. All ALT
+    /// alleles are treated as one, and missing or absent alleles as REF. A
+    /// record without FORMAT/GT is added as a site with only REF alleles. An
+    /// `InvalidInput` error is returned if the record does not have one value
+    /// per haplotype.
+    pub fn push_record(&mut self, record: &Record, header: &Header) -> error::Result<()> {
+        let alleles = self.record_alleles(record, header)?;
+        self.update(&alleles);
+        self.alleles = alleles;
+        Ok(())
+    }
+
+    /// Like [`push_record`](Self::push_record), but first report the
+    /// set-maximal matches ending before the site of `record` (see
+    /// [`set_maximal_matches`](Self::set_maximal_matches))
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use bcf_reader::pbwt::Pbwt;
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// let header = Header::from_string(&read_header(&mut f).unwrap());
+    /// let mut pbwt = Pbwt::new(header.get_samples().len() * 2);
+    /// let mut record = Record::default();
+    /// let mut matches = vec![];
+    /// while let Ok(_) = record.read(&mut f) {
+    ///     pbwt.push_record_with_matches(&record, &header, 20, |m| matches.push(m))
+    ///         .unwrap();
+    /// }
+    /// pbwt.set_maximal_matches(None, 20, |m| matches.push(m));
+    /// assert!(!matches.is_empty());
+    /// assert!(matches.iter().all(|m| m.end - m.start >= 20 && m.haplotype != m.other));
+    /// ```
+    pub fn push_record_with_matches(
+        &mut self,
+        record: &Record,
+        header: &Header,
+        min_len: usize,
+        report: impl FnMut(SetMaximalMatch),
+    ) -> error::Result<()> {
+        let alleles = self.record_alleles(record, header)?;
+        self.set_maximal_matches(Some(&alleles), min_len, report);
+        self.update(&alleles);
+        self.alleles = alleles;
+        Ok(())
+    }
+
+    /// The alleles of the haplotypes at the site of `record`, in the buffer
+    /// taken from `self.alleles`
+    fn record_alleles(&mut self, record: &Record, header: &Header) -> error::Result<Vec<u8>> {
+        let n = self.n_haplotypes();
+        self.gts.resize(n.max(self.gts.len()), 0);
+        let ploidy = record.decode_gt_into(header, &mut self.gts)?;
+        let n_values = record.n_sample as usize * ploidy;
+        if ploidy > 0 && n_values != n {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{n_values} GT values for {n} haplotypes"),
+            )
+            .into());
+        }
+        let mut alleles = std::mem::take(&mut self.alleles);
+        alleles.clear();
+        match ploidy {
+            0 => alleles.resize(n, 0),
+            _ => alleles.extend(self.gts[..n].iter().map(|&a| (a > 0) as u8)),
+        }
+        Ok(alleles)
+    }
+
+    /// Report the set-maximal matches ending at the current site, i.e. the
+    /// longest matches of each haplotype with any other haplotype, which
+    /// cannot be extended because they differ at the `next` site. Call this
+    /// before [`update`](Self::update) with the alleles of the next site, and
+    /// with `None` after the last site.
+    ///
+    /// Only matches of at least `min_len` sites (and at least one) are
+    /// reported. This is Algorithm 3 of Durbin (2014).
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::pbwt::Pbwt;
+    /// let haplotypes = [
+    ///     [0, 1, 0, 1, 1, 0],
+    ///     [1, 1, 0, 1, 0, 0],
+    ///     [0, 1, 0, 1, 0, 1],
+    /// ];
+    /// let mut pbwt = Pbwt::new(3);
+    /// let mut matches = vec![];
+    /// for k in 0..=6 {
+    ///     let next: Option<Vec<u8>> = (k < 6).then(|| haplotypes.iter().map(|h| h[k]).collect());
+    ///     pbwt.set_maximal_matches(next.as_deref(), 1, |m| matches.push(m));
+    ///     if let Some(alleles) = next {
+    ///         pbwt.update(&alleles);
+    ///     }
+    /// }
+    /// let mut found: Vec<_> = matches.iter().map(|m| (m.haplotype, m.other, m.start, m.end)).collect();
+    /// found.sort();
+    /// assert_eq!(
+    ///     found,
+    ///     [(0, 1, 5, 6), (0, 2, 0, 4), (1, 0, 5, 6), (1, 2, 1, 5), (2, 0, 0, 4), (2, 1, 1, 5)]
+    /// );
+    /// ```
+    pub fn set_maximal_matches(
+        &self,
+        next: Option<&[u8]>,
+        min_len: usize,
+        mut report: impl FnMut(SetMaximalMatch),
+    ) {
+        let (a, k) = (&self.prefix, self.n_sites);
+        let m = a.len();
+        if let Some(y) = next {
+            assert_eq!(y.len(), m, "expected one allele per haplotype");
+        }
+        // divergence with sentinels before the first and after the last
+        // haplotype
+        let d = |i: usize| match i {
+            0 => k + 1,
+            i if i == m => k + 1,
+            i => self.divergence[i],
+        };
+        // whether the match of haplotypes `x` and `y` extends to the next site
+        let extends = |x: usize, z: usize| next.is_some_and(|y| (y[x] == 0) == (y[z] == 0));
+        'haplotypes: for i in 0..m {
+            let (mut lo, mut hi) = (i, i + 1);
+            if d(i) <= d(i + 1) {
+                while lo > 0 && d(lo) <= d(i) {
+                    lo -= 1;
+                    if extends(a[lo], a[i]) {
+                        continue 'haplotypes;
+                    }
+                }
+            }
+            if d(i) >= d(i + 1) {
+                while hi < m && d(hi) <= d(i + 1) {
+                    if extends(a[hi], a[i]) {
+                        continue 'haplotypes;
+                    }
+                    hi += 1;
+                }
+            }
+            let before = (lo..i).map(|j| (j, d(i)));
+            let after = (i + 1..hi).map(|j| (j, d(i + 1)));
+            for (j, start) in before.chain(after) {
+                if k - start.min(k) >= min_len.max(1) {
+                    report(SetMaximalMatch {
+                        haplotype: a[i],
+                        other: a[j],
+                        start,
+                        end: k,
+                    });
+                }
+            }
+        }
+    }
+}
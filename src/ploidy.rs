@@ -0,0 +1,291 @@
+//! Expected ploidy of genotypes by region and sex.
+//!
+//! Genotypes on sex chromosomes and the mitochondrial genome are often
+//! haploid, and a haploid call elsewhere (or a diploid call on chrY of a
+//! male sample outside the PARs) usually signals a problem upstream. A
+//! [`PloidyMap`] describes the expected ploidy with the conventions of
+//! `bcftools --ploidy`: regions given as `CHROM FROM TO SEX PLOIDY` (1-based,
+//! inclusive), with `*` for the default of a sex.
+//!
+//! Maps can be built by hand, parsed from a bcftools ploidy file, taken
+//! from the GRCh37/GRCh38 presets, or read from `##ploidy` header lines:
+//! ```text
+//! ##ploidy=<CHROM=chrX,FROM=2781480,TO=155701381,SEX=M,PLOIDY=1>
+//! ```
+//!
+//! Example:
+//! ```
+//! use bcf_reader::*;
+//! use bcf_reader::ploidy::PloidyMap;
+//! let map = PloidyMap::grch38();
+//! assert_eq!(map.ploidy("chrX", 5_000_000, Some("M")), 1);
+//! assert_eq!(map.ploidy("chrX", 100_000, Some("M")), 2); // PAR1
+//! assert_eq!(map.ploidy("chrX", 5_000_000, Some("F")), 2);
+//! assert_eq!(map.ploidy("chrY", 5_000_000, Some("F")), 0);
+//! assert_eq!(map.ploidy("chr1", 5_000_000, Some("M")), 2);
+//!
+//! // test.bcf (P. falciparum) is haploid
+//! let mut f = smart_reader("testdata/test.bcf");
+//! let header = Header::from_string(&read_header(&mut f).unwrap());
+//! let map = PloidyMap::new(1);
+//! let mut record = Record::default();
+//! record.read(&mut f).unwrap();
+//! let sexes = vec![None; header.get_samples().len()];
+//! // the calls are diploid
+//! let mismatches = map.mismatched_samples(&record, &header, &sexes).unwrap();
+//! assert_eq!(mismatches.len(), header.get_samples().len());
+//! ```
+
+use crate::{
+    error::{self, Error},
+    Header, Record,
+};
+use std::io;
+
+/// Expected ploidy of a region, for one sex or all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PloidyRegion {
+    /// contig name, or `*` for all contigs
+    pub chrom: String,
+    /// first position (1-based, inclusive)
+    pub start: u64,
+    /// last position (1-based, inclusive)
+    pub end: u64,
+    /// sex of the samples the region applies to (`None` for all samples)
+    pub sex: Option<String>,
+    /// expected number of alleles per genotype
+    pub ploidy: u8,
+}
+
+impl PloidyRegion {
+    fn is_default(&self) -> bool {
+        self.chrom == "*"
+    }
+
+    fn matches(&self, chrom: &str, pos: u64, sex: Option<&str>) -> bool {
+        let sex_matches = match (&self.sex, sex) {
+            (None, _) => true,
+            (Some(a), Some(b)) => a == b,
+            (Some(_), None) => false,
+        };
+        sex_matches
+            && (self.is_default() || (self.chrom == chrom && self.start <= pos && pos <= self.end))
+    }
+}
+
+/// Expected ploidy by region and sex
+///
+/// Regions on a contig take precedence over the `*` defaults; among
+/// overlapping regions, the last one added wins. Positions without a
+/// matching region get the fallback ploidy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PloidyMap {
+    regions: Vec<PloidyRegion>,
+    fallback: u8,
+}
+
+impl Default for PloidyMap {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl PloidyMap {
+    /// Create a map without regions, with the ploidy of all positions
+    pub fn new(fallback: u8) -> Self {
+        Self {
+            regions: vec![],
+            fallback,
+        }
+    }
+
+    /// Add a region; `chrom` is `*` for the default of a sex
+    pub fn add(&mut self, region: PloidyRegion) -> &mut Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Get the regions of the map, in the order they were added
+    pub fn regions(&self) -> &[PloidyRegion] {
+        &self.regions
+    }
+
+    /// Parse a ploidy file in the format of `bcftools --ploidy-file`: one
+    /// region per line, `CHROM FROM TO SEX PLOIDY` separated by whitespace,
+    /// with `*` in the first three columns for defaults. Empty lines and
+    /// lines starting with `#` are skipped.
+    ///
+    /// ```
+    /// use bcf_reader::ploidy::PloidyMap;
+    /// let map = PloidyMap::parse("chrX 1 1000 M 1\n* * * M 2\n* * * F 2\n").unwrap();
+    /// assert_eq!(map.ploidy("chrX", 500, Some("M")), 1);
+    /// assert_eq!(map.ploidy("chrX", 1500, Some("M")), 2);
+    /// assert!(PloidyMap::parse("chrX 1 x M 1").is_err());
+    /// ```
+    pub fn parse(text: &str) -> error::Result<Self> {
+        let mut map = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || -> Error {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid ploidy definition on line {}: {line}", i + 1),
+                )
+                .into()
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [chrom, from, to, sex, ploidy] = fields[..] else {
+                return Err(error());
+            };
+            let position = |x: &str, wildcard: u64| match x {
+                "*" => Some(wildcard),
+                x => x.parse().ok(),
+            };
+            map.add(PloidyRegion {
+                chrom: chrom.into(),
+                start: position(from, 1).ok_or_else(error)?,
+                end: position(to, u64::MAX).ok_or_else(error)?,
+                sex: Some(sex.into()),
+                ploidy: ploidy.parse().map_err(|_| error())?,
+            });
+        }
+        Ok(map)
+    }
+
+    /// Read the `##ploidy=<CHROM=..,FROM=..,TO=..,SEX=..,PLOIDY=..>` lines of
+    /// a header. FROM/TO default to the whole contig and SEX to all samples.
+    /// Returns `None` if the header has no `##ploidy` line.
+    ///
+    /// ```
+    /// use bcf_reader::Header;
+    /// use bcf_reader::ploidy::PloidyMap;
+    /// let header = Header::from_string(concat!(
+    ///     "##fileformat=VCFv4.3\n",
+    ///     "##ploidy=<CHROM=chrM,PLOIDY=1>\n",
+    ///     "##ploidy=<CHROM=chrY,FROM=1,TO=57227415,SEX=F,PLOIDY=0>\n",
+    /// ));
+    /// let map = PloidyMap::from_header(&header).unwrap();
+    /// assert_eq!(map.ploidy("chrM", 100, None), 1);
+    /// assert_eq!(map.ploidy("chrY", 100, Some("F")), 0);
+    /// assert_eq!(map.ploidy("chrY", 100, Some("M")), 2);
+    /// ```
+    pub fn from_header(header: &Header) -> Option<Self> {
+        let mut map = Self::default();
+        for m in header
+            .lines()
+            .iter()
+            .filter(|l| l.key == "ploidy")
+            .filter_map(|l| l.attributes())
+        {
+            let (Some(chrom), Some(ploidy)) = (m.get("CHROM"), m.get("PLOIDY")) else {
+                continue;
+            };
+            let Ok(ploidy) = ploidy.parse() else {
+                continue;
+            };
+            map.add(PloidyRegion {
+                chrom: chrom.clone(),
+                start: m.get("FROM").and_then(|x| x.parse().ok()).unwrap_or(1),
+                end: m.get("TO").and_then(|x| x.parse().ok()).unwrap_or(u64::MAX),
+                sex: m.get("SEX").cloned(),
+                ploidy,
+            });
+        }
+        (!map.regions.is_empty()).then_some(map)
+    }
+
+    /// The `GRCh37` preset of bcftools (Ensembl contig names)
+    pub fn grch37() -> Self {
+        Self::parse(concat!(
+            "X 1 60000 M 1\n",
+            "X 2699521 154931043 M 1\n",
+            "Y 1 59373566 M 1\n",
+            "Y 1 59373566 F 0\n",
+            "MT 1 16569 M 1\n",
+            "MT 1 16569 F 1\n",
+            "* * * M 2\n",
+            "* * * F 2\n",
+        ))
+        .unwrap()
+    }
+
+    /// The `GRCh38` preset of bcftools (UCSC contig names)
+    pub fn grch38() -> Self {
+        Self::parse(concat!(
+            "chrX 1 9999 M 1\n",
+            "chrX 2781480 155701381 M 1\n",
+            "chrY 1 57227415 M 1\n",
+            "chrY 1 57227415 F 0\n",
+            "chrM 1 16569 M 1\n",
+            "chrM 1 16569 F 1\n",
+            "* * * M 2\n",
+            "* * * F 2\n",
+        ))
+        .unwrap()
+    }
+
+    /// Get the expected ploidy at a position (1-based) for a sex
+    /// (`None` if unknown: only regions without sex apply)
+    pub fn ploidy(&self, chrom: &str, pos: u64, sex: Option<&str>) -> u8 {
+        let specific = self
+            .regions
+            .iter()
+            .rev()
+            .find(|r| !r.is_default() && r.matches(chrom, pos, sex));
+        let default = || {
+            self.regions
+                .iter()
+                .rev()
+                .find(|r| r.is_default() && r.matches(chrom, pos, sex))
+        };
+        specific
+            .or_else(default)
+            .map(|r| r.ploidy)
+            .unwrap_or(self.fallback)
+    }
+
+    /// Get the expected ploidy of the genotypes of a record for a sex
+    pub fn record_ploidy(&self, record: &Record, header: &Header, sex: Option<&str>) -> u8 {
+        let chrom = header.rid_to_name(record.chrom()).unwrap_or_default();
+        self.ploidy(chrom, record.pos() as u64 + 1, sex)
+    }
+
+    /// Find the samples whose GT has a number of alleles (missing alleles
+    /// included, end-of-vector padding excluded) other than the expected
+    /// ploidy. `sexes` gives the sex of each sample of the record. Samples
+    /// with an expected ploidy of 0 are reported if they have any allele.
+    pub fn mismatched_samples(
+        &self,
+        record: &Record,
+        header: &Header,
+        sexes: &[Option<&str>],
+    ) -> error::Result<Vec<usize>> {
+        let n_sample = record.n_sample as usize;
+        if sexes.len() != n_sample {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} sexes given for a record with {n_sample} samples",
+                    sexes.len()
+                ),
+            )
+            .into());
+        }
+        let Some(width) = record.gt_field(header).map(|(_, n, _)| n) else {
+            return Ok(vec![]);
+        };
+        let mut gt = vec![0; width * n_sample];
+        record.decode_gt_into(header, &mut gt)?;
+        let mut mismatches = vec![];
+        for (i, (alleles, sex)) in gt.chunks(width.max(1)).zip(sexes).enumerate() {
+            let n_alleles = alleles.iter().filter(|&&a| a != -2).count();
+            if n_alleles != self.record_ploidy(record, header, *sex) as usize {
+                mismatches.push(i);
+            }
+        }
+        Ok(mismatches)
+    }
+}
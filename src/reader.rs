@@ -0,0 +1,1756 @@
+//! The sequential and indexed readers
+
+use crate::checks::{
+    apply_utf8_policy, check_conformance, check_duplicate, check_order, report_warnings,
+    CheckHeader, OrderTracker, WarningHandler,
+};
+use crate::records::{par_records, read_batch};
+use crate::scan::{scan_batches, SCAN_BATCH_BYTES};
+use crate::stats::{BufferSizing, CountingReader};
+use crate::{
+    error::{self, Error},
+    is_bcf21, no_more_records, read_header_limited, scan_allele_counts, AlleleCounts, BcfVersion,
+    Csi, DecompressionStats, DuplicateDetector, FieldSelection, Header, OrderCheck,
+    ParMultiGzipReader, ParseMode, PrefetchReader, ReadWarning, ReaderStats, Record, RecordBatches,
+    RecordSizeStats, RecordStream, Records, ResourceLimits, ScanBatch, Utf8Policy, VersionPolicy,
+    WarningKind,
+};
+#[cfg(feature = "format")]
+use crate::{read_format_column, ColumnValue, FormatColumn};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::time::Instant;
+
+/// A record skipped by [`BcfReader::read_record`] because it could not be
+/// parsed (see [`BcfReader::set_skip_corrupt_records`])
+#[derive(Debug)]
+pub struct SkippedRecord {
+    /// offset of the record in the decompressed data
+    pub offset: u64,
+    /// why the record could not be parsed
+    pub error: Error,
+}
+
+/// How far a [`BcfReader`] got in the data: the records read completely,
+/// including the corrupt records skipped, and where the next record starts.
+/// After the data ended within a record (e.g. an interrupted download),
+/// reading can resume from `offset` with the same header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Checkpoint {
+    /// number of records read completely
+    pub n_records: u64,
+    /// offset in the decompressed data of the end of the last complete
+    /// record (or of the header), where the next record starts
+    pub offset: u64,
+}
+
+/// The options shared by [`BcfReader`] and [`IndexedBcfReader`], and the
+/// checks they run on the header and on each record read
+#[derive(Default)]
+pub(crate) struct ReaderOptions {
+    pub(crate) field_selection: Option<FieldSelection>,
+    pub(crate) version_policy: VersionPolicy,
+    pub(crate) parse_mode: ParseMode,
+    pub(crate) utf8_policy: Utf8Policy,
+    pub(crate) limits: ResourceLimits,
+    pub(crate) order_check: OrderCheck,
+    pub(crate) stats_timing: bool,
+    pub(crate) buffer_sizing: BufferSizing,
+    pub(crate) warning_handler: Option<WarningHandler>,
+    pub(crate) bcf_version: Option<BcfVersion>,
+    pub(crate) header_warnings: Vec<String>,
+    /// the header, to check records against
+    pub(crate) header: CheckHeader,
+    pub(crate) record_warnings: Vec<String>,
+    pub(crate) order: OrderTracker,
+    pub(crate) duplicates: Option<DuplicateDetector>,
+}
+
+impl ReaderOptions {
+    fn set_field_selection(&mut self, selection: FieldSelection) {
+        self.field_selection = Some(selection);
+    }
+
+    fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.version_policy = policy;
+    }
+
+    fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.parse_mode = mode;
+    }
+
+    fn set_utf8_policy(&mut self, policy: Utf8Policy) {
+        self.utf8_policy = policy;
+    }
+
+    fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    fn set_order_check(&mut self, check: OrderCheck) {
+        self.order_check = check;
+    }
+
+    fn set_duplicate_detection(&mut self, enabled: bool) {
+        self.duplicates = enabled.then(DuplicateDetector::new);
+    }
+
+    fn set_stats_timing(&mut self, enabled: bool) {
+        self.stats_timing = enabled;
+    }
+
+    fn set_buffer_quantile(&mut self, q: Option<f64>) {
+        self.buffer_sizing.set_quantile(q);
+    }
+
+    fn set_warning_handler(&mut self, handler: impl FnMut(&ReadWarning) + Send + Sync + 'static) {
+        self.warning_handler = Some(Box::new(handler));
+    }
+
+    /// Read and check the header, keeping its version and warnings
+    fn read_header<R: Read>(&mut self, reader: &mut R) -> error::Result<Header> {
+        let mut raw =
+            read_header_limited(reader, &self.version_policy, self.limits.max_header_length)?;
+        self.bcf_version = Some(raw.version);
+        apply_utf8_policy(&mut raw, self.utf8_policy)?;
+        self.header_warnings = raw.warnings;
+        let header = Header::try_from_string(&raw.text)?;
+        let n_sample = header.get_samples().len();
+        if n_sample > self.limits.max_samples {
+            return Err(Error::LimitExceeded {
+                what: "number of samples",
+                value: n_sample as u64,
+                limit: self.limits.max_samples as u64,
+            });
+        }
+        let violations = header.check(self.parse_mode == ParseMode::Strict)?;
+        self.header_warnings
+            .extend(violations.iter().map(|v| v.to_string()));
+        let warnings = self.header_warnings.iter();
+        report_warnings(
+            &mut self.warning_handler,
+            warnings.map(|w| ReadWarning::new(WarningKind::Header, w.clone())),
+            None,
+        );
+        self.header = CheckHeader::new(header.clone());
+        Ok(header)
+    }
+
+    /// Run the checks on a record just read, at `offset` if known
+    fn check_record(&mut self, record: &Record, offset: Option<u64>) -> error::Result<()> {
+        check_conformance(
+            record,
+            &self.header,
+            self.parse_mode,
+            self.utf8_policy,
+            &mut self.record_warnings,
+            &mut self.warning_handler,
+            offset,
+        )?;
+        check_order(
+            record,
+            &self.header,
+            self.order_check,
+            &mut self.order,
+            &mut self.record_warnings,
+            &mut self.warning_handler,
+            offset,
+        )?;
+        check_duplicate(
+            record,
+            &self.header,
+            &mut self.duplicates,
+            &mut self.record_warnings,
+            &mut self.warning_handler,
+            offset,
+        );
+        Ok(())
+    }
+
+    /// Start the order and duplicate checks again, e.g. at a new interval
+    fn restart_checks(&mut self) {
+        self.order = OrderTracker::default();
+        if self.duplicates.is_some() {
+            self.duplicates = Some(DuplicateDetector::new());
+        }
+    }
+}
+
+/// BcfReader suitable for read through the BCF file.
+///
+/// This assumes that the source reader is in BCF format and stored as BGZF blocks.
+///
+/// # Example
+/// ```
+/// use bcf_reader::*;
+/// use std::{ fs::File, io::{BufReader, Seek} };
+/// // underlying reader can be stdin or a file
+/// let reader = std::fs::File::open("testdata/test3.bcf")
+/// .map(BufReader::new)
+/// .unwrap();
+/// // 1. for sequential decompression (works for data from stdin or a file)
+/// let reader = flate2::bufread::MultiGzDecoder::new(reader);
+/// // 2. for parallel decompression (works for data from stdin or a file)
+/// // however, when data is from stdin, jump is not supported for now.
+/// // let reader = ParMultiGzipReader::from_reader(reader, 3, None, None);
+///
+/// // create bcf reader
+/// let mut reader = BcfReader::from_reader(reader);
+/// // read though header
+/// let _header = reader.read_header();
+/// assert_eq!(reader.bcf_version(), Some(BcfVersion { major: 2, minor: 2 }));
+/// // create a reusable record
+/// let mut record = Record::default();
+///
+/// let mut pos_found = vec![];
+/// // iterate records from the targeted genome interval
+/// while let Ok(_) = reader.read_record(&mut record) {
+/// pos_found.push(record.pos() + 1);
+/// }
+///
+/// assert_eq!(pos_found[0], 72);
+/// assert_eq!(*pos_found.last().unwrap(), 1498841);
+/// ```
+pub struct BcfReader<R>
+where
+    R: Read,
+{
+    inner: R,
+    header_parsed: bool,
+    options: ReaderOptions,
+    skip_corrupt_records: bool,
+    skipped_records: Vec<SkippedRecord>,
+    /// offset of the next record in the decompressed data
+    offset: u64,
+    /// offset of the last record read
+    record_offset: u64,
+    checkpoint: Checkpoint,
+    stats: ReaderStats,
+    /// records reused by `read_batch`
+    record_pool: Vec<Record>,
+}
+
+impl<R> BcfReader<R>
+where
+    R: Read,
+{
+    /// `max_gzip`, the number of gzip blocks to read before batch
+    /// decompression.  See `ParMultiGzipReader::from_reader` (by default or
+    /// None, 3 gzip buffers will be used.)
+    pub fn from_reader(reader: R) -> Self {
+        Self {
+            inner: reader,
+            header_parsed: false,
+            options: ReaderOptions::default(),
+            skip_corrupt_records: false,
+            skipped_records: vec![],
+            offset: 0,
+            record_offset: 0,
+            checkpoint: Checkpoint::default(),
+            stats: ReaderStats::default(),
+            record_pool: vec![],
+        }
+    }
+
+    /// Only keep the selected INFO/FORMAT fields in records read by
+    /// `read_record` (see [`Record::read_selected`]).
+    pub fn set_field_selection(&mut self, selection: FieldSelection) {
+        self.options.set_field_selection(selection);
+    }
+
+    /// Read the header
+    pub fn read_header(&mut self) -> Header {
+        self.try_read_header().unwrap()
+    }
+
+    /// Read the header bytes, parse them and return a `Header`, or an error
+    /// if the BCF version is rejected by the version policy (see
+    /// [`BcfReader::set_version_policy`]) or the header is malformed.
+    /// Warnings, e.g. about an unknown minor version accepted by the
+    /// policy, are available from `header_warnings`.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // a BCF 2.3 file with an empty header
+    /// let mut data = b"BCF\x02\x03".to_vec();
+    /// let text = b"##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0";
+    /// data.extend((text.len() as u32).to_le_bytes());
+    /// data.extend(text);
+    ///
+    /// // accepted with a warning by default
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// assert!(reader.try_read_header().is_ok());
+    /// assert_eq!(reader.bcf_version(), Some(BcfVersion { major: 2, minor: 3 }));
+    /// assert_eq!(reader.header_warnings().len(), 1);
+    ///
+    /// // rejected when unknown minor versions are not accepted
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_version_policy(VersionPolicy {
+    ///     accept_unknown_minor: false,
+    ///     ..Default::default()
+    /// });
+    /// let err = reader.try_read_header().unwrap_err();
+    /// assert_eq!(err.to_string(), "unsupported BCF version 2.3");
+    /// ```
+    pub fn try_read_header(&mut self) -> error::Result<Header> {
+        let mut counted = CountingReader::new(&mut self.inner);
+        let header = self.options.read_header(&mut counted);
+        self.offset = counted.count;
+        self.checkpoint.offset = self.offset;
+        let header = header?;
+        self.header_parsed = true;
+        Ok(header)
+    }
+
+    /// Get the warnings raised while reading the header
+    pub fn header_warnings(&self) -> &[String] {
+        &self.options.header_warnings
+    }
+
+    /// Set the limits on the size of the header and records (see
+    /// [`ResourceLimits`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let limits = ResourceLimits {
+    ///     max_samples: 100,
+    ///     ..ResourceLimits::untrusted()
+    /// };
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.set_limits(limits.clone());
+    /// let err = reader.try_read_header().unwrap_err();
+    /// assert!(err.to_string().starts_with("number of samples"));
+    ///
+    /// // records longer than 1000 bytes are skipped
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.set_limits(ResourceLimits {
+    ///     max_record_length: 1000,
+    ///     ..ResourceLimits::untrusted()
+    /// });
+    /// reader.set_skip_corrupt_records(true);
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let mut n_read = 0;
+    /// while reader.read_record(&mut record).is_ok() {
+    ///     assert!(record.buf_shared().len() + record.buf_indiv().len() <= 1000);
+    ///     n_read += 1;
+    /// }
+    /// let skipped = reader.skipped_records();
+    /// assert_eq!(n_read + skipped.len(), 127);
+    /// assert!(matches!(skipped[0].error, Error::LimitExceeded { what: "record length", .. }));
+    /// ```
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.options.set_limits(limits);
+    }
+
+    /// Set how text that is not valid UTF-8 is handled in the header and
+    /// the records (see [`Utf8Policy`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // a file with a record whose ID is not valid UTF-8
+    /// let text = b"##fileformat=VCFv4.2\n##contig=<ID=chr1,length=1000>\n\
+    ///     #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0";
+    /// let header = |text: &[u8]| {
+    ///     let mut data = b"BCF\x02\x02".to_vec();
+    ///     data.extend((text.len() as u32).to_le_bytes());
+    ///     data.extend(text);
+    ///     data
+    /// };
+    /// let mut data = header(text);
+    /// let mut shared = vec![];
+    /// for x in [0i32, 99, 1] {
+    ///     shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    /// }
+    /// shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    /// shared.extend((1u32 << 16).to_le_bytes()); // 1 allele
+    /// shared.extend(0u32.to_le_bytes()); // no samples
+    /// shared.extend([0x27, b'r', 0xE9, 0x17, b'A', 0x00]); // ID, REF, FILTER
+    /// data.extend((shared.len() as u32).to_le_bytes());
+    /// data.extend(0u32.to_le_bytes());
+    /// data.extend(shared);
+    ///
+    /// let mut record = Record::default();
+    /// for (policy, n_warnings) in [(Utf8Policy::Lossy, 1), (Utf8Policy::Raw, 0)] {
+    ///     let mut reader = BcfReader::from_reader(data.as_slice());
+    ///     reader.set_utf8_policy(policy);
+    ///     reader.read_header();
+    ///     reader.read_record(&mut record).unwrap();
+    ///     assert_eq!(reader.record_warnings().len(), n_warnings);
+    ///     assert_eq!(record.id_bytes(), b"r\xE9");
+    /// }
+    ///
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_utf8_policy(Utf8Policy::Error);
+    /// reader.read_header();
+    /// let err = reader.read_record(&mut record).unwrap_err();
+    /// assert!(matches!(err, Error::InvalidUtf8 { .. }));
+    /// assert!(err.to_string().starts_with("invalid UTF-8 in the ID"));
+    ///
+    /// // the header text is only reported when a policy opts in
+    /// let data = header(b"##fileformat=VCFv4.2\n##source=caf\xE9\n\
+    ///     #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0");
+    /// for (policy, n_warnings) in [(Utf8Policy::Lossy, 1), (Utf8Policy::Raw, 0)] {
+    ///     let mut reader = BcfReader::from_reader(data.as_slice());
+    ///     reader.set_utf8_policy(policy);
+    ///     let header = reader.try_read_header().unwrap();
+    ///     assert_eq!(reader.header_warnings().len(), n_warnings);
+    ///     assert_eq!(header.to_text().lines().nth(1), Some("##source=caf\u{FFFD}"));
+    /// }
+    /// ```
+    pub fn set_utf8_policy(&mut self, policy: Utf8Policy) {
+        self.options.set_utf8_policy(policy);
+    }
+
+    /// Set how strictly the header and records are checked (see
+    /// [`ParseMode`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// // a file with a record with an empty ALT allele and a float INFO/DP
+    /// let bcf = |text: &str| {
+    ///     let mut data = b"BCF\x02\x02".to_vec();
+    ///     data.extend((text.len() as u32 + 1).to_le_bytes());
+    ///     data.extend(text.as_bytes());
+    ///     data.push(0);
+    ///     let mut shared = vec![];
+    ///     for x in [0i32, 99, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((1u32 | 2 << 16).to_le_bytes()); // 1 INFO, 2 alleles
+    ///     shared.extend(0u32.to_le_bytes()); // no samples
+    ///     shared.extend([0x07, 0x17, b'A', 0x07, 0x00]); // ID, alleles, FILTER
+    ///     shared.extend([0x11, 1, 0x15]); // DP
+    ///     shared.extend(10f32.to_le_bytes());
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    ///     data
+    /// };
+    /// let text = concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer>\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// );
+    ///
+    /// // lenient mode (the default) reports the deviations as warnings
+    /// let data = bcf(text);
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.read_header();
+    /// assert_eq!(reader.header_warnings(), ["error: header line 3: INFO/DP has no Description"]);
+    /// let mut record = Record::default();
+    /// reader.read_record(&mut record).unwrap();
+    /// assert_eq!(
+    ///     reader.record_warnings(),
+    ///     ["allele 1 is empty", "INFO/DP is declared as Integer but encoded as BCF2 type 5"]
+    /// );
+    ///
+    /// // strict mode rejects the header, and the record with a valid header
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_parse_mode(ParseMode::Strict);
+    /// assert!(reader.try_read_header().is_err());
+    /// let data = bcf(&text.replace("Integer>", "Integer,Description=\"Depth\">"));
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_parse_mode(ParseMode::Strict);
+    /// reader.try_read_header().unwrap();
+    /// let err = reader.read_record(&mut record).unwrap_err();
+    /// assert!(matches!(err, Error::Nonconforming(_)));
+    /// ```
+    pub fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.options.set_parse_mode(mode);
+    }
+
+    /// Get the deviations from the specification found in the last record
+    /// read in lenient mode (see [`Record::conformance_issues`])
+    pub fn record_warnings(&self) -> &[String] {
+        &self.options.record_warnings
+    }
+
+    /// Report recoverable oddities to `handler` as they are found, without
+    /// stopping: the header warnings, the record warnings (see
+    /// `record_warnings`) and the [`Record::anomalies`] of each record, such
+    /// as trailing bytes and bad padding. Finding the anomalies requires
+    /// scanning the values of each record, so it is only done when a handler
+    /// is set.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::sync::{Arc, Mutex};
+    /// let text = concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// );
+    /// let mut data = b"BCF\x02\x02".to_vec();
+    /// data.extend((text.len() as u32 + 1).to_le_bytes());
+    /// data.extend(text.as_bytes());
+    /// data.push(0);
+    /// for (filter, trailing) in [(0u8, 0), (5, 0), (0, 3)] {
+    ///     let mut shared = vec![];
+    ///     for x in [0i32, 99, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((1u32 | 1 << 16).to_le_bytes()); // 1 INFO, 1 allele
+    ///     shared.extend(0u32.to_le_bytes()); // no samples
+    ///     shared.extend([0x07, 0x17, b'A', 0x11, filter]); // ID, REF, FILTER
+    ///     shared.extend([0x11, 1, 0x11, 30]); // DP
+    ///     shared.extend(vec![0; trailing]);
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    /// }
+    ///
+    /// let warnings = Arc::new(Mutex::new(vec![]));
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// let sink = warnings.clone();
+    /// reader.set_warning_handler(move |w: &ReadWarning| sink.lock().unwrap().push(w.to_string()));
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let mut n_records = 0;
+    /// while reader.read_record(&mut record).is_ok() {
+    ///     n_records += 1;
+    /// }
+    /// assert_eq!(n_records, 3);
+    /// assert_eq!(
+    ///     *warnings.lock().unwrap(),
+    ///     [
+    ///         "record at byte 199: FILTER key 5 is not defined in the header",
+    ///         "record at byte 240: 3 bytes after the last field of the shared buffer",
+    ///     ]
+    /// );
+    /// ```
+    pub fn set_warning_handler(
+        &mut self,
+        handler: impl FnMut(&ReadWarning) + Send + Sync + 'static,
+    ) {
+        self.options.set_warning_handler(handler);
+    }
+
+    /// Check that the records are sorted as they are read (see
+    /// [`OrderCheck`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let text = concat!(
+    ///     "##fileformat=VCFv4.2\n",
+    ///     "##contig=<ID=chr1,length=1000>\n",
+    ///     "##contig=<ID=chr2,length=1000>\n",
+    ///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+    /// );
+    /// let mut data = b"BCF\x02\x02".to_vec();
+    /// data.extend((text.len() as u32 + 1).to_le_bytes());
+    /// data.extend(text.as_bytes());
+    /// data.push(0);
+    /// for (chrom, pos) in [(0i32, 99i32), (0, 9), (1, 9), (0, 199)] {
+    ///     let mut shared = vec![];
+    ///     for x in [chrom, pos, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((1u32 << 16).to_le_bytes()); // no INFO, 1 allele
+    ///     shared.extend(0u32.to_le_bytes()); // no samples
+    ///     shared.extend([0x07, 0x17, b'A', 0x00]); // ID, REF, FILTER
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    /// }
+    ///
+    /// // warnings for the second and the last records
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_order_check(OrderCheck::Warn);
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let mut warnings = vec![];
+    /// while reader.read_record(&mut record).is_ok() {
+    ///     warnings.extend(reader.record_warnings().iter().cloned());
+    /// }
+    /// assert_eq!(
+    ///     warnings,
+    ///     [
+    ///         "chr1:10 is after chr1:100",
+    ///         "chr1:200 is after records of other contigs following contig chr1",
+    ///     ]
+    /// );
+    ///
+    /// // an error for the second record
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_order_check(OrderCheck::Error);
+    /// reader.read_header();
+    /// reader.read_record(&mut record).unwrap();
+    /// let err = reader.read_record(&mut record).unwrap_err();
+    /// assert_eq!(err.to_string(), "records are not sorted: chr1:10 is after chr1:100");
+    /// ```
+    pub fn set_order_check(&mut self, check: OrderCheck) {
+        self.options.set_order_check(check);
+    }
+
+    /// Report records with the same contig, position and alleles as the
+    /// previous record as warnings (see `record_warnings` and
+    /// [`BcfReader::set_warning_handler`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut data = vec![];
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// f.read_to_end(&mut data).unwrap();
+    /// // the first record again after the header (the records start after l_text)
+    /// let l_text = u32::from_le_bytes(data[5..9].try_into().unwrap()) as usize;
+    /// let first = 9 + l_text;
+    /// let l_shared = u32::from_le_bytes(data[first..first + 4].try_into().unwrap()) as usize;
+    /// let l_indv = u32::from_le_bytes(data[first + 4..first + 8].try_into().unwrap()) as usize;
+    /// let record_bytes = data[first..first + 8 + l_shared + l_indv].to_vec();
+    /// data.splice(first..first, record_bytes);
+    ///
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_duplicate_detection(true);
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let mut duplicates = vec![];
+    /// while reader.read_record(&mut record).is_ok() {
+    ///     duplicates.extend(reader.record_warnings().iter().cloned());
+    /// }
+    /// assert_eq!(duplicates, ["Pf3D7_01_v3:1119 T,A is a duplicate of the previous record"]);
+    /// ```
+    pub fn set_duplicate_detection(&mut self, enabled: bool) {
+        self.options.set_duplicate_detection(enabled);
+    }
+
+    /// Set the BCF versions accepted by `read_header`
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.options.set_version_policy(policy);
+    }
+
+    /// Get the BCF version of the file, once the header is read
+    pub fn bcf_version(&self) -> Option<BcfVersion> {
+        self.options.bcf_version
+    }
+
+    /// Read one record. This should be called after the header is read and parsed.
+    /// Otherwise, it will panic.
+    pub fn read_record(&mut self, record: &mut Record) -> error::Result<()> {
+        self.read_next(record, false)?.ok_or_else(no_more_records)
+    }
+
+    /// Read one record as `read_record`, returning `Ok(None)` at the end of
+    /// the file (see [`Record::try_read`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let mut n_records = 0;
+    /// while let Some(()) = reader.try_read_record(&mut record).unwrap() {
+    ///     n_records += 1;
+    /// }
+    /// assert_eq!(n_records, 127);
+    /// ```
+    pub fn try_read_record(&mut self, record: &mut Record) -> error::Result<Option<()>> {
+        self.read_next(record, false)
+    }
+
+    /// Iterate over the remaining records. This should be called after the
+    /// header is read and parsed. Otherwise, the iteration will panic.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// let header = reader.try_read_header()?;
+    /// let mut positions = vec![];
+    /// for record in reader.records() {
+    ///     let record = record?;
+    ///     positions.push((header.rid_to_name(record.chrom()).unwrap().to_string(), record.pos() + 1));
+    /// }
+    /// assert_eq!(positions.len(), 127);
+    /// assert_eq!(positions[0], ("Pf3D7_01_v3".to_string(), 1119));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn records(&mut self) -> Records<'_, Self> {
+        Records::new(self)
+    }
+
+    /// Iterate over the remaining records without allocating a record for
+    /// each (see [`RecordStream`]). This should be called after the header
+    /// is read and parsed. Otherwise, the iteration will panic.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.read_header();
+    /// let mut stream = reader.stream_records();
+    /// let mut n_alleles = 0;
+    /// while let Some(record) = stream.next() {
+    ///     n_alleles += record.unwrap().n_allele() as usize;
+    /// }
+    /// assert!(n_alleles >= 2 * 127);
+    /// ```
+    pub fn stream_records(&mut self) -> RecordStream<'_, Self> {
+        RecordStream::with_record(self, Record::default())
+    }
+
+    /// Iterate over the remaining records in batches of `batch_size` records
+    /// (see [`RecordBatches`]). This should be called after the header is
+    /// read and parsed. Otherwise, the iteration will panic.
+    pub fn record_batches(&mut self, batch_size: usize) -> RecordBatches<'_, Self> {
+        RecordBatches::new(self.records(), batch_size)
+    }
+
+    /// Read the next `n` records, or fewer at the end of the data (an empty
+    /// batch after the last record). This should be called after the header
+    /// is read and parsed. Otherwise, it will panic.
+    ///
+    /// The records are taken from a pool of the reader, filled by
+    /// [`recycle_records`](Self::recycle_records), so that the buffers of
+    /// the records of a processed batch are reused for the next batches
+    /// instead of being allocated again. If a record cannot be read, the
+    /// error is returned and the records of the batch go back to the pool.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use rayon::prelude::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// let header = reader.read_header();
+    /// let mut n_records = 0;
+    /// let mut n_called = 0;
+    /// loop {
+    ///     let batch = reader.read_batch(50).unwrap();
+    ///     if batch.is_empty() {
+    ///         break;
+    ///     }
+    ///     n_records += batch.len();
+    ///     n_called += batch
+    ///         .par_iter()
+    ///         .map(|r| r.genotype_class_counts(&header).n_called())
+    ///         .sum::<u32>();
+    ///     reader.recycle_records(batch);
+    /// }
+    /// assert_eq!(n_records, 127);
+    /// assert!(n_called > 0);
+    /// ```
+    pub fn read_batch(&mut self, n: usize) -> error::Result<Vec<Record>> {
+        let mut pool = std::mem::take(&mut self.record_pool);
+        let batch = read_batch(self, &mut pool, n);
+        self.record_pool = pool;
+        batch
+    }
+
+    /// Give records back to the pool of [`read_batch`](Self::read_batch),
+    /// to be reused by the next batches
+    pub fn recycle_records(&mut self, records: impl IntoIterator<Item = Record>) {
+        self.record_pool.extend(records);
+    }
+
+    /// Read one FORMAT field of the remaining records (or of the next
+    /// `max_records` records) into a single buffer, `width` values per
+    /// sample (see [`Record::fmt_field_into`]).
+    ///
+    /// The records are read in a single pass that skips INFO and the other
+    /// FORMAT fields; the sample selection of
+    /// [`set_field_selection`](Self::set_field_selection), if any, still
+    /// applies. An `InvalidInput` error is returned if `tag` is not defined
+    /// in the header.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// let header = reader.read_header();
+    /// let dp = reader.read_format_column::<f32>(&header, "DP", 1, Some(100)).unwrap();
+    /// assert_eq!((dp.n_records(), dp.n_samples()), (100, header.get_samples().len()));
+    /// let rest = reader.read_format_column::<i32>(&header, "DP", 1, None).unwrap();
+    /// assert_eq!(rest.n_records(), 27);
+    ///
+    /// // the same values as the records
+    /// let mut f = smart_reader("testdata/test.bcf");
+    /// read_header(&mut f).unwrap();
+    /// let dp_key = header.get_idx_from_dictionary_str("FORMAT", "DP").unwrap();
+    /// let mut record = Record::default();
+    /// for r in 0..127 {
+    ///     record.read(&mut f).unwrap();
+    ///     let mut expected = vec![];
+    ///     record.fmt_field_into::<i32>(dp_key, 1, &mut expected).unwrap();
+    ///     if r < 100 {
+    ///         let values = dp.record(r);
+    ///         assert!(values.iter().zip(&expected).all(|(x, &y)| x.is_nan() || *x == y as f32));
+    ///     } else {
+    ///         assert_eq!(rest.record(r - 100), &expected[..]);
+    ///     }
+    /// }
+    /// assert!(reader.read_format_column::<i32>(&header, "XX", 1, None).is_err());
+    /// ```
+    #[cfg(feature = "format")]
+    pub fn read_format_column<T: ColumnValue>(
+        &mut self,
+        header: &Header,
+        tag: &str,
+        width: usize,
+        max_records: Option<usize>,
+    ) -> error::Result<FormatColumn<T>> {
+        let saved = self.options.field_selection.take();
+        if let Some(key) = header.get_idx_from_dictionary_str("FORMAT", tag) {
+            self.options.field_selection = Some(FieldSelection::only_format(saved.as_ref(), key));
+        }
+        let column = read_format_column(self, header, tag, width, max_records);
+        self.options.field_selection = saved;
+        column
+    }
+
+    /// Compute the allele counts (AC/AN, see [`Record::allele_counts`]) of
+    /// the remaining records and pass them to `f` with each record, and
+    /// return the number of records.
+    ///
+    /// The records are read in a single pass that only parses FORMAT/GT: the
+    /// records passed to `f` have no INFO field and no other FORMAT field.
+    /// The sample selection of
+    /// [`set_field_selection`](Self::set_field_selection), if any, still
+    /// applies.
+    ///
+    /// Example:
+    #[cfg_attr(feature = "info", doc = "```")]
+    #[cfg_attr(not(feature = "info"), doc = "```ignore")]
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test2.bcf"));
+    /// let header = reader.read_header();
+    /// let mut counts = vec![];
+    /// let n = reader
+    ///     .scan_allele_counts(&header, |record, c| counts.push((record.pos(), c.clone())))
+    ///     .unwrap();
+    /// assert_eq!(n, counts.len());
+    ///
+    /// // compare with INFO/AC and INFO/AN filled by bcftools
+    /// let mut f = smart_reader("testdata/test2.bcf");
+    /// read_header(&mut f).unwrap();
+    /// let ac_key = header.get_idx_from_dictionary_str("INFO", "AC").unwrap();
+    /// let an_key = header.get_idx_from_dictionary_str("INFO", "AN").unwrap();
+    /// let mut record = Record::default();
+    /// for (pos, c) in counts {
+    ///     record.read(&mut f).unwrap();
+    ///     assert_eq!(pos, record.pos());
+    ///     let ac: Vec<u32> = record.info_field_numeric(ac_key).map(|nv| nv.int_val().unwrap()).collect();
+    ///     let an = record.info_field_numeric(an_key).next().unwrap().int_val().unwrap();
+    ///     assert_eq!((c.ac(), c.an()), (&ac[..], an));
+    ///     if an > 0 {
+    ///         assert_eq!(c.af().unwrap()[0], ac[0] as f32 / an as f32);
+    ///     }
+    /// }
+    /// ```
+    pub fn scan_allele_counts<F>(&mut self, header: &Header, f: F) -> error::Result<usize>
+    where
+        F: FnMut(&Record, &AlleleCounts),
+    {
+        let saved = self.options.field_selection.take();
+        if let Some(key) = header.get_fmt_gt_id() {
+            self.options.field_selection = Some(FieldSelection::only_format(saved.as_ref(), key));
+        }
+        let n = scan_allele_counts(self, header, f);
+        self.options.field_selection = saved;
+        n
+    }
+
+    /// Iterate over the remaining records in parallel with rayon: batches of
+    /// `batch_size` records are read one after the other, and the records
+    /// of each batch are processed in parallel. The records are not
+    /// processed in file order. A batch in which a record cannot be read
+    /// yields the error, and the iteration stops after it.
+    ///
+    /// This should be called after the header is read and parsed. Otherwise,
+    /// the iteration will panic.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use rayon::prelude::*;
+    /// use std::{fs::File, io::BufReader};
+    /// // the inner reader should be `Send`, unlike `smart_reader`
+    /// let f = File::open("testdata/test.bcf").map(BufReader::new).unwrap();
+    /// let mut reader = BcfReader::from_reader(ParMultiGzipReader::from_reader(f, 3, None, None));
+    /// let header = reader.read_header();
+    /// let n_called: u32 = reader
+    ///     .par_records(16)
+    ///     .map(|record| record.unwrap().genotype_class_counts(&header).n_called())
+    ///     .sum();
+    ///
+    /// // the same count read sequentially
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.read_header();
+    /// let n_called_seq: u32 = reader
+    ///     .records()
+    ///     .map(|record| record.unwrap().genotype_class_counts(&header).n_called())
+    ///     .sum();
+    /// assert_eq!(n_called, n_called_seq);
+    /// assert!(n_called > 0);
+    /// ```
+    pub fn par_records(
+        &mut self,
+        batch_size: usize,
+    ) -> impl ParallelIterator<Item = error::Result<Record>> + '_
+    where
+        R: Send,
+    {
+        par_records(self, batch_size, usize::MAX)
+    }
+
+    /// Like [`par_records`](Self::par_records), with the size of the
+    /// batches chosen so that the records waiting to be processed use about
+    /// `memory_budget` bytes at most (see [`Record::memory_size`]).
+    ///
+    /// Each thread of the rayon pool holds one batch, so each batch gets
+    /// `memory_budget / rayon::current_num_threads()` bytes, and has at most
+    /// `max_batch_size` records. Large records make smaller batches (of one
+    /// record at least) rather than exceeding the budget, so that a job
+    /// slows down instead of running out of memory. The budget does not
+    /// include the buffers of the inner reader.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use rayon::prelude::*;
+    /// use std::{fs::File, io::BufReader};
+    /// let f = File::open("testdata/test.bcf").map(BufReader::new).unwrap();
+    /// let mut reader = BcfReader::from_reader(ParMultiGzipReader::from_reader(f, 3, None, None));
+    /// reader.read_header();
+    /// let n = reader.par_records_with_budget(1000, 1 << 20).count();
+    /// assert_eq!(n, 127);
+    /// ```
+    pub fn par_records_with_budget(
+        &mut self,
+        max_batch_size: usize,
+        memory_budget: usize,
+    ) -> impl ParallelIterator<Item = error::Result<Record>> + '_
+    where
+        R: Send,
+    {
+        let max_bytes = memory_budget / rayon::current_num_threads();
+        par_records(self, max_batch_size, max_bytes)
+    }
+
+    /// Read one record, skipping its sample-level data (see
+    /// [`Record::read_site_only`]). This should be called after the header is
+    /// read and parsed. Otherwise, it will panic.
+    pub fn read_record_site_only(&mut self, record: &mut Record) -> error::Result<()> {
+        self.read_next(record, true)?.ok_or_else(no_more_records)
+    }
+
+    /// Read the next record, skipping the corrupt ones if
+    /// `skip_corrupt_records` is set; `Ok(None)` at the end of the data
+    fn read_next(&mut self, record: &mut Record, site_only: bool) -> error::Result<Option<()>> {
+        assert!(
+            self.header_parsed,
+            "header should be parsed before reading records"
+        );
+        loop {
+            let start = self.options.stats_timing.then(Instant::now);
+            let mut counted = match start {
+                Some(_) => CountingReader::timed(&mut self.inner),
+                None => CountingReader::new(&mut self.inner),
+            };
+            self.options.buffer_sizing.before_read(record);
+            let res = record
+                .read_limited(
+                    &mut counted,
+                    self.options.field_selection.as_ref(),
+                    site_only,
+                    &self.options.limits,
+                )
+                .and_then(|read| {
+                    if read.is_none() {
+                        return Ok(None);
+                    }
+                    if !site_only && is_bcf21(self.options.bcf_version) {
+                        record.upgrade_bcf21_vectors();
+                    }
+                    self.options.check_record(record, Some(self.offset))?;
+                    Ok(Some(()))
+                });
+            let offset = self.offset;
+            self.offset += counted.count;
+            self.stats.n_bytes += counted.count;
+            self.stats.n_records += matches!(res, Ok(Some(()))) as u64;
+            if let Ok(Some(())) = res {
+                self.options.buffer_sizing.after_read(record);
+            }
+            if let (Some(start), Some(read_time)) = (start, counted.time) {
+                self.stats.read_time += read_time;
+                self.stats.parse_time += start.elapsed().saturating_sub(read_time);
+            }
+            // the record was read to its end, even if it is corrupt
+            let complete = match &res {
+                Ok(read) => read.is_some(),
+                Err(e) => !matches!(e, Error::Io(_)),
+            };
+            if complete {
+                self.checkpoint.n_records += 1;
+                self.checkpoint.offset = self.offset;
+            }
+            match res {
+                // the whole record was read, so the next one can be read
+                Err(error) if self.skip_corrupt_records && !matches!(error, Error::Io(_)) => {
+                    self.skipped_records.push(SkippedRecord { offset, error })
+                }
+                res => {
+                    self.record_offset = offset;
+                    return res;
+                }
+            }
+        }
+    }
+
+    /// Skip records that cannot be parsed instead of returning an error,
+    /// using their length (`l_shared` and `l_indv`) to find the next record.
+    /// The skipped records are reported by `skipped_records`. Errors reading
+    /// the data (e.g. a truncated file) are still returned.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let record = |pos: i32, alt: &[u8]| {
+    ///     let mut shared = vec![];
+    ///     for x in [0i32, pos, 1] {
+    ///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+    ///     }
+    ///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+    ///     shared.extend((2u32 << 16).to_le_bytes()); // 2 alleles, no INFO
+    ///     shared.extend(0u32.to_le_bytes()); // no samples
+    ///     shared.extend([0x07, 0x17, b'A']); // ID, REF
+    ///     shared.extend(alt);
+    ///     shared.push(0x00); // FILTER
+    ///     let mut data = vec![];
+    ///     data.extend((shared.len() as u32).to_le_bytes());
+    ///     data.extend(0u32.to_le_bytes());
+    ///     data.extend(shared);
+    ///     data
+    /// };
+    /// let text = b"##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0";
+    /// let mut data = b"BCF\x02\x02".to_vec();
+    /// data.extend((text.len() as u32).to_le_bytes());
+    /// data.extend(text);
+    /// data.extend(record(10, &[0x17, b'C']));
+    /// let corrupt_offset = data.len() as u64;
+    /// // an integer instead of the ALT allele
+    /// data.extend(record(20, &[0x11, 3]));
+    /// data.extend(record(30, &[0x17, b'G']));
+    ///
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.read_header();
+    /// reader.set_skip_corrupt_records(true);
+    /// let mut record = Record::default();
+    /// let mut positions = vec![];
+    /// while reader.read_record(&mut record).is_ok() {
+    ///     positions.push(record.pos());
+    /// }
+    /// assert_eq!(positions, [10, 30]);
+    /// let skipped = reader.skipped_records();
+    /// assert_eq!(skipped.len(), 1);
+    /// assert_eq!(skipped[0].offset, corrupt_offset);
+    ///
+    /// // without skipping, the error is returned but reading can go on
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.read_header();
+    /// assert!(reader.read_record(&mut record).is_ok());
+    /// assert!(reader.read_record(&mut record).is_err());
+    /// assert!(reader.read_record(&mut record).is_ok());
+    /// assert_eq!(record.pos(), 30);
+    /// ```
+    pub fn set_skip_corrupt_records(&mut self, skip: bool) {
+        self.skip_corrupt_records = skip;
+    }
+
+    /// Get the records skipped by `read_record` (see
+    /// [`BcfReader::set_skip_corrupt_records`])
+    pub fn skipped_records(&self) -> &[SkippedRecord] {
+        &self.skipped_records
+    }
+
+    /// Get the offset in the decompressed data of the next record, or of
+    /// the end of the data read so far after an error
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the offset in the decompressed data of the last record read, or
+    /// of the record that could not be read after an error
+    pub fn record_offset(&self) -> u64 {
+        self.record_offset
+    }
+
+    /// Get how far the reader got in the data (see [`Checkpoint`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut data = vec![];
+    /// smart_reader("testdata/test.bcf").read_to_end(&mut data).unwrap();
+    /// // the download was interrupted within a record
+    /// let partial = &data[..data.len() / 2];
+    /// let mut reader = BcfReader::from_reader(partial);
+    /// reader.read_header();
+    /// let mut record = Record::default();
+    /// let err = loop {
+    ///     if let Err(e) = reader.read_record(&mut record) {
+    ///         break e;
+    ///     }
+    /// };
+    /// assert!(matches!(err, Error::Io(_)));
+    /// let checkpoint = reader.checkpoint();
+    /// assert!(checkpoint.n_records > 0 && checkpoint.n_records < 127);
+    ///
+    /// // resume from the checkpoint once the rest of the data is available
+    /// let mut rest = &data[checkpoint.offset as usize..];
+    /// let mut n_records = checkpoint.n_records;
+    /// while let Some(()) = record.try_read(&mut rest).unwrap() {
+    ///     n_records += 1;
+    /// }
+    /// assert_eq!(n_records, 127);
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    /// Measure the time spent reading and parsing the records in
+    /// [`stats`](Self::stats) (off by default)
+    pub fn set_stats_timing(&mut self, enabled: bool) {
+        self.options.set_stats_timing(enabled);
+    }
+
+    /// Get the counters of the records read so far (see [`ReaderStats`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::{fs::File, io::BufReader};
+    /// let f = File::open("testdata/test.bcf").map(BufReader::new).unwrap();
+    /// let mut reader = BcfReader::from_reader(ParMultiGzipReader::from_reader(f, 3, None, None));
+    /// reader.read_header();
+    /// let header_end = reader.offset();
+    /// reader.set_stats_timing(true);
+    /// let mut record = Record::default();
+    /// while let Some(()) = reader.try_read_record(&mut record).unwrap() {}
+    /// let stats = reader.stats();
+    /// assert_eq!(stats.n_records, 127);
+    /// assert_eq!(stats.n_bytes, reader.offset() - header_end);
+    /// assert!(stats.parse_time > std::time::Duration::ZERO);
+    /// // the decompression counters of the inner reader
+    /// let decompressed = reader.get_ref().stats().decompressed_bytes;
+    /// assert_eq!(decompressed, reader.offset());
+    /// ```
+    pub fn stats(&self) -> ReaderStats {
+        self.stats
+    }
+
+    /// Get the distributions of the buffer lengths of the records read so
+    /// far (see [`RecordSizeStats`])
+    pub fn record_size_stats(&self) -> &RecordSizeStats {
+        &self.options.buffer_sizing.stats
+    }
+
+    /// Reserve the buffers of the records read into for the `q` quantile
+    /// (between 0 and 1, e.g. `0.99`) of the lengths of the records read so
+    /// far, updated after 1, 2, 4, .., 256 records and then every 256
+    /// records, or stop reserving with
+    /// `None` (the default). This applies to the records of
+    /// [`read_batch`](Self::read_batch) taken from its pool, which then grow
+    /// in one allocation instead of a burst of reallocations when record
+    /// sizes vary widely.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.read_header();
+    /// let batch = reader.read_batch(100).unwrap();
+    /// let stats = reader.record_size_stats();
+    /// assert_eq!(stats.n_records(), 100);
+    /// let target = stats.indv_quantile(0.99);
+    /// assert!(target > 0 && target <= stats.max_indv());
+    /// // the next records are reserved for the lengths seen
+    /// reader.set_buffer_quantile(Some(0.99));
+    /// reader.recycle_records(batch);
+    /// let batch = reader.read_batch(100).unwrap();
+    /// assert_eq!(batch.len(), 27);
+    /// assert!(batch.iter().all(|r| r.memory_size() >= target));
+    /// ```
+    pub fn set_buffer_quantile(&mut self, q: Option<f64>) {
+        self.options.set_buffer_quantile(q);
+    }
+
+    /// Parse the remaining records with a pipeline of threads and call `f`
+    /// with each batch, as [`scan_parallel`](crate::scan_parallel), and
+    /// return the number of records. This should be called after the header
+    /// is read, with that header.
+    ///
+    /// The records are read and checked as by `read_record`, with the
+    /// options of the reader (limits, field selection, parse mode, UTF-8
+    /// policy, order and duplicate checks); the length of each record is
+    /// checked before it is buffered. The warnings are only reported to the
+    /// warning handler, as `record_warnings` has no single last record. The
+    /// order and duplicate checks parse the shared data of each record on
+    /// the calling thread, in file order.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// let n_records = AtomicUsize::new(0);
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// let header = reader.read_header();
+    /// let n = reader
+    ///     .scan_parallel(&header, 2, |batch| {
+    ///         n_records.fetch_add(batch.records.len(), Ordering::Relaxed);
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!((n, n_records.into_inner()), (127, 127));
+    ///
+    /// // the limits of the reader apply
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.set_limits(ResourceLimits {
+    ///     max_record_length: 100,
+    ///     ..Default::default()
+    /// });
+    /// let header = reader.read_header();
+    /// let err = reader.scan_parallel(&header, 2, |_| {}).unwrap_err();
+    /// assert!(matches!(err, Error::LimitExceeded { what: "record length", .. }));
+    ///
+    /// // as do the checks, e.g. of the order: the records twice are unsorted
+    /// let mut data = vec![];
+    /// smart_reader("testdata/test.bcf").read_to_end(&mut data).unwrap();
+    /// let mut records = data.as_slice();
+    /// read_header(&mut records).unwrap();
+    /// let records = records.to_vec();
+    /// data.extend(records);
+    /// let mut reader = BcfReader::from_reader(data.as_slice());
+    /// reader.set_order_check(OrderCheck::Error);
+    /// let header = reader.read_header();
+    /// let err = reader.scan_parallel(&header, 2, |_| {}).unwrap_err();
+    /// assert!(matches!(err, Error::Unsorted(_)));
+    /// ```
+    pub fn scan_parallel<F>(
+        &mut self,
+        header: &Header,
+        n_threads: usize,
+        f: F,
+    ) -> error::Result<usize>
+    where
+        F: Fn(&ScanBatch) + Sync,
+    {
+        self.scan_batches(header, n_threads, SCAN_BATCH_BYTES, f)
+    }
+
+    /// [`scan_parallel`](Self::scan_parallel) with batches of at most
+    /// `batch_bytes` bytes of raw records
+    pub(crate) fn scan_batches<F>(
+        &mut self,
+        header: &Header,
+        n_threads: usize,
+        batch_bytes: usize,
+        f: F,
+    ) -> error::Result<usize>
+    where
+        F: Fn(&ScanBatch) + Sync,
+    {
+        assert!(
+            self.header_parsed,
+            "header should be parsed before reading records"
+        );
+        scan_batches(
+            &mut self.inner,
+            &mut self.options,
+            header,
+            n_threads,
+            batch_bytes,
+            f,
+        )
+    }
+
+    /// Get a reference to the inner reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl BcfReader<PrefetchReader> {
+    /// Create a reader that reads the data of `reader` ahead in a background
+    /// thread, `buffer_size` bytes at a time (see [`PrefetchReader`]), so
+    /// that the next records are read or decompressed while the current ones
+    /// are parsed
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let file = std::fs::File::open("testdata/test.bcf").unwrap();
+    /// let gz = ParMultiGzipReader::from_reader(std::io::BufReader::new(file), 3, None, None);
+    /// let mut reader = BcfReader::with_prefetch(gz, 1 << 16);
+    /// reader.read_header();
+    /// assert_eq!(reader.records().count(), 127);
+    /// assert_eq!(reader.stats().n_records, 127);
+    /// ```
+    pub fn with_prefetch<R>(reader: R, buffer_size: usize) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Self::from_reader(PrefetchReader::new(reader, buffer_size))
+    }
+}
+
+/// A genome interval defined by chromosome id, start, and end positions
+pub struct GenomeInterval {
+    pub chrom_id: usize,
+    pub start: i64,
+    pub end: Option<i64>,
+}
+
+/// IndexedBcfReader allows random access to a specific genome interval of the
+/// BCF file using a CSI index file. It is an wrapper around
+/// [`ParMultiGzipReader<BufReader<File>>`] to allow parallelizable bgzip
+/// decompression.
+///
+/// The source should be BCF file consisting of small BGZF blocks.
+///
+/// # Example
+///
+/// ```
+/// use bcf_reader::*;
+/// // create indexed bcf reader
+/// let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
+/// // read though header
+/// let _header = reader.read_header();
+/// // define targeted genome interval
+/// let interval = GenomeInterval {
+///     chrom_id: 0,
+///     start: 1489230 - 1,
+///     end: Some(1498509 - 1),
+/// };
+/// // set interval
+/// reader.set_interval(interval);
+/// // create a reusable record
+/// let mut record = Record::default();
+///
+/// let mut pos_found = vec![];
+/// // iterate records from the targeted genome interval
+/// while let Ok(_) = reader.read_record(&mut record) {
+///     pos_found.push(record.pos() + 1);
+/// }
+///
+/// assert_eq!(
+///     pos_found,
+///     vec![
+///         1489230, 1489979, 1490069, 1490311, 1492233, 1492337, 1493505, 1494178, 1495207,
+///         1495403, 1495746, 1496047, 1497964, 1498188,
+///     ]
+/// )
+/// ```
+pub struct IndexedBcfReader {
+    inner: ParMultiGzipReader<BufReader<File>>,
+    csi: Csi,
+    header_parsed: bool,
+    genome_interval: Option<GenomeInterval>,
+    options: ReaderOptions,
+    stats: ReaderStats,
+    /// records reused by `read_batch`
+    record_pool: Vec<Record>,
+}
+
+impl IndexedBcfReader {
+    /// Create an IndexedBcfReader from paths to a bcf file and a corresponding
+    /// csi index file.
+    ///
+    ///  - `max_gzip`, the number of gzip blocks to read before each batch
+    ///    parallelized decompression. See [`ParMultiGzipReader::from_reader`]
+    ///    (by default (None) use 3); this construct will automaticall read and
+    ///    parse the header
+    pub fn from_path(
+        path_bcf: impl AsRef<Path>,
+        path_csi: impl AsRef<Path>,
+        max_gzip: Option<usize>,
+    ) -> Self {
+        let reader = File::open(path_bcf.as_ref()).map(BufReader::new).unwrap();
+        let csi = Csi::from_path(path_csi.as_ref());
+        let reader = ParMultiGzipReader::from_reader(reader, max_gzip.unwrap_or(3), None, None);
+        Self {
+            inner: reader,
+            csi,
+            header_parsed: false,
+            genome_interval: None,
+            options: ReaderOptions::default(),
+            stats: ReaderStats::default(),
+            record_pool: vec![],
+        }
+    }
+    /// Read the header bytes, parse them and return a `Header`
+    pub fn read_header(&mut self) -> Header {
+        self.try_read_header().unwrap()
+    }
+
+    /// Read the header bytes, parse them and return a `Header`, or an error
+    /// if the BCF version is rejected by the version policy (see
+    /// [`IndexedBcfReader::set_version_policy`]) or the header is malformed.
+    /// Warnings, e.g. about an unknown minor version accepted by the
+    /// policy, are available from `header_warnings`.
+    pub fn try_read_header(&mut self) -> error::Result<Header> {
+        let header = self.options.read_header(&mut self.inner)?;
+        self.header_parsed = true;
+        Ok(header)
+    }
+
+    /// Get the warnings raised while reading the header
+    pub fn header_warnings(&self) -> &[String] {
+        &self.options.header_warnings
+    }
+
+    /// Set the limits on the size of the header and records (see
+    /// [`ResourceLimits`])
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.options.set_limits(limits);
+    }
+
+    /// Measure the time spent reading and parsing the records in
+    /// [`stats`](Self::stats) (off by default)
+    pub fn set_stats_timing(&mut self, enabled: bool) {
+        self.options.set_stats_timing(enabled);
+    }
+
+    /// Get the counters of the records read so far, including the records
+    /// skipped before the start of the intervals (see [`ReaderStats`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
+    /// reader.read_header();
+    /// reader.set_stats_timing(true);
+    /// reader.set_interval(GenomeInterval {
+    ///     chrom_id: 0,
+    ///     start: 1489230 - 1,
+    ///     end: Some(1498509 - 1),
+    /// });
+    /// let mut record = Record::default();
+    /// let mut n = 0;
+    /// while let Some(()) = reader.try_read_record(&mut record).unwrap() {
+    ///     n += 1;
+    /// }
+    /// assert_eq!(n, 14);
+    /// let stats = reader.stats();
+    /// assert!(stats.n_records >= 14 && stats.n_bytes > 0);
+    /// assert!(stats.read_time + stats.parse_time > std::time::Duration::ZERO);
+    /// assert!(reader.decompression_stats().decompressed_bytes >= stats.n_bytes);
+    /// ```
+    pub fn stats(&self) -> ReaderStats {
+        self.stats
+    }
+
+    /// Get the distributions of the buffer lengths of the records read so
+    /// far, including those before the start of the interval (see
+    /// [`RecordSizeStats`])
+    pub fn record_size_stats(&self) -> &RecordSizeStats {
+        &self.options.buffer_sizing.stats
+    }
+
+    /// Reserve the buffers of the records read into for the `q` quantile of
+    /// the lengths of the records read so far, or stop reserving with
+    /// `None` (the default); see [`BcfReader::set_buffer_quantile`]
+    pub fn set_buffer_quantile(&mut self, q: Option<f64>) {
+        self.options.set_buffer_quantile(q);
+    }
+
+    /// Get the counters of the BGZF blocks decompressed so far (see
+    /// [`ParMultiGzipReader::stats`])
+    pub fn decompression_stats(&self) -> DecompressionStats {
+        self.inner.stats()
+    }
+
+    /// Set how text that is not valid UTF-8 is handled in the header and
+    /// the records (see [`Utf8Policy`])
+    pub fn set_utf8_policy(&mut self, policy: Utf8Policy) {
+        self.options.set_utf8_policy(policy);
+    }
+
+    /// Set how strictly the header and records are checked (see
+    /// [`ParseMode`])
+    pub fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.options.set_parse_mode(mode);
+    }
+
+    /// Get the deviations from the specification found in the last record
+    /// read in lenient mode (see [`Record::conformance_issues`])
+    pub fn record_warnings(&self) -> &[String] {
+        &self.options.record_warnings
+    }
+
+    /// Report recoverable oddities to `handler` as they are found, without
+    /// stopping (see [`BcfReader::set_warning_handler`]). The offsets of the
+    /// records are not known.
+    pub fn set_warning_handler(
+        &mut self,
+        handler: impl FnMut(&ReadWarning) + Send + Sync + 'static,
+    ) {
+        self.options.set_warning_handler(handler);
+    }
+
+    /// Check that the records are sorted as they are read (see
+    /// [`OrderCheck`] and [`BcfReader::set_order_check`]). The check starts
+    /// again at each `set_interval`.
+    pub fn set_order_check(&mut self, check: OrderCheck) {
+        self.options.set_order_check(check);
+    }
+
+    /// Report records with the same contig, position and alleles as the
+    /// previous record as warnings (see [`BcfReader::set_duplicate_detection`])
+    pub fn set_duplicate_detection(&mut self, enabled: bool) {
+        self.options.set_duplicate_detection(enabled);
+    }
+
+    /// Set the BCF versions accepted by `read_header`
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.options.set_version_policy(policy);
+    }
+
+    /// Get the BCF version of the file, once the header is read
+    pub fn bcf_version(&self) -> Option<BcfVersion> {
+        self.options.bcf_version
+    }
+
+    /// Only keep the selected INFO/FORMAT fields in records read by
+    /// `read_record` (see [`Record::read_selected`]).
+    pub fn set_field_selection(&mut self, selection: FieldSelection) {
+        self.options.set_field_selection(selection);
+    }
+
+    /// Jump the file pointer to the begining to the targeted genome interval
+    ///
+    /// If no site within the genome interval, read_record will return Err(_)
+    pub fn set_interval(&mut self, genome_interval: GenomeInterval) {
+        // find the target based on csi
+        let bin_id = self
+            .csi
+            .get_bin_id(genome_interval.start, genome_interval.start + 1);
+
+        let (coffset, uoffset) = self
+            .csi
+            .get_bin_details(genome_interval.chrom_id, bin_id)
+            .chunks
+            .first()
+            .unwrap()
+            .chunk_beg
+            .get_coffset_uoffset();
+
+        let par_reader = &mut self.inner;
+        par_reader.inner.seek(io::SeekFrom::Start(coffset)).unwrap();
+
+        // clear buffer, especially things related to coffset
+        par_reader.buffer.iter_mut().for_each(|bgzf_buffer| {
+            bgzf_buffer.compressed.clear();
+            bgzf_buffer.uncompressed.clear();
+            bgzf_buffer.coffset = 0;
+            bgzf_buffer.gzip_size = 0;
+            bgzf_buffer.uncompressed_data_size = 0;
+        });
+        par_reader.ngzip = 0;
+        par_reader.igzip = 0;
+        par_reader.ibyte = 0;
+        par_reader.inner_eof = false;
+        par_reader.pending_error = None;
+        self.options.restart_checks();
+
+        // fill buffer
+        par_reader.refill();
+
+        // jump for uoffset
+        par_reader.ibyte = uoffset as usize;
+
+        self.genome_interval = Some(genome_interval);
+    }
+
+    /// Read one record. Should be called after header is parsed.
+    ///
+    /// If `set_interval` has been called, only records within the given interval
+    /// will be read. An error is returned after the last record of the
+    /// interval.
+    pub fn read_record(&mut self, record: &mut Record) -> error::Result<()> {
+        self.try_read_record(record)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "out of range").into())
+    }
+
+    /// Iterate over the remaining records of the interval (see
+    /// [`BcfReader::records`])
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = IndexedBcfReader::from_path("testdata/test3.bcf", "testdata/test3.bcf.csi", None);
+    /// reader.read_header();
+    /// reader.set_interval(GenomeInterval {
+    ///     chrom_id: 0,
+    ///     start: 1489230 - 1,
+    ///     end: Some(1498509 - 1),
+    /// });
+    /// let positions: Vec<_> = reader.records().map(|r| r.unwrap().pos() + 1).collect();
+    /// assert_eq!(positions.len(), 14);
+    /// assert_eq!(positions[0], 1489230);
+    /// ```
+    pub fn records(&mut self) -> Records<'_, Self> {
+        Records::new(self)
+    }
+
+    /// Iterate over the remaining records of the interval without
+    /// allocating a record for each (see [`BcfReader::stream_records`])
+    pub fn stream_records(&mut self) -> RecordStream<'_, Self> {
+        RecordStream::with_record(self, Record::default())
+    }
+
+    /// Iterate over the remaining records of the interval in batches of
+    /// `batch_size` records (see [`BcfReader::record_batches`])
+    pub fn record_batches(&mut self, batch_size: usize) -> RecordBatches<'_, Self> {
+        RecordBatches::new(self.records(), batch_size)
+    }
+
+    /// Read the next `n` records of the interval, reusing the records given
+    /// back by [`recycle_records`](Self::recycle_records) (see
+    /// [`BcfReader::read_batch`])
+    pub fn read_batch(&mut self, n: usize) -> error::Result<Vec<Record>> {
+        let mut pool = std::mem::take(&mut self.record_pool);
+        let batch = read_batch(self, &mut pool, n);
+        self.record_pool = pool;
+        batch
+    }
+
+    /// Give records back to the pool of [`read_batch`](Self::read_batch)
+    pub fn recycle_records(&mut self, records: impl IntoIterator<Item = Record>) {
+        self.record_pool.extend(records);
+    }
+
+    /// Read one FORMAT field of the remaining records of the interval (or
+    /// of the next `max_records` records) into a single buffer; see
+    /// [`BcfReader::read_format_column`].
+    #[cfg(feature = "format")]
+    pub fn read_format_column<T: ColumnValue>(
+        &mut self,
+        header: &Header,
+        tag: &str,
+        width: usize,
+        max_records: Option<usize>,
+    ) -> error::Result<FormatColumn<T>> {
+        let saved = self.options.field_selection.take();
+        if let Some(key) = header.get_idx_from_dictionary_str("FORMAT", tag) {
+            self.options.field_selection = Some(FieldSelection::only_format(saved.as_ref(), key));
+        }
+        let column = read_format_column(self, header, tag, width, max_records);
+        self.options.field_selection = saved;
+        column
+    }
+
+    /// Compute the allele counts of the remaining records of the interval
+    /// and pass them to `f` with each record; see
+    /// [`BcfReader::scan_allele_counts`].
+    pub fn scan_allele_counts<F>(&mut self, header: &Header, f: F) -> error::Result<usize>
+    where
+        F: FnMut(&Record, &AlleleCounts),
+    {
+        let saved = self.options.field_selection.take();
+        if let Some(key) = header.get_fmt_gt_id() {
+            self.options.field_selection = Some(FieldSelection::only_format(saved.as_ref(), key));
+        }
+        let n = scan_allele_counts(self, header, f);
+        self.options.field_selection = saved;
+        n
+    }
+
+    /// Iterate over the remaining records of the interval in parallel with
+    /// rayon (see [`BcfReader::par_records`])
+    pub fn par_records(
+        &mut self,
+        batch_size: usize,
+    ) -> impl ParallelIterator<Item = error::Result<Record>> + '_ {
+        par_records(self, batch_size, usize::MAX)
+    }
+
+    /// Iterate over the remaining records of the interval in parallel with
+    /// a memory budget (see [`BcfReader::par_records_with_budget`])
+    pub fn par_records_with_budget(
+        &mut self,
+        max_batch_size: usize,
+        memory_budget: usize,
+    ) -> impl ParallelIterator<Item = error::Result<Record>> + '_ {
+        let max_bytes = memory_budget / rayon::current_num_threads();
+        par_records(self, max_batch_size, max_bytes)
+    }
+
+    /// Read one record as `read_record`, returning `Ok(None)` after the last
+    /// record of the interval, or at the end of the file (see
+    /// [`Record::try_read`])
+    pub fn try_read_record(&mut self, record: &mut Record) -> error::Result<Option<()>> {
+        assert!(
+            self.header_parsed,
+            "header should be parsed before reading records"
+        );
+        let start = self.options.stats_timing.then(Instant::now);
+        let read_time = self.stats.read_time;
+        let res = self.read_interval_record(record);
+        if let Some(start) = start {
+            let read_time = self.stats.read_time - read_time;
+            self.stats.parse_time += start.elapsed().saturating_sub(read_time);
+        }
+        res
+    }
+
+    /// Read the next record of the interval, updating the counters of the
+    /// data read
+    fn read_interval_record(&mut self, record: &mut Record) -> error::Result<Option<()>> {
+        let start = self.genome_interval.as_ref().unwrap().start;
+        let end = self.genome_interval.as_ref().unwrap().end;
+        loop {
+            self.options.buffer_sizing.before_read(record);
+            let mut counted = match self.options.stats_timing {
+                true => CountingReader::timed(&mut self.inner),
+                false => CountingReader::new(&mut self.inner),
+            };
+            let res = record.read_limited(
+                &mut counted,
+                self.options.field_selection.as_ref(),
+                false,
+                &self.options.limits,
+            );
+            self.stats.n_bytes += counted.count;
+            self.stats.read_time += counted.time.unwrap_or_default();
+            self.stats.n_records += matches!(res, Ok(Some(()))) as u64;
+            if let Ok(Some(())) = res {
+                self.options.buffer_sizing.after_read(record);
+            }
+            match res {
+                Ok(None) => return Ok(None),
+                Ok(Some(())) => {
+                    if is_bcf21(self.options.bcf_version) {
+                        record.upgrade_bcf21_vectors();
+                    }
+                    if let Some(end) = end {
+                        if record.pos as i64 >= end {
+                            return Ok(None);
+                        }
+                    }
+                    if record.pos as i64 >= start {
+                        self.options.check_record(record, None)?;
+                        return Ok(Some(()));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Iteration over the records of the readers: one at a time, in batches or
+//! in parallel
+
+use crate::{error, BcfReader, IndexedBcfReader, Record};
+use rayon::prelude::*;
+use std::io::Read;
+
+/// A source of records, read one after the other into a [`Record`]:
+/// [`BcfReader`] and [`IndexedBcfReader`]
+pub trait ReadRecord {
+    /// Read the next record into `record`, or return `Ok(None)` when there
+    /// are no more records
+    fn try_read_record(&mut self, record: &mut Record) -> error::Result<Option<()>>;
+}
+
+impl<R: Read> ReadRecord for BcfReader<R> {
+    fn try_read_record(&mut self, record: &mut Record) -> error::Result<Option<()>> {
+        BcfReader::try_read_record(self, record)
+    }
+}
+
+impl ReadRecord for IndexedBcfReader {
+    fn try_read_record(&mut self, record: &mut Record) -> error::Result<Option<()>> {
+        IndexedBcfReader::try_read_record(self, record)
+    }
+}
+
+/// Iterator over the records of a reader, returned by
+/// [`BcfReader::records`] and [`IndexedBcfReader::records`]
+///
+/// Each record is a new [`Record`]; reuse a single record with
+/// `try_read_record` to avoid the allocations. The iteration stops after
+/// the first error.
+pub struct Records<'r, T: ?Sized> {
+    reader: &'r mut T,
+    done: bool,
+}
+
+impl<'r, T: ?Sized> Records<'r, T> {
+    pub(crate) fn new(reader: &'r mut T) -> Self {
+        Records {
+            reader,
+            done: false,
+        }
+    }
+}
+
+impl<T: ReadRecord + ?Sized> Iterator for Records<'_, T> {
+    type Item = error::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut record = Record::default();
+        match self.reader.try_read_record(&mut record) {
+            Ok(Some(())) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: ReadRecord + ?Sized> std::iter::FusedIterator for Records<'_, T> {}
+
+/// Iterator over batches of records of a reader, returned by
+/// [`BcfReader::record_batches`] and [`IndexedBcfReader::record_batches`]
+///
+/// All batches but the last have `batch_size` records, unless a memory
+/// budget is set with [`max_bytes`](Self::max_bytes). A batch in which a
+/// record cannot be read is an error, and the iteration stops after it.
+pub struct RecordBatches<'r, T: ?Sized> {
+    records: Records<'r, T>,
+    batch_size: usize,
+    max_bytes: usize,
+}
+
+impl<'r, T: ?Sized> RecordBatches<'r, T> {
+    pub(crate) fn new(records: Records<'r, T>, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "the batch size should be positive");
+        RecordBatches {
+            records,
+            batch_size,
+            max_bytes: usize::MAX,
+        }
+    }
+
+    /// End each batch as soon as its records use `max_bytes` bytes (see
+    /// [`Record::memory_size`]), even if it has fewer than `batch_size`
+    /// records, so that a few very large records (e.g. with many samples
+    /// or alleles) do not make a batch exceed the memory available. A batch
+    /// has at least one record, whatever its size.
+    ///
+    /// Example:
+    /// ```
+    /// use bcf_reader::*;
+    /// let mut reader = BcfReader::from_reader(smart_reader("testdata/test.bcf"));
+    /// reader.read_header();
+    /// let budget = 64 << 10;
+    /// let batches: Vec<_> = reader
+    ///     .record_batches(100)
+    ///     .max_bytes(budget)
+    ///     .map(|batch| batch.unwrap())
+    ///     .collect();
+    /// assert!(batches.len() > 2);
+    /// assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 127);
+    /// for batch in &batches {
+    ///     let size: usize = batch.iter().map(|r| r.memory_size()).sum();
+    ///     let last = batch.last().unwrap().memory_size();
+    ///     assert!(size - last < budget);
+    /// }
+    /// ```
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+impl<T: ReadRecord + ?Sized> Iterator for RecordBatches<'_, T> {
+    type Item = error::Result<Vec<Record>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut batch = vec![];
+        let mut n_bytes = 0;
+        while batch.len() < self.batch_size && n_bytes < self.max_bytes {
+            match self.records.next() {
+                Some(Ok(record)) => {
+                    n_bytes += record.memory_size();
+                    batch.push(record);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        (!batch.is_empty()).then_some(Ok(batch))
+    }
+}
+
+/// Read batches of records from `reader` and process the records of each
+/// batch in parallel (see [`BcfReader::par_records`]), with at most
+/// `max_bytes` bytes of records per batch
+pub(crate) fn par_records<T: ReadRecord + Send + ?Sized>(
+    reader: &mut T,
+    batch_size: usize,
+    max_bytes: usize,
+) -> impl ParallelIterator<Item = error::Result<Record>> + '_ {
+    let records = Records {
+        reader,
+        done: false,
+    };
+    let batches = RecordBatches::new(records, batch_size).max_bytes(max_bytes);
+    batches.par_bridge().flat_map_iter(|batch| {
+        let (records, error) = match batch {
+            Ok(records) => (records, None),
+            Err(e) => (vec![], Some(Err(e))),
+        };
+        records.into_iter().map(Ok).chain(error)
+    })
+}
+
+/// Read the next `n` records of `reader` (fewer at the end of the data),
+/// reusing the records of `pool` (see [`BcfReader::read_batch`])
+pub(crate) fn read_batch<T: ReadRecord + ?Sized>(
+    reader: &mut T,
+    pool: &mut Vec<Record>,
+    n: usize,
+) -> error::Result<Vec<Record>> {
+    let mut batch = Vec::with_capacity(n.min(1 << 16));
+    while batch.len() < n {
+        let mut record = pool.pop().unwrap_or_default();
+        match reader.try_read_record(&mut record) {
+            Ok(Some(())) => batch.push(record),
+            res => {
+                pool.push(record);
+                if let Err(e) = res {
+                    pool.append(&mut batch);
+                    return Err(e);
+                }
+                break;
+            }
+        }
+    }
+    Ok(batch)
+}
+
+/// An iterator whose items borrow from the iterator itself, so that each
+/// item must be dropped before the next one is requested. It cannot be used
+/// in a `for` loop; use `while let Some(item) = iter.next()` instead.
+pub trait LendingIterator {
+    /// The type of the items, borrowing from the iterator
+    type Item<'a>
+    where
+        Self: 'a;
+
+    /// Advance the iterator and return the next item
+    fn next(&mut self) -> Option<Self::Item<'_>>;
+}
+
+/// Lending iterator over the records of a reader, returned by
+/// [`BcfReader::stream_records`] and [`IndexedBcfReader::stream_records`]
+///
+/// Every record is read into the same [`Record`], so iterating does not
+/// allocate once the record buffers are large enough. The iteration stops
+/// after the first error.
+pub struct RecordStream<'r, T: ?Sized> {
+    reader: &'r mut T,
+    record: Record,
+    done: bool,
+}
+
+impl<'r, T: ?Sized> RecordStream<'r, T> {
+    /// Create a stream reading the records of `reader` into `record`, e.g.
+    /// a record kept from a previous stream to reuse its buffers
+    pub fn with_record(reader: &'r mut T, record: Record) -> Self {
+        Self {
+            reader,
+            record,
+            done: false,
+        }
+    }
+
+    /// Get the record back, e.g. to reuse its buffers
+    pub fn into_record(self) -> Record {
+        self.record
+    }
+}
+
+impl<T: ReadRecord + ?Sized> LendingIterator for RecordStream<'_, T> {
+    type Item<'a>
+        = error::Result<&'a Record>
+    where
+        Self: 'a;
+
+    fn next(&mut self) -> Option<Self::Item<'_>> {
+        if self.done {
+            return None;
+        }
+        match self.reader.try_read_record(&mut self.record) {
+            Ok(Some(())) => Some(Ok(&self.record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Parallel parsing of a whole BGZF-compressed BCF file
+
+use crate::checks::{check_conformance, check_duplicate, check_order};
+use crate::reader::ReaderOptions;
+use crate::{
+    error::{self, Error},
+    is_bcf21, no_more_records, read_record_lengths, BcfReader, Header, OrderCheck,
+    ParMultiGzipReader, Record, ResourceLimits,
+};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Target number of records of a batch of [`scan_parallel`]
+const SCAN_BATCH_RECORDS: usize = 1024;
+
+/// Target size in bytes of a batch of [`scan_parallel`]
+pub(crate) const SCAN_BATCH_BYTES: usize = 1 << 22;
+
+/// A batch of records passed to the callback of [`scan_parallel`]
+#[derive(Debug)]
+pub struct ScanBatch<'a> {
+    /// position of the batch in the file (0 for the first batch)
+    pub index: usize,
+    /// header of the file
+    pub header: &'a Header,
+    /// records of the batch, in file order
+    pub records: &'a [Record],
+}
+
+/// Scan a BCF file with a pipeline of threads and call `f` with each batch
+/// of records, and return the number of records.
+///
+/// The stages are:
+/// - decompression of the BGZF blocks, `n_threads` blocks at a time on the
+///   rayon thread pool (see [`ParMultiGzipReader`]);
+/// - splitting of the decompressed data into batches of raw records, on the
+///   calling thread;
+/// - parsing of the batches and calls of `f`, on `n_threads` worker threads.
+///
+/// At most `n_threads` batches wait for a worker, so that reading stops
+/// while the workers are busy rather than buffering the whole file. The
+/// batches are processed concurrently and may complete in any order; use
+/// [`ScanBatch::index`] to restore the file order.
+///
+/// The scan stops at the first error reading or parsing the data, which is
+/// returned; batches before it may still be passed to `f`. The records are
+/// read with the default options of [`BcfReader`]; see
+/// [`BcfReader::scan_parallel`] for other limits or checks.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use std::sync::Mutex;
+/// let positions = Mutex::new(vec![]);
+/// let n = scan_parallel("testdata/test.bcf", 4, |batch| {
+///     let pos: Vec<_> = batch.records.iter().map(|r| r.pos()).collect();
+///     positions.lock().unwrap().push((batch.index, pos));
+/// })
+/// .unwrap();
+/// assert_eq!(n, 127);
+/// let mut positions = positions.into_inner().unwrap();
+/// positions.sort();
+/// let positions: Vec<_> = positions.into_iter().flat_map(|(_, pos)| pos).collect();
+///
+/// let mut f = smart_reader("testdata/test.bcf");
+/// read_header(&mut f).unwrap();
+/// let mut record = Record::default();
+/// for pos in positions {
+///     record.read(&mut f).unwrap();
+///     assert_eq!(pos, record.pos());
+/// }
+/// ```
+pub fn scan_parallel<F>(path: impl AsRef<Path>, n_threads: usize, f: F) -> error::Result<usize>
+where
+    F: Fn(&ScanBatch) + Sync,
+{
+    scan_parallel_batches(path.as_ref(), n_threads, SCAN_BATCH_BYTES, f)
+}
+
+/// Like [`scan_parallel`], with the size of the batches chosen so that the
+/// pipeline uses about `memory_budget` bytes at most, plus the buffers of
+/// the decompression stage.
+///
+/// Up to `3 * n_threads + 1` batches are in memory at once: `n_threads`
+/// waiting for a worker, one being split, and `n_threads` being processed
+/// as raw data and as records; each batch gets a share of the budget. A
+/// smaller budget makes smaller batches (of one record at least) rather
+/// than more memory, so that the scan slows down instead of running out of
+/// memory on a constrained machine.
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// let n_batches = AtomicUsize::new(0);
+/// let n = scan_parallel_with_budget("testdata/test.bcf", 2, 256 << 10, |_| {
+///     n_batches.fetch_add(1, Ordering::Relaxed);
+/// })
+/// .unwrap();
+/// assert_eq!(n, 127);
+/// assert!(n_batches.into_inner() > 1);
+/// ```
+pub fn scan_parallel_with_budget<F>(
+    path: impl AsRef<Path>,
+    n_threads: usize,
+    memory_budget: usize,
+    f: F,
+) -> error::Result<usize>
+where
+    F: Fn(&ScanBatch) + Sync,
+{
+    let batch_bytes = memory_budget / (3 * n_threads + 1);
+    scan_parallel_batches(
+        path.as_ref(),
+        n_threads,
+        batch_bytes.min(SCAN_BATCH_BYTES),
+        f,
+    )
+}
+
+/// Run [`scan_parallel`] with batches of at most `batch_bytes` bytes of raw
+/// records
+fn scan_parallel_batches<F>(
+    path: &Path,
+    n_threads: usize,
+    batch_bytes: usize,
+    f: F,
+) -> error::Result<usize>
+where
+    F: Fn(&ScanBatch) + Sync,
+{
+    assert!(n_threads > 0, "the number of threads should be positive");
+    let file = File::open(path).map(BufReader::new)?;
+    let mut reader =
+        BcfReader::from_reader(ParMultiGzipReader::from_reader(file, n_threads, None, None));
+    let header = reader.try_read_header()?;
+    reader.scan_batches(&header, n_threads, batch_bytes, f)
+}
+
+/// Run the pipeline of [`scan_parallel`] on the records of `reader`, read
+/// and checked according to `options`
+pub(crate) fn scan_batches<R, F>(
+    reader: &mut R,
+    options: &mut ReaderOptions,
+    header: &Header,
+    n_threads: usize,
+    batch_bytes: usize,
+    f: F,
+) -> error::Result<usize>
+where
+    R: Read,
+    F: Fn(&ScanBatch) + Sync,
+{
+    assert!(n_threads > 0, "the number of threads should be positive");
+    let bcf21 = is_bcf21(options.bcf_version);
+    let ReaderOptions {
+        field_selection,
+        parse_mode,
+        utf8_policy,
+        limits,
+        order_check,
+        warning_handler,
+        header: check_header,
+        order,
+        duplicates,
+        ..
+    } = options;
+    let (selection, limits) = (field_selection.as_ref(), &*limits);
+    // the handler is shared by the workers and the splitting stage
+    let reports = warning_handler.is_some();
+    let handler = std::sync::Mutex::new(warning_handler.take());
+    let check = |record: &Record| {
+        let mut warnings = vec![];
+        let (mode, utf8) = (*parse_mode, *utf8_policy);
+        match reports {
+            true => {
+                let mut handler = handler.lock().unwrap();
+                check_conformance(
+                    record,
+                    check_header,
+                    mode,
+                    utf8,
+                    &mut warnings,
+                    &mut handler,
+                    None,
+                )
+            }
+            false => check_conformance(
+                record,
+                check_header,
+                mode,
+                utf8,
+                &mut warnings,
+                &mut None,
+                None,
+            ),
+        }
+    };
+    // the order and the duplicates are checked in file order, on the shared
+    // data of each record
+    let sequential = *order_check != OrderCheck::Off || duplicates.is_some();
+    let mut site = Record::default();
+    let mut check_sequence = |mut raw: &[u8]| -> error::Result<()> {
+        if !sequential {
+            return Ok(());
+        }
+        site.read_limited(&mut raw, None, true, limits)?;
+        let mut warnings = vec![];
+        let mut handler = handler.lock().unwrap();
+        check_order(
+            &site,
+            check_header,
+            *order_check,
+            order,
+            &mut warnings,
+            &mut handler,
+            None,
+        )?;
+        check_duplicate(
+            &site,
+            check_header,
+            duplicates,
+            &mut warnings,
+            &mut handler,
+            None,
+        );
+        Ok(())
+    };
+
+    let (tx, rx) = std::sync::mpsc::sync_channel::<(usize, usize, Vec<u8>)>(n_threads);
+    let rx = std::sync::Mutex::new(rx);
+    // the error of the earliest batch
+    let first_error = std::sync::Mutex::new(None::<(usize, Error)>);
+    let set_error = |index: usize, e: Error| {
+        let mut first = first_error.lock().unwrap();
+        if first.as_ref().is_none_or(|(i, _)| index < *i) {
+            *first = Some((index, e));
+        }
+    };
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    let n_records = std::thread::scope(|s| {
+        for _ in 0..n_threads {
+            s.spawn(|| {
+                let mut records: Vec<Record> = vec![];
+                loop {
+                    let received = rx.lock().unwrap().recv();
+                    let Ok((index, n, raw)) = received else {
+                        break;
+                    };
+                    if stop.load(std::sync::atomic::Ordering::Relaxed) {
+                        continue;
+                    }
+                    if records.len() < n {
+                        records.resize_with(n, Record::default);
+                    }
+                    let mut data = raw.as_slice();
+                    let parsed = records[..n].iter_mut().try_for_each(|r| {
+                        r.read_limited(&mut data, selection, false, limits)?
+                            .ok_or_else(no_more_records)?;
+                        if bcf21 {
+                            r.upgrade_bcf21_vectors();
+                        }
+                        check(r)
+                    });
+                    match parsed {
+                        Ok(()) => f(&ScanBatch {
+                            index,
+                            header,
+                            records: &records[..n],
+                        }),
+                        Err(e) => {
+                            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                            set_error(index, e);
+                        }
+                    }
+                }
+            });
+        }
+        let split = send_raw_batches(reader, batch_bytes, limits, &mut check_sequence, &tx, &stop);
+        drop(tx);
+        split
+    });
+    *warning_handler = handler.into_inner().unwrap();
+    let n_records = match n_records {
+        Ok(n) => n,
+        Err((index, e)) => {
+            set_error(index, e);
+            0
+        }
+    };
+    match first_error.into_inner().unwrap() {
+        Some((_, e)) => Err(e),
+        None => Ok(n_records),
+    }
+}
+
+/// Split the records of `reader` into batches of raw records of at most
+/// `batch_bytes` bytes (or one record) sent to `tx` with their index and
+/// number of records, and return the number of records, or an error with
+/// the index of the batch being read. The length of each record is checked
+/// against `limits` before it is buffered, and each raw record is passed to
+/// `check` in file order.
+fn send_raw_batches<R: Read>(
+    reader: &mut R,
+    batch_bytes: usize,
+    limits: &ResourceLimits,
+    check: &mut dyn FnMut(&[u8]) -> error::Result<()>,
+    tx: &std::sync::mpsc::SyncSender<(usize, usize, Vec<u8>)>,
+    stop: &std::sync::atomic::AtomicBool,
+) -> Result<usize, (usize, Error)> {
+    let (mut index, mut n, mut total) = (0, 0, 0);
+    let mut raw = vec![];
+    loop {
+        if stop.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(total);
+        }
+        let lengths = read_record_lengths(reader).map_err(|e| (index, e.into()))?;
+        let full = match lengths {
+            Some((l_shared, l_indv)) => {
+                let len = 8 + l_shared as usize + l_indv as usize;
+                n == SCAN_BATCH_RECORDS || raw.len() + len > batch_bytes
+            }
+            None => true,
+        };
+        if n > 0 && full {
+            if tx.send((index, n, std::mem::take(&mut raw))).is_err() {
+                return Ok(total);
+            }
+            total += n;
+            index += 1;
+            n = 0;
+        }
+        let Some((l_shared, l_indv)) = lengths else {
+            return Ok(total);
+        };
+        let len = l_shared as u64 + l_indv as u64;
+        limits.check_record_length(len).map_err(|e| (index, e))?;
+        let start = raw.len();
+        raw.extend(l_shared.to_le_bytes());
+        raw.extend(l_indv.to_le_bytes());
+        let read = reader
+            .take(len)
+            .read_to_end(&mut raw)
+            .map_err(|e| (index, e.into()))?;
+        if (read as u64) < len {
+            let e = io::Error::new(io::ErrorKind::UnexpectedEof, "the data ends in a record");
+            return Err((index, e.into()));
+        }
+        check(&raw[start..]).map_err(|e| (index, e))?;
+        n += 1;
+    }
+}
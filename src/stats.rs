@@ -0,0 +1,284 @@
+//! Counters of the data read by the readers, and the sizing of the record
+//! buffers
+
+use crate::Record;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// Counters of the records read by a [`BcfReader`](crate::BcfReader) or an
+/// [`IndexedBcfReader`](crate::IndexedBcfReader) (see
+/// [`BcfReader::stats`](crate::BcfReader::stats)), to tell where the time of a
+/// pipeline goes without an external profiler
+///
+/// The times are only measured after
+/// [`BcfReader::set_stats_timing`](crate::BcfReader::set_stats_timing), as
+/// measuring them costs a few clock reads per record. For the data decompressed
+/// by a [`ParMultiGzipReader`](crate::ParMultiGzipReader), see
+/// [`ParMultiGzipReader::stats`](crate::ParMultiGzipReader::stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReaderStats {
+    /// number of records read, including those of an [`IndexedBcfReader`](crate::IndexedBcfReader)
+    /// before the start of the interval
+    pub n_records: u64,
+    /// number of bytes of records read from the inner reader (decompressed
+    /// data)
+    pub n_bytes: u64,
+    /// time spent waiting for the inner reader, including the decompression
+    pub read_time: Duration,
+    /// time spent parsing and checking the records
+    pub parse_time: Duration,
+}
+
+/// Counters of the BGZF blocks read by a [`ParMultiGzipReader`](crate::ParMultiGzipReader) (see
+/// [`ParMultiGzipReader::stats`](crate::ParMultiGzipReader::stats))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecompressionStats {
+    /// number of BGZF blocks read
+    pub n_blocks: u64,
+    /// number of compressed bytes read, including the block headers
+    pub compressed_bytes: u64,
+    /// number of bytes after decompression
+    pub decompressed_bytes: u64,
+    /// time spent reading the compressed blocks from the inner reader
+    pub read_time: Duration,
+    /// time spent decompressing the blocks
+    pub decompression_time: Duration,
+}
+
+/// Number of buckets of a [`LengthHistogram`]: 8 buckets for the lengths
+/// below 8, then 8 buckets per power of two
+const LENGTH_BUCKETS: usize = 8 + 29 * 8;
+
+/// Histogram of record buffer lengths, with buckets of at most 1/8 of their
+/// lower bound
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LengthHistogram {
+    counts: [u64; LENGTH_BUCKETS],
+    max: u32,
+}
+
+impl Default for LengthHistogram {
+    fn default() -> Self {
+        LengthHistogram {
+            counts: [0; LENGTH_BUCKETS],
+            max: 0,
+        }
+    }
+}
+
+impl LengthHistogram {
+    fn bucket(len: u32) -> usize {
+        if len < 8 {
+            return len as usize;
+        }
+        let e = 31 - len.leading_zeros() as usize;
+        8 + (e - 3) * 8 + ((len >> (e - 3)) & 7) as usize
+    }
+
+    /// The largest length of bucket `b`
+    fn upper_bound(b: usize) -> u64 {
+        if b < 8 {
+            return b as u64;
+        }
+        let (e, sub) = (3 + (b - 8) / 8, (b - 8) % 8);
+        ((8 + sub as u64 + 1) << (e - 3)) - 1
+    }
+
+    fn add(&mut self, len: u32) {
+        self.counts[Self::bucket(len)] += 1;
+        self.max = self.max.max(len);
+    }
+
+    fn quantile(&self, n: u64, q: f64) -> usize {
+        let rank = (q.clamp(0.0, 1.0) * n as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (b, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::upper_bound(b).min(self.max as u64) as usize;
+            }
+        }
+        self.max as usize
+    }
+}
+
+/// Distributions of the lengths of the shared and indiv buffers
+/// (`l_shared` and `l_indv`) of the records read, to pre-size the buffers
+/// of the records.
+///
+/// The buffers of a [`Record`] grow as needed when records are read into
+/// it, so that reusing a record does not allocate once it has seen the
+/// largest records. When the sizes of the records vary widely along a
+/// chromosome, records from a pool or a fresh batch instead go through a
+/// burst of reallocations. [`presize`](Self::presize) reserves buffers for
+/// a high quantile of the lengths seen so far, e.g. `0.99`, in one
+/// allocation. The quantiles are rounded up by at most 1/8 and never
+/// exceed the largest length seen.
+///
+/// [`BcfReader`](crate::BcfReader) and
+/// [`IndexedBcfReader`](crate::IndexedBcfReader) track the lengths of the
+/// records they read (see
+/// [`BcfReader::record_size_stats`](crate::BcfReader::record_size_stats)) and
+/// pre-size the records they read into after
+/// [`BcfReader::set_buffer_quantile`](crate::BcfReader::set_buffer_quantile).
+///
+/// Example:
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// read_header(&mut f).unwrap();
+/// let mut sizes = RecordSizeStats::default();
+/// let mut record = Record::default();
+/// let mut max_indv = 0;
+/// while let Ok(_) = record.read(&mut f) {
+///     let (l_shared, l_indv) = record.record_lengths();
+///     sizes.observe(l_shared, l_indv);
+///     max_indv = max_indv.max(l_indv);
+/// }
+/// assert_eq!(sizes.n_records(), 127);
+/// assert_eq!(sizes.max_indv(), max_indv);
+/// assert!(sizes.indv_quantile(0.5) <= sizes.indv_quantile(0.99));
+/// assert_eq!(sizes.indv_quantile(1.0), max_indv);
+///
+/// // a new record with buffers for the median record
+/// let mut record = Record::default();
+/// sizes.presize(&mut record, 0.5);
+/// assert!(record.memory_size() >= sizes.indv_quantile(0.5));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordSizeStats {
+    n_records: u64,
+    shared: LengthHistogram,
+    indv: LengthHistogram,
+}
+
+impl RecordSizeStats {
+    /// Add the lengths of a record (see [`Record::record_lengths`])
+    pub fn observe(&mut self, l_shared: usize, l_indv: usize) {
+        self.n_records += 1;
+        self.shared.add(l_shared.try_into().unwrap_or(u32::MAX));
+        self.indv.add(l_indv.try_into().unwrap_or(u32::MAX));
+    }
+
+    /// Number of records observed
+    pub fn n_records(&self) -> u64 {
+        self.n_records
+    }
+
+    /// Largest `l_shared` observed
+    pub fn max_shared(&self) -> usize {
+        self.shared.max as usize
+    }
+
+    /// Largest `l_indv` observed
+    pub fn max_indv(&self) -> usize {
+        self.indv.max as usize
+    }
+
+    /// The `q` quantile (between 0 and 1) of the `l_shared` observed, or 0
+    /// if no record was observed
+    pub fn shared_quantile(&self, q: f64) -> usize {
+        self.shared.quantile(self.n_records, q)
+    }
+
+    /// The `q` quantile (between 0 and 1) of the `l_indv` observed, or 0 if
+    /// no record was observed
+    pub fn indv_quantile(&self, q: f64) -> usize {
+        self.indv.quantile(self.n_records, q)
+    }
+
+    /// Reserve the buffers of `record` for the `q` quantile of the lengths
+    /// observed (see [`Record::reserve_buffers`]); buffers that are already
+    /// large enough are left as they are
+    pub fn presize(&self, record: &mut Record, q: f64) {
+        record.reserve_buffers(self.shared_quantile(q), self.indv_quantile(q));
+    }
+}
+
+/// Number of records between updates of the buffer sizes of
+/// [`BufferSizing`], after updates at each power of two below
+const BUFFER_SIZING_PERIOD: u64 = 256;
+
+/// The record lengths seen by a reader and the buffer sizes it reserves in the
+/// records it reads into, see
+/// [`BcfReader::set_buffer_quantile`](crate::BcfReader::set_buffer_quantile)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BufferSizing {
+    pub(crate) stats: RecordSizeStats,
+    quantile: Option<f64>,
+    /// (l_shared, l_indv) at the quantile, updated periodically
+    target: (usize, usize),
+}
+
+impl BufferSizing {
+    pub(crate) fn set_quantile(&mut self, q: Option<f64>) {
+        self.quantile = q;
+        self.update_target();
+    }
+
+    fn update_target(&mut self) {
+        self.target = match self.quantile {
+            Some(q) => (self.stats.shared_quantile(q), self.stats.indv_quantile(q)),
+            None => (0, 0),
+        };
+    }
+
+    pub(crate) fn before_read(&self, record: &mut Record) {
+        if self.quantile.is_some() {
+            record.reserve_buffers(self.target.0, self.target.1);
+        }
+    }
+
+    pub(crate) fn after_read(&mut self, record: &Record) {
+        let (l_shared, l_indv) = record.record_lengths();
+        self.stats.observe(l_shared, l_indv);
+        let n = self.stats.n_records;
+        if self.quantile.is_some()
+            && (n.is_power_of_two() || n.is_multiple_of(BUFFER_SIZING_PERIOD))
+        {
+            self.update_target();
+        }
+    }
+}
+
+/// A reader counting the bytes read from the inner reader
+pub(crate) struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    pub(crate) count: u64,
+    /// time spent in the inner reader, if measured
+    pub(crate) time: Option<Duration>,
+}
+
+impl<'a, R: Read> CountingReader<'a, R> {
+    pub(crate) fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            count: 0,
+            time: None,
+        }
+    }
+
+    /// Also measure the time spent in the inner reader
+    pub(crate) fn timed(inner: &'a mut R) -> Self {
+        Self {
+            time: Some(Duration::ZERO),
+            ..Self::new(inner)
+        }
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.time {
+            Some(time) => {
+                let start = Instant::now();
+                let n = self.inner.read(buf);
+                *time += start.elapsed();
+                n?
+            }
+            None => self.inner.read(buf)?,
+        };
+        self.count += n as u64;
+        Ok(n)
+    }
+}
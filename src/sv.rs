@@ -0,0 +1,399 @@
+//! Helpers for structural variants (SVs).
+//!
+//! SV records use symbolic ALT alleles such as `<DEL>` or `<DUP:TANDEM>` and
+//! describe the event with the INFO fields SVTYPE, SVLEN and END. The
+//! functions in this module read these fields from a [`Record`] (with the
+//! correct signed decoding of SVLEN) and compute the interval affected by the
+//! variant.
+//!
+//! Breakend ALT alleles (`N[chr2:321682[` etc.) of translocations can be
+//! parsed with [`parse_breakend`], and the mates of a breakend found through
+//! INFO/MATEID with [`mate_ids`].
+//!
+//! The confidence intervals around breakpoints (CIPOS/CIEND) are taken into
+//! account by [`fuzzy_interval`] and [`overlaps_region`] for region queries
+//! and merging of SV calls.
+//!
+//! The functions reading INFO fields require the `info` feature, and
+//! [`sv_type`] and [`mate_ids`] also the `strings` feature (both enabled by
+//! default).
+//!
+//! Example:
+#![cfg_attr(all(feature = "info", feature = "strings"), doc = "```")]
+#![cfg_attr(not(all(feature = "info", feature = "strings")), doc = "```ignore")]
+//! use bcf_reader::*;
+//! let mut f = smart_reader("testdata/test.bcf");
+//! let header = Header::from_string(&read_header(&mut f).unwrap());
+//! let mut record = Record::default();
+//! while let Ok(_) = record.read(&mut f) {
+//!     // test.bcf only has small variants
+//!     assert!(!sv::has_symbolic_alt(&record));
+//!     assert!(sv::undeclared_symbolic_alleles(&record, &header).is_empty());
+//!     assert_eq!(sv::sv_type(&record, &header), None);
+//!     assert_eq!(sv::end(&record, &header), record.pos() + record.rlen());
+//!     assert_eq!(
+//!         sv::affected_interval(&record, &header),
+//!         record.pos()..record.pos() + record.rlen()
+//!     );
+//! }
+//! ```
+
+use crate::{Header, Record};
+use std::ops::Range;
+
+/// Type of a structural variant, from INFO/SVTYPE or a symbolic ALT allele
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvType {
+    /// deletion (`DEL`)
+    Del,
+    /// insertion (`INS`)
+    Ins,
+    /// duplication (`DUP`)
+    Dup,
+    /// inversion (`INV`)
+    Inv,
+    /// copy number variation (`CNV`)
+    Cnv,
+    /// breakend (`BND`)
+    Bnd,
+    /// any other type
+    Other(String),
+}
+
+impl From<&str> for SvType {
+    /// Parse an SV type from an SVTYPE value or the ID of a symbolic allele.
+    /// Subtypes are ignored (`DUP:TANDEM` is a [`SvType::Dup`]).
+    ///
+    /// ```
+    /// use bcf_reader::sv::SvType;
+    /// assert_eq!(SvType::from("DEL"), SvType::Del);
+    /// assert_eq!(SvType::from("DUP:TANDEM"), SvType::Dup);
+    /// assert_eq!(SvType::from("CN0"), SvType::Other("CN0".into()));
+    /// ```
+    fn from(s: &str) -> Self {
+        match s.split(':').next().unwrap_or("") {
+            "DEL" => SvType::Del,
+            "INS" => SvType::Ins,
+            "DUP" => SvType::Dup,
+            "INV" => SvType::Inv,
+            "CNV" => SvType::Cnv,
+            "BND" => SvType::Bnd,
+            _ => SvType::Other(s.to_string()),
+        }
+    }
+}
+
+/// Whether an allele is symbolic (`<ID>`)
+///
+/// ```
+/// use bcf_reader::sv::is_symbolic_allele;
+/// assert!(is_symbolic_allele(b"<DEL>"));
+/// assert!(!is_symbolic_allele(b"ACGT"));
+/// assert!(!is_symbolic_allele(b"*"));
+/// ```
+pub fn is_symbolic_allele(allele: &[u8]) -> bool {
+    allele.len() >= 2 && allele[0] == b'<' && allele[allele.len() - 1] == b'>'
+}
+
+/// The ID of a symbolic allele (`DEL:ME` for `<DEL:ME>`), or `None` if the
+/// allele is not symbolic
+///
+/// ```
+/// use bcf_reader::sv::symbolic_allele_id;
+/// assert_eq!(symbolic_allele_id(b"<DEL:ME>"), Some("DEL:ME"));
+/// assert_eq!(symbolic_allele_id(b"A"), None);
+/// ```
+pub fn symbolic_allele_id(allele: &[u8]) -> Option<&str> {
+    if !is_symbolic_allele(allele) {
+        return None;
+    }
+    std::str::from_utf8(&allele[1..allele.len() - 1]).ok()
+}
+
+/// Whether any ALT allele of the record is symbolic
+pub fn has_symbolic_alt(record: &Record) -> bool {
+    record.alt_allele_bytes().any(is_symbolic_allele)
+}
+
+/// List the symbolic ALT alleles of the record (IDs without angle brackets)
+/// that are not declared by an `##ALT` header line (see
+/// [`Header::resolve_symbolic_allele`]).
+pub fn undeclared_symbolic_alleles<'r>(record: &'r Record, header: &Header) -> Vec<&'r str> {
+    record
+        .alt_allele_bytes()
+        .filter(|a| header.resolve_symbolic_allele(a).is_none())
+        .filter_map(symbolic_allele_id)
+        .collect()
+}
+
+/// Get the SV type of the record from INFO/SVTYPE, or else from the first
+/// symbolic ALT allele. Returns `None` for records that are not SVs.
+#[cfg(all(feature = "info", feature = "strings"))]
+pub fn sv_type(record: &Record, header: &Header) -> Option<SvType> {
+    if let Some(key) = header.get_idx_from_dictionary_str("INFO", "SVTYPE") {
+        if let Some(s) = record.info_field_str(key) {
+            let s = s.trim_end_matches('\0');
+            if !s.is_empty() {
+                return Some(SvType::from(s));
+            }
+        }
+    }
+    record
+        .alt_allele_bytes()
+        .find_map(symbolic_allele_id)
+        .map(SvType::from)
+}
+
+/// Get the values of INFO/SVLEN (one per ALT allele in VCF 4.3+), decoded
+/// as signed integers so that deletions have negative lengths. Missing
+/// values are `None`; the vector is empty if SVLEN is absent.
+#[cfg(feature = "info")]
+pub fn sv_len(record: &Record, header: &Header) -> Vec<Option<i32>> {
+    match header.get_idx_from_dictionary_str("INFO", "SVLEN") {
+        None => vec![],
+        Some(key) => record
+            .info_field_numeric(key)
+            .take_while(|v| !v.is_end_of_vector())
+            .map(|v| v.signed_int_val())
+            .collect(),
+    }
+}
+
+/// Get the 1-based, inclusive end position of the record: INFO/END if
+/// present, otherwise `pos + rlen` (0-based position plus reference length).
+#[cfg(feature = "info")]
+pub fn end(record: &Record, header: &Header) -> i32 {
+    header
+        .get_idx_from_dictionary_str("INFO", "END")
+        .and_then(|key| record.info_field_numeric(key).next())
+        .and_then(|v| v.signed_int_val())
+        .unwrap_or(record.pos() + record.rlen())
+}
+
+/// Get the 0-based, half-open interval of reference bases affected by the
+/// record.
+///
+/// For records with a symbolic ALT allele, the first base (POS) is the
+/// padding base before the event and is excluded, so the interval is
+/// `pos + 1..end`; for insertions and breakends, where END is POS, the
+/// interval is empty (`pos + 1..pos + 1`). Other records cover
+/// `pos..pos + rlen`.
+#[cfg(feature = "info")]
+pub fn affected_interval(record: &Record, header: &Header) -> Range<i32> {
+    let end = end(record, header);
+    if has_symbolic_alt(record) {
+        let start = record.pos() + 1;
+        start..end.max(start)
+    } else {
+        record.pos()..end.max(record.pos())
+    }
+}
+
+/// A breakend ALT allele (VCF section 5.4), e.g. `G]17:198982]` or `.A`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakend<'a> {
+    /// the replacement string: the reference base plus any inserted
+    /// sequence (`t` in the VCF specification)
+    pub sequence: &'a str,
+    /// whether `sequence` comes before the join (`t[p[`, `t]p]`, `t.`)
+    /// rather than after it (`]p]t`, `[p[t`, `.t`)
+    pub sequence_first: bool,
+    /// the mate breakend, `None` for a single breakend (`t.` or `.t`)
+    pub mate: Option<BreakendMate<'a>>,
+}
+
+/// The mate position of a paired breakend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakendMate<'a> {
+    /// chromosome of the mate (`<ctg>` for an assembly contig)
+    pub chrom: &'a str,
+    /// 1-based position of the mate
+    pub pos: i32,
+    /// whether the joined piece extends to the right of the mate position
+    /// (`[` bracket) rather than to the left (`]` bracket)
+    pub extends_right: bool,
+}
+
+impl Breakend<'_> {
+    /// The inserted sequence, i.e. `sequence` without the reference base
+    /// (empty if no bases are inserted)
+    pub fn inserted_sequence(&self) -> &str {
+        match self.sequence_first {
+            true => self.sequence.get(1..).unwrap_or(""),
+            false => self
+                .sequence
+                .get(..self.sequence.len().saturating_sub(1))
+                .unwrap_or(""),
+        }
+    }
+}
+
+/// Parse a breakend ALT allele. Returns `None` if the allele is not in
+/// breakend notation.
+///
+/// ```
+/// use bcf_reader::sv::*;
+/// let bnd = parse_breakend(b"G]17:198982]").unwrap();
+/// assert_eq!(bnd.sequence, "G");
+/// assert!(bnd.sequence_first);
+/// let mate = bnd.mate.unwrap();
+/// assert_eq!((mate.chrom, mate.pos, mate.extends_right), ("17", 198982, false));
+///
+/// let bnd = parse_breakend(b"[13:123457[ACGTC").unwrap();
+/// assert!(!bnd.sequence_first);
+/// assert_eq!(bnd.inserted_sequence(), "ACGT");
+/// assert!(bnd.mate.unwrap().extends_right);
+///
+/// // single breakend
+/// let bnd = parse_breakend(b"C.").unwrap();
+/// assert_eq!(bnd.mate, None);
+///
+/// assert_eq!(parse_breakend(b"ACGT"), None);
+/// assert_eq!(parse_breakend(b"<DEL>"), None);
+/// ```
+pub fn parse_breakend(allele: &[u8]) -> Option<Breakend<'_>> {
+    let s = std::str::from_utf8(allele).ok()?;
+    if s.len() >= 2 && !s.contains(['[', ']']) {
+        if let Some(t) = s.strip_suffix('.') {
+            return Some(Breakend {
+                sequence: t,
+                sequence_first: true,
+                mate: None,
+            });
+        }
+        if let Some(t) = s.strip_prefix('.') {
+            return Some(Breakend {
+                sequence: t,
+                sequence_first: false,
+                mate: None,
+            });
+        }
+        return None;
+    }
+    let first = s.find(['[', ']'])?;
+    let bracket = s.as_bytes()[first];
+    let last = s.rfind(bracket as char)?;
+    if last == first {
+        return None;
+    }
+    let (chrom, pos) = s[first + 1..last].rsplit_once(':')?;
+    let pos = pos.parse().ok()?;
+    let (sequence, sequence_first) = match (first, last + 1 == s.len()) {
+        (0, false) => (&s[last + 1..], false),
+        (f, true) if f > 0 => (&s[..first], true),
+        _ => return None,
+    };
+    if sequence.is_empty() {
+        return None;
+    }
+    Some(Breakend {
+        sequence,
+        sequence_first,
+        mate: Some(BreakendMate {
+            chrom,
+            pos,
+            extends_right: bracket == b'[',
+        }),
+    })
+}
+
+/// Parse the ALT alleles of the record that are in breakend notation
+pub fn breakends(record: &Record) -> Vec<Breakend<'_>> {
+    record
+        .alt_allele_bytes()
+        .filter_map(parse_breakend)
+        .collect()
+}
+
+/// Get the IDs of the mate breakends from INFO/MATEID (empty if absent)
+#[cfg(all(feature = "info", feature = "strings"))]
+pub fn mate_ids<'r>(record: &'r Record, header: &Header) -> Vec<&'r str> {
+    header
+        .get_idx_from_dictionary_str("INFO", "MATEID")
+        .and_then(|key| record.info_field_str(key))
+        .map(|s| {
+            s.trim_end_matches('\0')
+                .split(',')
+                .filter(|x| !x.is_empty() && *x != ".")
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Read a confidence interval field (CIPOS/CIEND): a pair of signed offsets
+/// relative to the breakpoint, e.g. `(-10, 25)`
+#[cfg(feature = "info")]
+fn confidence_interval(record: &Record, header: &Header, tag: &str) -> Option<(i32, i32)> {
+    let key = header.get_idx_from_dictionary_str("INFO", tag)?;
+    let mut it = record.info_field_numeric(key).map(|v| v.signed_int_val());
+    match (it.next(), it.next()) {
+        (Some(Some(low)), Some(Some(high))) => Some((low, high)),
+        _ => None,
+    }
+}
+
+/// Get the confidence interval around POS from INFO/CIPOS, as offsets
+/// `(low, high)` relative to the start of the event
+#[cfg(feature = "info")]
+pub fn cipos(record: &Record, header: &Header) -> Option<(i32, i32)> {
+    confidence_interval(record, header, "CIPOS")
+}
+
+/// Get the confidence interval around END from INFO/CIEND, as offsets
+/// `(low, high)` relative to the end of the event
+#[cfg(feature = "info")]
+pub fn ciend(record: &Record, header: &Header) -> Option<(i32, i32)> {
+    confidence_interval(record, header, "CIEND")
+}
+
+/// Get the widest interval that may be affected by the record: the
+/// [`affected_interval`] with its start extended by the low CIPOS offset
+/// and its end extended by the high CIEND offset. Without CIPOS/CIEND, this
+/// is the same as [`affected_interval`].
+#[cfg(feature = "info")]
+pub fn fuzzy_interval(record: &Record, header: &Header) -> Range<i32> {
+    let interval = affected_interval(record, header);
+    let (start_low, _) = cipos(record, header).unwrap_or((0, 0));
+    let (_, end_high) = ciend(record, header).unwrap_or((0, 0));
+    let start = (interval.start + start_low.min(0)).max(0);
+    let end = (interval.end + end_high.max(0)).max(start);
+    start..end
+}
+
+/// Whether two 0-based half-open intervals overlap after extending each of
+/// them by `slop` bases on both sides. Empty intervals (insertions,
+/// breakends) are treated as a single point at their start.
+///
+/// ```
+/// use bcf_reader::sv::fuzzy_overlap;
+/// assert!(fuzzy_overlap(&(100..200), &(150..300), 0));
+/// assert!(!fuzzy_overlap(&(100..200), &(200..300), 0));
+/// assert!(fuzzy_overlap(&(100..200), &(205..300), 3));
+/// assert!(!fuzzy_overlap(&(100..200), &(206..300), 3));
+/// // an insertion point inside a deletion
+/// assert!(fuzzy_overlap(&(150..150), &(100..200), 0));
+/// ```
+pub fn fuzzy_overlap(a: &Range<i32>, b: &Range<i32>, slop: i32) -> bool {
+    let a_end = a.end.max(a.start + 1);
+    let b_end = b.end.max(b.start + 1);
+    a.start - slop < b_end + slop && b.start - slop < a_end + slop
+}
+
+/// Whether the record may overlap the 0-based half-open `region`, taking
+/// the breakpoint confidence intervals into account (see
+/// [`fuzzy_interval`]).
+///
+/// ```
+/// use bcf_reader::*;
+/// let mut f = smart_reader("testdata/test.bcf");
+/// let header = Header::from_string(&read_header(&mut f).unwrap());
+/// let mut record = Record::default();
+/// record.read(&mut f).unwrap();
+/// let pos = record.pos();
+/// assert!(sv::overlaps_region(&record, &header, &(pos..pos + 1)));
+/// assert!(!sv::overlaps_region(&record, &header, &(pos + 10..pos + 20)));
+/// ```
+#[cfg(feature = "info")]
+pub fn overlaps_region(record: &Record, header: &Header, region: &Range<i32>) -> bool {
+    fuzzy_overlap(&fuzzy_interval(record, header), region, 0)
+}
@@ -0,0 +1,411 @@
+//! Whole-file validation.
+//!
+//! [`validate_file`] reads a BCF file to the end and reports the problems
+//! found, instead of stopping at the first one: corrupt or truncated records,
+//! references to contigs, filters and fields missing from the header, values
+//! not matching the `Number=` and `Type=` of their header lines, records out
+//! of order and genotypes referring to alleles the record does not have.
+//!
+//! Example:
+//! ```
+//! use bcf_reader::validate::*;
+//! let report = validate_file("testdata/test.bcf");
+//! assert_eq!(report.n_records, 127);
+//! assert!(report.is_valid());
+//!
+//! let report = validate_file("testdata/no_such_file.bcf");
+//! assert!(!report.is_valid());
+//! assert_eq!(report.issues[0].kind, IssueKind::Framing);
+//! ```
+
+use crate::checks::OrderTracker;
+use crate::header::Severity;
+use crate::{BcfReader, FieldLayout, Header, Record};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// Number of issues kept in a [`ValidationReport`]; further issues are only
+/// counted
+pub const MAX_REPORTED_ISSUES: usize = 10_000;
+
+/// Kind of a problem found by [`validate_file`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueKind {
+    /// the header is malformed or does not follow the specification
+    Header,
+    /// the file cannot be read, is truncated, or a record cannot be parsed
+    Framing,
+    /// a contig, FILTER, INFO or FORMAT key not defined in the header
+    Dictionary,
+    /// a number of values not matching the `Number=` of the header line
+    ValueCount,
+    /// values encoded with a type not matching the `Type=` of the header
+    /// line, empty alleles or strings that are not valid UTF-8
+    Encoding,
+    /// a record before the previous one in the file order
+    Order,
+    /// a genotype referring to an allele the record does not have
+    Genotype,
+}
+
+/// A problem found by [`validate_file`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// what kind of problem
+    pub kind: IssueKind,
+    /// how serious the problem is
+    pub severity: Severity,
+    /// index of the record in the file (0-based), for problems in a record
+    pub record: Option<usize>,
+    /// offset of the record in the decompressed data, if known
+    pub offset: Option<u64>,
+    /// description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match (self.record, self.offset) {
+            (Some(i), Some(offset)) => write!(
+                f,
+                "{severity}: record {i} (byte {offset}): {}",
+                self.message
+            ),
+            (Some(i), None) => write!(f, "{severity}: record {i}: {}", self.message),
+            _ => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// The result of [`validate_file`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// number of records in the file, including those that cannot be parsed
+    pub n_records: usize,
+    /// the first [`MAX_REPORTED_ISSUES`] problems found
+    pub issues: Vec<ValidationIssue>,
+    /// number of problems found, including those not kept in `issues`
+    pub n_issues: usize,
+}
+
+impl ValidationReport {
+    /// Whether no error (as opposed to warnings) was found
+    pub fn is_valid(&self) -> bool {
+        self.issues.iter().all(|i| i.severity < Severity::Error)
+    }
+
+    /// Get the reported issues of a kind
+    pub fn issues_of(&self, kind: IssueKind) -> impl Iterator<Item = &ValidationIssue> + '_ {
+        self.issues.iter().filter(move |i| i.kind == kind)
+    }
+
+    fn push(
+        &mut self,
+        kind: IssueKind,
+        severity: Severity,
+        location: Option<(usize, u64)>,
+        message: String,
+    ) {
+        self.n_issues += 1;
+        if self.issues.len() < MAX_REPORTED_ISSUES {
+            self.issues.push(ValidationIssue {
+                kind,
+                severity,
+                record: location.map(|l| l.0),
+                offset: location.map(|l| l.1),
+                message,
+            });
+        }
+    }
+}
+
+/// Read a BCF file to the end and report the problems found (see the
+/// [module documentation](self)).
+///
+/// Example:
+/// ```
+/// use bcf_reader::validate::*;
+/// use bcf_reader::header::Severity;
+/// // an uncompressed file with 4 records
+/// let text = concat!(
+///     "##fileformat=VCFv4.2\n",
+///     "##contig=<ID=chr1,length=1000>\n",
+///     "##INFO=<ID=AC,Number=A,Type=Integer,Description=\"Allele count\">\n",
+///     "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n",
+///     "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\n",
+/// );
+/// let mut data = b"BCF\x02\x02".to_vec();
+/// data.extend((text.len() as u32 + 1).to_le_bytes());
+/// data.extend(text.as_bytes());
+/// data.push(0);
+/// let record = |chrom: i32, pos: i32, ac: &[u8], gt: [u8; 2]| {
+///     let mut shared = vec![];
+///     for x in [chrom, pos, 1] {
+///         shared.extend(x.to_le_bytes()); // chrom, pos, rlen
+///     }
+///     shared.extend(0x7F800001u32.to_le_bytes()); // missing qual
+///     shared.extend((1u32 | 2 << 16).to_le_bytes()); // 1 INFO, 2 alleles
+///     shared.extend((1u32 | 1 << 24).to_le_bytes()); // 1 sample, 1 FORMAT
+///     shared.extend([0x07, 0x17, b'A', 0x17, b'C', 0x00]); // ID, alleles, FILTER
+///     shared.extend([0x11, 1]); // AC
+///     shared.extend(ac);
+///     let indiv = [0x11, 2, 0x21, gt[0], gt[1]]; // GT
+///     let mut bytes = vec![];
+///     bytes.extend((shared.len() as u32).to_le_bytes());
+///     bytes.extend((indiv.len() as u32).to_le_bytes());
+///     bytes.extend(shared);
+///     bytes.extend(indiv);
+///     bytes
+/// };
+/// data.extend(record(0, 99, &[0x11, 1], [2, 4]));
+/// // two AC values, unknown contig, out of order, allele 2 in GT
+/// data.extend(record(0, 9, &[0x21, 1, 1], [2, 6]));
+/// data.extend(record(5, 99, &[0x11, 1], [2, 4]));
+/// // truncated
+/// let last = record(0, 199, &[0x11, 1], [2, 4]);
+/// data.extend(&last[..10]);
+///
+/// let path = std::env::temp_dir().join("bcf_reader_validate_doctest.bcf");
+/// std::fs::write(&path, &data).unwrap();
+/// let report = validate_file(&path);
+/// std::fs::remove_file(&path).unwrap();
+///
+/// assert_eq!(report.n_records, 4);
+/// assert!(!report.is_valid());
+/// let kinds: Vec<_> = report.issues.iter().map(|i| (i.kind, i.record)).collect();
+/// assert_eq!(
+///     kinds,
+///     [
+///         (IssueKind::Order, Some(1)),
+///         (IssueKind::ValueCount, Some(1)),
+///         (IssueKind::Genotype, Some(1)),
+///         (IssueKind::Dictionary, Some(2)),
+///         (IssueKind::Framing, Some(3)),
+///     ]
+/// );
+/// assert_eq!(
+///     report.issues_of(IssueKind::Genotype).next().unwrap().to_string(),
+///     "error: record 1 (byte 283): chr1:10: sample s1 has allele 2 but the record has 2 alleles"
+/// );
+/// ```
+pub fn validate_file(path: impl AsRef<Path>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let source = match crate::open_smart(path.as_ref()) {
+        Ok(source) => source,
+        Err(e) => {
+            report.push(
+                IssueKind::Framing,
+                Severity::Error,
+                None,
+                format!("cannot open {}: {e}", path.as_ref().display()),
+            );
+            return report;
+        }
+    };
+    let mut reader = BcfReader::from_reader(source);
+    // the records are checked below, not by the reader, so that each issue
+    // is reported once: the deviations found by the reader in lenient mode
+    // are not reported, and the other reader checks are off
+    reader.set_utf8_policy(crate::Utf8Policy::Raw);
+    reader.set_order_check(crate::OrderCheck::Off);
+    reader.set_skip_corrupt_records(true);
+    let header = match reader.try_read_header() {
+        Ok(header) => header,
+        Err(e) => {
+            report.push(IssueKind::Header, Severity::Error, None, e.to_string());
+            return report;
+        }
+    };
+    let violations = header.validate();
+    let violation_texts: HashSet<String> = violations.iter().map(|v| v.to_string()).collect();
+    for warning in reader.header_warnings() {
+        if !violation_texts.contains(warning) {
+            report.push(IssueKind::Header, Severity::Warning, None, warning.clone());
+        }
+    }
+    for v in violations {
+        report.push(IssueKind::Header, v.severity, None, v.to_string());
+    }
+
+    let mut checker = RecordChecker::default();
+    let mut record = Record::default();
+    let mut n_skipped = 0;
+    loop {
+        let res = reader.try_read_record(&mut record);
+        let location = (
+            report.n_records + reader.skipped_records().len() - n_skipped,
+            reader.record_offset(),
+        );
+        // records skipped by the reader come before the one just read
+        for skipped in &reader.skipped_records()[n_skipped..] {
+            report.push(
+                IssueKind::Framing,
+                Severity::Error,
+                Some((report.n_records, skipped.offset)),
+                skipped.error.to_string(),
+            );
+            report.n_records += 1;
+        }
+        n_skipped = reader.skipped_records().len();
+        match res {
+            Ok(Some(())) => {
+                checker.check(&header, &record, location, &mut report);
+                report.n_records += 1;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                report.push(
+                    IssueKind::Framing,
+                    Severity::Error,
+                    Some(location),
+                    e.to_string(),
+                );
+                report.n_records += 1;
+                break;
+            }
+        }
+    }
+    report
+}
+
+/// Checks of each record, and of the order of the records
+#[derive(Default)]
+struct RecordChecker {
+    order: OrderTracker,
+    gt: Vec<i32>,
+}
+
+impl RecordChecker {
+    fn check(
+        &mut self,
+        header: &Header,
+        record: &Record,
+        location: (usize, u64),
+        report: &mut ValidationReport,
+    ) {
+        // the message names the record and the previous one
+        if let Some(message) = self.order.check(Some(header), record.chrom(), record.pos()) {
+            report.push(IssueKind::Order, Severity::Error, Some(location), message);
+        }
+        let chrom = header.rid_to_name(record.chrom());
+        let site = format!("{}:{}", chrom.unwrap_or("?"), record.pos() + 1);
+        let mut push = |kind, message: String| {
+            report.push(
+                kind,
+                Severity::Error,
+                Some(location),
+                format!("{site}: {message}"),
+            )
+        };
+        if chrom.is_none() {
+            push(
+                IssueKind::Dictionary,
+                format!("contig {} is not defined in the header", record.chrom()),
+            );
+        }
+        for key in record.filters() {
+            let key = key.int_val().unwrap_or(0) as usize;
+            if header.dict_entry("FILTER", key).is_none() {
+                push(
+                    IssueKind::Dictionary,
+                    format!("FILTER key {key} is not defined in the header"),
+                );
+            }
+        }
+        let n_allele = record.n_allele() as usize;
+        for i in 0..n_allele {
+            if record.allele_bytes(i).is_empty() {
+                push(IssueKind::Encoding, format!("allele {i} is empty"));
+            }
+        }
+        for field in record.dropped_fields() {
+            push(
+                IssueKind::Encoding,
+                format!(
+                    "{} key {} uses the reserved BCF2 type {}",
+                    field.dictionary, field.key, field.typ
+                ),
+            );
+        }
+        if let Err(e) = record.check_utf8() {
+            push(IssueKind::Encoding, e.to_string());
+        }
+        let n_sample = header.get_samples().len();
+        if !record.buf_indiv().is_empty() && record.n_sample as usize != n_sample {
+            push(
+                IssueKind::Framing,
+                format!(
+                    "the record has {} samples but the header has {n_sample}",
+                    record.n_sample
+                ),
+            );
+        }
+        for (dictionary, fields) in [
+            (
+                "INFO",
+                record
+                    .info_fields()
+                    .map(FieldLayout::from)
+                    .collect::<Vec<_>>(),
+            ),
+            ("FORMAT", record.fmt_layout().collect()),
+        ] {
+            for field in fields {
+                let Ok(def) = header.number_and_type_for(dictionary, field.key) else {
+                    push(
+                        IssueKind::Dictionary,
+                        format!(
+                            "{dictionary} key {} is not defined in the header",
+                            field.key
+                        ),
+                    );
+                    continue;
+                };
+                let (id, _, ty) = def;
+                if let Some(message) = crate::type_mismatch(dictionary, id, ty, field.typ, field.n)
+                {
+                    push(IssueKind::Encoding, message);
+                } else if let Err(e) =
+                    crate::group_values_by_number(dictionary, def, n_allele, field.n)
+                {
+                    push(IssueKind::ValueCount, e.to_string());
+                }
+            }
+        }
+
+        let Some(ploidy) = header
+            .get_fmt_gt_id()
+            .and_then(|key| record.fmt_layout().find(|f| f.key == key))
+            .map(|f| f.n)
+            .filter(|&n| n > 0)
+        else {
+            return;
+        };
+        self.gt.resize(record.n_sample as usize * ploidy, 0);
+        if record.decode_gt_into(header, &mut self.gt).is_err() {
+            return;
+        }
+        let samples = header.get_samples();
+        for (i, gt) in self
+            .gt
+            .chunks(ploidy)
+            .take(record.n_sample as usize)
+            .enumerate()
+        {
+            if let Some(allele) = gt.iter().find(|&&a| a >= n_allele as i32) {
+                let sample = samples.get(i).map_or("?", |s| s.as_str());
+                push(
+                    IssueKind::Genotype,
+                    format!(
+                        "sample {sample} has allele {allele} but the record has {n_allele} alleles"
+                    ),
+                );
+            }
+        }
+    }
+}